@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::{GraphElement, Node, NodeGraph};
+
+/// A node as returned by the API.
+#[derive(Serialize)]
+pub struct NodeBody {
+    pub id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub rank: u64,
+    pub node_type: String,
+    pub text: String,
+    pub note: String,
+    pub author: String,
+    pub source: String,
+    pub created_time: String,
+    pub modified_time: String,
+    pub mirror_of: Option<Uuid>,
+}
+
+impl From<&Node> for NodeBody {
+    fn from(node: &Node) -> Self {
+        NodeBody {
+            id: node.id(),
+            parent_id: node.parent_id(),
+            rank: node.rank(),
+            node_type: node.node_type_str(),
+            text: node.text().to_owned(),
+            note: node.note().to_owned(),
+            author: node.author().to_owned(),
+            source: node.source_type_str(),
+            created_time: node.created_time_str(),
+            modified_time: node.modified_time_str(),
+            mirror_of: node.mirror_of(),
+        }
+    }
+}
+
+/// A node along with its whole subtree.
+#[derive(Serialize)]
+pub struct TreeBody {
+    #[serde(flatten)]
+    pub node: NodeBody,
+    pub children: Vec<TreeBody>,
+}
+
+impl TreeBody {
+    /// Builds the tree below `element`, or `None` when its node is not in `graph`.
+    pub fn new(graph: &NodeGraph, element: &GraphElement) -> Option<Self> {
+        let node = graph.node(&element.id)?;
+
+        Some(TreeBody {
+            node: NodeBody::from(node),
+            children: element
+                .children
+                .iter()
+                .filter_map(|child| TreeBody::new(graph, child))
+                .collect(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateBody {
+    pub parent_id: Option<Uuid>,
+    /// Position among the new siblings, the end when omitted
+    pub position: Option<usize>,
+    pub text: String,
+    pub node_type: Option<String>,
+    pub author: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateBody {
+    pub text: Option<String>,
+    pub note: Option<String>,
+    pub node_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MoveBody {
+    pub parent_id: Option<Uuid>,
+    /// Position among the new siblings, the end when omitted
+    pub position: Option<usize>,
+}
+
+/// Data of a `node_moved` event.
+#[derive(Serialize)]
+pub struct MovedBody {
+    #[serde(flatten)]
+    pub node: NodeBody,
+    pub previous_parent_id: Option<Uuid>,
+}
+
+/// Data of a `node_deleted` event.
+#[derive(Serialize)]
+pub struct NodeIdBody {
+    pub id: Uuid,
+}
+
+#[derive(Serialize)]
+pub struct DeletedBody {
+    pub deleted: usize,
+}
+
+#[derive(Serialize)]
+pub struct ErrorBody {
+    pub error: String,
+}
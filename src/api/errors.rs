@@ -0,0 +1,109 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::{domain::DomainError, interfaces::InterfaceError, services::errors::ServiceError};
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("Failed to start server: {0}")]
+    Server(String),
+    #[error("IO error: {0}")]
+    Io(io::Error),
+    #[error("Serialization error")]
+    Serialization,
+    #[error("Missing or invalid bearer token")]
+    Unauthorized,
+    #[error("No such endpoint")]
+    NotFound,
+    #[error("Method not allowed")]
+    MethodNotAllowed,
+    #[error("Request body is too large")]
+    PayloadTooLarge,
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Service(ServiceError),
+}
+
+impl ApiError {
+    /// HTTP status code to answer a request failing with this error.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            ApiError::Server(_) | ApiError::Io(_) | ApiError::Serialization => 500,
+            ApiError::Unauthorized => 401,
+            ApiError::NotFound => 404,
+            ApiError::MethodNotAllowed => 405,
+            ApiError::PayloadTooLarge => 413,
+            ApiError::BadRequest(_) => 400,
+            ApiError::Service(err) => service_status(err),
+        }
+    }
+}
+
+fn service_status(err: &ServiceError) -> u16 {
+    match err {
+        ServiceError::Domain(err) => domain_status(err),
+        ServiceError::Interface(err) => interface_status(err),
+        ServiceError::InvalidMove(_) => 409,
+        ServiceError::MissingView(_)
+        | ServiceError::MissingTemplate(_)
+        | ServiceError::MissingAttachment(_) => 404,
+        ServiceError::DuplicateTemplate(_) => 409,
+        ServiceError::AttachmentTooLarge { .. } => 413,
+        ServiceError::DuplicateImportedNode(_) => 400,
+        ServiceError::AttachedFile(_) => 500,
+        ServiceError::LogWrite => 500,
+    }
+}
+
+fn domain_status(err: &DomainError) -> u16 {
+    match err {
+        DomainError::MissingNode(_) => 404,
+        DomainError::CyclicMove(_) => 409,
+        DomainError::FieldParseError(_)
+        | DomainError::ClipboardFormat
+        | DomainError::InvalidSelection
+        | DomainError::InvalidTag(_)
+        | DomainError::InvalidQuery(..)
+        | DomainError::InvalidDate(_)
+        | DomainError::InvalidViewName
+        | DomainError::InvalidTemplateName
+        | DomainError::EmptyTemplate
+        | DomainError::MissingTemplateValue(_) => 400,
+        DomainError::InvalidDateTime => 500,
+    }
+}
+
+fn interface_status(err: &InterfaceError) -> u16 {
+    match err {
+        InterfaceError::MissingNode | InterfaceError::MissingNodeOperation => 404,
+        // another process is writing, so the request can be retried shortly
+        InterfaceError::Locked => 503,
+        InterfaceError::Domain(err) => domain_status(err),
+        InterfaceError::ImportParse { .. } => 400,
+        InterfaceError::DbConnection
+        | InterfaceError::TableCreation
+        | InterfaceError::InvalidQuery
+        | InterfaceError::NodeWrite
+        | InterfaceError::NodeUpdate
+        | InterfaceError::FieldParseError(_)
+        | InterfaceError::NodeDelete
+        | InterfaceError::TombstoneWrite
+        | InterfaceError::LinkWrite
+        | InterfaceError::TagWrite
+        | InterfaceError::PlanningWrite
+        | InterfaceError::CollapsedWrite
+        | InterfaceError::SettingWrite
+        | InterfaceError::ViewWrite
+        | InterfaceError::RevisionWrite
+        | InterfaceError::TemplateWrite
+        | InterfaceError::OperationWrite
+        | InterfaceError::AttachmentWrite
+        | InterfaceError::MissingBlob
+        | InterfaceError::Transaction
+        | InterfaceError::FileAccess
+        | InterfaceError::Serialization
+        | InterfaceError::Other => 500,
+    }
+}
@@ -0,0 +1,196 @@
+pub mod errors;
+pub use errors::ApiError;
+
+pub(crate) mod body;
+mod routes;
+
+use std::{
+    io::{Read, Write},
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    api::{
+        body::{MovedBody, NodeBody, NodeIdBody},
+        routes::Reply,
+    },
+    interfaces::NodeRepository,
+    services::{
+        events::{NodeEvent, POLL_INTERVAL},
+        graph_service::GraphService,
+        logging::LoggingService,
+    },
+};
+
+/// Largest request body accepted, in bytes.
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// Idle time after which an event stream is sent a comment, which keeps
+/// proxies from closing it and notices clients that have gone away.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Serves the outline over a JSON API at `address` until the process is stopped.
+/// Every request must carry `token` as a bearer token.
+///
+/// Requests are handled one at a time, so each sees the changes of the last.
+/// `GET /events` is the exception, streaming the outline's changes as
+/// Server-Sent Events from a thread of its own. Changes made by other
+/// processes are looked for between requests.
+pub fn serve<R, L>(
+    mut graph_service: GraphService<R, L>,
+    address: &str,
+    token: &str,
+) -> Result<(), ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let server = Server::http(address).map_err(|err| ApiError::Server(err.to_string()))?;
+
+    loop {
+        // a failed poll is simply tried again on the next one
+        let _ = graph_service.node_service.poll_changes();
+
+        let mut request = match server.recv_timeout(POLL_INTERVAL) {
+            Ok(Some(request)) => request,
+            Ok(None) => continue,
+            Err(err) => return Err(ApiError::Server(err.to_string())),
+        };
+
+        if *request.method() == Method::Get && request.url().split('?').next() == Some("/events") {
+            match authorize(&request, token) {
+                Ok(()) => {
+                    let receiver = graph_service.node_service.notifier.subscribe();
+                    thread::spawn(move || stream_events(request, receiver));
+                }
+                Err(err) => send(request, Reply::error(err)),
+            }
+            continue;
+        }
+
+        let reply = respond(&mut graph_service, &mut request, token).unwrap_or_else(Reply::error);
+        send(request, reply);
+    }
+}
+
+fn send(request: Request, reply: Reply) {
+    let mut response = Response::from_string(reply.body).with_status_code(reply.status);
+    if let Ok(header) = Header::from_bytes("Content-Type", reply.content_type) {
+        response = response.with_header(header);
+    }
+
+    // a client that hung up before the reply does not stop the server
+    let _ = request.respond(response);
+}
+
+fn respond<R, L>(
+    graph_service: &mut GraphService<R, L>,
+    request: &mut Request,
+    token: &str,
+) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    authorize(request, token)?;
+
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_string(&mut body)
+        .map_err(|_| ApiError::BadRequest("request body is not valid UTF-8".into()))?;
+
+    if body.len() as u64 > MAX_BODY_SIZE {
+        return Err(ApiError::PayloadTooLarge);
+    }
+
+    let method = request.method().clone();
+    routes::handle(graph_service, &method, request.url(), &body)
+}
+
+/// Writes events to the client as they arrive, until it disconnects.
+fn stream_events(request: Request, receiver: Receiver<NodeEvent>) {
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/event-stream\r\n\
+        Cache-Control: no-cache\r\n\
+        Connection: close\r\n\r\n";
+
+    let mut message = head.to_owned();
+
+    loop {
+        if writer
+            .write_all(message.as_bytes())
+            .and_then(|()| writer.flush())
+            .is_err()
+        {
+            return;
+        }
+
+        message = match receiver.recv_timeout(KEEPALIVE_INTERVAL) {
+            Ok(event) => match event_message(&event) {
+                Some(message) => message,
+                None => continue,
+            },
+            Err(RecvTimeoutError::Timeout) => ": keepalive\n\n".to_owned(),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+    }
+}
+
+/// Formats an event as a Server-Sent Events message.
+fn event_message(event: &NodeEvent) -> Option<String> {
+    let (name, data) = match event {
+        NodeEvent::NodeCreated(node) => {
+            ("node_created", serde_json::to_string(&NodeBody::from(node)))
+        }
+        NodeEvent::NodeUpdated(node) => {
+            ("node_updated", serde_json::to_string(&NodeBody::from(node)))
+        }
+        NodeEvent::NodeMoved {
+            node,
+            previous_parent_id,
+        } => (
+            "node_moved",
+            serde_json::to_string(&MovedBody {
+                node: NodeBody::from(node),
+                previous_parent_id: *previous_parent_id,
+            }),
+        ),
+        NodeEvent::NodeDeleted(node_id) => (
+            "node_deleted",
+            serde_json::to_string(&NodeIdBody { id: *node_id }),
+        ),
+    };
+
+    Some(format!("event: {name}\ndata: {}\n\n", data.ok()?))
+}
+
+fn authorize(request: &Request, token: &str) -> Result<(), ApiError> {
+    let provided = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+        .ok_or(ApiError::Unauthorized)?;
+
+    match constant_time_eq(provided.as_bytes(), token.as_bytes()) {
+        true => Ok(()),
+        false => Err(ApiError::Unauthorized),
+    }
+}
+
+/// Compares two byte strings in a time that does not depend on where they differ.
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0, |difference, (left, right)| difference | (left ^ right))
+            == 0
+}
@@ -0,0 +1,401 @@
+use std::{collections::HashMap, str::FromStr};
+
+use serde::{Serialize, de::DeserializeOwned};
+use tiny_http::Method;
+use uuid::Uuid;
+
+use crate::{
+    api::{
+        ApiError,
+        body::{CreateBody, DeletedBody, ErrorBody, MoveBody, NodeBody, TreeBody, UpdateBody},
+    },
+    domain::{
+        DomainError, Query, Selection,
+        node::{CreateNodeRequest, NodeType, Source},
+    },
+    interfaces::{
+        NodeRepository,
+        export::{ExportFormat, ExportOptions, export},
+    },
+    services::{
+        bulk::DeletePolicy, errors::ServiceError, graph_service::GraphService,
+        logging::LoggingService,
+    },
+};
+
+/// Author recorded on nodes created through the API when the request names none.
+const DEFAULT_AUTHOR: &str = "api";
+
+/// Response to a request, before it is handed to the server.
+pub struct Reply {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Reply {
+    fn json(status: u16, value: &impl Serialize) -> Result<Reply, ApiError> {
+        Ok(Reply {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_string(value).map_err(|_| ApiError::Serialization)?,
+        })
+    }
+
+    pub fn error(err: ApiError) -> Reply {
+        Reply {
+            status: err.status_code(),
+            content_type: "application/json",
+            body: serde_json::to_string(&ErrorBody {
+                error: err.to_string(),
+            })
+            .unwrap_or_default(),
+        }
+    }
+}
+
+/// Routes a request to the matching endpoint.
+pub fn handle<R, L>(
+    graph_service: &mut GraphService<R, L>,
+    method: &Method,
+    url: &str,
+    body: &str,
+) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let params = parse_params(query)?;
+
+    match (method, segments.as_slice()) {
+        (Method::Get, ["nodes"]) => list_nodes(graph_service),
+        (Method::Post, ["nodes"]) => create_node(graph_service, parse_body(body)?),
+        (Method::Get, ["nodes", node_id]) => get_node(graph_service, parse_id(node_id)?),
+        (Method::Patch, ["nodes", node_id]) => {
+            update_node(graph_service, parse_id(node_id)?, parse_body(body)?)
+        }
+        (Method::Delete, ["nodes", node_id]) => {
+            delete_node(graph_service, parse_id(node_id)?, &params)
+        }
+        (Method::Get, ["nodes", node_id, "subtree"]) => subtree(graph_service, parse_id(node_id)?),
+        (Method::Post, ["nodes", node_id, "move"]) => {
+            move_node(graph_service, parse_id(node_id)?, parse_body(body)?)
+        }
+        (Method::Get, ["tree"]) => tree(graph_service),
+        (Method::Get, ["search"]) => search(graph_service, &params),
+        (Method::Get, ["export"]) => export_outline(graph_service, &params),
+        (_, ["nodes"] | ["nodes", _] | ["nodes", _, "subtree" | "move"])
+        | (_, ["tree" | "search" | "export" | "events"]) => Err(ApiError::MethodNotAllowed),
+        _ => Err(ApiError::NotFound),
+    }
+}
+
+fn list_nodes<R, L>(graph_service: &GraphService<R, L>) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let nodes = graph_service
+        .node_service
+        .dump_nodes()
+        .map_err(ApiError::Service)?;
+
+    Reply::json(200, &nodes.iter().map(NodeBody::from).collect::<Vec<_>>())
+}
+
+fn create_node<R, L>(
+    graph_service: &GraphService<R, L>,
+    body: CreateBody,
+) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let node_service = &graph_service.node_service;
+    let node_type = match body.node_type {
+        Some(node_type) => parse_node_type(&node_type)?,
+        None => NodeType::Standard,
+    };
+
+    // the service would happily create a node under a parent that does not exist
+    if let Some(parent_id) = body.parent_id {
+        node_service
+            .get_node(&parent_id)
+            .map_err(ApiError::Service)?;
+    }
+
+    let request = CreateNodeRequest::new(
+        body.parent_id,
+        0,
+        node_type,
+        &body.text,
+        body.author.as_deref().unwrap_or(DEFAULT_AUTHOR),
+        Source::Application,
+    );
+    let node = node_service
+        .create_node_at(request, body.position.unwrap_or(usize::MAX))
+        .map_err(ApiError::Service)?;
+
+    Reply::json(201, &NodeBody::from(&node))
+}
+
+fn get_node<R, L>(graph_service: &GraphService<R, L>, node_id: Uuid) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let node = graph_service
+        .node_service
+        .get_node(&node_id)
+        .map_err(ApiError::Service)?;
+
+    Reply::json(200, &NodeBody::from(&node))
+}
+
+fn update_node<R, L>(
+    graph_service: &GraphService<R, L>,
+    node_id: Uuid,
+    body: UpdateBody,
+) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let node_service = &graph_service.node_service;
+    let node_type = body.node_type.as_deref().map(parse_node_type).transpose()?;
+    let mut node = node_service.get_node(&node_id).map_err(ApiError::Service)?;
+
+    if let Some(text) = body.text {
+        node_service
+            .update_node(&mut node, &text)
+            .map_err(ApiError::Service)?;
+    }
+
+    if let Some(note) = body.note {
+        node_service
+            .update_note(&mut node, &note)
+            .map_err(ApiError::Service)?;
+    }
+
+    if let Some(node_type) = node_type {
+        node_service
+            .set_node_type(&mut node, node_type)
+            .map_err(ApiError::Service)?;
+    }
+
+    get_node(graph_service, node_id)
+}
+
+fn delete_node<R, L>(
+    graph_service: &GraphService<R, L>,
+    node_id: Uuid,
+    params: &HashMap<String, String>,
+) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let policy = match params.get("policy") {
+        Some(policy) => DeletePolicy::from_str(policy)
+            .map_err(|_| ApiError::BadRequest(format!("unknown delete policy `{policy}`")))?,
+        None => DeletePolicy::Cascade,
+    };
+
+    let node_service = &graph_service.node_service;
+    node_service.get_node(&node_id).map_err(ApiError::Service)?;
+
+    let deleted = node_service
+        .bulk_delete(&Selection::single(node_id), policy)
+        .map_err(ApiError::Service)?;
+
+    Reply::json(200, &DeletedBody { deleted })
+}
+
+fn subtree<R, L>(graph_service: &mut GraphService<R, L>, node_id: Uuid) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    graph_service.refresh().map_err(ApiError::Service)?;
+
+    let graph = &graph_service.node_graph;
+    let tree = graph
+        .find(&node_id)
+        .and_then(|element| TreeBody::new(graph, element))
+        .ok_or(ApiError::Service(ServiceError::Domain(
+            DomainError::MissingNode(node_id),
+        )))?;
+
+    Reply::json(200, &tree)
+}
+
+fn move_node<R, L>(
+    graph_service: &GraphService<R, L>,
+    node_id: Uuid,
+    body: MoveBody,
+) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let node_service = &graph_service.node_service;
+
+    for node_id in std::iter::once(node_id).chain(body.parent_id) {
+        node_service.get_node(&node_id).map_err(ApiError::Service)?;
+    }
+
+    node_service
+        .bulk_move(
+            &Selection::single(node_id),
+            body.parent_id,
+            body.position.unwrap_or(usize::MAX),
+        )
+        .map_err(ApiError::Service)?;
+
+    get_node(graph_service, node_id)
+}
+
+fn tree<R, L>(graph_service: &mut GraphService<R, L>) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    graph_service.refresh().map_err(ApiError::Service)?;
+
+    let graph = &graph_service.node_graph;
+    let trees: Vec<TreeBody> = graph
+        .graph
+        .iter()
+        .filter_map(|element| TreeBody::new(graph, element))
+        .collect();
+
+    Reply::json(200, &trees)
+}
+
+fn search<R, L>(
+    graph_service: &GraphService<R, L>,
+    params: &HashMap<String, String>,
+) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let query = params
+        .get("q")
+        .ok_or(ApiError::BadRequest("missing `q` parameter".into()))?;
+    let query = Query::parse(query).map_err(|err| ApiError::Service(ServiceError::Domain(err)))?;
+
+    let graph = graph_service
+        .node_service
+        .search(&query)
+        .map_err(ApiError::Service)?;
+    let matches: Vec<NodeBody> = graph
+        .nodes
+        .iter()
+        .filter(|node| graph.is_match(&node.id()))
+        .map(NodeBody::from)
+        .collect();
+
+    Reply::json(200, &matches)
+}
+
+fn export_outline<R, L>(
+    graph_service: &mut GraphService<R, L>,
+    params: &HashMap<String, String>,
+) -> Result<Reply, ApiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let format = match params.get("format") {
+        Some(format) => ExportFormat::from_str(format)
+            .map_err(|_| ApiError::BadRequest(format!("unknown export format `{format}`")))?,
+        None => ExportFormat::Markdown,
+    };
+    let root = params.get("root").map(|id| parse_id(id)).transpose()?;
+    let max_depth = params
+        .get("max_depth")
+        .map(|depth| {
+            depth
+                .parse()
+                .map_err(|_| ApiError::BadRequest(format!("invalid depth `{depth}`")))
+        })
+        .transpose()?;
+    let metadata = params
+        .get("metadata")
+        .is_some_and(|metadata| metadata == "true");
+
+    graph_service.refresh().map_err(ApiError::Service)?;
+
+    let graph = &graph_service.node_graph;
+    if let Some(root) = root
+        && graph.find(&root).is_none()
+    {
+        return Err(ApiError::Service(ServiceError::Domain(
+            DomainError::MissingNode(root),
+        )));
+    }
+
+    Ok(Reply {
+        status: 200,
+        content_type: format.content_type(),
+        body: export(
+            graph,
+            format,
+            &ExportOptions {
+                root,
+                max_depth,
+                metadata,
+            },
+        ),
+    })
+}
+
+fn parse_id(id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id).map_err(|_| ApiError::BadRequest(format!("invalid node ID `{id}`")))
+}
+
+fn parse_node_type(node_type: &str) -> Result<NodeType, ApiError> {
+    NodeType::from_str(node_type)
+        .map_err(|_| ApiError::BadRequest(format!("unknown node type `{node_type}`")))
+}
+
+fn parse_body<T: DeserializeOwned>(body: &str) -> Result<T, ApiError> {
+    serde_json::from_str(body).map_err(|err| ApiError::BadRequest(err.to_string()))
+}
+
+/// Splits a query string into its decoded parameters.
+fn parse_params(query: &str) -> Result<HashMap<String, String>, ApiError> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((percent_decode(key)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+fn percent_decode(input: &str) -> Result<String, ApiError> {
+    let invalid = || ApiError::BadRequest(format!("invalid query string `{input}`"));
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = input.get(index + 1..index + 3).ok_or_else(invalid)?;
+                decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                index += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+
+    String::from_utf8(decoded).map_err(|_| invalid())
+}
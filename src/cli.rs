@@ -0,0 +1,403 @@
+use clap::{Args, Parser, Subcommand};
+use outliner::{
+    config::LogTarget,
+    domain::{
+        Selection,
+        node::{NodeType, Source},
+        view::ViewSort,
+    },
+    interfaces::{
+        export::{ExportFormat, ExportOptions},
+        import::ImportFormat,
+    },
+    services::bulk::DeletePolicy,
+};
+use std::{path::PathBuf, str::FromStr};
+use uuid::Uuid;
+
+#[derive(Parser)]
+#[command(name = "outliner", about = "A terminal outliner")]
+pub struct Cli {
+    /// Config file to read in place of the one in the XDG config directories
+    #[arg(long, global = true, env = "OUTLINER_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Outline database, overriding the configured one
+    #[arg(short, long, global = true)]
+    pub database: Option<PathBuf>,
+    /// Author recorded on new nodes, overriding the configured one
+    #[arg(long, global = true)]
+    pub author: Option<String>,
+    /// `user`, `agent` or `application`, overriding the configured source of new nodes
+    #[arg(long, global = true, value_parser = parse_arg::<Source>)]
+    pub source: Option<Source>,
+    /// `terminal`, `file` or `none`, overriding where the configured logs go
+    #[arg(long, global = true, value_parser = parse_arg::<LogTarget>)]
+    pub log: Option<LogTarget>,
+    /// Browses the outline when omitted
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Browse an outline database interactively
+    Tui {
+        /// Saved view to open at start
+        #[arg(long)]
+        view: Option<String>,
+    },
+    /// Serve the outline over a JSON API on localhost
+    Serve {
+        /// Port to listen on, overriding the configured one
+        #[arg(long)]
+        port: Option<u16>,
+        /// Bearer token clients must send, overriding the configured one. A random
+        /// token is printed when neither is set
+        #[arg(long, env = "OUTLINER_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+    },
+    /// Serve the outline to an agent over the Model Context Protocol on standard input and output
+    Mcp {
+        /// Name recorded as the author of the agent's writes, the client's own name when omitted
+        #[arg(long)]
+        agent: Option<String>,
+        /// Limit the agent to this node and everything below it
+        #[arg(long)]
+        scope: Option<Uuid>,
+    },
+    /// Convert an outline between storage backends
+    #[command(subcommand)]
+    Convert(ConvertCommand),
+    /// Inspect links between nodes
+    #[command(subcommand)]
+    Links(LinksCommand),
+    /// Merge two copies of an outline, so that both end up the same
+    Sync { local: PathBuf, remote: PathBuf },
+    /// Manage saved views, which are named searches over the outline
+    #[command(subcommand)]
+    Views(ViewsCommand),
+    /// Manage templates, which are structures of nodes to add again and again
+    #[command(subcommand)]
+    Templates(TemplatesCommand),
+    /// Manage the files attached to nodes
+    #[command(subcommand)]
+    Attachments(AttachmentsCommand),
+    /// Add a node, printing its ID
+    Add {
+        text: String,
+        /// Node to add under, the root of the outline when omitted
+        #[arg(long)]
+        parent: Option<Uuid>,
+        /// Position among the new siblings, the end when omitted
+        #[arg(long)]
+        position: Option<usize>,
+        #[arg(long = "type", default_value = "standard", value_parser = parse_arg::<NodeType>)]
+        node_type: NodeType,
+        /// Longer body shown below the text
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Show a node in a second place, sharing its text, note and children
+    Mirror {
+        node_id: Uuid,
+        /// Node to add the mirror under, the root of the outline when omitted
+        #[arg(long)]
+        parent: Option<Uuid>,
+        /// Position among the new siblings, the end when omitted
+        #[arg(long)]
+        position: Option<usize>,
+    },
+    /// Print the note of a node, or replace it
+    Note {
+        node_id: Uuid,
+        /// New note, read from standard input when `-`
+        note: Option<String>,
+    },
+    /// Print the ID of the journal entry for a day, creating it if needed
+    Journal {
+        /// `today`, `yesterday`, or a date such as `2026-10-18`
+        #[arg(default_value = "today")]
+        date: String,
+        /// Move unfinished tasks from the previous entry into a new one
+        #[arg(long)]
+        rollover: bool,
+    },
+    /// Print the earlier text and notes of a node, oldest first
+    History { node_id: Uuid },
+    /// Print a subtree in clipboard form
+    Copy { node_id: Uuid },
+    /// Print a subtree in clipboard form, so that pasting it moves the nodes
+    ///
+    /// Pasted into another outline, the nodes are added there with new IDs and
+    /// stay in this one.
+    Cut { node_id: Uuid },
+    /// Paste a subtree read from standard input
+    Paste {
+        /// Node to paste under, the root of the outline when omitted
+        #[arg(long)]
+        parent: Option<Uuid>,
+        /// Position among the new siblings, the end when omitted
+        #[arg(long)]
+        position: Option<usize>,
+    },
+    /// Deep copy a subtree with new node IDs
+    Duplicate {
+        node_id: Uuid,
+        /// Node to copy under, the original parent when omitted
+        #[arg(long)]
+        parent: Option<Uuid>,
+        /// Position among the new siblings, the end when omitted
+        #[arg(long)]
+        position: Option<usize>,
+    },
+    /// Print the outline, or one branch of it
+    Export {
+        /// Node whose subtree to print, the whole outline when omitted
+        #[arg(long)]
+        root: Option<Uuid>,
+        #[command(flatten)]
+        export: ExportArgs,
+    },
+    /// Add an outline read from a file, printing the IDs of its top-level nodes
+    Import(ImportArgs),
+    /// Print the nodes matching a search, along with their ancestors
+    Query {
+        /// Search such as `type:todo author:astra modified:>2026-10-01 "some text"`
+        query: String,
+        #[command(flatten)]
+        export: ExportArgs,
+    },
+    /// Apply one operation to several nodes at once
+    Bulk {
+        #[command(flatten)]
+        selection: SelectionArgs,
+        #[command(subcommand)]
+        action: BulkAction,
+    },
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// File to read, standard input when omitted
+    pub file: Option<PathBuf>,
+    /// `text`, `taskpaper`, `org` or `json`, going by the file extension when omitted
+    #[arg(long, value_parser = parse_arg::<ImportFormat>)]
+    pub format: Option<ImportFormat>,
+    /// Node to add under, the root of the outline when omitted
+    #[arg(long)]
+    pub parent: Option<Uuid>,
+    /// Position among the new siblings, the end when omitted
+    #[arg(long, conflicts_with = "merge")]
+    pub position: Option<usize>,
+    /// Make the nodes under the parent match the file by node ID, printing
+    /// what was created, updated, moved and deleted
+    #[arg(long)]
+    pub merge: bool,
+    /// Delete the nodes under the parent that the file leaves out
+    #[arg(long, requires = "merge")]
+    pub delete: bool,
+    /// Print what merging would change without changing anything
+    #[arg(long, requires = "merge")]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// `markdown`, `opml`, `html`, `text`, `json` or `org`
+    #[arg(long, default_value = "markdown", value_parser = parse_arg::<ExportFormat>)]
+    pub format: ExportFormat,
+    /// Levels to print below each printed node, all of them when omitted
+    #[arg(long)]
+    pub max_depth: Option<usize>,
+    /// Include the ID, author, source and times of each node
+    #[arg(long)]
+    pub metadata: bool,
+}
+
+impl ExportArgs {
+    pub fn options(&self, root: Option<Uuid>) -> ExportOptions {
+        ExportOptions {
+            root,
+            max_depth: self.max_depth,
+            metadata: self.metadata,
+        }
+    }
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+pub struct SelectionArgs {
+    /// Comma separated nodes to act on, anywhere in the outline
+    #[arg(long, value_delimiter = ',')]
+    nodes: Vec<Uuid>,
+    /// First and last of a run of siblings to act on
+    #[arg(long, num_args = 2, value_names = ["FIRST", "LAST"])]
+    range: Vec<Uuid>,
+}
+
+impl SelectionArgs {
+    pub fn selection(&self) -> Selection {
+        match self.range.as_slice() {
+            [anchor, head] => Selection::Range {
+                anchor: *anchor,
+                head: *head,
+            },
+            _ => Selection::Set(self.nodes.clone()),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum BulkAction {
+    /// Set the type of every selected node
+    SetType {
+        #[arg(value_parser = parse_arg::<NodeType>)]
+        node_type: NodeType,
+    },
+    /// Delete the selected nodes
+    Delete {
+        /// `cascade` to delete whole subtrees, `reparent` to keep the children
+        #[arg(long, default_value = "cascade", value_parser = parse_arg::<DeletePolicy>)]
+        policy: DeletePolicy,
+    },
+    /// Move the selected subtrees together
+    Move {
+        /// Node to move under, the root of the outline when omitted
+        #[arg(long)]
+        parent: Option<Uuid>,
+        /// Position among the new siblings, the end when omitted
+        #[arg(long)]
+        position: Option<usize>,
+    },
+    /// Move the selected siblings under the sibling above them
+    Indent,
+    /// Move the selected siblings out of their parent
+    Outdent,
+    /// Add a tag to every selected node
+    Tag { tag: String },
+    /// Remove a tag from every selected node
+    Untag { tag: String },
+    /// Print the selected subtrees
+    Export(ExportArgs),
+}
+
+fn parse_arg<T: FromStr>(input: &str) -> Result<T, String> {
+    input
+        .parse()
+        .map_err(|_| format!("unrecognised value `{input}`"))
+}
+
+#[derive(Subcommand)]
+pub enum ViewsCommand {
+    /// List the saved views
+    List,
+    /// Save a view, replacing any view with the same name
+    Save {
+        name: String,
+        /// Search such as `type:inprogress source:agent` or `modified:>=7d`
+        query: String,
+        /// `outline`, `created`, `modified`, `text` or `type`, with a leading `-` to reverse
+        #[arg(long, default_value = "outline", allow_hyphen_values = true)]
+        sort: ViewSort,
+        /// Node to limit the view to
+        #[arg(long)]
+        root: Option<Uuid>,
+    },
+    /// Delete a saved view
+    Delete { name: String },
+    /// Print the current results of a saved view
+    Open {
+        name: String,
+        #[command(flatten)]
+        export: ExportArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TemplatesCommand {
+    /// List the templates with the placeholders they use
+    List,
+    /// Save a template, printing its ID
+    Create {
+        name: String,
+        /// Node whose subtree becomes the template, otherwise nodes in clipboard
+        /// form are read from standard input
+        #[arg(long)]
+        from: Option<Uuid>,
+    },
+    /// Print the nodes of a template in clipboard form
+    Show {
+        /// Template ID or name
+        template: String,
+    },
+    /// Delete a template
+    Delete {
+        /// Template ID or name
+        template: String,
+    },
+    /// Add the nodes of a template to the outline, printing the IDs of the new roots
+    Use {
+        /// Template ID or name
+        template: String,
+        /// Node to add under, the root of the outline when omitted
+        #[arg(long)]
+        parent: Option<Uuid>,
+        /// Position among the new siblings, the end when omitted
+        #[arg(long)]
+        position: Option<usize>,
+        /// Value for a placeholder, such as `title=Weekly sync`
+        #[arg(long = "var", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+    },
+}
+
+fn parse_var(input: &str) -> Result<(String, String), String> {
+    input
+        .split_once('=')
+        .map(|(name, value)| (name.trim().to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("expected `name=value`, found `{input}`"))
+}
+
+#[derive(Subcommand)]
+pub enum AttachmentsCommand {
+    /// Attach a file to a node, printing the attachment ID
+    Add {
+        node_id: Uuid,
+        path: PathBuf,
+        /// Refer to the file where it is instead of storing a copy in the outline
+        #[arg(long)]
+        reference: bool,
+    },
+    /// List the files attached to a node
+    List { node_id: Uuid },
+    /// Write the contents of an attachment to a file, or to standard output
+    Extract {
+        attachment_id: Uuid,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Remove an attachment from its node
+    Remove { attachment_id: Uuid },
+}
+
+#[derive(Subcommand)]
+pub enum ConvertCommand {
+    /// Copy a SQLite database into a JSON directory
+    SqliteToJson {
+        database: PathBuf,
+        directory: PathBuf,
+    },
+    /// Copy a JSON directory into a SQLite database
+    JsonToSqlite {
+        directory: PathBuf,
+        database: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LinksCommand {
+    /// List the nodes linking to a node
+    Backlinks { node_id: Uuid },
+    /// Report links whose target node no longer exists
+    Broken,
+}
@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config file `{}`", .0.display())]
+    FileAccess(PathBuf),
+    #[error("Invalid config file `{}`: {}", .0.display(), .1)]
+    Syntax(PathBuf, String),
+    #[error("Invalid `{0}` in config file: {1}")]
+    InvalidValue(String, String),
+    #[error("Cannot find the home directory")]
+    MissingHome,
+    #[error("Failed to create directory `{}`", .0.display())]
+    DirectoryCreation(PathBuf),
+}
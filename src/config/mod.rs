@@ -0,0 +1,438 @@
+pub mod errors;
+pub use errors::ConfigError;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use ratatui::style::Color;
+use serde::Deserialize;
+
+use crate::{
+    domain::{
+        attachment::DEFAULT_SIZE_LIMIT,
+        node::Source,
+        rank::{DEFAULT_RANK_GAP, MAX_RANK_GAP, MIN_RANK_GAP},
+    },
+    services::logging::{FileLogging, LoggingService, NullLogging, TerminalLogging},
+    tui::{
+        Settings,
+        keymap::{self, Action, Key, Keymap, Mode, Preset},
+    },
+};
+
+/// Directory holding the application's files under each XDG base directory.
+const APP_DIR: &str = "outliner";
+
+/// Port the API server listens on unless configured otherwise.
+pub const DEFAULT_API_PORT: u16 = 7373;
+
+/// Where log messages are written.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LogTarget {
+    Terminal,
+    /// The configured log file
+    File,
+    None,
+}
+
+impl FromStr for LogTarget {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<LogTarget, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "terminal" => Ok(LogTarget::Terminal),
+            "file" => Ok(LogTarget::File),
+            "none" => Ok(LogTarget::None),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Per-user defaults, read from `outliner/config.toml` in the XDG config
+/// directories. Every setting is optional, for example:
+///
+/// ```toml
+/// database = "~/notes/outline.db"
+/// author = "astra"
+/// source = "user"
+/// rank_gap = 1000
+/// attachment_limit = 52428800
+///
+/// [logging]
+/// target = "file"
+/// path = "~/notes/outliner.log"
+///
+/// [journal]
+/// rollover = true
+///
+/// [api]
+/// port = 7373
+/// token = "secret"
+///
+/// [tui]
+/// preset = "vim"
+///
+/// [tui.keys]
+/// down = ["j", "down", "ctrl-n"]
+/// quit = ["Z Q"]
+///
+/// [tui.keys.insert]
+/// commit = ["esc", "ctrl-s"]
+///
+/// [tui.theme]
+/// selection = "#303446"
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Outline to open, the `outline.db` in the XDG data directory when `None`
+    pub database: Option<PathBuf>,
+    /// Author recorded on the nodes the user creates
+    pub author: String,
+    /// Source recorded on the nodes the user creates
+    pub source: Source,
+    pub rank_gap: u64,
+    /// Largest file, in bytes, that may be stored inside the outline
+    pub attachment_limit: u64,
+    pub log_target: LogTarget,
+    /// File for the `file` log target, `outliner.log` in the XDG state directory when `None`
+    pub log_file: Option<PathBuf>,
+    pub api_port: u16,
+    /// Bearer token for the API server, a random one is made up when `None`
+    pub api_token: Option<String>,
+    /// Whether new journal entries take over the unfinished tasks of the previous one
+    pub journal_rollover: bool,
+    pub tui: Settings,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    database: Option<PathBuf>,
+    author: Option<String>,
+    source: Option<String>,
+    rank_gap: Option<u64>,
+    attachment_limit: Option<u64>,
+    logging: LoggingSection,
+    journal: JournalSection,
+    api: ApiSection,
+    tui: TuiSection,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct LoggingSection {
+    target: Option<String>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct JournalSection {
+    rollover: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ApiSection {
+    port: Option<u16>,
+    token: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TuiSection {
+    preset: Option<String>,
+    keys: BTreeMap<String, KeysEntry>,
+    theme: BTreeMap<String, String>,
+}
+
+/// Keys of a normal mode action, or a table of the bindings of another mode.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeysEntry {
+    Keys(Vec<String>),
+    Mode(BTreeMap<String, Vec<String>>),
+}
+
+impl Config {
+    /// Reads the configuration from `path`, or else from the first config file
+    /// found in the XDG config directories. Defaults are used for anything not
+    /// set, or for everything when there is no file.
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let path = match path {
+            Some(path) => Some(path.to_owned()),
+            None => config_paths().into_iter().find(|path| path.is_file()),
+        };
+
+        let file = match &path {
+            Some(path) => {
+                let text =
+                    fs::read_to_string(path).map_err(|_| ConfigError::FileAccess(path.clone()))?;
+                toml::from_str(&text).map_err(|err| {
+                    ConfigError::Syntax(path.clone(), err.to_string().trim_end().to_owned())
+                })?
+            }
+            None => ConfigFile::default(),
+        };
+
+        Config::from_file(file)
+    }
+
+    fn from_file(file: ConfigFile) -> Result<Config, ConfigError> {
+        let source = match file.source {
+            Some(source) => Source::from_str(&source)
+                .map_err(|_| invalid("source", format!("unknown source `{source}`")))?,
+            None => Source::User,
+        };
+
+        let rank_gap = file.rank_gap.unwrap_or(DEFAULT_RANK_GAP);
+        if !(MIN_RANK_GAP..=MAX_RANK_GAP).contains(&rank_gap) {
+            return Err(invalid(
+                "rank_gap",
+                format!("must be between {MIN_RANK_GAP} and {MAX_RANK_GAP}"),
+            ));
+        }
+
+        let author = match file.author {
+            Some(author) if author.trim().is_empty() => {
+                return Err(invalid("author", "must not be empty".into()));
+            }
+            Some(author) => author,
+            None => default_author(),
+        };
+
+        let log_target = match file.logging.target {
+            Some(target) => LogTarget::from_str(&target).map_err(|_| {
+                invalid(
+                    "logging.target",
+                    format!("expected `terminal`, `file` or `none`, found `{target}`"),
+                )
+            })?,
+            None => LogTarget::Terminal,
+        };
+
+        if file.api.token.as_deref().is_some_and(str::is_empty) {
+            return Err(invalid("api.token", "must not be empty".into()));
+        }
+
+        Ok(Config {
+            database: file.database.map(expand_home).transpose()?,
+            author,
+            source,
+            rank_gap,
+            attachment_limit: file.attachment_limit.unwrap_or(DEFAULT_SIZE_LIMIT),
+            log_target,
+            log_file: file.logging.path.map(expand_home).transpose()?,
+            api_port: file.api.port.unwrap_or(DEFAULT_API_PORT),
+            api_token: file.api.token,
+            journal_rollover: file.journal.rollover,
+            tui: Settings {
+                rollover: file.journal.rollover,
+                ..tui_settings(file.tui)?
+            },
+        })
+    }
+
+    /// Outline to open, creating the XDG data directory for the default one.
+    pub fn database_path(&self) -> Result<PathBuf, ConfigError> {
+        if let Some(database) = &self.database {
+            return Ok(database.clone());
+        }
+
+        let directory = xdg_dir("XDG_DATA_HOME", ".local/share")?;
+        fs::create_dir_all(&directory)
+            .map_err(|_| ConfigError::DirectoryCreation(directory.clone()))?;
+
+        Ok(directory.join("outline.db"))
+    }
+
+    /// Logger for the configured target. Front ends that own standard output,
+    /// such as the TUI, get no terminal logging.
+    pub fn logger(&self, owns_stdout: bool) -> Result<Box<dyn LoggingService>, ConfigError> {
+        Ok(match self.log_target {
+            LogTarget::Terminal if !owns_stdout => Box::new(TerminalLogging::new()),
+            LogTarget::Terminal | LogTarget::None => Box::new(NullLogging::new()),
+            LogTarget::File => {
+                let path = match &self.log_file {
+                    Some(path) => path.clone(),
+                    None => xdg_dir("XDG_STATE_HOME", ".local/state")?.join("outliner.log"),
+                };
+
+                if let Some(directory) = path.parent() {
+                    fs::create_dir_all(directory)
+                        .map_err(|_| ConfigError::DirectoryCreation(directory.to_owned()))?;
+                }
+
+                Box::new(FileLogging::new(path))
+            }
+        })
+    }
+}
+
+fn tui_settings(section: TuiSection) -> Result<Settings, ConfigError> {
+    let preset = match section.preset {
+        Some(preset) => Preset::from_str(&preset).map_err(|_| {
+            invalid(
+                "tui.preset",
+                format!("expected `default`, `vim` or `emacs`, found `{preset}`"),
+            )
+        })?,
+        None => Preset::Default,
+    };
+
+    let mut settings = Settings {
+        keymap: Keymap::from_preset(preset),
+        ..Settings::default()
+    };
+    let mut bound: HashMap<(Mode, Vec<Key>), Action> = HashMap::new();
+
+    for (name, entry) in section.keys {
+        match entry {
+            KeysEntry::Keys(keys) => bind_keys(
+                &mut settings.keymap,
+                &mut bound,
+                Mode::Normal,
+                &format!("tui.keys.{name}"),
+                &name,
+                &keys,
+            )?,
+            KeysEntry::Mode(actions) => {
+                let mode = Mode::from_str(&name).map_err(|_| {
+                    invalid(
+                        &format!("tui.keys.{name}"),
+                        "expected a list of keys, or a table for the `normal`, `visual` or `insert` mode".into(),
+                    )
+                })?;
+
+                for (action, keys) in actions {
+                    let field = format!("tui.keys.{name}.{action}");
+                    bind_keys(
+                        &mut settings.keymap,
+                        &mut bound,
+                        mode,
+                        &field,
+                        &action,
+                        &keys,
+                    )?;
+                }
+            }
+        }
+    }
+
+    for (name, color) in section.theme {
+        let field = format!("tui.theme.{name}");
+        let color = Color::from_str(&color)
+            .map_err(|_| invalid(&field, format!("unknown colour `{color}`")))?;
+        let theme = &mut settings.theme;
+
+        match name.as_str() {
+            "selection" => theme.selection = color,
+            "border" => theme.border = color,
+            "breadcrumbs" => theme.breadcrumbs = color,
+            "status" => theme.status = color,
+            "code" => theme.code = color,
+            "link" => theme.link = color,
+            _ => return Err(invalid(&field, "unknown theme colour".into())),
+        }
+    }
+
+    Ok(settings)
+}
+
+/// Binds the key sequences in `keys` to the action named `name` in `mode`,
+/// recording them in `bound` to catch sequences given to two actions.
+fn bind_keys(
+    keymap: &mut Keymap,
+    bound: &mut HashMap<(Mode, Vec<Key>), Action>,
+    mode: Mode,
+    field: &str,
+    name: &str,
+    keys: &[String],
+) -> Result<(), ConfigError> {
+    let action = Action::from_str(name).map_err(|_| invalid(field, "unknown action".into()))?;
+
+    if action.edits_text() != (mode == Mode::Insert) {
+        return Err(invalid(field, "action cannot be used in this mode".into()));
+    }
+
+    let sequences = keys
+        .iter()
+        .map(|key| {
+            let parsed = keymap::parse_sequence(key)
+                .ok_or_else(|| invalid(field, format!("unknown key `{key}`")))?;
+
+            match bound.insert((mode, parsed.clone()), action) {
+                Some(other) if other != action => Err(invalid(
+                    field,
+                    format!("`{key}` is also bound to `{other}`"),
+                )),
+                _ => Ok(parsed),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    keymap.bind(mode, action, sequences);
+
+    Ok(())
+}
+
+fn invalid(field: &str, message: String) -> ConfigError {
+    ConfigError::InvalidValue(field.to_owned(), message)
+}
+
+fn default_author() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "user".to_owned())
+}
+
+/// Candidate config files, most specific first.
+fn config_paths() -> Vec<PathBuf> {
+    let user = xdg_dir("XDG_CONFIG_HOME", ".config").ok();
+    let system = env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_owned());
+
+    user.into_iter()
+        .chain(
+            env::split_paths(&system)
+                .filter(|dir| dir.is_absolute())
+                .map(|dir| dir.join(APP_DIR)),
+        )
+        .map(|dir| dir.join("config.toml"))
+        .collect()
+}
+
+/// The application's directory under the XDG base directory named by `variable`,
+/// or under `fallback` in the home directory. Relative paths in the variable are
+/// ignored, as the specification asks.
+fn xdg_dir(variable: &str, fallback: &str) -> Result<PathBuf, ConfigError> {
+    let base = match env::var_os(variable).map(PathBuf::from) {
+        Some(base) if base.is_absolute() => base,
+        _ => home()?.join(fallback),
+    };
+
+    Ok(base.join(APP_DIR))
+}
+
+fn home() -> Result<PathBuf, ConfigError> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .ok_or(ConfigError::MissingHome)
+}
+
+/// Replaces a leading `~` with the home directory.
+fn expand_home(path: PathBuf) -> Result<PathBuf, ConfigError> {
+    match path.strip_prefix("~") {
+        Ok(rest) => Ok(home()?.join(rest)),
+        Err(_) => Ok(path),
+    }
+}
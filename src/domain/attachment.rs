@@ -0,0 +1,98 @@
+use hifitime::Epoch;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Largest file stored inside the outline unless configured otherwise.
+pub const DEFAULT_SIZE_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// Number of leading bytes looked at to tell what kind of file something is.
+pub const SNIFF_LENGTH: usize = 512;
+
+/// A file attached to a node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub id: Uuid,
+    /// The canonical node the file belongs to
+    pub node_id: Uuid,
+    /// File name, without any directories
+    pub name: String,
+    pub mime_type: String,
+    /// Size in bytes when the file was attached
+    pub size: u64,
+    pub content: AttachmentContent,
+    pub added_time: Epoch,
+}
+
+/// Where the contents of an attachment are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttachmentContent {
+    /// Stored inside the outline under the SHA-256 hash of the contents, so
+    /// identical files are kept once
+    Blob(String),
+    /// Left where it is on disk and only referred to
+    Path(PathBuf),
+}
+
+/// Hex encoded SHA-256 hash of `data`, used as the key of a stored blob.
+pub fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Works out the MIME type of a file from its leading bytes, falling back on
+/// the extension of `name` to tell text formats apart.
+pub fn sniff_mime_type(data: &[u8], name: &str) -> &'static str {
+    const SIGNATURES: [(&[u8], &str); 7] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+    ];
+
+    let data = &data[..data.len().min(SNIFF_LENGTH)];
+
+    if let Some((_, mime_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| data.starts_with(signature))
+    {
+        return mime_type;
+    }
+
+    if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+
+    if !is_text(data) {
+        return "application/octet-stream";
+    }
+
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("md" | "markdown") => "text/markdown",
+        Some("json") => "application/json",
+        Some("csv") => "text/csv",
+        Some("html" | "htm") => "text/html",
+        Some("svg") => "image/svg+xml",
+        Some("xml" | "opml") => "application/xml",
+        _ => "text/plain",
+    }
+}
+
+/// Whether `data` reads as UTF-8 text, allowing for a character cut off at the end.
+fn is_text(data: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(data) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+
+    valid && !data.contains(&0)
+}
@@ -0,0 +1,96 @@
+use hifitime::Epoch;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::node::{NodeType, Source};
+
+pub mod sequence;
+pub use sequence::Sequence;
+
+pub mod text;
+pub use text::{Text, TextEdit};
+
+pub mod tree;
+pub use tree::{Location, MoveTree};
+
+pub mod replica;
+pub use replica::Replica;
+
+/// Lamport timestamp identifying an operation. Timestamps from different
+/// replicas are ordered by counter first and replica ID second, giving every
+/// replica the same total order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Timestamp {
+    pub counter: u64,
+    pub replica: Uuid,
+}
+
+impl Timestamp {
+    /// Timestamp `offset` places after this one from the same replica.
+    pub fn offset(&self, offset: u64) -> Timestamp {
+        Timestamp {
+            counter: self.counter + offset,
+            replica: self.replica,
+        }
+    }
+}
+
+/// A change made by one replica, to be applied by every other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    pub id: Timestamp,
+    /// Wall clock time of the change, only used for the nodes' modified times
+    pub time: Epoch,
+    pub kind: OperationKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OperationKind {
+    Create {
+        node_id: Uuid,
+        node_type: NodeType,
+        author: String,
+        source_type: Source,
+        mirror_of: Option<Uuid>,
+    },
+    /// Places a node after the sibling element `after`, or first when `None`.
+    /// The move's own ID becomes the node's element among its new siblings.
+    Move {
+        node_id: Uuid,
+        location: Location,
+        after: Option<Timestamp>,
+    },
+    SetType {
+        node_id: Uuid,
+        node_type: NodeType,
+    },
+    InsertText {
+        node_id: Uuid,
+        after: Option<Timestamp>,
+        text: String,
+    },
+    RemoveText {
+        node_id: Uuid,
+        removed: Vec<Timestamp>,
+    },
+}
+
+impl Operation {
+    pub fn node_id(&self) -> Uuid {
+        match &self.kind {
+            OperationKind::Create { node_id, .. }
+            | OperationKind::Move { node_id, .. }
+            | OperationKind::SetType { node_id, .. }
+            | OperationKind::InsertText { node_id, .. }
+            | OperationKind::RemoveText { node_id, .. } => *node_id,
+        }
+    }
+
+    /// Number of timestamps the operation uses up, one per inserted character.
+    fn span(&self) -> u64 {
+        match &self.kind {
+            OperationKind::InsertText { text, .. } => text.chars().count().max(1) as u64,
+            _ => 1,
+        }
+    }
+}
@@ -0,0 +1,635 @@
+use std::collections::{HashMap, HashSet};
+
+use hifitime::Epoch;
+use uuid::Uuid;
+
+use crate::domain::{
+    DomainError, Node,
+    crdt::{Location, MoveTree, Operation, OperationKind, Sequence, Text, Timestamp},
+    node::{CreateNodeRequest, NodeType, Source},
+    rank::spread_ranks,
+};
+
+/// One copy of a collaboratively edited outline.
+///
+/// Sibling order is a [`Sequence`] per parent, parents come from a [`MoveTree`]
+/// and each node's text is a [`Text`]. Local edits return the operations to
+/// send to the other replicas, and once every replica has applied the same
+/// operations, in any order, they all hold the same outline. Node ranks are
+/// not replicated, but derived from sibling order when nodes are read.
+#[derive(Debug, Clone)]
+pub struct Replica {
+    id: Uuid,
+    /// Spacing of the ranks derived from sibling order
+    rank_gap: u64,
+    counter: u64,
+    tree: MoveTree,
+    siblings: HashMap<Location, Sequence<Uuid>>,
+    nodes: HashMap<Uuid, NodeState>,
+    applied: HashSet<Timestamp>,
+    history: Vec<Operation>,
+    /// Operations received before the ones they depend on
+    pending: Vec<Operation>,
+}
+
+#[derive(Debug, Clone)]
+struct NodeState {
+    created_time: Epoch,
+    modified_time: Epoch,
+    /// Last writer wins, by operation ID
+    node_type: (NodeType, Timestamp),
+    author: String,
+    source_type: Source,
+    mirror_of: Option<Uuid>,
+    text: Text,
+}
+
+impl Replica {
+    pub fn new(id: Uuid, rank_gap: u64) -> Self {
+        Self {
+            id,
+            rank_gap,
+            counter: 0,
+            tree: MoveTree::new(),
+            siblings: HashMap::new(),
+            nodes: HashMap::new(),
+            applied: HashSet::new(),
+            history: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn rank_gap(&self) -> u64 {
+        self.rank_gap
+    }
+
+    /// Every operation applied so far, in the order it was applied. Applying
+    /// them to a new replica brings it up to date.
+    pub fn history(&self) -> &[Operation] {
+        &self.history
+    }
+
+    /// Number of received operations still waiting for ones they depend on.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Applies an operation from another replica. Operations may arrive in any
+    /// order and more than once; ones depending on operations not yet seen are
+    /// held back until those arrive.
+    pub fn apply(&mut self, operation: Operation) {
+        if self.applied.contains(&operation.id)
+            || self
+                .pending
+                .iter()
+                .any(|pending| pending.id == operation.id)
+        {
+            return;
+        }
+
+        self.pending.push(operation);
+
+        while let Some(index) = self
+            .pending
+            .iter()
+            .position(|operation| self.is_ready(operation))
+        {
+            let operation = self.pending.remove(index);
+            self.integrate(operation);
+        }
+    }
+
+    /// Adds the nodes of an existing outline, keeping their IDs, times and order.
+    /// Nodes whose parent is missing are left out.
+    pub fn seed(&mut self, nodes: &[Node]) -> Result<Vec<Operation>, DomainError> {
+        let mut children: HashMap<Option<Uuid>, Vec<&Node>> = HashMap::new();
+        for node in nodes {
+            children.entry(node.parent_id()).or_default().push(node);
+        }
+
+        let mut operations = Vec::new();
+        let mut parents = vec![None];
+
+        while let Some(parent_id) = parents.pop() {
+            let Some(siblings) = children.get_mut(&parent_id) else {
+                continue;
+            };
+            siblings.sort_by_key(|node| (node.rank(), node.id()));
+
+            for (position, node) in siblings.iter().enumerate() {
+                operations.extend(self.insert_node(
+                    node.id(),
+                    request_of(node),
+                    position,
+                    node.created_time(),
+                    node.modified_time(),
+                )?);
+                parents.push(Some(node.id()));
+            }
+        }
+
+        Ok(operations)
+    }
+
+    /// Creates a node at `position` among its siblings. The request's rank is
+    /// ignored, as ranks follow from sibling order.
+    pub fn create_node(
+        &mut self,
+        request: CreateNodeRequest,
+        position: usize,
+    ) -> Result<(Uuid, Vec<Operation>), DomainError> {
+        let node_id = Uuid::new_v4();
+        let now = Epoch::now().map_err(|_| DomainError::InvalidDateTime)?;
+
+        let operations = self.insert_node(node_id, request, position, now, now)?;
+
+        Ok((node_id, operations))
+    }
+
+    /// Adds an existing node, keeping its ID and times, directly after the
+    /// sibling `after`, or first when `None`. The node's rank is ignored.
+    pub fn add_node(
+        &mut self,
+        node: &Node,
+        after: Option<Uuid>,
+    ) -> Result<Vec<Operation>, DomainError> {
+        let location = self.location_of(node.parent_id())?;
+        let position = self.position_after(location, after, &node.id())?;
+
+        self.insert_node(
+            node.id(),
+            request_of(node),
+            position,
+            node.created_time(),
+            node.modified_time(),
+        )
+    }
+
+    /// Moves a node to `position` among the children of `parent_id`, counted
+    /// without the node itself.
+    pub fn move_node(
+        &mut self,
+        node_id: Uuid,
+        parent_id: Option<Uuid>,
+        position: usize,
+    ) -> Result<Vec<Operation>, DomainError> {
+        self.check_visible(&node_id)?;
+        let location = self.location_of(parent_id)?;
+
+        self.relocate(node_id, location, position)
+    }
+
+    /// Moves a node directly after the sibling `after` under `parent_id`, or
+    /// first when `None`, bringing it back when it has been deleted. Nothing
+    /// is sent when the node is already there.
+    pub fn place_node(
+        &mut self,
+        node_id: Uuid,
+        parent_id: Option<Uuid>,
+        after: Option<Uuid>,
+    ) -> Result<Vec<Operation>, DomainError> {
+        if !self.contains(&node_id) {
+            return Err(DomainError::MissingNode(node_id));
+        }
+        let location = self.location_of(parent_id)?;
+
+        let children = self.children(location);
+        if let Some(index) = children
+            .iter()
+            .position(|(_, child_id)| *child_id == node_id)
+            && index.checked_sub(1).map(|previous| children[previous].1) == after
+        {
+            return Ok(Vec::new());
+        }
+
+        let position = self.position_after(location, after, &node_id)?;
+
+        self.relocate(node_id, location, position)
+    }
+
+    /// Deletes a node along with its subtree.
+    pub fn delete_node(&mut self, node_id: Uuid) -> Result<Vec<Operation>, DomainError> {
+        self.check_visible(&node_id)?;
+
+        let operation = self.local(
+            OperationKind::Move {
+                node_id,
+                location: Location::Trash,
+                after: None,
+            },
+            Epoch::now().map_err(|_| DomainError::InvalidDateTime)?,
+        );
+
+        Ok(vec![operation])
+    }
+
+    /// Replaces the text of a node. Only the changed characters are sent, so
+    /// edits made concurrently elsewhere in the text are kept.
+    pub fn set_text(&mut self, node_id: Uuid, text: &str) -> Result<Vec<Operation>, DomainError> {
+        self.check_visible(&node_id)?;
+
+        let now = Epoch::now().map_err(|_| DomainError::InvalidDateTime)?;
+        let edit = self.nodes[&node_id].text.diff(text);
+        let mut operations = Vec::new();
+
+        if !edit.removed.is_empty() {
+            operations.push(self.local(
+                OperationKind::RemoveText {
+                    node_id,
+                    removed: edit.removed,
+                },
+                now,
+            ));
+        }
+
+        if !edit.inserted.is_empty() {
+            operations.push(self.local(
+                OperationKind::InsertText {
+                    node_id,
+                    after: edit.after,
+                    text: edit.inserted,
+                },
+                now,
+            ));
+        }
+
+        Ok(operations)
+    }
+
+    pub fn set_node_type(
+        &mut self,
+        node_id: Uuid,
+        node_type: NodeType,
+    ) -> Result<Vec<Operation>, DomainError> {
+        self.check_visible(&node_id)?;
+
+        let operation = self.local(
+            OperationKind::SetType { node_id, node_type },
+            Epoch::now().map_err(|_| DomainError::InvalidDateTime)?,
+        );
+
+        Ok(vec![operation])
+    }
+
+    /// Whether `node_id` has been created here, even when it has since been deleted.
+    pub fn contains(&self, node_id: &Uuid) -> bool {
+        self.nodes.contains_key(node_id)
+    }
+
+    /// Whether `node_id` is part of the outline, directly under `parent_id`.
+    pub fn is_child_of(&self, node_id: &Uuid, parent_id: Option<Uuid>) -> bool {
+        self.is_visible(node_id)
+            && self
+                .tree
+                .placement(node_id)
+                .is_some_and(|(location, _)| location == Location::of(parent_id))
+    }
+
+    /// IDs of the children of `parent_id`, in order.
+    pub fn child_ids(&self, parent_id: Option<Uuid>) -> Vec<Uuid> {
+        self.children(Location::of(parent_id))
+            .into_iter()
+            .map(|(_, child_id)| child_id)
+            .collect()
+    }
+
+    /// Returns a node as it currently stands, unless it has been deleted.
+    pub fn node(&self, node_id: &Uuid) -> Option<Node> {
+        let (location, _) = self.tree.placement(node_id)?;
+        self.is_visible(node_id).then_some(())?;
+
+        let position = self
+            .children(location)
+            .iter()
+            .position(|(_, child_id)| child_id == node_id)?;
+
+        Some(self.build_node(
+            *node_id,
+            location,
+            (position as u64 + 1).saturating_mul(self.rank_gap),
+        ))
+    }
+
+    /// Returns every node of the outline, parents before their children, with
+    /// ranks spread evenly by sibling order.
+    pub fn nodes(&self) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut locations = vec![Location::Root];
+
+        while let Some(location) = locations.pop() {
+            let children = self.children(location);
+
+            for ((_, node_id), rank) in children
+                .iter()
+                .zip(spread_ranks(children.len(), self.rank_gap))
+            {
+                nodes.push(self.build_node(*node_id, location, rank));
+                locations.push(Location::Under(*node_id));
+            }
+        }
+
+        nodes
+    }
+
+    fn insert_node(
+        &mut self,
+        node_id: Uuid,
+        request: CreateNodeRequest,
+        position: usize,
+        created_time: Epoch,
+        modified_time: Epoch,
+    ) -> Result<Vec<Operation>, DomainError> {
+        let location = self.location_of(request.parent_id)?;
+        let after = self.anchor(location, position, &node_id);
+
+        let mut operations = vec![
+            self.local(
+                OperationKind::Create {
+                    node_id,
+                    node_type: request.node_type,
+                    author: request.author,
+                    source_type: request.source_type,
+                    mirror_of: request.mirror_of,
+                },
+                created_time,
+            ),
+            self.local(
+                OperationKind::Move {
+                    node_id,
+                    location,
+                    after,
+                },
+                modified_time,
+            ),
+        ];
+
+        if !request.text.is_empty() {
+            operations.push(self.local(
+                OperationKind::InsertText {
+                    node_id,
+                    after: None,
+                    text: request.text,
+                },
+                modified_time,
+            ));
+        }
+
+        Ok(operations)
+    }
+
+    fn relocate(
+        &mut self,
+        node_id: Uuid,
+        location: Location,
+        position: usize,
+    ) -> Result<Vec<Operation>, DomainError> {
+        if self.tree.creates_cycle(&node_id, location) {
+            return Err(DomainError::CyclicMove(node_id));
+        }
+
+        let after = self.anchor(location, position, &node_id);
+        let operation = self.local(
+            OperationKind::Move {
+                node_id,
+                location,
+                after,
+            },
+            Epoch::now().map_err(|_| DomainError::InvalidDateTime)?,
+        );
+
+        Ok(vec![operation])
+    }
+
+    /// Makes an operation with the next timestamp and applies it here.
+    fn local(&mut self, kind: OperationKind, time: Epoch) -> Operation {
+        let operation = Operation {
+            id: Timestamp {
+                counter: self.counter + 1,
+                replica: self.id,
+            },
+            time,
+            kind,
+        };
+
+        self.integrate(operation.clone());
+
+        operation
+    }
+
+    fn is_ready(&self, operation: &Operation) -> bool {
+        let created = |node_id: &Uuid| self.nodes.contains_key(node_id);
+
+        match &operation.kind {
+            OperationKind::Create { .. } => true,
+            OperationKind::Move {
+                node_id,
+                location,
+                after,
+            } => {
+                let parent_created = match location {
+                    Location::Under(parent_id) => created(parent_id),
+                    Location::Root | Location::Trash => true,
+                };
+                let after_received = after.is_none_or(|after| {
+                    self.siblings
+                        .get(location)
+                        .is_some_and(|siblings| siblings.contains(&after))
+                });
+
+                created(node_id) && parent_created && after_received
+            }
+            OperationKind::SetType { node_id, .. } => created(node_id),
+            OperationKind::InsertText { node_id, after, .. } => self
+                .nodes
+                .get(node_id)
+                .is_some_and(|node| after.is_none_or(|after| node.text.contains(&after))),
+            OperationKind::RemoveText { node_id, removed } => self
+                .nodes
+                .get(node_id)
+                .is_some_and(|node| removed.iter().all(|id| node.text.contains(id))),
+        }
+    }
+
+    fn integrate(&mut self, operation: Operation) {
+        self.counter = self
+            .counter
+            .max(operation.id.counter + operation.span() - 1);
+
+        match &operation.kind {
+            OperationKind::Create {
+                node_id,
+                node_type,
+                author,
+                source_type,
+                mirror_of,
+            } => {
+                self.nodes.entry(*node_id).or_insert_with(|| NodeState {
+                    created_time: operation.time,
+                    modified_time: operation.time,
+                    node_type: (node_type.clone(), operation.id),
+                    author: author.clone(),
+                    source_type: source_type.clone(),
+                    mirror_of: *mirror_of,
+                    text: Text::new(),
+                });
+            }
+            OperationKind::Move {
+                node_id,
+                location,
+                after,
+            } => {
+                if *location != Location::Trash {
+                    self.siblings.entry(*location).or_default().insert(
+                        operation.id,
+                        *after,
+                        *node_id,
+                    );
+                }
+                self.tree.apply(operation.id, *node_id, *location);
+            }
+            OperationKind::SetType { node_id, node_type } => {
+                if let Some(node) = self.nodes.get_mut(node_id)
+                    && operation.id > node.node_type.1
+                {
+                    node.node_type = (node_type.clone(), operation.id);
+                }
+            }
+            OperationKind::InsertText {
+                node_id,
+                after,
+                text,
+            } => {
+                if let Some(node) = self.nodes.get_mut(node_id) {
+                    node.text.insert(operation.id, *after, text);
+                }
+            }
+            OperationKind::RemoveText { node_id, removed } => {
+                if let Some(node) = self.nodes.get_mut(node_id) {
+                    node.text.remove(removed);
+                }
+            }
+        }
+
+        if let Some(node) = self.nodes.get_mut(&operation.node_id()) {
+            node.modified_time = node.modified_time.max(operation.time);
+        }
+
+        self.applied.insert(operation.id);
+        self.history.push(operation);
+    }
+
+    /// Children currently placed under `location`, in order, along with the
+    /// sibling element placing each of them. Elements left behind by moves
+    /// that have since been superseded are skipped.
+    fn children(&self, location: Location) -> Vec<(Timestamp, Uuid)> {
+        let Some(siblings) = self.siblings.get(&location) else {
+            return Vec::new();
+        };
+
+        siblings
+            .iter()
+            .filter(|(id, node_id)| self.tree.placement(node_id) == Some((location, *id)))
+            .map(|(id, node_id)| (id, *node_id))
+            .collect()
+    }
+
+    /// Sibling element to insert after to land at `position`, not counting `node_id`.
+    fn anchor(&self, location: Location, position: usize, node_id: &Uuid) -> Option<Timestamp> {
+        let siblings: Vec<Timestamp> = self
+            .children(location)
+            .into_iter()
+            .filter(|(_, child_id)| child_id != node_id)
+            .map(|(id, _)| id)
+            .collect();
+
+        siblings[..position.min(siblings.len())].last().copied()
+    }
+
+    /// Position just after the sibling `after` under `location`, not counting `node_id`.
+    fn position_after(
+        &self,
+        location: Location,
+        after: Option<Uuid>,
+        node_id: &Uuid,
+    ) -> Result<usize, DomainError> {
+        let Some(after) = after else {
+            return Ok(0);
+        };
+
+        self.children(location)
+            .iter()
+            .filter(|(_, child_id)| child_id != node_id)
+            .position(|(_, child_id)| *child_id == after)
+            .map(|index| index + 1)
+            .ok_or(DomainError::MissingNode(after))
+    }
+
+    fn location_of(&self, parent_id: Option<Uuid>) -> Result<Location, DomainError> {
+        match parent_id {
+            Some(parent_id) => {
+                self.check_visible(&parent_id)?;
+                Ok(Location::Under(parent_id))
+            }
+            None => Ok(Location::Root),
+        }
+    }
+
+    fn check_visible(&self, node_id: &Uuid) -> Result<(), DomainError> {
+        match self.is_visible(node_id) {
+            true => Ok(()),
+            false => Err(DomainError::MissingNode(*node_id)),
+        }
+    }
+
+    /// Whether a node is part of the outline, rather than in a deleted subtree.
+    fn is_visible(&self, node_id: &Uuid) -> bool {
+        let mut current = *node_id;
+
+        loop {
+            match self.tree.placement(&current) {
+                Some((Location::Root, _)) => return true,
+                Some((Location::Under(parent_id), _)) => current = parent_id,
+                Some((Location::Trash, _)) | None => return false,
+            }
+        }
+    }
+
+    fn build_node(&self, node_id: Uuid, location: Location, rank: u64) -> Node {
+        let state = &self.nodes[&node_id];
+        let parent_id = match location {
+            Location::Under(parent_id) => Some(parent_id),
+            Location::Root | Location::Trash => None,
+        };
+
+        let mut request = CreateNodeRequest::new(
+            parent_id,
+            rank,
+            state.node_type.0.clone(),
+            &state.text.to_string(),
+            &state.author,
+            state.source_type.clone(),
+        );
+        request.mirror_of = state.mirror_of;
+
+        Node::from_parts(node_id, request, state.created_time, state.modified_time)
+    }
+}
+
+/// Request creating a copy of `node`, with the rank left to sibling order.
+fn request_of(node: &Node) -> CreateNodeRequest {
+    let mut request = CreateNodeRequest::new(
+        node.parent_id(),
+        0,
+        node.node_type().clone(),
+        node.text(),
+        node.author(),
+        node.source_type().clone(),
+    );
+    request.mirror_of = node.mirror_of();
+
+    request
+}
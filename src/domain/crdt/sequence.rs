@@ -0,0 +1,91 @@
+use crate::domain::crdt::Timestamp;
+
+/// Replicated list where each element is inserted after another and never
+/// moves. Concurrent inserts after the same element are ordered newest first,
+/// so every replica ends up with the same order whatever order the inserts
+/// arrive in. Removed elements stay behind as tombstones, as later inserts
+/// may still refer to them.
+#[derive(Debug, Clone)]
+pub struct Sequence<T> {
+    elements: Vec<Element<T>>,
+}
+
+#[derive(Debug, Clone)]
+struct Element<T> {
+    id: Timestamp,
+    value: T,
+    removed: bool,
+}
+
+impl<T> Default for Sequence<T> {
+    fn default() -> Self {
+        Self {
+            elements: Vec::new(),
+        }
+    }
+}
+
+impl<T> Sequence<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, id: &Timestamp) -> bool {
+        self.position(id).is_some()
+    }
+
+    /// Inserts `value` directly after the element `after`, or at the start when
+    /// `None`. The element `after` must already be part of the sequence, and
+    /// inserting an ID a second time does nothing.
+    pub fn insert(&mut self, id: Timestamp, after: Option<Timestamp>, value: T) {
+        if self.contains(&id) {
+            return;
+        }
+
+        let mut index = match after {
+            Some(after) => match self.position(&after) {
+                Some(position) => position + 1,
+                None => return,
+            },
+            None => 0,
+        };
+
+        // skip past inserts made concurrently after the same element that are
+        // newer than this one, along with everything inserted after them
+        while index < self.elements.len() && self.elements[index].id > id {
+            index += 1;
+        }
+
+        self.elements.insert(
+            index,
+            Element {
+                id,
+                value,
+                removed: false,
+            },
+        );
+    }
+
+    /// Marks the element `id` as removed, returning whether it was found.
+    pub fn remove(&mut self, id: &Timestamp) -> bool {
+        match self.position(id) {
+            Some(index) => {
+                self.elements[index].removed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Elements that have not been removed, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (Timestamp, &T)> {
+        self.elements
+            .iter()
+            .filter(|element| !element.removed)
+            .map(|element| (element.id, &element.value))
+    }
+
+    fn position(&self, id: &Timestamp) -> Option<usize> {
+        self.elements.iter().position(|element| element.id == *id)
+    }
+}
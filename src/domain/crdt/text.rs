@@ -0,0 +1,82 @@
+use std::fmt::{self, Write};
+
+use crate::domain::crdt::{Sequence, Timestamp};
+
+/// Replicated node text, a sequence of characters. Characters inserted
+/// together take consecutive IDs starting from the ID of the insert.
+#[derive(Debug, Clone, Default)]
+pub struct Text {
+    chars: Sequence<char>,
+}
+
+/// Change turning one text into another, found by [`Text::diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub removed: Vec<Timestamp>,
+    /// Character the new text is inserted after, `None` being the start
+    pub after: Option<Timestamp>,
+    pub inserted: String,
+}
+
+impl Text {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, id: &Timestamp) -> bool {
+        self.chars.contains(id)
+    }
+
+    pub fn insert(&mut self, id: Timestamp, after: Option<Timestamp>, text: &str) {
+        let mut after = after;
+
+        for (offset, char) in text.chars().enumerate() {
+            let char_id = id.offset(offset as u64);
+            self.chars.insert(char_id, after, char);
+            after = Some(char_id);
+        }
+    }
+
+    pub fn remove(&mut self, ids: &[Timestamp]) {
+        for id in ids {
+            self.chars.remove(id);
+        }
+    }
+
+    /// Finds the smallest single replacement turning this text into `text`,
+    /// keeping the characters both share at the start and end.
+    pub fn diff(&self, text: &str) -> TextEdit {
+        let current: Vec<(Timestamp, char)> =
+            self.chars.iter().map(|(id, char)| (id, *char)).collect();
+        let target: Vec<char> = text.chars().collect();
+
+        let prefix = current
+            .iter()
+            .zip(&target)
+            .take_while(|((_, current), target)| current == *target)
+            .count();
+        let suffix = current[prefix..]
+            .iter()
+            .rev()
+            .zip(target[prefix..].iter().rev())
+            .take_while(|((_, current), target)| current == *target)
+            .count();
+
+        TextEdit {
+            removed: current[prefix..current.len() - suffix]
+                .iter()
+                .map(|(id, _)| *id)
+                .collect(),
+            after: prefix.checked_sub(1).map(|index| current[index].0),
+            inserted: target[prefix..target.len() - suffix].iter().collect(),
+        }
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chars
+            .iter()
+            .try_for_each(|(_, char)| f.write_char(*char))
+    }
+}
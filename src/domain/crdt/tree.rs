@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::crdt::Timestamp;
+
+/// Where a node of a replicated outline has been placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Location {
+    Root,
+    Under(Uuid),
+    /// Deleted nodes are moved here, taking their subtree with them
+    Trash,
+}
+
+impl Location {
+    /// Location of the children of `parent_id`, the top level when `None`.
+    pub fn of(parent_id: Option<Uuid>) -> Location {
+        match parent_id {
+            Some(parent_id) => Location::Under(parent_id),
+            None => Location::Root,
+        }
+    }
+}
+
+/// Replicated parent pointers, where every change of parent is a move.
+///
+/// Moves are applied in timestamp order whatever order they arrive in: a move
+/// older than ones already applied undoes them, takes effect, then redoes them.
+/// A move that would make a node its own ancestor is skipped, so concurrent
+/// moves can never form a cycle, and the later of two conflicting moves is the
+/// one that loses.
+#[derive(Debug, Clone, Default)]
+pub struct MoveTree {
+    log: Vec<LogEntry>,
+    /// Current location of each node, and the move that put it there
+    placements: HashMap<Uuid, (Location, Timestamp)>,
+}
+
+#[derive(Debug, Clone)]
+struct LogEntry {
+    id: Timestamp,
+    node_id: Uuid,
+    location: Location,
+    previous: Option<(Location, Timestamp)>,
+}
+
+impl MoveTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the location of `node_id` and the ID of the move that put it there.
+    pub fn placement(&self, node_id: &Uuid) -> Option<(Location, Timestamp)> {
+        self.placements.get(node_id).copied()
+    }
+
+    pub fn apply(&mut self, id: Timestamp, node_id: Uuid, location: Location) {
+        let index = self.log.partition_point(|entry| entry.id < id);
+
+        if self.log.get(index).is_some_and(|entry| entry.id == id) {
+            return;
+        }
+
+        let later = self.log.split_off(index);
+        for entry in later.iter().rev() {
+            self.undo(entry);
+        }
+
+        let entry = self.perform(id, node_id, location);
+        self.log.push(entry);
+
+        for entry in later {
+            let redone = self.perform(entry.id, entry.node_id, entry.location);
+            self.log.push(redone);
+        }
+    }
+
+    /// Whether moving `node_id` to `location` would make it its own ancestor.
+    pub fn creates_cycle(&self, node_id: &Uuid, location: Location) -> bool {
+        let mut current = location;
+
+        while let Location::Under(parent_id) = current {
+            if parent_id == *node_id {
+                return true;
+            }
+
+            current = match self.placements.get(&parent_id) {
+                Some((location, _)) => *location,
+                None => return false,
+            };
+        }
+
+        false
+    }
+
+    fn perform(&mut self, id: Timestamp, node_id: Uuid, location: Location) -> LogEntry {
+        let previous = self.placement(&node_id);
+
+        if !self.creates_cycle(&node_id, location) {
+            self.placements.insert(node_id, (location, id));
+        }
+
+        LogEntry {
+            id,
+            node_id,
+            location,
+            previous,
+        }
+    }
+
+    fn undo(&mut self, entry: &LogEntry) {
+        match entry.previous {
+            Some(previous) => self.placements.insert(entry.node_id, previous),
+            None => self.placements.remove(&entry.node_id),
+        };
+    }
+}
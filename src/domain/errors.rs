@@ -1,9 +1,32 @@
 use thiserror::Error;
+use uuid::Uuid;
 
 #[derive(Error, Debug)]
 pub enum DomainError {
     #[error("Invalid datetime string provided")]
     InvalidDateTime,
+    #[error("Invalid date `{0}`, expected `today`, `yesterday`, `3d` or `YYYY-MM-DD`")]
+    InvalidDate(String),
     #[error("Failed to parse supplied field: `{0}`")]
     FieldParseError(String),
+    #[error("Node `{0}` is not part of the graph")]
+    MissingNode(Uuid),
+    #[error("Clipboard contents are not a valid outline")]
+    ClipboardFormat,
+    #[error("Selection range must start and end on siblings")]
+    InvalidSelection,
+    #[error("Invalid tag: `{0}`")]
+    InvalidTag(String),
+    #[error("Invalid query at character {0}: {1}")]
+    InvalidQuery(usize, String),
+    #[error("View name must not be empty")]
+    InvalidViewName,
+    #[error("Template name must not be empty")]
+    InvalidTemplateName,
+    #[error("Template must contain at least one node")]
+    EmptyTemplate,
+    #[error("No value given for template placeholder `{0}`")]
+    MissingTemplateValue(String),
+    #[error("Cannot move node `{0}` into its own subtree")]
+    CyclicMove(Uuid),
 }
@@ -0,0 +1,24 @@
+use hifitime::Epoch;
+
+use crate::domain::{DomainError, query::parse_date};
+
+/// Text of the top level node the journal lives under.
+pub const JOURNAL_TITLE: &str = "Journal";
+
+/// Parses the day of a journal entry, written as for `created:` searches.
+pub fn parse_day(date: &str) -> Result<Epoch, DomainError> {
+    parse_date(date.trim()).ok_or_else(|| DomainError::InvalidDate(date.to_owned()))
+}
+
+/// Texts of the year, month and day nodes leading from the journal to the
+/// entry for `date`, such as `2026`, `2026-10` and `2026-10-18`. Each sorts
+/// after the entries before it.
+pub fn entry_path(date: Epoch) -> [String; 3] {
+    let (year, month, day, ..) = date.to_gregorian_utc();
+
+    [
+        format!("{year:04}"),
+        format!("{year:04}-{month:02}"),
+        format!("{year:04}-{month:02}-{day:02}"),
+    ]
+}
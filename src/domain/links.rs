@@ -0,0 +1,87 @@
+use uuid::Uuid;
+
+/// Delimiters recognised around a linked node ID, `[[id]]` and `((id))`.
+const LINK_DELIMITERS: [(&str, &str); 2] = [("[[", "]]"), ("((", "))")];
+
+/// A node reference found in a node's text.
+#[derive(Debug, PartialEq)]
+pub struct Link {
+    pub target_id: Uuid,
+    /// Byte range of the whole reference, delimiters included
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Finds every `[[id]]` and `((id))` reference in `text`, in order of appearance.
+/// References whose contents are not a valid node ID are ignored.
+pub fn find_links(text: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut position = 0;
+
+    while position < text.len() {
+        let next = LINK_DELIMITERS
+            .iter()
+            .filter_map(|(open, close)| {
+                text[position..]
+                    .find(open)
+                    .map(|offset| (position + offset, *open, *close))
+            })
+            .min_by_key(|(start, _, _)| *start);
+
+        let Some((start, open, close)) = next else {
+            break;
+        };
+
+        let inner_start = start + open.len();
+        let Some(inner_len) = text[inner_start..].find(close) else {
+            break;
+        };
+
+        let end = inner_start + inner_len + close.len();
+
+        match Uuid::parse_str(text[inner_start..inner_start + inner_len].trim()) {
+            Ok(target_id) => {
+                links.push(Link {
+                    target_id,
+                    start,
+                    end,
+                });
+                position = end;
+            }
+            Err(_) => position = inner_start,
+        }
+    }
+
+    links
+}
+
+/// Returns the distinct node IDs referenced by `text`.
+pub fn parse_links(text: &str) -> Vec<Uuid> {
+    let mut targets: Vec<Uuid> = Vec::new();
+
+    for link in find_links(text) {
+        if !targets.contains(&link.target_id) {
+            targets.push(link.target_id);
+        }
+    }
+
+    targets
+}
+
+/// Rewrites every reference in `text` with `label`, leaving references it
+/// returns `None` for untouched.
+pub fn replace_links(text: &str, label: impl Fn(&Uuid) -> Option<String>) -> String {
+    let mut replaced = String::with_capacity(text.len());
+    let mut position = 0;
+
+    for link in find_links(text) {
+        if let Some(label) = label(&link.target_id) {
+            replaced.push_str(&text[position..link.start]);
+            replaced.push_str(&label);
+            position = link.end;
+        }
+    }
+
+    replaced.push_str(&text[position..]);
+    replaced
+}
@@ -0,0 +1,333 @@
+use uuid::Uuid;
+
+use crate::domain::links::{Link, find_links};
+
+/// Emphasis applied to a run of node text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Emphasis {
+    pub bold: bool,
+    pub italic: bool,
+    pub strikethrough: bool,
+    pub code: bool,
+}
+
+/// Where a linked run of text points.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Url(String),
+    /// A `[[id]]` or `((id))` reference, whose text is the reference itself
+    Node(Uuid),
+}
+
+/// A run of node text sharing one emphasis and link.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inline {
+    pub text: String,
+    pub emphasis: Emphasis,
+    pub link: Option<Target>,
+}
+
+/// Splits node text into runs by its inline Markdown: `**bold**`, `*italic*`,
+/// `` `code` ``, `~~strikethrough~~` and `[label](url)`, along with node
+/// references. Underscores work as for asterisks, except inside words, and a
+/// backslash makes the punctuation after it literal. Delimiters without a
+/// closing match are kept as text.
+pub fn parse_inline(text: &str) -> Vec<Inline> {
+    let parser = Parser {
+        text,
+        node_links: find_links(text),
+    };
+
+    let mut inlines = Vec::new();
+    parser.parse(0, text.len(), Emphasis::default(), None, &mut inlines);
+
+    inlines
+}
+
+/// Node text with its inline Markdown removed, keeping node references as written.
+pub fn plain_text(text: &str) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|inline| inline.text)
+        .collect()
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    node_links: Vec<Link>,
+}
+
+impl Parser<'_> {
+    /// Parses `text[start..end]` into `inlines`, on top of `emphasis` and `link`.
+    fn parse(
+        &self,
+        start: usize,
+        end: usize,
+        emphasis: Emphasis,
+        link: Option<&Target>,
+        inlines: &mut Vec<Inline>,
+    ) {
+        let mut literal = String::new();
+        let mut position = start;
+
+        let flush = |literal: &mut String, inlines: &mut Vec<Inline>| {
+            if !literal.is_empty() {
+                push(inlines, std::mem::take(literal), emphasis, link.cloned());
+            }
+        };
+
+        while position < end {
+            let rest = &self.text[position..end];
+
+            if let Some(node_link) = self.node_link_at(position, end) {
+                flush(&mut literal, inlines);
+                push(
+                    inlines,
+                    self.text[node_link.start..node_link.end].to_owned(),
+                    emphasis,
+                    Some(Target::Node(node_link.target_id)),
+                );
+                position = node_link.end;
+                continue;
+            }
+
+            if let Some(escaped) = escaped_char(rest) {
+                literal.push(escaped);
+                position += 1 + escaped.len_utf8();
+                continue;
+            }
+
+            if rest.starts_with('`') {
+                let ticks = rest.len() - rest.trim_start_matches('`').len();
+
+                match self.find_code_end(position + ticks, end, ticks) {
+                    Some(close) => {
+                        flush(&mut literal, inlines);
+                        let code = &self.text[position + ticks..close];
+                        let code =
+                            match code.len() > 2 && code.starts_with(' ') && code.ends_with(' ') {
+                                true => &code[1..code.len() - 1],
+                                false => code,
+                            };
+
+                        push(
+                            inlines,
+                            code.to_owned(),
+                            Emphasis {
+                                code: true,
+                                ..emphasis
+                            },
+                            link.cloned(),
+                        );
+                        position = close + ticks;
+                    }
+                    None => {
+                        literal.push_str(&rest[..ticks]);
+                        position += ticks;
+                    }
+                }
+                continue;
+            }
+
+            // links do not nest
+            if rest.starts_with('[')
+                && link.is_none()
+                && let Some((label_end, url_end)) = self.find_link_end(position, end)
+            {
+                flush(&mut literal, inlines);
+                let url = Target::Url(self.text[label_end + 2..url_end].trim().to_owned());
+                self.parse(position + 1, label_end, emphasis, Some(&url), inlines);
+                position = url_end + 1;
+                continue;
+            }
+
+            if let Some(delimiter) = delimiter_at(self.text, position, end)
+                && let Some(close) = self.find_closing(position + delimiter.len(), end, delimiter)
+            {
+                flush(&mut literal, inlines);
+                let inner = match delimiter {
+                    "**" | "__" => Emphasis {
+                        bold: true,
+                        ..emphasis
+                    },
+                    "~~" => Emphasis {
+                        strikethrough: true,
+                        ..emphasis
+                    },
+                    _ => Emphasis {
+                        italic: true,
+                        ..emphasis
+                    },
+                };
+                self.parse(position + delimiter.len(), close, inner, link, inlines);
+                position = close + delimiter.len();
+                continue;
+            }
+
+            let char = rest.chars().next().unwrap_or_default();
+            literal.push(char);
+            position += char.len_utf8();
+        }
+
+        flush(&mut literal, inlines);
+    }
+
+    fn node_link_at(&self, position: usize, end: usize) -> Option<&Link> {
+        self.node_links
+            .iter()
+            .find(|link| link.start == position && link.end <= end)
+    }
+
+    /// Start of the run of exactly `ticks` backticks closing a code span.
+    fn find_code_end(&self, from: usize, end: usize, ticks: usize) -> Option<usize> {
+        let mut position = from;
+
+        while position < end {
+            let rest = &self.text[position..end];
+            let Some(offset) = rest.find('`') else {
+                break;
+            };
+
+            let run_start = position + offset;
+            let run = self.text[run_start..end].len()
+                - self.text[run_start..end].trim_start_matches('`').len();
+
+            if run == ticks {
+                return Some(run_start);
+            }
+            position = run_start + run;
+        }
+
+        None
+    }
+
+    /// For a `[label](url)` starting at `start`, the positions of the `]` and
+    /// of the closing `)`.
+    fn find_link_end(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        let mut depth = 0;
+        let mut position = start;
+
+        let label_end = loop {
+            let rest = &self.text[position..end];
+            let char = rest.chars().next()?;
+
+            match char {
+                '\\' if escaped_char(rest).is_some() => position += 1,
+                '[' => depth += 1,
+                ']' if depth == 1 => break position,
+                ']' => depth -= 1,
+                _ => {}
+            }
+            position += char.len_utf8();
+        };
+
+        if !self.text[label_end..end].starts_with("](") {
+            return None;
+        }
+
+        let url_start = label_end + 2;
+        let url_end = url_start + self.text[url_start..end].find(')')?;
+
+        match self.text[url_start..url_end].trim() {
+            url if url.is_empty() || url.contains(char::is_whitespace) => None,
+            _ => Some((label_end, url_end)),
+        }
+    }
+
+    /// Start of the delimiter closing one opened just before `from`, skipping
+    /// over code spans, node references and escapes.
+    fn find_closing(&self, from: usize, end: usize, delimiter: &str) -> Option<usize> {
+        let mut position = from;
+
+        while position < end {
+            let rest = &self.text[position..end];
+
+            if let Some(node_link) = self.node_link_at(position, end) {
+                position = node_link.end;
+                continue;
+            }
+
+            if escaped_char(rest).is_some() {
+                position += 1 + rest[1..].chars().next()?.len_utf8();
+                continue;
+            }
+
+            if rest.starts_with('`') {
+                let ticks = rest.len() - rest.trim_start_matches('`').len();
+                position = match self.find_code_end(position + ticks, end, ticks) {
+                    Some(close) => close + ticks,
+                    None => position + ticks,
+                };
+                continue;
+            }
+
+            if let Some(found) = delimiter_kind(rest) {
+                if found == delimiter
+                    && position > from
+                    && closes(self.text, position, end, delimiter)
+                {
+                    return Some(position);
+                }
+
+                // a different delimiter, such as `**` inside `*...*`, is stepped over whole
+                position += found.len();
+                continue;
+            }
+
+            position += rest.chars().next()?.len_utf8();
+        }
+
+        None
+    }
+}
+
+/// Appends a run, joining it to the previous one when they look the same.
+fn push(inlines: &mut Vec<Inline>, text: String, emphasis: Emphasis, link: Option<Target>) {
+    match inlines.last_mut() {
+        Some(last) if last.emphasis == emphasis && last.link.is_none() && link.is_none() => {
+            last.text.push_str(&text);
+        }
+        _ => inlines.push(Inline {
+            text,
+            emphasis,
+            link,
+        }),
+    }
+}
+
+/// The character made literal by a backslash at the start of `text`.
+fn escaped_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some('\\'), Some(char)) if char.is_ascii_punctuation() => Some(char),
+        _ => None,
+    }
+}
+
+/// The emphasis delimiter at `position`, if one can open there.
+fn delimiter_at(text: &str, position: usize, end: usize) -> Option<&'static str> {
+    let delimiter = delimiter_kind(&text[position..end])?;
+    let next = text[position + delimiter.len()..end].chars().next();
+    let previous = text[..position].chars().next_back();
+
+    let opens = next.is_some_and(|char| !char.is_whitespace())
+        && !(delimiter.starts_with('_') && previous.is_some_and(char::is_alphanumeric));
+
+    opens.then_some(delimiter)
+}
+
+fn delimiter_kind(text: &str) -> Option<&'static str> {
+    ["**", "__", "~~", "*", "_"]
+        .into_iter()
+        .find(|delimiter| text.starts_with(delimiter))
+}
+
+/// Whether `delimiter` at `position` can close an emphasis.
+fn closes(text: &str, position: usize, end: usize, delimiter: &str) -> bool {
+    let previous = text[..position].chars().next_back();
+    let next = text[position + delimiter.len()..end].chars().next();
+
+    previous.is_some_and(|char| !char.is_whitespace())
+        && !(delimiter.starts_with('_') && next.is_some_and(char::is_alphanumeric))
+}
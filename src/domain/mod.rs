@@ -5,4 +5,43 @@ pub mod errors;
 pub use errors::DomainError;
 
 pub mod node_graph;
-pub use node_graph::NodeGraph;
+pub use node_graph::{CollapseChanges, GraphElement, GraphView, NodeGraph};
+
+pub mod links;
+pub use links::Link;
+
+pub mod markup;
+pub use markup::Inline;
+
+pub mod rank;
+
+pub mod revision;
+pub use revision::Revision;
+
+pub mod attachment;
+pub use attachment::Attachment;
+
+pub mod subtree;
+pub use subtree::{Clipboard, Subtree};
+
+pub mod selection;
+pub use selection::Selection;
+
+pub mod tag;
+
+pub mod planning;
+pub use planning::Planning;
+
+pub mod query;
+pub use query::Query;
+
+pub mod view;
+pub use view::SavedView;
+
+pub mod template;
+pub use template::Template;
+
+pub mod journal;
+
+pub mod crdt;
+pub use crdt::Replica;
@@ -1,9 +1,10 @@
 use crate::domain::DomainError;
 use hifitime::Epoch;
+use serde::{Deserialize, Serialize};
 use std::{fmt, str::FromStr};
 use uuid::Uuid;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Node {
     id: Uuid,
     parent_id: Option<Uuid>,
@@ -12,11 +13,14 @@ pub struct Node {
     modified_time: Epoch,
     node_type: NodeType,
     text: String,
+    /// Longer body shown below the text, possibly over several lines
+    note: String,
     author: String,
     source_type: Source,
+    mirror_of: Option<Uuid>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Source {
     User,
     Agent,
@@ -48,7 +52,8 @@ impl fmt::Display for Source {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// Node types, ordered by how far along the work they describe is.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub enum NodeType {
     Standard,
     Todo,
@@ -85,21 +90,50 @@ impl fmt::Display for NodeType {
 
 impl Node {
     pub fn new(request: CreateNodeRequest) -> Result<Self, DomainError> {
+        Node::new_with_id(Uuid::new_v4(), request)
+    }
+
+    /// Creates a node with a known ID, such as one moved between documents.
+    pub fn new_with_id(id: Uuid, request: CreateNodeRequest) -> Result<Self, DomainError> {
         let now = Epoch::now().map_err(|_| DomainError::InvalidDateTime)?;
 
         Ok(Node {
-            id: Uuid::new_v4(),
+            id,
             parent_id: request.parent_id,
             rank: request.rank,
             created_time: now,
             modified_time: now,
             node_type: request.node_type,
             text: request.text,
+            note: request.note,
             author: request.author,
             source_type: request.source_type,
+            mirror_of: request.mirror_of,
         })
     }
 
+    /// Rebuilds a node whose times are already known, such as one held by a replica.
+    pub fn from_parts(
+        id: Uuid,
+        request: CreateNodeRequest,
+        created_time: Epoch,
+        modified_time: Epoch,
+    ) -> Self {
+        Node {
+            id,
+            parent_id: request.parent_id,
+            rank: request.rank,
+            created_time,
+            modified_time,
+            node_type: request.node_type,
+            text: request.text,
+            note: request.note,
+            author: request.author,
+            source_type: request.source_type,
+            mirror_of: request.mirror_of,
+        }
+    }
+
     pub fn id_str(&self) -> String {
         self.id.to_string()
     }
@@ -120,6 +154,14 @@ impl Node {
         self.parent_id
     }
 
+    pub fn created_time(&self) -> Epoch {
+        self.created_time
+    }
+
+    pub fn modified_time(&self) -> Epoch {
+        self.modified_time
+    }
+
     pub fn created_time_str(&self) -> String {
         self.created_time.to_string()
     }
@@ -128,10 +170,18 @@ impl Node {
         self.modified_time.to_string()
     }
 
+    pub fn node_type(&self) -> &NodeType {
+        &self.node_type
+    }
+
     pub fn node_type_str(&self) -> String {
         self.node_type.to_string()
     }
 
+    pub fn source_type(&self) -> &Source {
+        &self.source_type
+    }
+
     pub fn source_type_str(&self) -> String {
         self.source_type.to_string()
     }
@@ -140,10 +190,29 @@ impl Node {
         &self.text
     }
 
+    pub fn note(&self) -> &str {
+        &self.note
+    }
+
     pub fn author(&self) -> &str {
         &self.author
     }
 
+    /// ID of the canonical node when this node is a mirror of it.
+    pub fn mirror_of(&self) -> Option<Uuid> {
+        self.mirror_of
+    }
+
+    pub fn mirror_of_str(&self) -> Option<String> {
+        self.mirror_of.map(|id| id.to_string())
+    }
+
+    /// ID of the node holding this node's content, which is itself unless it is a mirror.
+    pub fn canonical_id(&self) -> Uuid {
+        self.mirror_of.unwrap_or(self.id)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn from_raw_strs(
         id_str: String,
         parent_id_str: Option<String>,
@@ -152,8 +221,10 @@ impl Node {
         modified_time_str: String,
         node_type_str: String,
         text: String,
+        note: String,
         author: String,
         source_type_str: String,
+        mirror_of_str: Option<String>,
     ) -> Result<Self, DomainError> {
         let id = Uuid::parse_str(&id_str).map_err(|_| DomainError::FieldParseError("id".into()))?;
 
@@ -177,6 +248,14 @@ impl Node {
         let source_type = Source::from_str(&source_type_str)
             .map_err(|_| DomainError::FieldParseError("source".to_owned()))?;
 
+        let mirror_of = match mirror_of_str {
+            Some(str) => match Uuid::parse_str(&str) {
+                Ok(id) => Some(id),
+                Err(_) => return Err(DomainError::FieldParseError("mirror_of".into())),
+            },
+            None => None,
+        };
+
         Ok(Node {
             id,
             parent_id,
@@ -185,8 +264,10 @@ impl Node {
             modified_time,
             node_type,
             text,
+            note,
             author,
             source_type,
+            mirror_of,
         })
     }
 
@@ -196,6 +277,36 @@ impl Node {
 
         Ok(())
     }
+
+    pub fn set_note(&mut self, note: impl Into<String>) -> Result<(), DomainError> {
+        self.note = note.into();
+        self.modified_time = Epoch::now().map_err(|_| DomainError::InvalidDateTime)?;
+
+        Ok(())
+    }
+
+    pub fn set_node_type(&mut self, node_type: NodeType) -> Result<(), DomainError> {
+        self.node_type = node_type;
+        self.modified_time = Epoch::now().map_err(|_| DomainError::InvalidDateTime)?;
+
+        Ok(())
+    }
+
+    pub fn move_to(&mut self, parent_id: Option<Uuid>, rank: u64) -> Result<(), DomainError> {
+        self.parent_id = parent_id;
+        self.rank = rank;
+        self.modified_time = Epoch::now().map_err(|_| DomainError::InvalidDateTime)?;
+
+        Ok(())
+    }
+
+    /// Points this node at a new canonical node, or makes it canonical itself when `None`.
+    pub fn set_mirror_of(&mut self, mirror_of: Option<Uuid>) -> Result<(), DomainError> {
+        self.mirror_of = mirror_of;
+        self.modified_time = Epoch::now().map_err(|_| DomainError::InvalidDateTime)?;
+
+        Ok(())
+    }
 }
 
 pub struct CreateNodeRequest {
@@ -203,8 +314,10 @@ pub struct CreateNodeRequest {
     pub rank: u64,
     pub node_type: NodeType,
     pub text: String,
+    pub note: String,
     pub author: String,
     pub source_type: Source,
+    pub mirror_of: Option<Uuid>,
 }
 
 impl CreateNodeRequest {
@@ -221,8 +334,10 @@ impl CreateNodeRequest {
             rank,
             node_type,
             text: text.into(),
+            note: String::new(),
             author: author.into(),
             source_type,
+            mirror_of: None,
         }
     }
 }
@@ -1,73 +1,561 @@
 #![allow(dead_code)]
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::domain::Node;
+use crate::domain::{
+    DomainError, Node, Planning,
+    view::{SortKey, ViewSort},
+};
 use uuid::Uuid;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GraphElement {
     pub id: Uuid,
+    /// Identifies this appearance of the node. It is the node's ID at its own
+    /// place in the outline, and derived from the enclosing mirror's key when
+    /// the node is shown inside a mirror, so that every row has a key of its own.
+    pub key: Uuid,
     pub parent_id: Option<Uuid>,
     pub rank: u64,
     pub depth: usize,
+    pub collapsed: bool,
+    pub mirror_of: Option<Uuid>,
     pub children: Vec<GraphElement>,
 }
 
 pub struct NodeGraph {
     pub nodes: Vec<Node>,
     pub graph: Vec<GraphElement>,
+    node_index: HashMap<Uuid, usize>,
+    tags: HashMap<Uuid, Vec<String>>,
+    planning: HashMap<Uuid, Vec<Planning>>,
+    /// Nodes matching the search the graph was filtered by, `None` when unfiltered
+    matches: Option<HashSet<Uuid>>,
+}
+
+/// A hoisted view of the graph, where `root` acts as the temporary root of the outline.
+#[derive(Debug)]
+pub struct GraphView {
+    /// Hoisted node, `None` when the whole outline is shown
+    pub root: Option<Uuid>,
+    /// Ancestors of the hoisted node, outermost first
+    pub breadcrumbs: Vec<Uuid>,
+    /// Children of the hoisted node, with depths rebased to start at zero
+    pub elements: Vec<GraphElement>,
+}
+
+impl GraphView {
+    /// Iterates over every element of the view in display order.
+    pub fn rows(&self) -> Rows<'_> {
+        Rows::new(&self.elements, false)
+    }
+
+    /// Iterates over the elements of the view that are not inside a collapsed branch.
+    pub fn visible_rows(&self) -> Rows<'_> {
+        Rows::new(&self.elements, true)
+    }
+}
+
+/// Depth-first iterator over graph elements in display order.
+pub struct Rows<'a> {
+    stack: Vec<&'a GraphElement>,
+    skip_collapsed: bool,
+}
+
+impl<'a> Rows<'a> {
+    fn new(elements: &'a [GraphElement], skip_collapsed: bool) -> Self {
+        Rows {
+            stack: elements.iter().rev().collect(),
+            skip_collapsed,
+        }
+    }
+}
+
+impl<'a> Iterator for Rows<'a> {
+    type Item = &'a GraphElement;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let element = self.stack.pop()?;
+
+        // hidden subtrees are never walked
+        if !(self.skip_collapsed && element.collapsed) {
+            self.stack.extend(element.children.iter().rev());
+        }
+
+        Some(element)
+    }
+}
+
+/// Collapsed flags changed by a folding operation, to be persisted by the caller.
+#[derive(Debug, Default)]
+pub struct CollapseChanges {
+    pub collapsed: Vec<Uuid>,
+    pub expanded: Vec<Uuid>,
 }
 
 impl NodeGraph {
     pub fn new(nodes: Vec<Node>) -> Self {
         let mut graph: Vec<GraphElement> = Vec::new();
-        let mut node_map = create_parent_children_map(&nodes);
+        let node_map = create_parent_children_map(&nodes);
+        let mirror_map: HashMap<Uuid, Uuid> = nodes
+            .iter()
+            .filter_map(|node| node.mirror_of().map(|canonical| (node.id(), canonical)))
+            .collect();
 
-        for node in &nodes {
-            // only want to consider root nodes
-            if node.parent_id().is_some() {
-                continue;
-            }
+        // top level nodes are ordered by rank, as children are
+        let mut roots: Vec<&Node> = nodes
+            .iter()
+            .filter(|node| node.parent_id().is_none())
+            .collect();
+        roots.sort_by_key(|node| node.rank());
 
-            let element = construct_sub_tree(&mut node_map, None, node.id(), node.rank(), 0);
+        for node in roots {
+            let element = construct_sub_tree(
+                &node_map,
+                &mirror_map,
+                &mut Vec::new(),
+                None,
+                None,
+                node.id(),
+                node.rank(),
+                0,
+            );
 
             graph.push(element);
         }
 
-        Self { nodes, graph }
+        let node_index = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.id(), index))
+            .collect();
+
+        Self {
+            nodes,
+            graph,
+            node_index,
+            tags: HashMap::new(),
+            planning: HashMap::new(),
+            matches: None,
+        }
+    }
+
+    /// Builds a graph of the nodes in `matches` along with their ancestors,
+    /// so that every match is shown in the context of where it sits. `root` and
+    /// its ancestors are kept as well, so the graph can be viewed from `root`
+    /// even when nothing matches.
+    pub fn filtered(nodes: Vec<Node>, matches: HashSet<Uuid>, root: Option<Uuid>) -> Self {
+        let parents: HashMap<Uuid, Uuid> = nodes
+            .iter()
+            .filter_map(|node| node.parent_id().map(|parent_id| (node.id(), parent_id)))
+            .collect();
+
+        let mut kept = matches.clone();
+        kept.extend(root);
+
+        for node_id in matches.iter().chain(root.iter()) {
+            let mut current = *node_id;
+
+            // stops at nodes already kept, which also guards against parent cycles
+            while let Some(parent_id) = parents.get(&current) {
+                if !kept.insert(*parent_id) {
+                    break;
+                }
+                current = *parent_id;
+            }
+        }
+
+        let mut graph = Self::new(
+            nodes
+                .into_iter()
+                .filter(|node| kept.contains(&node.id()))
+                .collect(),
+        );
+        graph.matches = Some(matches);
+
+        graph
+    }
+
+    /// Whether `node_id` matched the search the graph was filtered by. Every
+    /// node matches in an unfiltered graph, while ancestors kept only for
+    /// context do not.
+    pub fn is_match(&self, node_id: &Uuid) -> bool {
+        self.matches
+            .as_ref()
+            .is_none_or(|matches| matches.contains(node_id))
+    }
+
+    /// Reorders the siblings at every level of the graph.
+    pub fn sort(&mut self, sort: ViewSort) {
+        let nodes = &self.nodes;
+        let node_index = &self.node_index;
+        let mut stack: Vec<&mut Vec<GraphElement>> = vec![&mut self.graph];
+
+        while let Some(siblings) = stack.pop() {
+            siblings.sort_by(|a, b| {
+                let (Some(a_node), Some(b_node)) = (
+                    node_index.get(&a.id).map(|index| &nodes[*index]),
+                    node_index.get(&b.id).map(|index| &nodes[*index]),
+                ) else {
+                    return a.rank.cmp(&b.rank);
+                };
+
+                // ties keep outline order so the result is stable
+                let ordering = match sort.key {
+                    SortKey::Outline => a.rank.cmp(&b.rank),
+                    SortKey::Created => a_node.created_time().cmp(&b_node.created_time()),
+                    SortKey::Modified => a_node.modified_time().cmp(&b_node.modified_time()),
+                    SortKey::Text => a_node
+                        .text()
+                        .to_lowercase()
+                        .cmp(&b_node.text().to_lowercase()),
+                    SortKey::Type => a_node.node_type().cmp(b_node.node_type()),
+                }
+                .then(a.rank.cmp(&b.rank));
+
+                if sort.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+
+            stack.extend(siblings.iter_mut().map(|element| &mut element.children));
+        }
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.matches.as_ref().map_or(self.nodes.len(), HashSet::len)
     }
+
+    /// Marks the elements whose keys are in `collapsed` as collapsed.
+    pub fn set_collapsed_nodes(&mut self, collapsed: &HashSet<Uuid>) {
+        let mut stack: Vec<&mut GraphElement> = self.graph.iter_mut().collect();
+
+        while let Some(element) = stack.pop() {
+            element.collapsed = collapsed.contains(&element.key);
+            stack.extend(element.children.iter_mut());
+        }
+    }
+
+    /// Iterates over every element of the graph in display order.
+    pub fn rows(&self) -> Rows<'_> {
+        Rows::new(&self.graph, false)
+    }
+
+    /// Iterates over the elements that are not inside a collapsed branch.
+    pub fn visible_rows(&self) -> Rows<'_> {
+        Rows::new(&self.graph, true)
+    }
+
+    /// Attaches tags, given as `(node_id, tag)` pairs, to the nodes of the graph.
+    pub fn set_tags(&mut self, tags: Vec<(Uuid, String)>) {
+        self.tags.clear();
+
+        for (node_id, tag) in tags {
+            self.tags.entry(node_id).or_default().push(tag);
+        }
+    }
+
+    /// Tags of `node_id`, where mirrors share the tags of their canonical node.
+    pub fn tags(&self, node_id: &Uuid) -> &[String] {
+        let canonical_id = self
+            .node(node_id)
+            .map_or(*node_id, |node| node.canonical_id());
+
+        self.tags.get(&canonical_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Attaches planned dates, given with the node they belong to, to the nodes of the graph.
+    pub fn set_planning(&mut self, planning: Vec<(Uuid, Planning)>) {
+        self.planning.clear();
+
+        for (node_id, planning) in planning {
+            self.planning.entry(node_id).or_default().push(planning);
+        }
+    }
+
+    /// Planned dates of `node_id`, where mirrors share those of their canonical node.
+    pub fn planning(&self, node_id: &Uuid) -> &[Planning] {
+        let canonical_id = self
+            .node(node_id)
+            .map_or(*node_id, |node| node.canonical_id());
+
+        self.planning.get(&canonical_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Folds or unfolds the element with `key`, leaving other appearances of
+    /// its node as they are.
+    pub fn set_collapsed(&mut self, key: &Uuid, collapsed: bool) -> Result<(), DomainError> {
+        let element =
+            find_element_mut(&mut self.graph, key).ok_or(DomainError::MissingNode(*key))?;
+        element.collapsed = collapsed;
+
+        Ok(())
+    }
+
+    /// Folds the outline below `root` so that only `level` levels stay visible.
+    pub fn fold_to_level(
+        &mut self,
+        root: Option<Uuid>,
+        level: usize,
+    ) -> Result<CollapseChanges, DomainError> {
+        let (elements, base_depth) = self.subtree_mut(root)?;
+        let level = level.max(1);
+        let mut changes = CollapseChanges::default();
+        let mut stack: Vec<&mut GraphElement> = elements.iter_mut().collect();
+
+        while let Some(element) = stack.pop() {
+            if !element.children.is_empty() {
+                let collapsed = element.depth - base_depth + 1 >= level;
+
+                if element.collapsed != collapsed {
+                    element.collapsed = collapsed;
+
+                    if collapsed {
+                        changes.collapsed.push(element.key);
+                    } else {
+                        changes.expanded.push(element.key);
+                    }
+                }
+            }
+
+            stack.extend(element.children.iter_mut());
+        }
+
+        Ok(changes)
+    }
+
+    /// Expands every collapsed element below `root`, returning the keys that changed.
+    pub fn unfold_all(&mut self, root: Option<Uuid>) -> Result<Vec<Uuid>, DomainError> {
+        let (elements, _) = self.subtree_mut(root)?;
+        let mut expanded = Vec::new();
+        let mut stack: Vec<&mut GraphElement> = elements.iter_mut().collect();
+
+        while let Some(element) = stack.pop() {
+            if element.collapsed {
+                element.collapsed = false;
+                expanded.push(element.key);
+            }
+
+            stack.extend(element.children.iter_mut());
+        }
+
+        Ok(expanded)
+    }
+
+    /// Returns the children of `root` (or the whole graph) and the depth they start at.
+    fn subtree_mut(
+        &mut self,
+        root: Option<Uuid>,
+    ) -> Result<(&mut Vec<GraphElement>, usize), DomainError> {
+        match root {
+            Some(root_id) => {
+                let element = find_element_mut(&mut self.graph, &root_id)
+                    .ok_or(DomainError::MissingNode(root_id))?;
+                let depth = element.depth + 1;
+
+                Ok((&mut element.children, depth))
+            }
+            None => Ok((&mut self.graph, 0)),
+        }
+    }
+
+    pub fn node(&self, node_id: &Uuid) -> Option<&Node> {
+        self.node_index
+            .get(node_id)
+            .map(|index| &self.nodes[*index])
+    }
+
+    /// Finds the element with `key`. A node's ID is the key of its own place,
+    /// so looking up a node never lands on one of its appearances in a mirror.
+    pub fn find(&self, key: &Uuid) -> Option<&GraphElement> {
+        find_element(&self.graph, key)
+    }
+
+    /// Keys of the elements enclosing the element with `key`, outermost first.
+    /// Unlike [`NodeGraph::ancestors`], this follows appearances inside mirrors.
+    pub fn enclosing_keys(&self, key: &Uuid) -> Vec<Uuid> {
+        let mut path = Vec::new();
+
+        if !element_path(&self.graph, key, &mut path) {
+            return Vec::new();
+        }
+
+        path.pop();
+        path
+    }
+
+    /// Position of `node_id` among its siblings.
+    pub fn sibling_index(&self, node_id: &Uuid) -> Option<usize> {
+        let siblings = match self.node(node_id)?.parent_id() {
+            Some(parent_id) => &self.find(&parent_id)?.children,
+            None => &self.graph,
+        };
+
+        siblings.iter().position(|element| element.key == *node_id)
+    }
+
+    /// Returns the IDs of every ancestor of `node_id`, outermost first.
+    pub fn ancestors(&self, node_id: &Uuid) -> Vec<Uuid> {
+        let mut ancestors = Vec::new();
+        let mut current = self.node(node_id).and_then(|node| node.parent_id());
+
+        while let Some(parent_id) = current {
+            // guard against malformed data containing a parent cycle
+            if ancestors.contains(&parent_id) {
+                break;
+            }
+
+            ancestors.push(parent_id);
+            current = self.node(&parent_id).and_then(|node| node.parent_id());
+        }
+
+        ancestors.reverse();
+        ancestors
+    }
+
+    /// Hoists `root` as the temporary root of the outline, or shows the whole
+    /// outline when `root` is `None`.
+    pub fn view(&self, root: Option<Uuid>) -> Result<GraphView, DomainError> {
+        let Some(root_id) = root else {
+            return Ok(GraphView {
+                root: None,
+                breadcrumbs: Vec::new(),
+                elements: self.graph.clone(),
+            });
+        };
+
+        let element = self
+            .find(&root_id)
+            .ok_or(DomainError::MissingNode(root_id))?;
+
+        let elements = element
+            .children
+            .iter()
+            .cloned()
+            .map(|mut child| {
+                rebase_depth(&mut child, element.depth + 1);
+                child
+            })
+            .collect();
+
+        Ok(GraphView {
+            root: Some(root_id),
+            breadcrumbs: self.ancestors(&root_id),
+            elements,
+        })
+    }
+}
+
+fn find_element<'a>(elements: &'a [GraphElement], key: &Uuid) -> Option<&'a GraphElement> {
+    for element in elements {
+        if element.key == *key {
+            return Some(element);
+        }
+
+        if let Some(found) = find_element(&element.children, key) {
+            return Some(found);
+        }
+    }
+
+    None
+}
+
+fn find_element_mut<'a>(
+    elements: &'a mut [GraphElement],
+    key: &Uuid,
+) -> Option<&'a mut GraphElement> {
+    for element in elements {
+        if element.key == *key {
+            return Some(element);
+        }
+
+        if let Some(found) = find_element_mut(&mut element.children, key) {
+            return Some(found);
+        }
+    }
+
+    None
 }
 
+/// Pushes the keys from the top of the graph down to the element with `key`
+/// onto `path`, returning whether it was found.
+fn element_path(elements: &[GraphElement], key: &Uuid, path: &mut Vec<Uuid>) -> bool {
+    for element in elements {
+        path.push(element.key);
+
+        if element.key == *key || element_path(&element.children, key, path) {
+            return true;
+        }
+
+        path.pop();
+    }
+
+    false
+}
+
+fn rebase_depth(element: &mut GraphElement, offset: usize) {
+    element.depth -= offset;
+
+    for child in &mut element.children {
+        rebase_depth(child, offset);
+    }
+}
+
+/// Builds the element for `current_id`. Mirrors are expanded with the children of
+/// their canonical node, and any node already being expanded further up `path`
+/// is shown as a leaf so that mirrors placed inside their own subtree terminate.
+/// `enclosing` is the key of the innermost mirror the element is shown in.
+#[allow(clippy::too_many_arguments)]
 fn construct_sub_tree(
-    node_map: &mut HashMap<Uuid, Vec<(Uuid, u64)>>,
+    node_map: &HashMap<Uuid, Vec<(Uuid, u64)>>,
+    mirror_map: &HashMap<Uuid, Uuid>,
+    path: &mut Vec<Uuid>,
+    enclosing: Option<Uuid>,
     parent_id: Option<Uuid>,
     current_id: Uuid,
     current_rank: u64,
     current_depth: usize,
 ) -> GraphElement {
-    let node_children = node_map.remove(&current_id);
+    let mirror_of = mirror_map.get(&current_id).copied();
+    let source_id = mirror_of.unwrap_or(current_id);
+    let key = enclosing.map_or(current_id, |enclosing| {
+        Uuid::new_v5(&enclosing, current_id.as_bytes())
+    });
     let mut child_elements: Vec<GraphElement> = Vec::new();
 
-    if let Some(children) = node_children {
+    if !path.contains(&source_id)
+        && let Some(children) = node_map.get(&source_id)
+    {
+        path.push(source_id);
+
         child_elements = children
-            .into_iter()
+            .iter()
             .map(|(child_id, child_rank)| {
                 construct_sub_tree(
                     node_map,
+                    mirror_map,
+                    path,
+                    mirror_of.map_or(enclosing, |_| Some(key)),
                     Some(current_id),
-                    child_id,
-                    child_rank,
+                    *child_id,
+                    *child_rank,
                     current_depth + 1,
                 )
             })
             .collect();
+
+        path.pop();
     };
 
     GraphElement {
         id: current_id,
+        key,
         parent_id,
         rank: current_rank,
         depth: current_depth,
+        collapsed: false,
+        mirror_of,
         children: child_elements,
     }
 }
@@ -80,15 +568,15 @@ pub fn create_parent_children_map(nodes: &Vec<Node>) -> HashMap<Uuid, Vec<(Uuid,
         let mut children_rank_ids: Vec<(Uuid, u64)> = Vec::new();
 
         for child_node in nodes {
-            if let Some(childs_parent_id) = child_node.parent_id() {
-                if parent_node.id() == childs_parent_id {
-                    children_rank_ids.push((child_node.id(), child_node.rank()));
-                }
+            if let Some(childs_parent_id) = child_node.parent_id()
+                && parent_node.id() == childs_parent_id
+            {
+                children_rank_ids.push((child_node.id(), child_node.rank()));
             }
         }
 
         if !children_rank_ids.is_empty() {
-            children_rank_ids.sort_by(|a, b| a.1.cmp(&b.1));
+            children_rank_ids.sort_by_key(|a| a.1);
         }
 
         children_by_parent.insert(parent_node.id(), children_rank_ids);
@@ -0,0 +1,45 @@
+use hifitime::Epoch;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// What a planned date means for a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanningKind {
+    /// When work on the node is meant to start
+    Scheduled,
+    /// When the node is due
+    Deadline,
+}
+
+impl FromStr for PlanningKind {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<PlanningKind, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "scheduled" => Ok(PlanningKind::Scheduled),
+            "deadline" => Ok(PlanningKind::Deadline),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for PlanningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            PlanningKind::Scheduled => "scheduled",
+            PlanningKind::Deadline => "deadline",
+        };
+
+        f.write_str(label)
+    }
+}
+
+/// A date a node is planned for. Nodes have at most one of each kind, and
+/// mirrors share the planning of their canonical node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Planning {
+    pub kind: PlanningKind,
+    /// UTC time, midnight when only a day was given
+    pub time: Epoch,
+}
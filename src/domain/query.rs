@@ -0,0 +1,375 @@
+use std::{
+    collections::{HashMap, HashSet},
+    iter::Peekable,
+    str::{CharIndices, FromStr},
+};
+
+use hifitime::{Epoch, Unit};
+use uuid::Uuid;
+
+use crate::domain::{
+    DomainError, Node, Planning,
+    node::{NodeType, Source},
+    planning::PlanningKind,
+    tag::normalize_tag,
+};
+
+/// A parsed search over the outline, such as
+/// `type:todo author:astra modified:>2026-10-01 deadline:<2026-11-01 under:<id> "some text"`.
+///
+/// Terms are separated by whitespace and must all hold for a node to match.
+/// A term prefixed with `-` must not hold instead. Bare words and quoted
+/// phrases search the node text and note, ignoring ASCII case.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// Node text or note contains the phrase, ignoring ASCII case
+    Text(String),
+    Type(NodeType),
+    /// Author equals the name, ignoring case
+    Author(String),
+    Source(Source),
+    Tag(String),
+    /// Node has a date of the kind within the range
+    Planned(PlanningKind, TimeRange),
+    Created(TimeRange),
+    Modified(TimeRange),
+    /// Node is a descendant of the given node
+    Under(Uuid),
+}
+
+/// Times from `start` (inclusive) up to `end` (exclusive), either bound being open when `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub start: Option<Epoch>,
+    pub end: Option<Epoch>,
+}
+
+impl TimeRange {
+    pub fn contains(&self, time: Epoch) -> bool {
+        self.start.is_none_or(|start| time >= start) && self.end.is_none_or(|end| time < end)
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, DomainError> {
+        Parser {
+            chars: input.char_indices().peekable(),
+            input,
+        }
+        .parse()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Checks `node` against every term. `ancestors` are the IDs of the node's
+    /// ancestors, and `tags` and `planning` the tags and dates it carries.
+    pub fn matches(
+        &self,
+        node: &Node,
+        ancestors: &[Uuid],
+        tags: &[String],
+        planning: &[Planning],
+    ) -> bool {
+        self.terms.iter().all(|term| {
+            let matched = match &term.filter {
+                Filter::Text(text) => {
+                    let text = text.to_ascii_lowercase();
+
+                    node.text().to_ascii_lowercase().contains(&text)
+                        || node.note().to_ascii_lowercase().contains(&text)
+                }
+                Filter::Type(node_type) => node.node_type() == node_type,
+                Filter::Author(author) => node.author().eq_ignore_ascii_case(author),
+                Filter::Source(source) => node.source_type() == source,
+                Filter::Tag(tag) => tags.contains(tag),
+                Filter::Planned(kind, range) => planning
+                    .iter()
+                    .any(|planning| planning.kind == *kind && range.contains(planning.time)),
+                Filter::Created(range) => range.contains(node.created_time()),
+                Filter::Modified(range) => range.contains(node.modified_time()),
+                Filter::Under(ancestor_id) => ancestors.contains(ancestor_id),
+            };
+
+            matched != term.negated
+        })
+    }
+
+    /// Keeps the nodes matching the query. `tags` and `planning` cover the
+    /// whole outline, with mirrors sharing those of their canonical node.
+    pub fn filter_nodes(
+        &self,
+        nodes: Vec<Node>,
+        tags: &[(Uuid, String)],
+        planning: &[(Uuid, Planning)],
+    ) -> Vec<Node> {
+        let parents: HashMap<Uuid, Uuid> = nodes
+            .iter()
+            .filter_map(|node| node.parent_id().map(|parent_id| (node.id(), parent_id)))
+            .collect();
+
+        let mut node_tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for (node_id, tag) in tags {
+            node_tags.entry(*node_id).or_default().push(tag.clone());
+        }
+
+        let mut node_planning: HashMap<Uuid, Vec<Planning>> = HashMap::new();
+        for (node_id, planning) in planning {
+            node_planning
+                .entry(*node_id)
+                .or_default()
+                .push(planning.clone());
+        }
+
+        nodes
+            .into_iter()
+            .filter(|node| {
+                let mut ancestors = Vec::new();
+                let mut seen = HashSet::new();
+                let mut current = node.id();
+
+                // the seen set guards against a corrupt parent chain
+                while let Some(parent_id) = parents.get(&current) {
+                    if !seen.insert(*parent_id) {
+                        break;
+                    }
+                    ancestors.push(*parent_id);
+                    current = *parent_id;
+                }
+
+                let tags = node_tags
+                    .get(&node.canonical_id())
+                    .map_or(&[][..], Vec::as_slice);
+                let planning = node_planning
+                    .get(&node.canonical_id())
+                    .map_or(&[][..], Vec::as_slice);
+
+                self.matches(node, &ancestors, tags, planning)
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Query {
+    type Err = DomainError;
+
+    fn from_str(input: &str) -> Result<Query, Self::Err> {
+        Query::parse(input)
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    input: &'a str,
+}
+
+impl Parser<'_> {
+    fn parse(mut self) -> Result<Query, DomainError> {
+        let mut terms = Vec::new();
+
+        loop {
+            while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+
+            if self.chars.peek().is_none() {
+                return Ok(Query { terms });
+            }
+
+            terms.push(self.term()?);
+        }
+    }
+
+    fn term(&mut self) -> Result<Term, DomainError> {
+        let negated = self.chars.next_if(|(_, c)| *c == '-').is_some();
+        let start = self.position();
+
+        if self.chars.peek().is_some_and(|(_, c)| *c == '"') {
+            return Ok(Term {
+                negated,
+                filter: Filter::Text(self.quoted()?),
+            });
+        }
+
+        let word = self.word(|c| c == ':');
+
+        if self.chars.next_if(|(_, c)| *c == ':').is_none() {
+            if word.is_empty() {
+                return Err(error(start, "expected a search term"));
+            }
+
+            return Ok(Term {
+                negated,
+                filter: Filter::Text(word),
+            });
+        }
+
+        let value_start = self.position();
+        let value = match self.chars.peek() {
+            Some((_, '"')) => self.quoted()?,
+            _ => self.word(|_| false),
+        };
+
+        if value.is_empty() {
+            return Err(error(value_start, &format!("missing value for `{word}`")));
+        }
+
+        let filter = field_filter(&word, &value).map_err(|message| match message {
+            FieldError::UnknownField => error(start, &format!("unknown field `{word}`")),
+            FieldError::InvalidValue(message) => error(value_start, &message),
+        })?;
+
+        Ok(Term { negated, filter })
+    }
+
+    /// Reads up to the next whitespace, or a character for which `stop` holds.
+    fn word(&mut self, stop: impl Fn(char) -> bool) -> String {
+        let mut word = String::new();
+
+        while let Some((_, c)) = self.chars.next_if(|(_, c)| !c.is_whitespace() && !stop(*c)) {
+            word.push(c);
+        }
+
+        word
+    }
+
+    /// Reads a double quoted string, where `\` escapes the next character.
+    fn quoted(&mut self) -> Result<String, DomainError> {
+        let start = self.position();
+        self.chars.next();
+
+        let mut text = String::new();
+
+        while let Some((_, c)) = self.chars.next() {
+            match c {
+                '"' => return Ok(text),
+                '\\' => match self.chars.next() {
+                    Some((_, escaped)) => text.push(escaped),
+                    None => break,
+                },
+                _ => text.push(c),
+            }
+        }
+
+        Err(error(start, "unterminated quote"))
+    }
+
+    /// Character offset of the next character, counted from one.
+    fn position(&mut self) -> usize {
+        let byte_offset = self
+            .chars
+            .peek()
+            .map_or(self.input.len(), |(offset, _)| *offset);
+
+        self.input[..byte_offset].chars().count() + 1
+    }
+}
+
+enum FieldError {
+    UnknownField,
+    InvalidValue(String),
+}
+
+fn field_filter(field: &str, value: &str) -> Result<Filter, FieldError> {
+    let filter = match field.to_ascii_lowercase().as_str() {
+        "text" => Filter::Text(value.to_owned()),
+        "type" => Filter::Type(value.parse().map_err(|_| {
+            FieldError::InvalidValue(format!(
+                "unknown node type `{value}`, expected standard, todo, inprogress or done"
+            ))
+        })?),
+        "author" => Filter::Author(value.to_owned()),
+        "source" => Filter::Source(value.parse().map_err(|_| {
+            FieldError::InvalidValue(format!(
+                "unknown source `{value}`, expected user, agent or application"
+            ))
+        })?),
+        "tag" => Filter::Tag(
+            normalize_tag(value).map_err(|err| FieldError::InvalidValue(err.to_string()))?,
+        ),
+        "scheduled" | "deadline" => Filter::Planned(
+            field.parse().map_err(|_| FieldError::UnknownField)?,
+            parse_time_range(value).map_err(FieldError::InvalidValue)?,
+        ),
+        "created" => Filter::Created(parse_time_range(value).map_err(FieldError::InvalidValue)?),
+        "modified" => Filter::Modified(parse_time_range(value).map_err(FieldError::InvalidValue)?),
+        "under" => Filter::Under(
+            Uuid::parse_str(value)
+                .map_err(|_| FieldError::InvalidValue(format!("`{value}` is not a node ID")))?,
+        ),
+        _ => return Err(FieldError::UnknownField),
+    };
+
+    Ok(filter)
+}
+
+/// Parses a comparison against a whole UTC day, such as `>2026-10-01` or `2026-10-01`.
+/// Days relative to today are resolved when the query is parsed, so a saved
+/// query like `modified:>=7d` keeps following the calendar.
+fn parse_time_range(value: &str) -> Result<TimeRange, String> {
+    let (operator, date) = ["<=", ">=", "<", ">", "="]
+        .iter()
+        .find_map(|operator| value.strip_prefix(operator).map(|date| (*operator, date)))
+        .unwrap_or(("=", value));
+
+    let day_start = parse_date(date).ok_or_else(|| {
+        format!("`{date}` is not a date such as 2026-10-01, today, yesterday, 7d or 2w")
+    })?;
+    let day_end = day_start + Unit::Day * 1;
+
+    let (start, end) = match operator {
+        "<" => (None, Some(day_start)),
+        "<=" => (None, Some(day_end)),
+        ">" => (Some(day_end), None),
+        ">=" => (Some(day_start), None),
+        _ => (Some(day_start), Some(day_end)),
+    };
+
+    Ok(TimeRange { start, end })
+}
+
+/// Midnight UTC at the start of `date`, written as `today`, `yesterday`, a
+/// number of days or weeks ago such as `3d` or `2w`, or as `YYYY-MM-DD`.
+pub fn parse_date(date: &str) -> Option<Epoch> {
+    let days_ago = match date.to_ascii_lowercase().as_str() {
+        "today" => Some(0),
+        "yesterday" => Some(1),
+        relative => relative
+            .strip_suffix('d')
+            .and_then(|days| days.parse::<i64>().ok())
+            .or_else(|| {
+                relative
+                    .strip_suffix('w')
+                    .and_then(|weeks| weeks.parse::<i64>().ok())
+                    .map(|weeks| weeks * 7)
+            }),
+    };
+
+    if let Some(days_ago) = days_ago {
+        let (year, month, day, ..) = Epoch::now().ok()?.to_gregorian_utc();
+        let today = Epoch::maybe_from_gregorian_utc(year, month, day, 0, 0, 0, 0).ok()?;
+
+        return Some(today - Unit::Day * days_ago);
+    }
+
+    let mut parts = date.splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+
+    Epoch::maybe_from_gregorian_utc(year, month, day, 0, 0, 0, 0).ok()
+}
+
+fn error(position: usize, message: &str) -> DomainError {
+    DomainError::InvalidQuery(position, message.to_owned())
+}
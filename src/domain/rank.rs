@@ -0,0 +1,44 @@
+/// Spacing left between the ranks of consecutive siblings, so that nodes can
+/// be inserted between them without renumbering.
+pub const DEFAULT_RANK_GAP: u64 = 100;
+
+/// Smallest gap that still leaves room for a node between two siblings.
+pub const MIN_RANK_GAP: u64 = 2;
+
+/// Largest gap, which still leaves room for billions of siblings before
+/// their ranks run out.
+pub const MAX_RANK_GAP: u64 = 1 << 32;
+
+/// Computes `count` ascending ranks strictly between `previous` and `next`,
+/// or `None` when there is not enough room and the siblings need respacing.
+/// Ranks after the last sibling are `gap` apart.
+pub fn ranks_between(
+    previous: Option<u64>,
+    next: Option<u64>,
+    count: usize,
+    gap: u64,
+) -> Option<Vec<u64>> {
+    let start = previous.unwrap_or(0);
+    let count = count as u64;
+
+    let step = match next {
+        Some(next) => next.checked_sub(start)? / (count + 1),
+        None => gap,
+    };
+
+    if step == 0 {
+        return None;
+    }
+
+    (1..=count)
+        .map(|offset| start.checked_add(step.checked_mul(offset)?))
+        .collect()
+}
+
+/// Ranks `gap` apart for `count` siblings. Ranks past the largest one stay
+/// at it rather than wrapping around.
+pub fn spread_ranks(count: usize, gap: u64) -> Vec<u64> {
+    (1..=count as u64)
+        .map(|offset| offset.saturating_mul(gap))
+        .collect()
+}
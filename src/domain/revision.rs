@@ -0,0 +1,18 @@
+use hifitime::Epoch;
+use uuid::Uuid;
+
+use crate::domain::node::Source;
+
+/// Content a node had before an edit replaced it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    /// The canonical node the content belongs to
+    pub node_id: Uuid,
+    /// When the content was replaced
+    pub replaced_time: Epoch,
+    pub text: String,
+    pub note: String,
+    /// Who made the edit that replaced the content, when it was recorded
+    pub author: Option<String>,
+    pub source_type: Option<Source>,
+}
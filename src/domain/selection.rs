@@ -0,0 +1,97 @@
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+use crate::domain::{DomainError, NodeGraph};
+
+/// A group of nodes that bulk operations act on together.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selection {
+    /// Contiguous run of siblings from `anchor` to `head`, inclusive and in either order
+    Range { anchor: Uuid, head: Uuid },
+    /// Any nodes, wherever they are in the outline
+    Set(Vec<Uuid>),
+}
+
+impl Selection {
+    pub fn single(node_id: Uuid) -> Self {
+        Selection::Set(vec![node_id])
+    }
+
+    /// Returns the IDs of the selected nodes in outline order.
+    pub fn resolve(&self, graph: &NodeGraph) -> Result<Vec<Uuid>, DomainError> {
+        match self {
+            Selection::Range { anchor, head } => {
+                let anchor_node = graph
+                    .node(anchor)
+                    .ok_or(DomainError::MissingNode(*anchor))?;
+                let head_node = graph.node(head).ok_or(DomainError::MissingNode(*head))?;
+
+                if anchor_node.parent_id() != head_node.parent_id() {
+                    return Err(DomainError::InvalidSelection);
+                }
+
+                let siblings = match anchor_node.parent_id() {
+                    Some(parent_id) => {
+                        &graph
+                            .find(&parent_id)
+                            .ok_or(DomainError::MissingNode(parent_id))?
+                            .children
+                    }
+                    None => &graph.graph,
+                };
+
+                let position = |id: &Uuid| {
+                    siblings
+                        .iter()
+                        .position(|element| element.id == *id)
+                        .ok_or(DomainError::MissingNode(*id))
+                };
+
+                let (start, end) = (position(anchor)?, position(head)?);
+                let range = start.min(end)..=start.max(end);
+
+                Ok(siblings[range].iter().map(|element| element.id).collect())
+            }
+            Selection::Set(node_ids) => {
+                // mirrors repeat elements, so only the first appearance counts
+                let mut order: HashMap<Uuid, usize> = HashMap::new();
+                for (index, element) in graph.rows().enumerate() {
+                    order.entry(element.id).or_insert(index);
+                }
+
+                let mut selected: Vec<Uuid> = Vec::with_capacity(node_ids.len());
+                for node_id in node_ids {
+                    if !order.contains_key(node_id) {
+                        return Err(DomainError::MissingNode(*node_id));
+                    }
+
+                    if !selected.contains(node_id) {
+                        selected.push(*node_id);
+                    }
+                }
+
+                selected.sort_by_key(|node_id| order[node_id]);
+
+                Ok(selected)
+            }
+        }
+    }
+
+    /// Returns the selected nodes that have no selected ancestor, in outline order.
+    /// Acting on the subtrees of these covers every selected node exactly once.
+    pub fn roots(&self, graph: &NodeGraph) -> Result<Vec<Uuid>, DomainError> {
+        let selected = self.resolve(graph)?;
+        let selected_set: HashSet<Uuid> = selected.iter().copied().collect();
+
+        Ok(selected
+            .into_iter()
+            .filter(|node_id| {
+                !graph
+                    .ancestors(node_id)
+                    .iter()
+                    .any(|ancestor| selected_set.contains(ancestor))
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::{
+    DomainError, Planning,
+    node::{NodeType, Source},
+};
+
+/// A branch of the outline detached from any repository. Ranks are not kept,
+/// only the order of `children`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subtree {
+    /// ID of the node this entry was taken from, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    pub node_type: NodeType,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    pub author: String,
+    pub source_type: Source,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub planning: Vec<Planning>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Subtree>,
+}
+
+impl Subtree {
+    /// Number of nodes in this subtree, including its root.
+    pub fn node_count(&self) -> usize {
+        1 + self.children.iter().map(Subtree::node_count).sum::<usize>()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardMode {
+    /// Pasting creates new nodes
+    Copy,
+    /// Pasting moves the original nodes when they exist in the target document,
+    /// and adds copies with new IDs when they do not
+    Cut,
+}
+
+/// Subtrees in transit between documents or processes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Clipboard {
+    pub mode: ClipboardMode,
+    pub roots: Vec<Subtree>,
+}
+
+impl Clipboard {
+    pub fn node_count(&self) -> usize {
+        self.roots.iter().map(Subtree::node_count).sum()
+    }
+
+    pub fn to_text(&self) -> Result<String, DomainError> {
+        serde_json::to_string_pretty(self).map_err(|_| DomainError::ClipboardFormat)
+    }
+
+    pub fn from_text(text: &str) -> Result<Clipboard, DomainError> {
+        serde_json::from_str(text).map_err(|_| DomainError::ClipboardFormat)
+    }
+}
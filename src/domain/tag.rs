@@ -0,0 +1,13 @@
+use crate::domain::DomainError;
+
+/// Normalises a user supplied tag, accepting it with or without a leading `#`.
+pub fn normalize_tag(tag: &str) -> Result<String, DomainError> {
+    let tag = tag.trim();
+    let tag = tag.strip_prefix('#').unwrap_or(tag);
+
+    if tag.is_empty() || tag.chars().any(char::is_whitespace) {
+        return Err(DomainError::InvalidTag(tag.to_owned()));
+    }
+
+    Ok(tag.to_owned())
+}
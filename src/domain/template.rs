@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::domain::{DomainError, Subtree};
+
+/// A named structure of nodes that can be added to the outline again and
+/// again. Node text and notes may hold `{{name}}` placeholders, filled in
+/// each time the template is used.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub id: Uuid,
+    pub name: String,
+    pub roots: Vec<Subtree>,
+}
+
+impl Template {
+    /// Creates a template, checking that it has a name and at least one node.
+    pub fn new(name: &str, roots: Vec<Subtree>) -> Result<Template, DomainError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(DomainError::InvalidTemplateName);
+        }
+
+        if roots.is_empty() {
+            return Err(DomainError::EmptyTemplate);
+        }
+
+        Ok(Template {
+            id: Uuid::new_v4(),
+            name: name.to_owned(),
+            roots,
+        })
+    }
+
+    /// Names of the placeholders used anywhere in the template, in the order
+    /// they first appear.
+    pub fn placeholders(&self) -> Vec<String> {
+        fn collect(subtree: &Subtree, names: &mut Vec<String>) {
+            for text in [&subtree.text, &subtree.note] {
+                for (_, name) in find_placeholders(text) {
+                    if !names.iter().any(|existing| existing == name) {
+                        names.push(name.to_owned());
+                    }
+                }
+            }
+
+            for child in &subtree.children {
+                collect(child, names);
+            }
+        }
+
+        let mut names = Vec::new();
+        for root in &self.roots {
+            collect(root, &mut names);
+        }
+
+        names
+    }
+
+    /// The template's nodes with every placeholder replaced by its value in
+    /// `values`, failing on the first placeholder without one.
+    pub fn expand(&self, values: &HashMap<String, String>) -> Result<Vec<Subtree>, DomainError> {
+        fn expand_subtree(
+            subtree: &Subtree,
+            values: &HashMap<String, String>,
+        ) -> Result<Subtree, DomainError> {
+            Ok(Subtree {
+                id: None,
+                text: fill(&subtree.text, values)?,
+                note: fill(&subtree.note, values)?,
+                children: subtree
+                    .children
+                    .iter()
+                    .map(|child| expand_subtree(child, values))
+                    .collect::<Result<_, _>>()?,
+                ..subtree.clone()
+            })
+        }
+
+        self.roots
+            .iter()
+            .map(|root| expand_subtree(root, values))
+            .collect()
+    }
+}
+
+/// Replaces the placeholders in `text` with their values.
+fn fill(text: &str, values: &HashMap<String, String>) -> Result<String, DomainError> {
+    let mut filled = String::new();
+    let mut position = 0;
+
+    for (range, name) in find_placeholders(text) {
+        let value = values
+            .get(name)
+            .ok_or_else(|| DomainError::MissingTemplateValue(name.to_owned()))?;
+
+        filled.push_str(&text[position..range.start]);
+        filled.push_str(value);
+        position = range.end;
+    }
+
+    filled.push_str(&text[position..]);
+    Ok(filled)
+}
+
+/// Byte ranges and names of the `{{name}}` placeholders in `text`. Names are
+/// made of letters, digits, `_` and `-`, and may be padded with spaces.
+fn find_placeholders(text: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut placeholders = Vec::new();
+    let mut position = 0;
+
+    while let Some(offset) = text[position..].find("{{") {
+        let start = position + offset;
+        let Some(length) = text[start + 2..].find("}}") else {
+            break;
+        };
+
+        let end = start + 2 + length + 2;
+        let name = text[start + 2..end - 2].trim();
+
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|char| char.is_alphanumeric() || char == '_' || char == '-');
+
+        match valid {
+            true => {
+                placeholders.push((start..end, name));
+                position = end;
+            }
+            // the braces are text, but a placeholder may still start inside them
+            false => position = start + 1,
+        }
+    }
+
+    placeholders
+}
@@ -0,0 +1,117 @@
+use std::{fmt, str::FromStr};
+
+use uuid::Uuid;
+
+use crate::domain::{
+    DomainError, Query,
+    query::{Filter, Term},
+};
+
+/// A named search over the outline, stored alongside it and re-evaluated
+/// every time it is opened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedView {
+    pub name: String,
+    /// Query in its text form, as accepted by `Query::parse`
+    pub query: String,
+    pub sort: ViewSort,
+    /// Node the view is limited to and shown from, the whole outline when `None`
+    pub root: Option<Uuid>,
+}
+
+impl SavedView {
+    /// Creates a view, checking that `query` parses.
+    pub fn new(
+        name: &str,
+        query: &str,
+        sort: ViewSort,
+        root: Option<Uuid>,
+    ) -> Result<SavedView, DomainError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(DomainError::InvalidViewName);
+        }
+
+        Query::parse(query)?;
+
+        Ok(SavedView {
+            name: name.to_owned(),
+            query: query.to_owned(),
+            sort,
+            root,
+        })
+    }
+
+    /// Parses the view's query, limited to the view's root.
+    pub fn query(&self) -> Result<Query, DomainError> {
+        let mut query = Query::parse(&self.query)?;
+
+        if let Some(root) = self.root {
+            query.terms.push(Term {
+                negated: false,
+                filter: Filter::Under(root),
+            });
+        }
+
+        Ok(query)
+    }
+}
+
+/// Order of siblings within a view, written as the key name for ascending
+/// order or with a leading `-` for descending order, such as `-modified`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ViewSort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortKey {
+    /// Order of the outline itself
+    #[default]
+    Outline,
+    Created,
+    Modified,
+    Text,
+    Type,
+}
+
+impl FromStr for ViewSort {
+    type Err = DomainError;
+
+    fn from_str(input: &str) -> Result<ViewSort, Self::Err> {
+        let (descending, key) = match input.trim().strip_prefix('-') {
+            Some(key) => (true, key),
+            None => (false, input.trim()),
+        };
+
+        let key = match key.to_ascii_lowercase().as_str() {
+            "outline" => SortKey::Outline,
+            "created" => SortKey::Created,
+            "modified" => SortKey::Modified,
+            "text" => SortKey::Text,
+            "type" => SortKey::Type,
+            _ => return Err(DomainError::FieldParseError("sort".to_owned())),
+        };
+
+        Ok(ViewSort { key, descending })
+    }
+}
+
+impl fmt::Display for ViewSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.key {
+            SortKey::Outline => "outline",
+            SortKey::Created => "created",
+            SortKey::Modified => "modified",
+            SortKey::Text => "text",
+            SortKey::Type => "type",
+        };
+
+        if self.descending {
+            f.write_str("-")?;
+        }
+
+        f.write_str(label)
+    }
+}
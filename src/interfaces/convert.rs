@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use super::{InterfaceError, NodeRepository};
+use crate::domain::{Node, attachment::AttachmentContent};
+use uuid::Uuid;
+
+/// Copies every node, link, tag, collapsed flag, setting, saved view,
+/// template, tombstone, revision, attachment and logged operation held by `source` into
+/// `target` in a single transaction, returning the number of nodes copied.
+/// Node IDs are preserved, so `target` should not already contain any of them.
+pub fn copy_repository(
+    source: &impl NodeRepository,
+    target: &impl NodeRepository,
+) -> Result<usize, InterfaceError> {
+    let nodes = source.dump_nodes()?;
+
+    target.begin_transaction()?;
+    match copy_contents(source, target, &nodes) {
+        Ok(()) => target.commit_transaction()?,
+        Err(err) => {
+            target.rollback_transaction()?;
+            return Err(err);
+        }
+    }
+
+    Ok(nodes.len())
+}
+
+fn copy_contents(
+    source: &impl NodeRepository,
+    target: &impl NodeRepository,
+    nodes: &[Node],
+) -> Result<(), InterfaceError> {
+    for node in nodes {
+        target.add_node(node)?;
+    }
+
+    let mut links: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for (source_id, target_id) in source.dump_links()? {
+        links.entry(source_id).or_default().push(target_id);
+    }
+
+    for (source_id, target_ids) in links {
+        target.set_links(&source_id, &target_ids)?;
+    }
+
+    for (node_id, tag) in source.dump_tags()? {
+        target.add_tag(&[node_id], &tag)?;
+    }
+
+    for (node_id, planning) in source.dump_planning()? {
+        target.set_planning(&node_id, planning.kind, Some(planning.time))?;
+    }
+
+    let collapsed: Vec<_> = source.collapsed_nodes()?.into_iter().collect();
+    target.set_collapsed(&collapsed, true)?;
+
+    for (key, value) in source.dump_settings()? {
+        target.set_setting(&key, Some(&value))?;
+    }
+
+    for view in source.dump_views()? {
+        target.save_view(&view)?;
+    }
+
+    for template in source.dump_templates()? {
+        target.save_template(&template)?;
+    }
+
+    for (node_id, deleted_time) in source.dump_tombstones()? {
+        target.add_tombstone(&node_id, deleted_time)?;
+    }
+
+    for revision in source.dump_revisions()? {
+        target.add_revision(&revision)?;
+    }
+
+    for attachment in source.dump_attachments()? {
+        if let AttachmentContent::Blob(hash) = &attachment.content {
+            let data = source.get_blob(hash)?.ok_or(InterfaceError::MissingBlob)?;
+            target.add_blob(hash, &data)?;
+        }
+
+        target.add_attachment(&attachment)?;
+    }
+
+    target.add_operations(&source.get_operations(0)?)?;
+
+    Ok(())
+}
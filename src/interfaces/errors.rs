@@ -22,6 +22,40 @@ pub enum InterfaceError {
     FieldParseError(String),
     #[error("Node deletion error")]
     NodeDelete,
+    #[error("Tombstone write error")]
+    TombstoneWrite,
+    #[error("Link write error")]
+    LinkWrite,
+    #[error("Tag write error")]
+    TagWrite,
+    #[error("Planning write error")]
+    PlanningWrite,
+    #[error("Collapsed state write error")]
+    CollapsedWrite,
+    #[error("Setting write error")]
+    SettingWrite,
+    #[error("View write error")]
+    ViewWrite,
+    #[error("Revision write error")]
+    RevisionWrite,
+    #[error("Template write error")]
+    TemplateWrite,
+    #[error("Operation write error")]
+    OperationWrite,
+    #[error("Attachment write error")]
+    AttachmentWrite,
+    #[error("Attached file was not found in DB")]
+    MissingBlob,
+    #[error("Line {line}: {reason}")]
+    ImportParse { line: usize, reason: String },
+    #[error("Transaction error")]
+    Transaction,
+    #[error("File access error")]
+    FileAccess,
+    #[error("Serialization error")]
+    Serialization,
+    #[error("Repository is locked by another writer")]
+    Locked,
     #[error("Other error encountered")]
     Other,
     #[error("Domain Error: `{0}`")]
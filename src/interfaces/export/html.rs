@@ -0,0 +1,119 @@
+use super::{
+    ExportOptions, Exporter,
+    opml::{escape, inline_html},
+};
+use crate::domain::{GraphElement, NodeGraph, node::NodeType};
+
+const STYLE: &str = "\
+body { font-family: sans-serif; line-height: 1.5; max-width: 50em; margin: 2em auto; }
+ul { list-style: none; padding-left: 1.5em; }
+summary { cursor: pointer; }
+.task { margin-right: 0.3em; }
+.done > .text, .done > details > summary > .text { text-decoration: line-through; opacity: 0.6; }
+.tag { color: #2a7ab0; margin-left: 0.4em; }
+.note { white-space: pre-wrap; color: #555; margin: 0.2em 0 0.4em; }
+.meta { font-size: 0.8em; color: #888; }
+";
+
+/// Renders elements as a standalone HTML page. Nodes with children become
+/// `<details>` elements that can be folded, starting folded when they are
+/// collapsed in the outline. Inline Markdown becomes HTML markup.
+pub struct Html;
+
+impl Exporter for Html {
+    fn export(
+        &self,
+        graph: &NodeGraph,
+        elements: &[&GraphElement],
+        options: &ExportOptions,
+    ) -> String {
+        let mut output = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Outline</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n<ul>\n"
+        );
+
+        for element in elements {
+            write_element(graph, element, 0, options, &mut output);
+        }
+
+        output.push_str("</ul>\n</body>\n</html>\n");
+        output
+    }
+}
+
+fn write_element(
+    graph: &NodeGraph,
+    element: &GraphElement,
+    level: usize,
+    options: &ExportOptions,
+    output: &mut String,
+) {
+    let Some(node) = graph.node(&element.id) else {
+        return;
+    };
+
+    let indent = "  ".repeat(level + 1);
+
+    let (class, marker) = match node.node_type() {
+        NodeType::Standard => ("", ""),
+        NodeType::Todo => (" class=\"todo\"", "\u{2610}"),
+        NodeType::InProgress => (" class=\"in-progress\"", "\u{25d0}"),
+        NodeType::Done => (" class=\"done\"", "\u{2611}"),
+    };
+
+    let mut line = String::new();
+    if !marker.is_empty() {
+        line.push_str(&format!("<span class=\"task\">{marker}</span>"));
+    }
+    line.push_str(&format!(
+        "<span class=\"text\">{}</span>",
+        inline_html(node.text())
+    ));
+    for tag in graph.tags(&element.id) {
+        line.push_str(&format!("<span class=\"tag\">#{}</span>", escape(tag)));
+    }
+
+    let mut body = String::new();
+    if !node.note().is_empty() {
+        body.push_str(&format!(
+            "<div class=\"note\">{}</div>",
+            escape(node.note()).replace("&#10;", "\n")
+        ));
+    }
+    if options.metadata {
+        body.push_str(&format!(
+            "<div class=\"meta\">{} \u{b7} {} \u{b7} created {} \u{b7} modified {}</div>",
+            escape(node.author()),
+            node.source_type_str(),
+            node.created_time_str(),
+            node.modified_time_str()
+        ));
+    }
+
+    output.push_str(&indent);
+    output.push_str("<li");
+    output.push_str(class);
+    if options.metadata {
+        output.push_str(&format!(" id=\"{}\"", node.id()));
+    }
+    output.push('>');
+
+    if element.children.is_empty() || !options.includes_children(level) {
+        output.push_str(&line);
+        output.push_str(&body);
+        output.push_str("</li>\n");
+        return;
+    }
+
+    let open = if element.collapsed { "" } else { " open" };
+    output.push_str(&format!("<details{open}><summary>{line}</summary>{body}\n"));
+    output.push_str(&indent);
+    output.push_str("<ul>\n");
+
+    for child in &element.children {
+        write_element(graph, child, level + 1, options, output);
+    }
+
+    output.push_str(&indent);
+    output.push_str("</ul></details></li>\n");
+}
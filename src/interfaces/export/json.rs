@@ -0,0 +1,98 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{ExportOptions, Exporter};
+use crate::domain::{
+    GraphElement, NodeGraph, Planning,
+    node::{NodeType, Source},
+};
+
+/// Renders elements as a JSON array of nested objects, each holding the fields
+/// of its place in the outline along with those of its node. Metadata adds the
+/// author, source and times.
+pub struct JsonTree;
+
+#[derive(Serialize)]
+struct TreeNode<'a> {
+    id: Uuid,
+    parent_id: Option<Uuid>,
+    rank: u64,
+    depth: usize,
+    collapsed: bool,
+    mirror_of: Option<Uuid>,
+    node_type: &'a NodeType,
+    text: &'a str,
+    note: &'a str,
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    planning: &'a [Planning],
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata<'a>>,
+    children: Vec<TreeNode<'a>>,
+}
+
+#[derive(Serialize)]
+struct Metadata<'a> {
+    author: &'a str,
+    source_type: &'a Source,
+    created_time: String,
+    modified_time: String,
+}
+
+impl Exporter for JsonTree {
+    fn export(
+        &self,
+        graph: &NodeGraph,
+        elements: &[&GraphElement],
+        options: &ExportOptions,
+    ) -> String {
+        let tree: Vec<_> = elements
+            .iter()
+            .filter_map(|element| tree_node(graph, element, 0, options))
+            .collect();
+
+        // the tree holds only strings, numbers, times and IDs, which always serialize
+        serde_json::to_string_pretty(&tree).unwrap_or_default() + "\n"
+    }
+}
+
+fn tree_node<'a>(
+    graph: &'a NodeGraph,
+    element: &'a GraphElement,
+    level: usize,
+    options: &ExportOptions,
+) -> Option<TreeNode<'a>> {
+    let node = graph.node(&element.id)?;
+
+    let children = match options.includes_children(level) {
+        true => element
+            .children
+            .iter()
+            .filter_map(|child| tree_node(graph, child, level + 1, options))
+            .collect(),
+        false => Vec::new(),
+    };
+
+    let metadata = options.metadata.then(|| Metadata {
+        author: node.author(),
+        source_type: node.source_type(),
+        created_time: node.created_time_str(),
+        modified_time: node.modified_time_str(),
+    });
+
+    Some(TreeNode {
+        id: element.id,
+        parent_id: element.parent_id,
+        rank: element.rank,
+        depth: element.depth,
+        collapsed: element.collapsed,
+        mirror_of: element.mirror_of,
+        node_type: node.node_type(),
+        text: node.text(),
+        note: node.note(),
+        tags: graph.tags(&element.id),
+        planning: graph.planning(&element.id),
+        metadata,
+        children,
+    })
+}
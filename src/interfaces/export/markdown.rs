@@ -0,0 +1,83 @@
+use super::{ExportOptions, Exporter};
+use crate::domain::{GraphElement, NodeGraph, node::NodeType};
+
+/// Renders elements as a nested Markdown list, with tasks as checkboxes. Node
+/// text is written as it is, its inline markup being Markdown already, and
+/// notes follow as paragraphs indented under their item. Metadata goes in an
+/// HTML comment at the end of each item.
+pub struct Markdown;
+
+impl Exporter for Markdown {
+    fn export(
+        &self,
+        graph: &NodeGraph,
+        elements: &[&GraphElement],
+        options: &ExportOptions,
+    ) -> String {
+        let mut output = String::new();
+
+        for element in elements {
+            write_element(graph, element, 0, options, &mut output);
+        }
+
+        output
+    }
+}
+
+fn write_element(
+    graph: &NodeGraph,
+    element: &GraphElement,
+    level: usize,
+    options: &ExportOptions,
+    output: &mut String,
+) {
+    let Some(node) = graph.node(&element.id) else {
+        return;
+    };
+
+    let checkbox = match node.node_type() {
+        NodeType::Standard => "",
+        NodeType::Todo => "[ ] ",
+        NodeType::InProgress => "[/] ",
+        NodeType::Done => "[x] ",
+    };
+
+    output.push_str(&"  ".repeat(level));
+    output.push_str("- ");
+    output.push_str(checkbox);
+    output.push_str(node.text());
+
+    for tag in graph.tags(&element.id) {
+        output.push_str(" #");
+        output.push_str(tag);
+    }
+
+    if options.metadata {
+        output.push_str(&format!(
+            " <!-- id: {}, author: {}, source: {}, created: {}, modified: {} -->",
+            node.id(),
+            node.author(),
+            node.source_type_str(),
+            node.created_time_str(),
+            node.modified_time_str()
+        ));
+    }
+
+    output.push('\n');
+
+    for line in node.note().lines() {
+        if !line.trim().is_empty() {
+            output.push_str(&"  ".repeat(level + 1));
+            output.push_str(line);
+        }
+        output.push('\n');
+    }
+
+    if !options.includes_children(level) {
+        return;
+    }
+
+    for child in &element.children {
+        write_element(graph, child, level + 1, options, output);
+    }
+}
@@ -0,0 +1,128 @@
+pub mod html;
+pub mod json;
+pub mod markdown;
+pub mod opml;
+pub mod org;
+pub mod text;
+
+use std::{fmt, str::FromStr};
+
+use uuid::Uuid;
+
+use crate::domain::{GraphElement, NodeGraph};
+
+/// Renders branches of an outline in one format.
+pub trait Exporter {
+    /// Renders the subtrees of `elements`, which may come from a view of `graph`.
+    fn export(
+        &self,
+        graph: &NodeGraph,
+        elements: &[&GraphElement],
+        options: &ExportOptions,
+    ) -> String;
+}
+
+/// What to include in an export.
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Node whose subtree is exported, the whole outline when `None`
+    pub root: Option<Uuid>,
+    /// Levels exported below each exported node, all of them when `None`
+    pub max_depth: Option<usize>,
+    /// Whether to include the ID, author, source and times of each node
+    pub metadata: bool,
+}
+
+impl ExportOptions {
+    /// Whether the children of a node `level` levels below an exported node
+    /// are exported too.
+    pub(crate) fn includes_children(&self, level: usize) -> bool {
+        self.max_depth.is_none_or(|max_depth| level < max_depth)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Opml,
+    Html,
+    Text,
+    Json,
+    Org,
+}
+
+impl ExportFormat {
+    pub fn exporter(&self) -> &'static dyn Exporter {
+        match self {
+            ExportFormat::Markdown => &markdown::Markdown,
+            ExportFormat::Opml => &opml::Opml,
+            ExportFormat::Html => &html::Html,
+            ExportFormat::Text => &text::PlainText,
+            ExportFormat::Json => &json::JsonTree,
+            ExportFormat::Org => &org::Org,
+        }
+    }
+
+    /// MIME type of documents in this format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Opml => "text/x-opml; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Text => "text/plain; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Org => "text/x-org; charset=utf-8",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<ExportFormat, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "opml" => Ok(ExportFormat::Opml),
+            "html" => Ok(ExportFormat::Html),
+            "text" | "txt" => Ok(ExportFormat::Text),
+            "json" => Ok(ExportFormat::Json),
+            "org" => Ok(ExportFormat::Org),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Opml => "OPML",
+            ExportFormat::Html => "HTML",
+            ExportFormat::Text => "plain text",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Org => "Org",
+        };
+
+        f.write_str(label)
+    }
+}
+
+/// Renders the subtree of `options.root`, or the whole outline when it is not set.
+pub fn export(graph: &NodeGraph, format: ExportFormat, options: &ExportOptions) -> String {
+    let elements: Vec<_> = match options.root {
+        Some(root) => graph.find(&root).into_iter().collect(),
+        None => graph.graph.iter().collect(),
+    };
+
+    export_elements(graph, &elements, format, options)
+}
+
+/// Renders the subtrees of `elements`, which may come from a view of `graph`.
+pub fn export_elements(
+    graph: &NodeGraph,
+    elements: &[&GraphElement],
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> String {
+    format.exporter().export(graph, elements, options)
+}
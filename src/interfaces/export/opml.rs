@@ -0,0 +1,168 @@
+use super::{ExportOptions, Exporter};
+use crate::domain::{
+    GraphElement, NodeGraph,
+    markup::{Target, parse_inline},
+    node::NodeType,
+};
+
+/// Renders elements as an OPML 2.0 document. Task states are kept in a
+/// `_status` attribute, notes in a `_note` attribute and tags in the standard
+/// `category` attribute. Inline Markdown becomes encoded HTML in the `text`
+/// attribute, as the format allows. Metadata goes in `_id`, `_author`,
+/// `_source`, `_created` and `_modified` attributes.
+pub struct Opml;
+
+impl Exporter for Opml {
+    fn export(
+        &self,
+        graph: &NodeGraph,
+        elements: &[&GraphElement],
+        options: &ExportOptions,
+    ) -> String {
+        let mut output = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <opml version=\"2.0\">\n  \
+             <head>\n    <title>Outline</title>\n  </head>\n  \
+             <body>\n",
+        );
+
+        for element in elements {
+            write_element(graph, element, 0, options, &mut output);
+        }
+
+        output.push_str("  </body>\n</opml>\n");
+        output
+    }
+}
+
+fn write_element(
+    graph: &NodeGraph,
+    element: &GraphElement,
+    level: usize,
+    options: &ExportOptions,
+    output: &mut String,
+) {
+    let Some(node) = graph.node(&element.id) else {
+        return;
+    };
+
+    // outlines start inside `<opml>` and `<body>`
+    let indent = "  ".repeat(level + 2);
+
+    output.push_str(&indent);
+    output.push_str("<outline text=\"");
+    output.push_str(&escape(&inline_html(node.text())));
+    output.push('"');
+
+    if *node.node_type() != NodeType::Standard {
+        output.push_str(" _status=\"");
+        output.push_str(&node.node_type_str().to_ascii_lowercase());
+        output.push('"');
+    }
+
+    if !node.note().is_empty() {
+        output.push_str(" _note=\"");
+        output.push_str(&escape(node.note()));
+        output.push('"');
+    }
+
+    let tags = graph.tags(&element.id);
+    if !tags.is_empty() {
+        output.push_str(" category=\"");
+        output.push_str(&escape(&tags.join(",")));
+        output.push('"');
+    }
+
+    if options.metadata {
+        for (attribute, value) in [
+            ("_id", node.id_str()),
+            ("_author", node.author().to_owned()),
+            ("_source", node.source_type_str()),
+            ("_created", node.created_time_str()),
+            ("_modified", node.modified_time_str()),
+        ] {
+            output.push_str(&format!(" {attribute}=\"{}\"", escape(&value)));
+        }
+    }
+
+    if element.children.is_empty() || !options.includes_children(level) {
+        output.push_str("/>\n");
+        return;
+    }
+
+    output.push_str(">\n");
+
+    for child in &element.children {
+        write_element(graph, child, level + 1, options, output);
+    }
+
+    output.push_str(&indent);
+    output.push_str("</outline>\n");
+}
+
+/// Node text with its inline Markdown turned into HTML markup.
+pub(crate) fn inline_html(text: &str) -> String {
+    let mut html = String::new();
+    let mut open_link: Option<&str> = None;
+    let inlines = parse_inline(text);
+
+    for inline in &inlines {
+        let url = match &inline.link {
+            Some(Target::Url(url)) => Some(url.as_str()),
+            _ => None,
+        };
+
+        // runs of one link share one anchor
+        if url != open_link {
+            if open_link.is_some() {
+                html.push_str("</a>");
+            }
+            if let Some(url) = url {
+                html.push_str(&format!("<a href=\"{}\">", escape(url)));
+            }
+            open_link = url;
+        }
+
+        let emphasis = inline.emphasis;
+        let tags: Vec<&str> = [
+            (emphasis.bold, "b"),
+            (emphasis.italic, "i"),
+            (emphasis.strikethrough, "s"),
+            (emphasis.code, "code"),
+        ]
+        .into_iter()
+        .filter_map(|(applies, tag)| applies.then_some(tag))
+        .collect();
+
+        for tag in &tags {
+            html.push_str(&format!("<{tag}>"));
+        }
+        html.push_str(&escape(&inline.text));
+        for tag in tags.iter().rev() {
+            html.push_str(&format!("</{tag}>"));
+        }
+    }
+
+    if open_link.is_some() {
+        html.push_str("</a>");
+    }
+
+    html
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            _ => escaped.push(character),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,224 @@
+use super::{ExportOptions, Exporter};
+use hifitime::{Epoch, Weekday};
+
+use crate::domain::{
+    GraphElement, NodeGraph,
+    markup::{Emphasis, Target, parse_inline},
+    node::NodeType,
+    planning::PlanningKind,
+};
+
+/// Planning keywords which Org expects on the line after a headline.
+pub(crate) const PLANNING_KEYWORDS: [&str; 3] = ["SCHEDULED:", "DEADLINE:", "CLOSED:"];
+
+/// Renders elements as an Org document, one headline a node. Task states
+/// become `TODO`, `DOING` and `DONE` keywords and tags are listed at the end
+/// of the headline. Each node's ID is kept in its `:PROPERTIES:` drawer so
+/// the document can be imported again over the same nodes, with the other
+/// metadata alongside it when asked for. Scheduled and deadline dates go on
+/// the planning line before the drawer as Org requires, together with any
+/// planning line such as `CLOSED` that the note starts with. The rest of the
+/// note becomes the body. Inline Markdown in node text becomes
+/// Org markup, `*bold*`, `/italic/`, `+strikethrough+`, `~code~` and
+/// `[[url][label]]`, while node references are written as they are.
+pub struct Org;
+
+impl Exporter for Org {
+    fn export(
+        &self,
+        graph: &NodeGraph,
+        elements: &[&GraphElement],
+        options: &ExportOptions,
+    ) -> String {
+        let mut output = String::new();
+
+        for element in elements {
+            write_element(graph, element, 0, options, &mut output);
+        }
+
+        output
+    }
+}
+
+fn write_element(
+    graph: &NodeGraph,
+    element: &GraphElement,
+    level: usize,
+    options: &ExportOptions,
+    output: &mut String,
+) {
+    let Some(node) = graph.node(&element.id) else {
+        return;
+    };
+
+    let keyword = match node.node_type() {
+        NodeType::Standard => "",
+        NodeType::Todo => "TODO ",
+        NodeType::InProgress => "DOING ",
+        NodeType::Done => "DONE ",
+    };
+
+    output.push_str(&"*".repeat(level + 1));
+    output.push(' ');
+    output.push_str(keyword);
+    output.push_str(&inline_org(node.text()));
+
+    let tags = graph.tags(&element.id);
+    if !tags.is_empty() {
+        output.push_str(&format!(" :{}:", tags.join(":")));
+    }
+
+    output.push('\n');
+
+    let mut planning: Vec<String> = graph
+        .planning(&element.id)
+        .iter()
+        .map(|planning| {
+            let keyword = match planning.kind {
+                PlanningKind::Scheduled => "SCHEDULED:",
+                PlanningKind::Deadline => "DEADLINE:",
+            };
+            format!("{keyword} {}", org_timestamp(planning.time))
+        })
+        .collect();
+
+    let mut lines = node.note().lines().peekable();
+    while let Some(line) = lines.next_if(|line| is_planning(line)) {
+        planning.push(line.trim().to_owned());
+    }
+
+    if !planning.is_empty() {
+        output.push_str(&planning.join(" "));
+        output.push('\n');
+    }
+
+    output.push_str(":PROPERTIES:\n");
+    output.push_str(&format!(":ID:       {}\n", node.id()));
+    if options.metadata {
+        output.push_str(&format!(":AUTHOR:   {}\n", node.author()));
+        output.push_str(&format!(":SOURCE:   {}\n", node.source_type_str()));
+        output.push_str(&format!(":CREATED:  {}\n", node.created_time_str()));
+        output.push_str(&format!(":MODIFIED: {}\n", node.modified_time_str()));
+    }
+    output.push_str(":END:\n");
+
+    for line in lines {
+        // a body line starting with `*` would read as a headline
+        if line.starts_with('*') {
+            output.push(' ');
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    if !options.includes_children(level) {
+        return;
+    }
+
+    for child in &element.children {
+        write_element(graph, child, level + 1, options, output);
+    }
+}
+
+/// Node text with its inline Markdown turned into Org markup.
+pub(crate) fn inline_org(text: &str) -> String {
+    let mut org = String::new();
+    let mut open_link: Option<&str> = None;
+    // markers of the emphasis open at this point, innermost last
+    let mut open: Vec<char> = Vec::new();
+    let inlines = parse_inline(text);
+
+    for inline in &inlines {
+        let url = match &inline.link {
+            Some(Target::Url(url)) => Some(url.as_str()),
+            _ => None,
+        };
+
+        // runs of one link share one link, which emphasis does not cross
+        if url != open_link {
+            close_markers(&mut org, &mut open, 0);
+            if open_link.is_some() {
+                org.push_str("]]");
+            }
+            if let Some(url) = url {
+                org.push_str(&format!("[[{url}]["));
+            }
+            open_link = url;
+        }
+
+        let wanted = markers(inline.emphasis);
+        let kept = open
+            .iter()
+            .position(|marker| !wanted.contains(marker))
+            .unwrap_or(open.len());
+        close_markers(&mut org, &mut open, kept);
+
+        for marker in wanted {
+            if !open.contains(&marker) {
+                org.push(marker);
+                open.push(marker);
+            }
+        }
+
+        match inline.emphasis.code {
+            true => org.push_str(&format!("~{}~", inline.text)),
+            false => org.push_str(&inline.text),
+        }
+    }
+
+    close_markers(&mut org, &mut open, 0);
+    if open_link.is_some() {
+        org.push_str("]]");
+    }
+
+    org
+}
+
+/// Org markers for the emphasis of a run, other than code.
+fn markers(emphasis: Emphasis) -> Vec<char> {
+    [
+        (emphasis.bold, '*'),
+        (emphasis.italic, '/'),
+        (emphasis.strikethrough, '+'),
+    ]
+    .into_iter()
+    .filter_map(|(applies, marker)| applies.then_some(marker))
+    .collect()
+}
+
+/// Closes the open markers after the first `keep`, innermost first.
+fn close_markers(org: &mut String, open: &mut Vec<char>, keep: usize) {
+    while open.len() > keep {
+        if let Some(marker) = open.pop() {
+            org.push(marker);
+        }
+    }
+}
+
+/// An active Org timestamp such as `<2026-10-20 Tue>`, with the time of day
+/// unless it is midnight.
+pub(crate) fn org_timestamp(time: Epoch) -> String {
+    let (year, month, day, hour, minute, ..) = time.to_gregorian_utc();
+    let weekday = match time.weekday_utc() {
+        Weekday::Monday => "Mon",
+        Weekday::Tuesday => "Tue",
+        Weekday::Wednesday => "Wed",
+        Weekday::Thursday => "Thu",
+        Weekday::Friday => "Fri",
+        Weekday::Saturday => "Sat",
+        Weekday::Sunday => "Sun",
+    };
+
+    match (hour, minute) {
+        (0, 0) => format!("<{year:04}-{month:02}-{day:02} {weekday}>"),
+        _ => format!("<{year:04}-{month:02}-{day:02} {weekday} {hour:02}:{minute:02}>"),
+    }
+}
+
+/// Whether `line` holds planning information such as `SCHEDULED: <2026-10-20 Tue>`.
+pub(crate) fn is_planning(line: &str) -> bool {
+    let line = line.trim_start();
+    PLANNING_KEYWORDS
+        .iter()
+        .any(|keyword| line.starts_with(keyword))
+}
@@ -0,0 +1,127 @@
+use super::{ExportOptions, Exporter};
+use crate::domain::{
+    GraphElement, NodeGraph,
+    markup::{Target, parse_inline},
+    node::NodeType,
+};
+
+/// Renders elements as plain text indented by two spaces a level, for pasting
+/// into an email. Inline Markdown is dropped, except that link addresses
+/// follow their text in angle brackets.
+pub struct PlainText;
+
+impl Exporter for PlainText {
+    fn export(
+        &self,
+        graph: &NodeGraph,
+        elements: &[&GraphElement],
+        options: &ExportOptions,
+    ) -> String {
+        let mut output = String::new();
+
+        for element in elements {
+            write_element(graph, element, 0, options, &mut output);
+        }
+
+        output
+    }
+}
+
+fn write_element(
+    graph: &NodeGraph,
+    element: &GraphElement,
+    level: usize,
+    options: &ExportOptions,
+    output: &mut String,
+) {
+    let Some(node) = graph.node(&element.id) else {
+        return;
+    };
+
+    let marker = match node.node_type() {
+        NodeType::Standard => "- ",
+        NodeType::Todo => "[ ] ",
+        NodeType::InProgress => "[/] ",
+        NodeType::Done => "[x] ",
+    };
+
+    output.push_str(&"  ".repeat(level));
+    output.push_str(marker);
+    output.push_str(&text_with_urls(node.text()));
+
+    for tag in graph.tags(&element.id) {
+        output.push_str(" #");
+        output.push_str(tag);
+    }
+
+    output.push('\n');
+
+    // notes and metadata line up with the text, past the marker
+    let indent = "  ".repeat(level) + &" ".repeat(marker.len());
+
+    for line in node.note().lines() {
+        if !line.trim().is_empty() {
+            output.push_str(&indent);
+            output.push_str(line);
+        }
+        output.push('\n');
+    }
+
+    if options.metadata {
+        output.push_str(&format!(
+            "{indent}({}, {}, created {}, modified {}, {})\n",
+            node.author(),
+            node.source_type_str(),
+            node.created_time_str(),
+            node.modified_time_str(),
+            node.id()
+        ));
+    }
+
+    if !options.includes_children(level) {
+        return;
+    }
+
+    for child in &element.children {
+        write_element(graph, child, level + 1, options, output);
+    }
+}
+
+/// Node text without its markup, each link followed by its address unless
+/// the text already is the address.
+fn text_with_urls(text: &str) -> String {
+    let mut plain = String::new();
+    let mut link_text = String::new();
+    let mut open_link: Option<&str> = None;
+    let inlines = parse_inline(text);
+
+    for inline in &inlines {
+        let url = match &inline.link {
+            Some(Target::Url(url)) => Some(url.as_str()),
+            _ => None,
+        };
+
+        if url != open_link {
+            if let Some(open_url) = open_link
+                && link_text != open_url
+            {
+                plain.push_str(&format!(" <{open_url}>"));
+            }
+            link_text.clear();
+            open_link = url;
+        }
+
+        if open_link.is_some() {
+            link_text.push_str(&inline.text);
+        }
+        plain.push_str(&inline.text);
+    }
+
+    if let Some(open_url) = open_link
+        && link_text != open_url
+    {
+        plain.push_str(&format!(" <{open_url}>"));
+    }
+
+    plain
+}
@@ -0,0 +1,78 @@
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{Importer, parse_error};
+use crate::{
+    domain::{
+        Planning, Subtree,
+        node::{NodeType, Source},
+    },
+    interfaces::InterfaceError,
+};
+
+/// Reads the nested JSON written by the JSON tree export. Node IDs are kept,
+/// as are the author and source when the export included metadata. Fields
+/// describing a node's place in the outline are left out, its place in the
+/// tree being what counts.
+pub struct JsonTree;
+
+#[derive(Deserialize)]
+struct TreeNode {
+    #[serde(default)]
+    id: Option<Uuid>,
+    #[serde(default = "standard")]
+    node_type: NodeType,
+    text: String,
+    #[serde(default)]
+    note: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    planning: Vec<Planning>,
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default)]
+    source_type: Option<Source>,
+    #[serde(default)]
+    children: Vec<TreeNode>,
+}
+
+fn standard() -> NodeType {
+    NodeType::Standard
+}
+
+impl Importer for JsonTree {
+    fn import(&self, text: &str, author: &str) -> Result<Vec<Subtree>, InterfaceError> {
+        let tree: Vec<TreeNode> = serde_json::from_str(text).map_err(|err| {
+            // the position is reported as the line number instead
+            let message = err.to_string();
+            let reason = message.split(" at line ").next().unwrap_or_default();
+            parse_error(err.line(), reason)
+        })?;
+
+        Ok(tree
+            .into_iter()
+            .map(|node| node.into_subtree(author))
+            .collect())
+    }
+}
+
+impl TreeNode {
+    fn into_subtree(self, author: &str) -> Subtree {
+        Subtree {
+            id: self.id,
+            node_type: self.node_type,
+            text: self.text,
+            note: self.note,
+            author: self.author.unwrap_or_else(|| author.to_owned()),
+            source_type: self.source_type.unwrap_or(Source::User),
+            tags: self.tags,
+            planning: self.planning,
+            children: self
+                .children
+                .into_iter()
+                .map(|child| child.into_subtree(author))
+                .collect(),
+        }
+    }
+}
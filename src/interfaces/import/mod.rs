@@ -0,0 +1,184 @@
+pub mod json;
+pub mod org;
+pub mod taskpaper;
+pub mod text;
+
+use std::{fmt, path::Path, str::FromStr};
+
+use super::InterfaceError;
+use crate::domain::Subtree;
+
+/// Reads outlines written in one format.
+pub trait Importer {
+    /// Parses `text` into subtrees, crediting their nodes to `author`.
+    fn import(&self, text: &str, author: &str) -> Result<Vec<Subtree>, InterfaceError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Text,
+    TaskPaper,
+    Org,
+    Json,
+}
+
+impl ImportFormat {
+    pub fn importer(&self) -> &'static dyn Importer {
+        match self {
+            ImportFormat::Text => &text::PlainText,
+            ImportFormat::TaskPaper => &taskpaper::TaskPaper,
+            ImportFormat::Org => &org::Org,
+            ImportFormat::Json => &json::JsonTree,
+        }
+    }
+
+    /// Format of the file at `path` going by its extension, plain text when
+    /// the extension is not known.
+    pub fn detect(path: &Path) -> ImportFormat {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("taskpaper") => ImportFormat::TaskPaper,
+            Some("org") => ImportFormat::Org,
+            Some("json") => ImportFormat::Json,
+            _ => ImportFormat::Text,
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<ImportFormat, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Ok(ImportFormat::Text),
+            "taskpaper" => Ok(ImportFormat::TaskPaper),
+            "org" => Ok(ImportFormat::Org),
+            "json" => Ok(ImportFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ImportFormat::Text => "plain text",
+            ImportFormat::TaskPaper => "TaskPaper",
+            ImportFormat::Org => "Org",
+            ImportFormat::Json => "JSON",
+        };
+
+        f.write_str(label)
+    }
+}
+
+/// A non-blank line of an indented outline.
+pub(crate) struct Line<'a> {
+    /// Position of the line in the text, counting from 1
+    pub number: usize,
+    pub level: usize,
+    /// The line without its indentation or trailing whitespace
+    pub content: &'a str,
+}
+
+/// Splits `text` into its non-blank lines along with their level. A tab
+/// indents by one level, as does the smallest indentation made of spaces
+/// found in the text.
+pub(crate) fn indented_lines(text: &str) -> Result<Vec<Line<'_>>, InterfaceError> {
+    let lines: Vec<(usize, &str, &str)> = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index, line.trim_end()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| {
+            let content = line.trim_start_matches([' ', '\t']);
+            (index + 1, &line[..line.len() - content.len()], content)
+        })
+        .collect();
+
+    let width = lines
+        .iter()
+        .map(|(_, indent, _)| indent.len())
+        .filter(|&length| length > 0)
+        .min()
+        .unwrap_or(1);
+
+    let mut parsed: Vec<Line> = Vec::with_capacity(lines.len());
+
+    for (number, indent, content) in lines {
+        let level = if indent.chars().all(|char| char == '\t') {
+            indent.len()
+        } else if indent.chars().all(|char| char == ' ') {
+            if indent.len() % width != 0 {
+                return Err(parse_error(
+                    number,
+                    format!(
+                        "indented by {} spaces, which is not a multiple of {width}",
+                        indent.len()
+                    ),
+                ));
+            }
+            indent.len() / width
+        } else {
+            return Err(parse_error(number, "indentation mixes tabs and spaces"));
+        };
+
+        match parsed.last() {
+            None if level > 0 => {
+                return Err(parse_error(number, "the first line is indented"));
+            }
+            Some(above) if level > above.level + 1 => {
+                return Err(parse_error(
+                    number,
+                    "indented more than one level below the line above",
+                ));
+            }
+            _ => {}
+        }
+
+        parsed.push(Line {
+            number,
+            level,
+            content,
+        });
+    }
+
+    Ok(parsed)
+}
+
+/// Nests each subtree under the closest one before it with a lower level.
+/// Levels may only grow by one from a subtree to the next.
+pub(crate) fn build_tree(nodes: impl IntoIterator<Item = (usize, Subtree)>) -> Vec<Subtree> {
+    fn close(open: &mut Vec<Subtree>, roots: &mut Vec<Subtree>) {
+        if let Some(subtree) = open.pop() {
+            match open.last_mut() {
+                Some(parent) => parent.children.push(subtree),
+                None => roots.push(subtree),
+            }
+        }
+    }
+
+    let mut roots = Vec::new();
+    let mut open: Vec<Subtree> = Vec::new();
+
+    for (level, subtree) in nodes {
+        while open.len() > level {
+            close(&mut open, &mut roots);
+        }
+        open.push(subtree);
+    }
+
+    while !open.is_empty() {
+        close(&mut open, &mut roots);
+    }
+
+    roots
+}
+
+pub(crate) fn parse_error(line: usize, reason: impl Into<String>) -> InterfaceError {
+    InterfaceError::ImportParse {
+        line,
+        reason: reason.into(),
+    }
+}
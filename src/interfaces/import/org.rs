@@ -0,0 +1,408 @@
+use hifitime::Epoch;
+use uuid::Uuid;
+
+use super::{Importer, build_tree, parse_error};
+use crate::{
+    domain::{
+        Planning, Subtree,
+        node::{NodeType, Source},
+        planning::PlanningKind,
+    },
+    interfaces::{
+        InterfaceError,
+        export::org::{PLANNING_KEYWORDS, is_planning},
+    },
+};
+
+/// Reads an Org document, one node a headline. `TODO`, `DOING` and `DONE`
+/// keywords give the node's task state and tags at the end of the headline
+/// its tags. Org markup in the headline becomes the inline Markdown of the
+/// node text, and a link to a node ID such as `[[id]]` stays a reference.
+/// `SCHEDULED` and `DEADLINE` timestamps become the node's planned dates,
+/// dropping any repeater or warning period. The body becomes the note,
+/// starting with the rest of the planning line, such as `CLOSED`, and an
+/// `:ID:` in the `:PROPERTIES:` drawer becomes the node's ID, so that importing updates the node with that ID
+/// when it exists. Keyword lines and comments before the first headline are
+/// skipped.
+pub struct Org;
+
+/// A headline being read, along with the lines of its body.
+struct Entry<'a> {
+    level: usize,
+    subtree: Subtree,
+    planning: Vec<&'a str>,
+    body: Vec<&'a str>,
+}
+
+impl Importer for Org {
+    fn import(&self, text: &str, author: &str) -> Result<Vec<Subtree>, InterfaceError> {
+        let mut nodes = Vec::new();
+        let mut current: Option<Entry> = None;
+        let mut lines = text.lines().enumerate().peekable();
+
+        while let Some((index, line)) = lines.next() {
+            let number = index + 1;
+
+            let Some((stars, headline)) = parse_headline(line) else {
+                match &mut current {
+                    Some(entry) => entry.body.push(line),
+                    None if line.trim().is_empty() || line.starts_with('#') => {}
+                    None => {
+                        return Err(parse_error(number, "text before the first headline"));
+                    }
+                }
+                continue;
+            };
+
+            let level = stars - 1;
+            match &current {
+                None if level > 0 => {
+                    return Err(parse_error(number, "the first headline is nested"));
+                }
+                Some(above) if level > above.level + 1 => {
+                    return Err(parse_error(
+                        number,
+                        "nested more than one level below the headline above",
+                    ));
+                }
+                _ => {}
+            }
+
+            if let Some(entry) = current.take() {
+                nodes.push(entry.finish());
+            }
+
+            let (node_type, text, tags) = parse_title(headline);
+            let mut entry = Entry {
+                level,
+                subtree: Subtree {
+                    id: None,
+                    node_type,
+                    text: markdown_of(text),
+                    note: String::new(),
+                    author: author.to_owned(),
+                    source_type: Source::User,
+                    tags,
+                    planning: Vec::new(),
+                    children: Vec::new(),
+                },
+                planning: Vec::new(),
+                body: Vec::new(),
+            };
+
+            while let Some((_, line)) = lines.next_if(|(_, line)| is_planning(line)) {
+                entry.planning.push(line.trim());
+            }
+
+            if let Some((index, _)) =
+                lines.next_if(|(_, line)| line.trim().eq_ignore_ascii_case(":PROPERTIES:"))
+            {
+                entry.subtree.id = read_drawer(&mut lines, index + 1)?;
+            }
+
+            current = Some(entry);
+        }
+
+        if let Some(entry) = current {
+            nodes.push(entry.finish());
+        }
+
+        Ok(build_tree(nodes))
+    }
+}
+
+impl Entry<'_> {
+    /// The headline's level and subtree, with its planned dates taken out of
+    /// its planning lines and what remains of them and the body as the note.
+    fn finish(mut self) -> (usize, Subtree) {
+        let body = dedent(&self.body);
+
+        let planning: Vec<String> = self
+            .planning
+            .iter()
+            .map(|line| take_planning(line, &mut self.subtree.planning))
+            .collect();
+        let mut note: Vec<&str> = planning
+            .iter()
+            .map(String::as_str)
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        // the export puts a space before body lines starting with `*`
+        note.extend(body.iter().map(|line| match line.strip_prefix(" *") {
+            Some(_) => &line[1..],
+            None => line.as_str(),
+        }));
+
+        self.subtree.note = note.join("\n");
+        (self.level, self.subtree)
+    }
+}
+
+/// Moves the `SCHEDULED` and `DEADLINE` dates of a planning line into
+/// `planning`, returning the rest of the line. Timestamps that cannot be read
+/// stay in the line.
+fn take_planning(line: &str, planning: &mut Vec<Planning>) -> String {
+    let mut rest = Vec::new();
+    let mut remaining = line.trim();
+
+    while !remaining.is_empty() {
+        let kind = [
+            ("SCHEDULED:", PlanningKind::Scheduled),
+            ("DEADLINE:", PlanningKind::Deadline),
+        ]
+        .into_iter()
+        .find_map(|(keyword, kind)| Some((kind, remaining.strip_prefix(keyword)?)));
+
+        if let Some((kind, after)) = kind
+            && let Some((time, after)) = parse_timestamp(after.trim_start())
+        {
+            planning.push(Planning { kind, time });
+            remaining = after.trim_start();
+            continue;
+        }
+
+        // keeps the next keyword and its timestamp, such as `CLOSED: [...]`
+        let end = PLANNING_KEYWORDS
+            .iter()
+            .filter_map(|keyword| remaining[1..].find(keyword))
+            .min()
+            .map_or(remaining.len(), |offset| offset + 1);
+        rest.push(remaining[..end].trim());
+        remaining = remaining[end..].trim_start();
+    }
+
+    rest.join(" ")
+}
+
+/// Reads a timestamp such as `<2026-10-20 Tue 10:00 +1w>` at the start of
+/// `text` as a UTC time, returning it with the text after it.
+fn parse_timestamp(text: &str) -> Option<(Epoch, &str)> {
+    let close = match text.chars().next()? {
+        '<' => '>',
+        '[' => ']',
+        _ => return None,
+    };
+    let end = text.find(close)?;
+    let mut parts = text[1..end].split_whitespace();
+
+    let mut date = parts.next()?.splitn(3, '-');
+    let year = date.next()?.parse().ok()?;
+    let month = date.next()?.parse().ok()?;
+    let day = date.next()?.parse().ok()?;
+
+    // a time range such as `10:00-11:00` is planned for its start
+    let (hour, minute) = parts
+        .find_map(|part| {
+            let (hour, minute) = part.split('-').next()?.split_once(':')?;
+            Some((hour.parse().ok()?, minute.parse().ok()?))
+        })
+        .unwrap_or((0, 0));
+
+    let time = Epoch::maybe_from_gregorian_utc(year, month, day, hour, minute, 0, 0).ok()?;
+    Some((time, &text[end + 1..]))
+}
+
+/// Number of stars and the rest of `line` when it is a headline.
+fn parse_headline(line: &str) -> Option<(usize, &str)> {
+    let stars = line.len() - line.trim_start_matches('*').len();
+    let rest = &line[stars..];
+
+    match stars > 0 && (rest.is_empty() || rest.starts_with(' ')) {
+        true => Some((stars, rest.trim())),
+        false => None,
+    }
+}
+
+/// Task state, text and tags of a headline.
+fn parse_title(headline: &str) -> (NodeType, &str, Vec<String>) {
+    let (node_type, rest) = [
+        ("TODO", NodeType::Todo),
+        ("DOING", NodeType::InProgress),
+        ("DONE", NodeType::Done),
+    ]
+    .into_iter()
+    .find_map(|(keyword, node_type)| {
+        let rest = headline.strip_prefix(keyword)?;
+        (rest.is_empty() || rest.starts_with(' ')).then_some((node_type, rest.trim_start()))
+    })
+    .unwrap_or((NodeType::Standard, headline));
+
+    // tags are a last word such as `:work:urgent:`
+    if let Some((text, last)) = rest.rsplit_once(' ')
+        && last.len() > 2
+        && last.starts_with(':')
+        && last.ends_with(':')
+        && !last.contains("::")
+    {
+        let tags = last[1..last.len() - 1]
+            .split(':')
+            .map(str::to_owned)
+            .collect();
+        return (node_type, text.trim_end(), tags);
+    }
+
+    (node_type, rest, Vec::new())
+}
+
+/// Headline text with its Org markup turned into inline Markdown. Markers
+/// only count where Org would read them, so `and/or` stays as it is.
+fn markdown_of(text: &str) -> String {
+    let mut markdown = String::new();
+    let mut position = 0;
+
+    while position < text.len() {
+        let rest = &text[position..];
+
+        if rest.starts_with("[[")
+            && let Some(close) = rest.find("]]")
+        {
+            markdown.push_str(&link_of(&rest[2..close]));
+            position += close + 2;
+            continue;
+        }
+
+        if let Some((marker, close)) = emphasis_at(text, position) {
+            let inner = &text[position + 1..close];
+            let converted = match marker {
+                '~' | '=' => code_of(inner),
+                '*' => format!("**{}**", markdown_of(inner)),
+                '+' => format!("~~{}~~", markdown_of(inner)),
+                _ => {
+                    let inner = markdown_of(inner);
+                    // `*` next to the asterisks of bold would read as more bold
+                    match inner.starts_with('*') || inner.ends_with('*') {
+                        true => format!("_{inner}_"),
+                        false => format!("*{inner}*"),
+                    }
+                }
+            };
+            markdown.push_str(&converted);
+            position = close + 1;
+            continue;
+        }
+
+        let char = rest.chars().next().unwrap_or_default();
+        markdown.push(char);
+        position += char.len_utf8();
+    }
+
+    markdown
+}
+
+/// Markdown for the inside of an Org `[[...]]` link.
+fn link_of(link: &str) -> String {
+    match link.split_once("][") {
+        Some((url, label)) => format!("[{}]({url})", markdown_of(label)),
+        // node references are written the same way in both
+        None if Uuid::parse_str(link).is_ok() || link.contains(char::is_whitespace) => {
+            format!("[[{link}]]")
+        }
+        None => format!("[{link}]({link})"),
+    }
+}
+
+fn code_of(code: &str) -> String {
+    match code.contains('`') {
+        true => format!("`` {code} ``"),
+        false => format!("`{code}`"),
+    }
+}
+
+/// The emphasis marker at `position` along with the position of the one
+/// closing it, following Org's rules on what may surround them.
+fn emphasis_at(text: &str, position: usize) -> Option<(char, usize)> {
+    let marker = text[position..].chars().next()?;
+    if !"*/+~=".contains(marker) {
+        return None;
+    }
+
+    let before = text[..position].chars().next_back();
+    let after = text[position + 1..].chars().next();
+    let opens = before.is_none_or(|char| char.is_whitespace() || "-({'\"".contains(char))
+        && after.is_some_and(|char| !char.is_whitespace() && char != marker);
+    if !opens {
+        return None;
+    }
+
+    text[position + 1..]
+        .match_indices(marker)
+        .map(|(offset, _)| position + 1 + offset)
+        .find(|&close| {
+            let before = text[..close].chars().next_back();
+            let after = text[close + 1..].chars().next();
+
+            close > position + 1
+                && before.is_some_and(|char| !char.is_whitespace())
+                && after.is_none_or(|char| char.is_whitespace() || "-.,;:!?')}\"".contains(char))
+        })
+        .map(|close| (marker, close))
+}
+
+/// Reads a `:PROPERTIES:` drawer up to its `:END:`, returning the node ID it
+/// holds. `number` is the line number of the drawer's first line.
+fn read_drawer<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    number: usize,
+) -> Result<Option<Uuid>, InterfaceError> {
+    let mut id = None;
+
+    for (index, line) in lines {
+        let line = line.trim();
+        if line.eq_ignore_ascii_case(":END:") {
+            return Ok(id);
+        }
+
+        let Some((name, value)) = line
+            .strip_prefix(':')
+            .and_then(|property| property.split_once(':'))
+        else {
+            return Err(parse_error(
+                index + 1,
+                "expected a property such as `:ID: ...`",
+            ));
+        };
+
+        if name.eq_ignore_ascii_case("ID") {
+            let value = value.trim();
+            let parsed = Uuid::parse_str(value)
+                .map_err(|_| parse_error(index + 1, format!("the ID `{value}` is not a UUID")))?;
+            id = Some(parsed);
+        }
+    }
+
+    Err(parse_error(
+        number,
+        "the properties drawer is missing its `:END:`",
+    ))
+}
+
+/// `lines` without their common indentation or surrounding blank lines.
+fn dedent(lines: &[&str]) -> Vec<String> {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|line| match line.trim().is_empty() {
+            true => "",
+            // the indentation is made of single byte characters
+            false => line[indent..].trim_end(),
+        })
+        .collect();
+
+    let start = lines.iter().position(|line| !line.is_empty());
+    let end = lines.iter().rposition(|line| !line.is_empty());
+
+    match (start, end) {
+        (Some(start), Some(end)) => lines[start..=end]
+            .iter()
+            .map(|line| line.to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
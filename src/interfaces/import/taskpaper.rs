@@ -0,0 +1,137 @@
+use std::ops::Range;
+
+use super::{Importer, build_tree, indented_lines, parse_error};
+use crate::{
+    domain::{
+        Subtree,
+        node::{NodeType, Source},
+    },
+    interfaces::InterfaceError,
+};
+
+/// Reads a TaskPaper document. Tasks (`- item`) become `Todo` nodes, or
+/// `Done` ones when tagged `@done`, projects (`Name:`) become nodes named
+/// after the project, and any other line becomes a node of its own. Tags other
+/// than `@done` are kept in the text.
+pub struct TaskPaper;
+
+impl Importer for TaskPaper {
+    fn import(&self, text: &str, author: &str) -> Result<Vec<Subtree>, InterfaceError> {
+        let mut nodes = Vec::new();
+
+        for line in indented_lines(text)? {
+            let (node_type, text) =
+                parse_item(line.content).map_err(|reason| parse_error(line.number, reason))?;
+
+            let subtree = Subtree {
+                id: None,
+                node_type,
+                text,
+                note: String::new(),
+                author: author.to_owned(),
+                source_type: Source::User,
+                tags: Vec::new(),
+                planning: Vec::new(),
+                children: Vec::new(),
+            };
+
+            nodes.push((line.level, subtree));
+        }
+
+        Ok(build_tree(nodes))
+    }
+}
+
+/// Task state and text of a line.
+fn parse_item(content: &str) -> Result<(NodeType, String), String> {
+    let tags = find_tags(content)?;
+
+    if let Some(task) = content
+        .strip_prefix("- ")
+        .or((content == "-").then_some(""))
+    {
+        // tag ranges are counted from the start of the line
+        let offset = content.len() - task.len();
+        let done = tags
+            .iter()
+            .find(|(_, name)| *name == "done")
+            .map(|(range, _)| range.start - offset..range.end - offset);
+
+        return Ok(match done {
+            Some(range) => (NodeType::Done, remove(task, range)),
+            None => (NodeType::Todo, task.to_owned()),
+        });
+    }
+
+    // a project's colon may be followed by tags
+    let mut trailing_tags = content.len();
+    for (range, _) in tags.iter().rev() {
+        if range.end > trailing_tags || !content[range.end..trailing_tags].trim().is_empty() {
+            break;
+        }
+        trailing_tags = range.start;
+    }
+    let name = content[..trailing_tags].trim_end();
+
+    match name.strip_suffix(':') {
+        Some(name) => {
+            let tags = content[trailing_tags..].trim();
+            let text = match tags.is_empty() {
+                true => name.to_owned(),
+                false => format!("{name} {tags}"),
+            };
+            Ok((NodeType::Standard, text))
+        }
+        None => Ok((NodeType::Standard, content.to_owned())),
+    }
+}
+
+/// Byte ranges and names of the `@tag` and `@tag(value)` tags in `text`.
+fn find_tags(text: &str) -> Result<Vec<(Range<usize>, &str)>, String> {
+    let mut tags = Vec::new();
+    let mut previous = ' ';
+    // end of the last tag, so that tags inside a tag's value are not counted
+    let mut last_end = 0;
+
+    for (start, char) in text.char_indices() {
+        let starts_tag = char == '@' && previous.is_whitespace() && start >= last_end;
+        previous = char;
+        if !starts_tag {
+            continue;
+        }
+
+        let name_start = start + 1;
+        let name_end = text[name_start..]
+            .find(|char: char| !(char.is_alphanumeric() || matches!(char, '-' | '_' | '.')))
+            .map_or(text.len(), |length| name_start + length);
+
+        if name_end == name_start {
+            continue;
+        }
+        let name = &text[name_start..name_end];
+
+        let end = match text[name_end..].starts_with('(') {
+            true => match text[name_end..].find(')') {
+                Some(length) => name_end + length + 1,
+                None => return Err(format!("the value of tag `@{name}` is missing its `)`")),
+            },
+            false => name_end,
+        };
+
+        tags.push((start..end, name));
+        last_end = end;
+    }
+
+    Ok(tags)
+}
+
+/// `text` without the tag at `range` and the space before it.
+fn remove(text: &str, range: Range<usize>) -> String {
+    let before = text[..range.start].trim_end();
+    let after = text[range.end..].trim_start();
+
+    match (before.is_empty(), after.is_empty()) {
+        (false, false) => format!("{before} {after}"),
+        _ => format!("{before}{after}"),
+    }
+}
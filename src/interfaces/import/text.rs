@@ -0,0 +1,59 @@
+use super::{Importer, build_tree, indented_lines};
+use crate::{
+    domain::{
+        Subtree,
+        node::{NodeType, Source},
+    },
+    interfaces::InterfaceError,
+};
+
+/// Reads an outline from indented plain text, such as one pasted from an
+/// email. Every line becomes a node. Leading `-`, `*`, `+` and `•` bullets
+/// are dropped, and `[ ]`, `[/]` and `[x]` checkboxes give the node's task
+/// state, as written by the plain text export.
+pub struct PlainText;
+
+impl Importer for PlainText {
+    fn import(&self, text: &str, author: &str) -> Result<Vec<Subtree>, InterfaceError> {
+        let nodes = indented_lines(text)?.into_iter().map(|line| {
+            let (node_type, text) = parse_item(line.content);
+
+            let subtree = Subtree {
+                id: None,
+                node_type,
+                text: text.to_owned(),
+                note: String::new(),
+                author: author.to_owned(),
+                source_type: Source::User,
+                tags: Vec::new(),
+                planning: Vec::new(),
+                children: Vec::new(),
+            };
+
+            (line.level, subtree)
+        });
+
+        Ok(build_tree(nodes))
+    }
+}
+
+/// Task state and text of an item, without its bullet and checkbox.
+fn parse_item(content: &str) -> (NodeType, &str) {
+    let content = ["- ", "* ", "+ ", "\u{2022} "]
+        .iter()
+        .find_map(|bullet| content.strip_prefix(bullet))
+        .unwrap_or(content);
+
+    for (checkbox, node_type) in [
+        ("[ ] ", NodeType::Todo),
+        ("[/] ", NodeType::InProgress),
+        ("[x] ", NodeType::Done),
+        ("[X] ", NodeType::Done),
+    ] {
+        if let Some(text) = content.strip_prefix(checkbox) {
+            return (node_type, text.trim_start());
+        }
+    }
+
+    (NodeType::Standard, content.trim_start())
+}
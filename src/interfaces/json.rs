@@ -0,0 +1,1140 @@
+use super::{InterfaceError, NodeRepository};
+use crate::domain::{
+    Attachment, Node, Planning, Revision, SavedView, Subtree, Template,
+    attachment::AttachmentContent,
+    crdt::{Operation, OperationKind, Timestamp},
+    node::Source,
+    planning::PlanningKind,
+};
+use hifitime::Epoch;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
+use uuid::Uuid;
+
+const DOCUMENT_FILE: &str = "outline.json";
+const TEMP_FILE: &str = "outline.json.tmp";
+const LOCK_FILE: &str = "outline.lock";
+/// Directory holding attached files, each named by the hash of its contents
+const BLOB_DIRECTORY: &str = "blobs";
+
+/// Stores an outline as pretty-printed JSON inside a directory, so it can be
+/// kept under version control and diffed like any other text file.
+pub struct JsonRepository {
+    directory: PathBuf,
+    transaction: RefCell<Option<Transaction>>,
+}
+
+/// Changes made inside a transaction, held in memory under the lock until the
+/// outermost transaction commits.
+struct Transaction {
+    _lock: LockGuard,
+    document: JsonDocument,
+    /// Document state at the start of each nested transaction
+    savepoints: Vec<JsonDocument>,
+    /// Blobs that may be unused once the transaction ends: those in use when
+    /// it began and those added during it
+    blobs: HashSet<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct JsonDocument {
+    nodes: Vec<JsonNode>,
+    #[serde(default)]
+    links: BTreeMap<String, BTreeSet<String>>,
+    #[serde(default)]
+    tags: BTreeMap<String, BTreeSet<String>>,
+    /// Planned dates by node ID, then by kind
+    #[serde(default)]
+    planning: BTreeMap<String, BTreeMap<PlanningKind, String>>,
+    #[serde(default)]
+    collapsed: BTreeSet<String>,
+    #[serde(default)]
+    settings: BTreeMap<String, String>,
+    #[serde(default)]
+    views: BTreeMap<String, JsonView>,
+    /// Deletion times of deleted nodes, by node ID
+    #[serde(default)]
+    tombstones: BTreeMap<String, String>,
+    /// Templates by ID
+    #[serde(default)]
+    templates: BTreeMap<String, JsonTemplate>,
+    /// Earlier content of edited nodes, oldest first, by node ID
+    #[serde(default)]
+    revisions: BTreeMap<String, Vec<JsonRevision>>,
+    /// Attached files, in the order they were added, by node ID
+    #[serde(default)]
+    attachments: BTreeMap<String, Vec<JsonAttachment>>,
+    /// Log of collaborative edits, in the order they were added
+    #[serde(default)]
+    operations: Vec<JsonOperation>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JsonRevision {
+    replaced_time: String,
+    text: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    note: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_type: Option<Source>,
+}
+
+impl JsonRevision {
+    fn to_revision(&self, node_id: &str) -> Result<Revision, InterfaceError> {
+        Ok(Revision {
+            node_id: parse_uuid(node_id, "node_id")?,
+            replaced_time: Epoch::from_str(&self.replaced_time)
+                .map_err(|_| InterfaceError::FieldParseError("replaced_time".to_owned()))?,
+            text: self.text.clone(),
+            note: self.note.clone(),
+            author: self.author.clone(),
+            source_type: self.source_type.clone(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JsonOperation {
+    counter: u64,
+    replica: String,
+    time: String,
+    kind: OperationKind,
+}
+
+impl From<&Operation> for JsonOperation {
+    fn from(operation: &Operation) -> Self {
+        JsonOperation {
+            counter: operation.id.counter,
+            replica: operation.id.replica.to_string(),
+            time: operation.time.to_string(),
+            kind: operation.kind.clone(),
+        }
+    }
+}
+
+impl JsonOperation {
+    fn to_operation(&self) -> Result<Operation, InterfaceError> {
+        Ok(Operation {
+            id: Timestamp {
+                counter: self.counter,
+                replica: parse_uuid(&self.replica, "replica")?,
+            },
+            time: Epoch::from_str(&self.time)
+                .map_err(|_| InterfaceError::FieldParseError("time".to_owned()))?,
+            kind: self.kind.clone(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JsonAttachment {
+    id: String,
+    name: String,
+    mime_type: String,
+    size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blob: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    added_time: String,
+}
+
+impl From<&Attachment> for JsonAttachment {
+    fn from(attachment: &Attachment) -> Self {
+        let (blob, path) = match &attachment.content {
+            AttachmentContent::Blob(hash) => (Some(hash.clone()), None),
+            AttachmentContent::Path(path) => (None, Some(path.to_string_lossy().into_owned())),
+        };
+
+        JsonAttachment {
+            id: attachment.id.to_string(),
+            name: attachment.name.clone(),
+            mime_type: attachment.mime_type.clone(),
+            size: attachment.size,
+            blob,
+            path,
+            added_time: attachment.added_time.to_string(),
+        }
+    }
+}
+
+impl JsonAttachment {
+    fn to_attachment(&self, node_id: &str) -> Result<Attachment, InterfaceError> {
+        let content = match (&self.blob, &self.path) {
+            (Some(hash), _) => AttachmentContent::Blob(hash.clone()),
+            (None, Some(path)) => AttachmentContent::Path(PathBuf::from(path)),
+            (None, None) => return Err(InterfaceError::FieldParseError("blob".to_owned())),
+        };
+
+        Ok(Attachment {
+            id: parse_uuid(&self.id, "id")?,
+            node_id: parse_uuid(node_id, "node_id")?,
+            name: self.name.clone(),
+            mime_type: self.mime_type.clone(),
+            size: self.size,
+            content,
+            added_time: Epoch::from_str(&self.added_time)
+                .map_err(|_| InterfaceError::FieldParseError("added_time".to_owned()))?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JsonTemplate {
+    name: String,
+    roots: Vec<Subtree>,
+}
+
+impl JsonTemplate {
+    fn to_template(&self, id: &str) -> Result<Template, InterfaceError> {
+        Ok(Template {
+            id: parse_uuid(id, "id")?,
+            name: self.name.clone(),
+            roots: self.roots.clone(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JsonView {
+    query: String,
+    sort: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    root: Option<String>,
+}
+
+impl JsonView {
+    fn to_view(&self, name: &str) -> Result<SavedView, InterfaceError> {
+        Ok(SavedView {
+            name: name.to_owned(),
+            query: self.query.clone(),
+            sort: self.sort.parse().map_err(InterfaceError::Domain)?,
+            root: self
+                .root
+                .as_deref()
+                .map(|id| parse_uuid(id, "root"))
+                .transpose()?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JsonNode {
+    id: String,
+    parent_id: Option<String>,
+    rank: u64,
+    created_time: String,
+    modified_time: String,
+    node_type: String,
+    text: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    note: String,
+    author: String,
+    source_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mirror_of: Option<String>,
+}
+
+impl From<&Node> for JsonNode {
+    fn from(node: &Node) -> Self {
+        JsonNode {
+            id: node.id_str(),
+            parent_id: node.parent_id_str(),
+            rank: node.rank(),
+            created_time: node.created_time_str(),
+            modified_time: node.modified_time_str(),
+            node_type: node.node_type_str(),
+            text: node.text().to_owned(),
+            note: node.note().to_owned(),
+            author: node.author().to_owned(),
+            source_type: node.source_type_str(),
+            mirror_of: node.mirror_of_str(),
+        }
+    }
+}
+
+impl TryFrom<&JsonNode> for Node {
+    type Error = InterfaceError;
+
+    fn try_from(node: &JsonNode) -> Result<Self, Self::Error> {
+        Node::from_raw_strs(
+            node.id.clone(),
+            node.parent_id.clone(),
+            node.rank,
+            node.created_time.clone(),
+            node.modified_time.clone(),
+            node.node_type.clone(),
+            node.text.clone(),
+            node.note.clone(),
+            node.author.clone(),
+            node.source_type.clone(),
+            node.mirror_of.clone(),
+        )
+        .map_err(InterfaceError::Domain)
+    }
+}
+
+/// Held while a write is in progress. The lock is an advisory lock on the
+/// lock file, which the operating system releases when the file is closed or
+/// the process dies, so a crashed writer never leaves the outline locked.
+struct LockGuard {
+    _file: File,
+}
+
+impl LockGuard {
+    fn acquire(path: PathBuf) -> Result<LockGuard, InterfaceError> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|_| InterfaceError::FileAccess)?;
+
+        file.try_lock().map_err(|err| match err {
+            TryLockError::WouldBlock => InterfaceError::Locked,
+            TryLockError::Error(_) => InterfaceError::FileAccess,
+        })?;
+
+        // record the owner to tell who holds the lock
+        let _ = file
+            .set_len(0)
+            .and_then(|_| write!(file, "{}", process::id()));
+
+        Ok(LockGuard { _file: file })
+    }
+}
+
+impl JsonRepository {
+    pub fn open(directory: impl AsRef<Path>) -> Result<JsonRepository, InterfaceError> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory).map_err(|_| InterfaceError::DbConnection)?;
+
+        let repository = JsonRepository {
+            directory,
+            transaction: RefCell::new(None),
+        };
+
+        if !repository.document_path().exists() {
+            repository.modify(|_| Ok(()))?;
+        }
+
+        Ok(repository)
+    }
+
+    fn document_path(&self) -> PathBuf {
+        self.directory.join(DOCUMENT_FILE)
+    }
+
+    /// Runs `read` against the current document, which inside a transaction
+    /// is the one being changed, borrowed rather than copied.
+    fn with_document<T>(
+        &self,
+        read: impl FnOnce(&JsonDocument) -> Result<T, InterfaceError>,
+    ) -> Result<T, InterfaceError> {
+        match self.transaction.borrow().as_ref() {
+            Some(transaction) => read(&transaction.document),
+            None => read(&self.load_document()?),
+        }
+    }
+
+    fn load_document(&self) -> Result<JsonDocument, InterfaceError> {
+        let contents = match fs::read_to_string(self.document_path()) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(JsonDocument::default()),
+            Err(_) => return Err(InterfaceError::FileAccess),
+        };
+
+        serde_json::from_str(&contents).map_err(|_| InterfaceError::Serialization)
+    }
+
+    fn write_document(&self, document: &mut JsonDocument) -> Result<(), InterfaceError> {
+        // keep a stable ordering so that unrelated edits produce minimal diffs
+        document.nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut contents =
+            serde_json::to_string_pretty(document).map_err(|_| InterfaceError::Serialization)?;
+        contents.push('\n');
+
+        let temp_path = self.directory.join(TEMP_FILE);
+        let mut file = File::create(&temp_path).map_err(|_| InterfaceError::FileAccess)?;
+        file.write_all(contents.as_bytes())
+            .and_then(|_| file.sync_all())
+            .map_err(|_| InterfaceError::FileAccess)?;
+
+        fs::rename(&temp_path, self.document_path()).map_err(|_| InterfaceError::FileAccess)
+    }
+
+    /// Path of the stored blob with content hash `hash`.
+    fn blob_path(&self, hash: &str) -> Result<PathBuf, InterfaceError> {
+        // the hash becomes a file name, so nothing but hex digits may reach the path
+        if hash.is_empty() || !hash.chars().all(|char| char.is_ascii_hexdigit()) {
+            return Err(InterfaceError::FieldParseError("blob".to_owned()));
+        }
+
+        Ok(self.directory.join(BLOB_DIRECTORY).join(hash))
+    }
+
+    /// Removes the blob files among `candidates` that no attachment in
+    /// `document` uses. Only blobs a change could have left unused are
+    /// candidates, as other writers may have added blobs they are about to use.
+    fn delete_unused_blobs(
+        &self,
+        document: &JsonDocument,
+        candidates: &HashSet<String>,
+    ) -> Result<(), InterfaceError> {
+        let used = used_blobs(document);
+
+        for hash in candidates.difference(&used) {
+            match fs::remove_file(self.blob_path(hash)?) {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(_) => return Err(InterfaceError::FileAccess),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs `operation` against the current document under the lock file and
+    /// atomically replaces the document with the result. Inside a transaction
+    /// the change is only applied in memory.
+    fn modify<T>(
+        &self,
+        operation: impl FnOnce(&mut JsonDocument) -> Result<T, InterfaceError>,
+    ) -> Result<T, InterfaceError> {
+        if let Some(transaction) = self.transaction.borrow_mut().as_mut() {
+            return operation(&mut transaction.document);
+        }
+
+        let _lock = LockGuard::acquire(self.directory.join(LOCK_FILE))?;
+
+        let mut document = self.load_document()?;
+        let blobs = used_blobs(&document);
+
+        let result = operation(&mut document)?;
+        self.write_document(&mut document)?;
+        self.delete_unused_blobs(&document, &blobs)?;
+
+        Ok(result)
+    }
+}
+
+fn used_blobs(document: &JsonDocument) -> HashSet<String> {
+    document
+        .attachments
+        .values()
+        .flatten()
+        .filter_map(|attachment| attachment.blob.clone())
+        .collect()
+}
+
+fn parse_uuid(id: &str, field: &str) -> Result<Uuid, InterfaceError> {
+    Uuid::parse_str(id).map_err(|_| InterfaceError::FieldParseError(field.to_owned()))
+}
+
+fn to_planning(kind: PlanningKind, time: &str) -> Result<Planning, InterfaceError> {
+    Ok(Planning {
+        kind,
+        time: Epoch::from_str(time)
+            .map_err(|_| InterfaceError::FieldParseError("planning".to_owned()))?,
+    })
+}
+
+impl NodeRepository for JsonRepository {
+    fn begin_transaction(&self) -> Result<(), InterfaceError> {
+        let mut transaction = self.transaction.borrow_mut();
+
+        match transaction.as_mut() {
+            Some(transaction) => transaction.savepoints.push(transaction.document.clone()),
+            None => {
+                let lock = LockGuard::acquire(self.directory.join(LOCK_FILE))?;
+                let document = self.load_document()?;
+
+                *transaction = Some(Transaction {
+                    _lock: lock,
+                    savepoints: vec![document.clone()],
+                    blobs: used_blobs(&document),
+                    document,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn commit_transaction(&self) -> Result<(), InterfaceError> {
+        let mut transaction = self.transaction.borrow_mut();
+        let active = transaction.as_mut().ok_or(InterfaceError::Transaction)?;
+
+        active.savepoints.pop();
+
+        if active.savepoints.is_empty()
+            && let Some(mut finished) = transaction.take()
+        {
+            self.write_document(&mut finished.document)?;
+            self.delete_unused_blobs(&finished.document, &finished.blobs)?;
+        }
+
+        Ok(())
+    }
+
+    fn rollback_transaction(&self) -> Result<(), InterfaceError> {
+        let mut transaction = self.transaction.borrow_mut();
+        let active = transaction.as_mut().ok_or(InterfaceError::Transaction)?;
+
+        if let Some(savepoint) = active.savepoints.pop() {
+            active.document = savepoint;
+        }
+
+        if active.savepoints.is_empty() {
+            // blobs added during the transaction are now unused
+            self.delete_unused_blobs(&active.document, &active.blobs)?;
+            *transaction = None;
+        }
+
+        Ok(())
+    }
+
+    fn add_node(&self, node: &Node) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            let id = node.id_str();
+
+            if document.nodes.iter().any(|existing| existing.id == id) {
+                return Err(InterfaceError::NodeWrite);
+            }
+
+            document.nodes.push(JsonNode::from(node));
+            document.tombstones.remove(&id);
+
+            Ok(())
+        })
+    }
+
+    fn get_node(&self, node_id: &Uuid) -> Result<Node, InterfaceError> {
+        let id = node_id.to_string();
+
+        self.with_document(|document| {
+            document
+                .nodes
+                .iter()
+                .find(|node| node.id == id)
+                .ok_or(InterfaceError::MissingNode)
+                .and_then(Node::try_from)
+        })
+    }
+
+    fn update_node(&self, updated_node: &Node) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            let id = updated_node.id_str();
+
+            let node = document
+                .nodes
+                .iter_mut()
+                .find(|node| node.id == id)
+                .ok_or(InterfaceError::MissingNodeOperation)?;
+
+            // the creation metadata of a node never changes
+            *node = JsonNode {
+                created_time: node.created_time.clone(),
+                author: node.author.clone(),
+                source_type: node.source_type.clone(),
+                ..JsonNode::from(updated_node)
+            };
+
+            Ok(())
+        })
+    }
+
+    fn delete_node(&self, node_id: &Uuid) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            let id = node_id.to_string();
+            let node_count = document.nodes.len();
+
+            document.nodes.retain(|node| node.id != id);
+
+            if document.nodes.len() == node_count {
+                return Err(InterfaceError::MissingNode);
+            }
+
+            document.collapsed.remove(&id);
+            document.tags.remove(&id);
+            document.planning.remove(&id);
+            document.revisions.remove(&id);
+            document.attachments.remove(&id);
+            // incoming links are kept so that they can be reported as broken
+            document.links.remove(&id);
+
+            let deleted_time = Epoch::now().map_err(|_| InterfaceError::NodeDelete)?;
+            document.tombstones.insert(id, deleted_time.to_string());
+
+            Ok(())
+        })
+    }
+
+    fn add_tombstone(&self, node_id: &Uuid, deleted_time: Epoch) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            document
+                .tombstones
+                .insert(node_id.to_string(), deleted_time.to_string());
+
+            Ok(())
+        })
+    }
+
+    fn dump_tombstones(&self) -> Result<Vec<(Uuid, Epoch)>, InterfaceError> {
+        self.with_document(|document| {
+            document
+                .tombstones
+                .iter()
+                .map(|(node_id, deleted_time)| {
+                    Ok((
+                        parse_uuid(node_id, "id")?,
+                        Epoch::from_str(deleted_time).map_err(|_| {
+                            InterfaceError::FieldParseError("deleted_time".to_owned())
+                        })?,
+                    ))
+                })
+                .collect()
+        })
+    }
+
+    fn dump_nodes(&self) -> Result<Vec<Node>, InterfaceError> {
+        self.with_document(|document| document.nodes.iter().map(Node::try_from).collect())
+    }
+
+    fn get_children(&self, parent_id: Option<&Uuid>) -> Result<Vec<Node>, InterfaceError> {
+        let parent_id = parent_id.map(|id| id.to_string());
+
+        let mut children = self.with_document(|document| {
+            document
+                .nodes
+                .iter()
+                .filter(|node| node.parent_id == parent_id)
+                .map(Node::try_from)
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+
+        children.sort_by_key(|node| node.rank());
+
+        Ok(children)
+    }
+
+    fn get_mirrors(&self, canonical_id: &Uuid) -> Result<Vec<Node>, InterfaceError> {
+        let id = canonical_id.to_string();
+
+        self.with_document(|document| {
+            document
+                .nodes
+                .iter()
+                .filter(|node| node.mirror_of.as_ref() == Some(&id))
+                .map(Node::try_from)
+                .collect()
+        })
+    }
+
+    fn set_links(&self, source_id: &Uuid, target_ids: &[Uuid]) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            let targets: BTreeSet<String> = target_ids.iter().map(Uuid::to_string).collect();
+
+            if targets.is_empty() {
+                document.links.remove(&source_id.to_string());
+            } else {
+                document.links.insert(source_id.to_string(), targets);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_backlinks(&self, target_id: &Uuid) -> Result<Vec<Uuid>, InterfaceError> {
+        let target_id = target_id.to_string();
+
+        self.with_document(|document| {
+            document
+                .links
+                .iter()
+                .filter(|(_, targets)| targets.contains(&target_id))
+                .map(|(source_id, _)| parse_uuid(source_id, "source_id"))
+                .collect()
+        })
+    }
+
+    fn dump_links(&self) -> Result<Vec<(Uuid, Uuid)>, InterfaceError> {
+        self.with_document(|document| {
+            let mut links = Vec::new();
+
+            for (source_id, targets) in &document.links {
+                for target_id in targets {
+                    links.push((
+                        parse_uuid(source_id, "source_id")?,
+                        parse_uuid(target_id, "target_id")?,
+                    ));
+                }
+            }
+
+            Ok(links)
+        })
+    }
+
+    fn add_tag(&self, node_ids: &[Uuid], tag: &str) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            for node_id in node_ids {
+                document
+                    .tags
+                    .entry(node_id.to_string())
+                    .or_default()
+                    .insert(tag.to_owned());
+            }
+
+            Ok(())
+        })
+    }
+
+    fn remove_tag(&self, node_ids: &[Uuid], tag: &str) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            for node_id in node_ids {
+                let id = node_id.to_string();
+
+                if let Some(tags) = document.tags.get_mut(&id) {
+                    tags.remove(tag);
+
+                    if tags.is_empty() {
+                        document.tags.remove(&id);
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_tags(&self, node_id: &Uuid) -> Result<Vec<String>, InterfaceError> {
+        self.with_document(|document| {
+            Ok(document
+                .tags
+                .get(&node_id.to_string())
+                .map(|tags| tags.iter().cloned().collect())
+                .unwrap_or_default())
+        })
+    }
+
+    fn dump_tags(&self) -> Result<Vec<(Uuid, String)>, InterfaceError> {
+        self.with_document(|document| {
+            let mut tags = Vec::new();
+
+            for (node_id, node_tags) in &document.tags {
+                for tag in node_tags {
+                    tags.push((parse_uuid(node_id, "node_id")?, tag.clone()));
+                }
+            }
+
+            Ok(tags)
+        })
+    }
+
+    fn set_planning(
+        &self,
+        node_id: &Uuid,
+        kind: PlanningKind,
+        time: Option<Epoch>,
+    ) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            let id = node_id.to_string();
+
+            match time {
+                Some(time) => {
+                    document
+                        .planning
+                        .entry(id)
+                        .or_default()
+                        .insert(kind, time.to_string());
+                }
+                None => {
+                    if let Some(planning) = document.planning.get_mut(&id) {
+                        planning.remove(&kind);
+
+                        if planning.is_empty() {
+                            document.planning.remove(&id);
+                        }
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_planning(&self, node_id: &Uuid) -> Result<Vec<Planning>, InterfaceError> {
+        self.with_document(|document| {
+            document
+                .planning
+                .get(&node_id.to_string())
+                .into_iter()
+                .flatten()
+                .map(|(kind, time)| to_planning(*kind, time))
+                .collect()
+        })
+    }
+
+    fn dump_planning(&self) -> Result<Vec<(Uuid, Planning)>, InterfaceError> {
+        self.with_document(|document| {
+            let mut planning = Vec::new();
+
+            for (node_id, node_planning) in &document.planning {
+                for (kind, time) in node_planning {
+                    planning.push((parse_uuid(node_id, "node_id")?, to_planning(*kind, time)?));
+                }
+            }
+
+            Ok(planning)
+        })
+    }
+
+    fn collapsed_nodes(&self) -> Result<HashSet<Uuid>, InterfaceError> {
+        self.with_document(|document| {
+            document
+                .collapsed
+                .iter()
+                .map(|id| parse_uuid(id, "id"))
+                .collect()
+        })
+    }
+
+    fn set_collapsed(&self, node_ids: &[Uuid], collapsed: bool) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            for node_id in node_ids {
+                if collapsed {
+                    document.collapsed.insert(node_id.to_string());
+                } else {
+                    document.collapsed.remove(&node_id.to_string());
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_setting(&self, key: &str) -> Result<Option<String>, InterfaceError> {
+        self.with_document(|document| Ok(document.settings.get(key).cloned()))
+    }
+
+    fn set_setting(&self, key: &str, value: Option<&str>) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            match value {
+                Some(value) => document.settings.insert(key.to_owned(), value.to_owned()),
+                None => document.settings.remove(key),
+            };
+
+            Ok(())
+        })
+    }
+
+    fn dump_settings(&self) -> Result<Vec<(String, String)>, InterfaceError> {
+        self.with_document(|document| {
+            Ok(document
+                .settings
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect())
+        })
+    }
+
+    fn save_view(&self, view: &SavedView) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            document.views.insert(
+                view.name.clone(),
+                JsonView {
+                    query: view.query.clone(),
+                    sort: view.sort.to_string(),
+                    root: view.root.map(|id| id.to_string()),
+                },
+            );
+
+            Ok(())
+        })
+    }
+
+    fn get_view(&self, name: &str) -> Result<Option<SavedView>, InterfaceError> {
+        self.with_document(|document| {
+            document
+                .views
+                .get(name)
+                .map(|view| view.to_view(name))
+                .transpose()
+        })
+    }
+
+    fn delete_view(&self, name: &str) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            document.views.remove(name);
+
+            Ok(())
+        })
+    }
+
+    fn dump_views(&self) -> Result<Vec<SavedView>, InterfaceError> {
+        self.with_document(|document| {
+            document
+                .views
+                .iter()
+                .map(|(name, view)| view.to_view(name))
+                .collect()
+        })
+    }
+
+    fn save_template(&self, template: &Template) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            document.templates.insert(
+                template.id.to_string(),
+                JsonTemplate {
+                    name: template.name.clone(),
+                    roots: template.roots.clone(),
+                },
+            );
+
+            Ok(())
+        })
+    }
+
+    fn get_template(&self, template_id: &Uuid) -> Result<Option<Template>, InterfaceError> {
+        let id = template_id.to_string();
+
+        self.with_document(|document| {
+            document
+                .templates
+                .get(&id)
+                .map(|template| template.to_template(&id))
+                .transpose()
+        })
+    }
+
+    fn delete_template(&self, template_id: &Uuid) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            document.templates.remove(&template_id.to_string());
+
+            Ok(())
+        })
+    }
+
+    fn dump_templates(&self) -> Result<Vec<Template>, InterfaceError> {
+        let mut templates = self.with_document(|document| {
+            document
+                .templates
+                .iter()
+                .map(|(id, template)| template.to_template(id))
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(templates)
+    }
+
+    fn add_revision(&self, revision: &Revision) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            document
+                .revisions
+                .entry(revision.node_id.to_string())
+                .or_default()
+                .push(JsonRevision {
+                    replaced_time: revision.replaced_time.to_string(),
+                    text: revision.text.clone(),
+                    note: revision.note.clone(),
+                    author: revision.author.clone(),
+                    source_type: revision.source_type.clone(),
+                });
+
+            Ok(())
+        })
+    }
+
+    fn get_revisions(&self, node_id: &Uuid) -> Result<Vec<Revision>, InterfaceError> {
+        let id = node_id.to_string();
+
+        self.with_document(|document| {
+            document
+                .revisions
+                .get(&id)
+                .into_iter()
+                .flatten()
+                .map(|revision| revision.to_revision(&id))
+                .collect()
+        })
+    }
+
+    fn dump_revisions(&self) -> Result<Vec<Revision>, InterfaceError> {
+        self.with_document(|document| {
+            document
+                .revisions
+                .iter()
+                .flat_map(|(node_id, revisions)| {
+                    revisions
+                        .iter()
+                        .map(move |revision| revision.to_revision(node_id))
+                })
+                .collect()
+        })
+    }
+
+    fn add_attachment(&self, attachment: &Attachment) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            let id = attachment.id.to_string();
+
+            for attachments in document.attachments.values_mut() {
+                attachments.retain(|existing| existing.id != id);
+            }
+            document
+                .attachments
+                .retain(|_, attachments| !attachments.is_empty());
+
+            document
+                .attachments
+                .entry(attachment.node_id.to_string())
+                .or_default()
+                .push(JsonAttachment::from(attachment));
+
+            Ok(())
+        })
+    }
+
+    fn get_attachment(&self, attachment_id: &Uuid) -> Result<Option<Attachment>, InterfaceError> {
+        let id = attachment_id.to_string();
+
+        self.with_document(|document| {
+            document
+                .attachments
+                .iter()
+                .find_map(|(node_id, attachments)| {
+                    attachments
+                        .iter()
+                        .find(|attachment| attachment.id == id)
+                        .map(|attachment| attachment.to_attachment(node_id))
+                })
+                .transpose()
+        })
+    }
+
+    fn get_attachments(&self, node_id: &Uuid) -> Result<Vec<Attachment>, InterfaceError> {
+        let id = node_id.to_string();
+
+        self.with_document(|document| {
+            document
+                .attachments
+                .get(&id)
+                .into_iter()
+                .flatten()
+                .map(|attachment| attachment.to_attachment(&id))
+                .collect()
+        })
+    }
+
+    fn delete_attachment(&self, attachment_id: &Uuid) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            let id = attachment_id.to_string();
+
+            for attachments in document.attachments.values_mut() {
+                attachments.retain(|attachment| attachment.id != id);
+            }
+            document
+                .attachments
+                .retain(|_, attachments| !attachments.is_empty());
+
+            Ok(())
+        })
+    }
+
+    fn dump_attachments(&self) -> Result<Vec<Attachment>, InterfaceError> {
+        self.with_document(|document| {
+            document
+                .attachments
+                .iter()
+                .flat_map(|(node_id, attachments)| {
+                    attachments
+                        .iter()
+                        .map(move |attachment| attachment.to_attachment(node_id))
+                })
+                .collect()
+        })
+    }
+
+    /// Blobs are written straight away, even inside a transaction, and removed
+    /// again should the transaction roll back.
+    fn add_blob(&self, hash: &str, data: &[u8]) -> Result<(), InterfaceError> {
+        let path = self.blob_path(hash)?;
+        if path.exists() {
+            return Ok(());
+        }
+
+        if let Some(transaction) = self.transaction.borrow_mut().as_mut() {
+            transaction.blobs.insert(hash.to_owned());
+        }
+
+        fs::create_dir_all(self.directory.join(BLOB_DIRECTORY))
+            .map_err(|_| InterfaceError::FileAccess)?;
+
+        let temp_path = path.with_extension("tmp");
+        let mut file = File::create(&temp_path).map_err(|_| InterfaceError::FileAccess)?;
+        file.write_all(data)
+            .and_then(|_| file.sync_all())
+            .map_err(|_| InterfaceError::FileAccess)?;
+
+        fs::rename(&temp_path, path).map_err(|_| InterfaceError::FileAccess)
+    }
+
+    fn get_blob(&self, hash: &str) -> Result<Option<Vec<u8>>, InterfaceError> {
+        match fs::read(self.blob_path(hash)?) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(_) => Err(InterfaceError::FileAccess),
+        }
+    }
+
+    fn add_operations(&self, operations: &[Operation]) -> Result<(), InterfaceError> {
+        self.modify(|document| {
+            document
+                .operations
+                .extend(operations.iter().map(JsonOperation::from));
+
+            Ok(())
+        })
+    }
+
+    fn get_operations(&self, skip: usize) -> Result<Vec<Operation>, InterfaceError> {
+        self.with_document(|document| {
+            document
+                .operations
+                .iter()
+                .skip(skip)
+                .map(JsonOperation::to_operation)
+                .collect()
+        })
+    }
+
+    /// Every commit replaces the document file, so its modification time
+    /// changes along with the contents.
+    fn data_version(&self) -> Result<u64, InterfaceError> {
+        let modified = match fs::metadata(self.document_path()) {
+            Ok(metadata) => metadata
+                .modified()
+                .map_err(|_| InterfaceError::FileAccess)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(_) => return Err(InterfaceError::FileAccess),
+        };
+
+        let elapsed = modified
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| InterfaceError::FileAccess)?;
+
+        Ok(elapsed.as_nanos() as u64)
+    }
+}
@@ -4,5 +4,14 @@ pub use ports::NodeRepository;
 pub mod sqlite;
 pub use sqlite::SqliteRepository;
 
+pub mod json;
+pub use json::JsonRepository;
+
+pub mod convert;
+
+pub mod export;
+
+pub mod import;
+
 pub mod errors;
 pub use errors::InterfaceError;
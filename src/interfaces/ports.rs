@@ -1,15 +1,161 @@
 use super::errors::InterfaceError;
-use crate::domain::Node;
+use crate::domain::{
+    Attachment, Node, Planning, Query, Revision, SavedView, Template, crdt::Operation,
+    planning::PlanningKind,
+};
+use hifitime::Epoch;
+use std::collections::HashSet;
 use uuid::Uuid;
 
 pub trait NodeRepository {
+    /// Starts a transaction. Transactions nest, and changes only become visible
+    /// to other readers once the outermost transaction commits.
+    fn begin_transaction(&self) -> Result<(), InterfaceError>;
+
+    fn commit_transaction(&self) -> Result<(), InterfaceError>;
+
+    /// Discards every change made since the matching `begin_transaction`.
+    fn rollback_transaction(&self) -> Result<(), InterfaceError>;
+
+    /// Adds `node`, clearing any tombstone left by an earlier deletion of the same ID.
     fn add_node(&self, node: &Node) -> Result<(), InterfaceError>;
 
     fn get_node(&self, node_id: &Uuid) -> Result<Node, InterfaceError>;
 
     fn update_node(&self, node: &Node) -> Result<(), InterfaceError>;
 
+    /// Deletes `node_id`, leaving a tombstone so the deletion can be synced.
+    /// Its attachments go with it, along with any stored blob no other
+    /// attachment uses.
     fn delete_node(&self, node_id: &Uuid) -> Result<(), InterfaceError>;
 
+    /// Records that `node_id` was deleted at `deleted_time`, replacing any
+    /// existing tombstone for it.
+    fn add_tombstone(&self, node_id: &Uuid, deleted_time: Epoch) -> Result<(), InterfaceError>;
+
+    /// Returns every deleted node ID with the time it was deleted.
+    fn dump_tombstones(&self) -> Result<Vec<(Uuid, Epoch)>, InterfaceError>;
+
     fn dump_nodes(&self) -> Result<Vec<Node>, InterfaceError>;
+
+    /// Returns the children of `parent_id` (or the root nodes when `None`) ordered by rank.
+    fn get_children(&self, parent_id: Option<&Uuid>) -> Result<Vec<Node>, InterfaceError>;
+
+    /// Returns the nodes matching `query`. Backends without a query engine of
+    /// their own evaluate it in memory over every node.
+    fn query_nodes(&self, query: &Query) -> Result<Vec<Node>, InterfaceError> {
+        Ok(query.filter_nodes(
+            self.dump_nodes()?,
+            &self.dump_tags()?,
+            &self.dump_planning()?,
+        ))
+    }
+
+    /// Returns every node mirroring the canonical node `canonical_id`.
+    fn get_mirrors(&self, canonical_id: &Uuid) -> Result<Vec<Node>, InterfaceError>;
+
+    /// Replaces the outgoing links of `source_id` with `target_ids`.
+    fn set_links(&self, source_id: &Uuid, target_ids: &[Uuid]) -> Result<(), InterfaceError>;
+
+    /// Returns the IDs of every node linking to `target_id`.
+    fn get_backlinks(&self, target_id: &Uuid) -> Result<Vec<Uuid>, InterfaceError>;
+
+    /// Returns every link as a `(source_id, target_id)` pair.
+    fn dump_links(&self) -> Result<Vec<(Uuid, Uuid)>, InterfaceError>;
+
+    fn add_tag(&self, node_ids: &[Uuid], tag: &str) -> Result<(), InterfaceError>;
+
+    fn remove_tag(&self, node_ids: &[Uuid], tag: &str) -> Result<(), InterfaceError>;
+
+    fn get_tags(&self, node_id: &Uuid) -> Result<Vec<String>, InterfaceError>;
+
+    /// Returns every tag as a `(node_id, tag)` pair.
+    fn dump_tags(&self) -> Result<Vec<(Uuid, String)>, InterfaceError>;
+
+    /// Sets the `kind` date of `node_id` to `time`, removing it when `None`.
+    fn set_planning(
+        &self,
+        node_id: &Uuid,
+        kind: PlanningKind,
+        time: Option<Epoch>,
+    ) -> Result<(), InterfaceError>;
+
+    /// Returns the planned dates of `node_id` ordered by kind.
+    fn get_planning(&self, node_id: &Uuid) -> Result<Vec<Planning>, InterfaceError>;
+
+    /// Returns every planned date with the node it belongs to.
+    fn dump_planning(&self) -> Result<Vec<(Uuid, Planning)>, InterfaceError>;
+
+    fn collapsed_nodes(&self) -> Result<HashSet<Uuid>, InterfaceError>;
+
+    fn set_collapsed(&self, node_ids: &[Uuid], collapsed: bool) -> Result<(), InterfaceError>;
+
+    /// Reads a per-document setting, such as persisted view state.
+    fn get_setting(&self, key: &str) -> Result<Option<String>, InterfaceError>;
+
+    /// Stores a per-document setting, removing it when `value` is `None`.
+    fn set_setting(&self, key: &str, value: Option<&str>) -> Result<(), InterfaceError>;
+
+    fn dump_settings(&self) -> Result<Vec<(String, String)>, InterfaceError>;
+
+    /// Stores `view`, replacing any view with the same name.
+    fn save_view(&self, view: &SavedView) -> Result<(), InterfaceError>;
+
+    fn get_view(&self, name: &str) -> Result<Option<SavedView>, InterfaceError>;
+
+    fn delete_view(&self, name: &str) -> Result<(), InterfaceError>;
+
+    /// Returns every saved view ordered by name.
+    fn dump_views(&self) -> Result<Vec<SavedView>, InterfaceError>;
+
+    /// Stores `template`, replacing any template with the same ID.
+    fn save_template(&self, template: &Template) -> Result<(), InterfaceError>;
+
+    fn get_template(&self, template_id: &Uuid) -> Result<Option<Template>, InterfaceError>;
+
+    fn delete_template(&self, template_id: &Uuid) -> Result<(), InterfaceError>;
+
+    /// Returns every template ordered by name.
+    fn dump_templates(&self) -> Result<Vec<Template>, InterfaceError>;
+
+    /// Records content a node had before an edit.
+    fn add_revision(&self, revision: &Revision) -> Result<(), InterfaceError>;
+
+    /// Returns the revisions of `node_id`, oldest first.
+    fn get_revisions(&self, node_id: &Uuid) -> Result<Vec<Revision>, InterfaceError>;
+
+    /// Returns every revision, each node's oldest first.
+    fn dump_revisions(&self) -> Result<Vec<Revision>, InterfaceError>;
+
+    /// Stores `attachment`, replacing any attachment with the same ID. A blob it
+    /// refers to should be added first.
+    fn add_attachment(&self, attachment: &Attachment) -> Result<(), InterfaceError>;
+
+    fn get_attachment(&self, attachment_id: &Uuid) -> Result<Option<Attachment>, InterfaceError>;
+
+    /// Returns the attachments of `node_id` in the order they were added.
+    fn get_attachments(&self, node_id: &Uuid) -> Result<Vec<Attachment>, InterfaceError>;
+
+    /// Deletes `attachment_id`, and its stored blob once no other attachment uses it.
+    fn delete_attachment(&self, attachment_id: &Uuid) -> Result<(), InterfaceError>;
+
+    /// Returns every attachment, each node's in the order they were added.
+    fn dump_attachments(&self) -> Result<Vec<Attachment>, InterfaceError>;
+
+    /// Stores `data` under its content hash `hash`, keeping any blob already stored there.
+    fn add_blob(&self, hash: &str, data: &[u8]) -> Result<(), InterfaceError>;
+
+    fn get_blob(&self, hash: &str) -> Result<Option<Vec<u8>>, InterfaceError>;
+
+    /// Appends operations to the log of collaborative edits.
+    fn add_operations(&self, operations: &[Operation]) -> Result<(), InterfaceError>;
+
+    /// Returns the logged operations in the order they were added, leaving out
+    /// the first `skip`.
+    fn get_operations(&self, skip: usize) -> Result<Vec<Operation>, InterfaceError>;
+
+    /// Returns a value that changes whenever changes made elsewhere, through
+    /// another connection or by another process, are committed. Changes made
+    /// through this repository need not change it.
+    fn data_version(&self) -> Result<u64, InterfaceError>;
 }
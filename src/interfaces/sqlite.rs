@@ -1,42 +1,427 @@
 use super::{InterfaceError, NodeRepository};
-use crate::domain::Node;
-use rusqlite::{Connection, Error, Row};
+use crate::domain::{
+    Attachment, Node, Planning, Query, Revision, SavedView, Subtree, Template,
+    attachment::AttachmentContent,
+    crdt::{Operation, Timestamp},
+    node::Source,
+    planning::PlanningKind,
+    query::{Filter, TimeRange},
+};
+use hifitime::Epoch;
+use rusqlite::{Connection, Error, OptionalExtension, Row, params_from_iter};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 use uuid::Uuid;
 
 pub struct SqliteRepository {
     connection: Connection,
 }
 
+/// How long to wait for another connection to finish writing before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
 impl SqliteRepository {
     pub fn new_memory() -> Result<SqliteRepository, InterfaceError> {
         let connection = Connection::open_in_memory().map_err(|_| InterfaceError::DbConnection)?;
 
+        create_tables(&connection)?;
+
+        Ok(SqliteRepository { connection })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<SqliteRepository, InterfaceError> {
+        let connection = Connection::open(path).map_err(|_| InterfaceError::DbConnection)?;
+
+        // the TUI, the API server and their change watchers may all share the file
         connection
+            .busy_timeout(BUSY_TIMEOUT)
+            .map_err(|_| InterfaceError::DbConnection)?;
+
+        create_tables(&connection)?;
+
+        Ok(SqliteRepository { connection })
+    }
+
+    fn query_revisions(
+        &self,
+        sql: &str,
+        params: &[String],
+    ) -> Result<Vec<Revision>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare(sql)
+            .map_err(|_| InterfaceError::Other)?;
+
+        let revisions = query
+            .query_map(params_from_iter(params), |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        revisions
+            .map(|revision| {
+                let (node_id, replaced_time, text, note, author, source_type): (
+                    String,
+                    String,
+                    String,
+                    String,
+                    Option<String>,
+                    Option<String>,
+                ) = revision.map_err(|_| InterfaceError::Other)?;
+
+                Ok(Revision {
+                    node_id: parse_uuid(&node_id, "node_id")?,
+                    replaced_time: Epoch::from_str(&replaced_time)
+                        .map_err(|_| InterfaceError::FieldParseError("replaced_time".to_owned()))?,
+                    text,
+                    note,
+                    author,
+                    source_type: source_type
+                        .map(|source_type| {
+                            Source::from_str(&source_type).map_err(|_| {
+                                InterfaceError::FieldParseError("source_type".to_owned())
+                            })
+                        })
+                        .transpose()?,
+                })
+            })
+            .collect()
+    }
+
+    fn query_planning(
+        &self,
+        sql: &str,
+        params: &[String],
+    ) -> Result<Vec<(Uuid, Planning)>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare(sql)
+            .map_err(|_| InterfaceError::Other)?;
+
+        let rows = query
+            .query_map(params_from_iter(params), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        rows.map(|row| {
+            let (node_id, kind, time): (String, String, String) =
+                row.map_err(|_| InterfaceError::Other)?;
+
+            let planning = Planning {
+                kind: PlanningKind::from_str(&kind)
+                    .map_err(|_| InterfaceError::FieldParseError("kind".to_owned()))?,
+                time: Epoch::from_str(&time)
+                    .map_err(|_| InterfaceError::FieldParseError("time".to_owned()))?,
+            };
+
+            Ok((parse_uuid(&node_id, "node_id")?, planning))
+        })
+        .collect()
+    }
+
+    fn query_attachments(
+        &self,
+        sql: &str,
+        params: &[String],
+    ) -> Result<Vec<Attachment>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare(sql)
+            .map_err(|_| InterfaceError::Other)?;
+
+        let attachments = query
+            .query_map(params_from_iter(params), |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                    row.get(7)?,
+                ))
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        attachments
+            .map(|attachment| {
+                let (id, node_id, name, mime_type, size, blob_hash, path, added_time): (
+                    String,
+                    String,
+                    String,
+                    String,
+                    u64,
+                    Option<String>,
+                    Option<String>,
+                    String,
+                ) = attachment.map_err(|_| InterfaceError::Other)?;
+
+                let content = match (blob_hash, path) {
+                    (Some(hash), _) => AttachmentContent::Blob(hash),
+                    (None, Some(path)) => AttachmentContent::Path(PathBuf::from(path)),
+                    (None, None) => {
+                        return Err(InterfaceError::FieldParseError("blob_hash".to_owned()));
+                    }
+                };
+
+                Ok(Attachment {
+                    id: parse_uuid(&id, "id")?,
+                    node_id: parse_uuid(&node_id, "node_id")?,
+                    name,
+                    mime_type,
+                    size,
+                    content,
+                    added_time: Epoch::from_str(&added_time)
+                        .map_err(|_| InterfaceError::FieldParseError("added_time".to_owned()))?,
+                })
+            })
+            .collect()
+    }
+
+    /// Deletes every stored blob that no attachment uses any more.
+    fn delete_unused_blobs(&self) -> Result<(), InterfaceError> {
+        self.connection
             .execute(
-                "CREATE TABLE outline (
-                id            TEXT PRIMARY KEY,
-                parent_id     TEXT,
-                rank          INTEGER,
-                created_time  TEXT,
-                modified_time TEXT,
-                node_type     TEXT,
-                text          TEXT,
-                author        TEXT,
-                source_type   TEXT
-                )",
+                "DELETE FROM blobs WHERE hash NOT IN (SELECT blob_hash FROM attachments WHERE blob_hash IS NOT NULL)",
                 (),
             )
-            .map_err(|_| InterfaceError::TableCreation)?;
+            .map_err(|_| InterfaceError::AttachmentWrite)?;
 
-        Ok(SqliteRepository { connection })
+        Ok(())
+    }
+}
+
+fn create_tables(connection: &Connection) -> Result<(), InterfaceError> {
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS outline (
+            id            TEXT PRIMARY KEY,
+            parent_id     TEXT,
+            rank          INTEGER,
+            created_time  TEXT,
+            modified_time TEXT,
+            node_type     TEXT,
+            text          TEXT,
+            author        TEXT,
+            source_type   TEXT,
+            mirror_of     TEXT,
+            note          TEXT NOT NULL DEFAULT ''
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    // outlines created before mirrors existed lack the column
+    add_column_if_missing(connection, "outline", "mirror_of", "TEXT")?;
+    add_column_if_missing(connection, "outline", "note", "TEXT NOT NULL DEFAULT ''")?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS node_links (
+            source_id TEXT,
+            target_id TEXT,
+            PRIMARY KEY (source_id, target_id)
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS node_tags (
+            node_id TEXT,
+            tag     TEXT,
+            PRIMARY KEY (node_id, tag)
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS planning (
+            node_id TEXT,
+            kind    TEXT,
+            time    TEXT,
+            PRIMARY KEY (node_id, kind)
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS collapsed_nodes (
+            id TEXT PRIMARY KEY
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS settings (
+            key   TEXT PRIMARY KEY,
+            value TEXT
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS tombstones (
+            id           TEXT PRIMARY KEY,
+            deleted_time TEXT
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS views (
+            name  TEXT PRIMARY KEY,
+            query TEXT,
+            sort  TEXT,
+            root  TEXT
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS templates (
+            id    TEXT PRIMARY KEY,
+            name  TEXT,
+            roots TEXT
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS revisions (
+            node_id       TEXT,
+            replaced_time TEXT,
+            text          TEXT,
+            note          TEXT,
+            author        TEXT,
+            source_type   TEXT
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    add_column_if_missing(connection, "revisions", "author", "TEXT")?;
+    add_column_if_missing(connection, "revisions", "source_type", "TEXT")?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS attachments (
+            id         TEXT PRIMARY KEY,
+            node_id    TEXT,
+            name       TEXT,
+            mime_type  TEXT,
+            size       INTEGER,
+            blob_hash  TEXT,
+            path       TEXT,
+            added_time TEXT
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS blobs (
+            hash TEXT PRIMARY KEY,
+            data BLOB
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    connection
+        .execute(
+            "CREATE TABLE IF NOT EXISTS operations (
+            counter INTEGER,
+            replica TEXT,
+            time    TEXT,
+            kind    TEXT
+            )",
+            (),
+        )
+        .map_err(|_| InterfaceError::TableCreation)?;
+
+    Ok(())
+}
+
+fn add_column_if_missing(
+    connection: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), InterfaceError> {
+    let exists: bool = connection
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+            (table, column),
+            |row| row.get(0),
+        )
+        .map_err(|_| InterfaceError::InvalidQuery)?;
+
+    if !exists {
+        connection
+            .execute(
+                &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+                (),
+            )
+            .map_err(|_| InterfaceError::TableCreation)?;
     }
+
+    Ok(())
 }
 
 impl NodeRepository for SqliteRepository {
+    // savepoints rather than BEGIN, as they nest
+    fn begin_transaction(&self) -> Result<(), InterfaceError> {
+        self.connection
+            .execute_batch("SAVEPOINT outliner")
+            .map_err(|_| InterfaceError::Transaction)
+    }
+
+    fn commit_transaction(&self) -> Result<(), InterfaceError> {
+        self.connection
+            .execute_batch("RELEASE outliner")
+            .map_err(|_| InterfaceError::Transaction)
+    }
+
+    fn rollback_transaction(&self) -> Result<(), InterfaceError> {
+        self.connection
+            .execute_batch("ROLLBACK TO outliner; RELEASE outliner")
+            .map_err(|_| InterfaceError::Transaction)
+    }
+
     fn add_node(&self, node: &Node) -> Result<(), InterfaceError> {
         self.connection
             .execute(
-                "INSERT INTO outline (id, parent_id, rank, created_time, modified_time, node_type, text, author, source_type) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                "INSERT INTO outline (id, parent_id, rank, created_time, modified_time, node_type, text, author, source_type, mirror_of, note) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
                 rusqlite::params![
                     node.id_str(),
                     node.parent_id_str(),
@@ -47,10 +432,16 @@ impl NodeRepository for SqliteRepository {
                     node.text(),
                     node.author(),
                     node.source_type_str(),
+                    node.mirror_of_str(),
+                    node.note(),
                 ],
             )
             .map_err(|_| InterfaceError::NodeWrite)?;
 
+        self.connection
+            .execute("DELETE FROM tombstones WHERE id = ?1", (node.id_str(),))
+            .map_err(|_| InterfaceError::TombstoneWrite)?;
+
         Ok(())
     }
 
@@ -71,10 +462,15 @@ impl NodeRepository for SqliteRepository {
         let update_row_count = self
             .connection
             .execute(
-                "UPDATE outline SET text = ?1, modified_time = ?2 WHERE id = ?3",
+                "UPDATE outline SET parent_id = ?1, rank = ?2, modified_time = ?3, node_type = ?4, text = ?5, mirror_of = ?6, note = ?7 WHERE id = ?8",
                 (
-                    updated_node.text(),
+                    updated_node.parent_id_str(),
+                    updated_node.rank(),
                     updated_node.modified_time_str(),
+                    updated_node.node_type_str(),
+                    updated_node.text(),
+                    updated_node.mirror_of_str(),
+                    updated_node.note(),
                     updated_node.id_str(),
                 ),
             )
@@ -97,9 +493,90 @@ impl NodeRepository for SqliteRepository {
             return Err(InterfaceError::MissingNode);
         }
 
+        self.connection
+            .execute(
+                "DELETE FROM collapsed_nodes WHERE id = ?1",
+                (node_id.to_string(),),
+            )
+            .map_err(|_| InterfaceError::NodeDelete)?;
+
+        self.connection
+            .execute(
+                "DELETE FROM node_tags WHERE node_id = ?1",
+                (node_id.to_string(),),
+            )
+            .map_err(|_| InterfaceError::NodeDelete)?;
+
+        self.connection
+            .execute(
+                "DELETE FROM planning WHERE node_id = ?1",
+                (node_id.to_string(),),
+            )
+            .map_err(|_| InterfaceError::NodeDelete)?;
+
+        self.connection
+            .execute(
+                "DELETE FROM revisions WHERE node_id = ?1",
+                (node_id.to_string(),),
+            )
+            .map_err(|_| InterfaceError::NodeDelete)?;
+
+        self.connection
+            .execute(
+                "DELETE FROM attachments WHERE node_id = ?1",
+                (node_id.to_string(),),
+            )
+            .map_err(|_| InterfaceError::NodeDelete)?;
+        self.delete_unused_blobs()?;
+
+        // incoming links are kept so that they can be reported as broken
+        self.connection
+            .execute(
+                "DELETE FROM node_links WHERE source_id = ?1",
+                (node_id.to_string(),),
+            )
+            .map_err(|_| InterfaceError::NodeDelete)?;
+
+        let deleted_time = Epoch::now().map_err(|_| InterfaceError::NodeDelete)?;
+        self.add_tombstone(node_id, deleted_time)
+    }
+
+    fn add_tombstone(&self, node_id: &Uuid, deleted_time: Epoch) -> Result<(), InterfaceError> {
+        self.connection
+            .execute(
+                "INSERT INTO tombstones (id, deleted_time) VALUES (?1, ?2)
+                ON CONFLICT(id) DO UPDATE SET deleted_time = excluded.deleted_time",
+                (node_id.to_string(), deleted_time.to_string()),
+            )
+            .map_err(|_| InterfaceError::TombstoneWrite)?;
+
         Ok(())
     }
 
+    fn dump_tombstones(&self) -> Result<Vec<(Uuid, Epoch)>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT id, deleted_time FROM tombstones")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let tombstones = query
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        tombstones
+            .map(|tombstone| {
+                let (node_id, deleted_time) = tombstone.map_err(|_| InterfaceError::Other)?;
+                Ok((
+                    parse_uuid(&node_id, "id")?,
+                    Epoch::from_str(&deleted_time)
+                        .map_err(|_| InterfaceError::FieldParseError("deleted_time".to_owned()))?,
+                ))
+            })
+            .collect()
+    }
+
     fn dump_nodes(&self) -> Result<Vec<Node>, InterfaceError> {
         let mut query = self
             .connection
@@ -114,6 +591,717 @@ impl NodeRepository for SqliteRepository {
             .collect::<Result<Vec<_>, _>>()
             .map_err(|_| InterfaceError::Other)
     }
+
+    fn get_children(&self, parent_id: Option<&Uuid>) -> Result<Vec<Node>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT * FROM outline WHERE parent_id IS ?1 ORDER BY rank")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let nodes = query
+            .query_map([parent_id.map(|id| id.to_string())], |row| {
+                row_to_node(row).map_err(|_| Error::InvalidQuery)
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        nodes
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| InterfaceError::Other)
+    }
+
+    fn query_nodes(&self, query: &Query) -> Result<Vec<Node>, InterfaceError> {
+        let (condition, params) = compile_query(query);
+
+        let mut statement = self
+            .connection
+            .prepare(&format!("SELECT * FROM outline WHERE {condition}"))
+            .map_err(|_| InterfaceError::Other)?;
+
+        let nodes = statement
+            .query_map(params_from_iter(params), |row| {
+                row_to_node(row).map_err(|_| Error::InvalidQuery)
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        nodes
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| InterfaceError::Other)
+    }
+
+    fn get_mirrors(&self, canonical_id: &Uuid) -> Result<Vec<Node>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT * FROM outline WHERE mirror_of = ?1")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let nodes = query
+            .query_map([canonical_id.to_string()], |row| {
+                row_to_node(row).map_err(|_| Error::InvalidQuery)
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        nodes
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| InterfaceError::Other)
+    }
+
+    fn set_links(&self, source_id: &Uuid, target_ids: &[Uuid]) -> Result<(), InterfaceError> {
+        self.connection
+            .execute(
+                "DELETE FROM node_links WHERE source_id = ?1",
+                (source_id.to_string(),),
+            )
+            .map_err(|_| InterfaceError::LinkWrite)?;
+
+        let mut query = self
+            .connection
+            .prepare("INSERT OR IGNORE INTO node_links (source_id, target_id) VALUES (?1, ?2)")
+            .map_err(|_| InterfaceError::Other)?;
+
+        for target_id in target_ids {
+            query
+                .execute((source_id.to_string(), target_id.to_string()))
+                .map_err(|_| InterfaceError::LinkWrite)?;
+        }
+
+        Ok(())
+    }
+
+    fn get_backlinks(&self, target_id: &Uuid) -> Result<Vec<Uuid>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT source_id FROM node_links WHERE target_id = ?1")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let ids = query
+            .query_map([target_id.to_string()], |row| row.get::<_, String>(0))
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        ids.map(|id| parse_uuid(&id.map_err(|_| InterfaceError::Other)?, "source_id"))
+            .collect()
+    }
+
+    fn dump_links(&self) -> Result<Vec<(Uuid, Uuid)>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT source_id, target_id FROM node_links")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let links = query
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        links
+            .map(|link| {
+                let (source_id, target_id) = link.map_err(|_| InterfaceError::Other)?;
+                Ok((
+                    parse_uuid(&source_id, "source_id")?,
+                    parse_uuid(&target_id, "target_id")?,
+                ))
+            })
+            .collect()
+    }
+
+    fn add_tag(&self, node_ids: &[Uuid], tag: &str) -> Result<(), InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("INSERT OR IGNORE INTO node_tags (node_id, tag) VALUES (?1, ?2)")
+            .map_err(|_| InterfaceError::Other)?;
+
+        for node_id in node_ids {
+            query
+                .execute((node_id.to_string(), tag))
+                .map_err(|_| InterfaceError::TagWrite)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove_tag(&self, node_ids: &[Uuid], tag: &str) -> Result<(), InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("DELETE FROM node_tags WHERE node_id = ?1 AND tag = ?2")
+            .map_err(|_| InterfaceError::Other)?;
+
+        for node_id in node_ids {
+            query
+                .execute((node_id.to_string(), tag))
+                .map_err(|_| InterfaceError::TagWrite)?;
+        }
+
+        Ok(())
+    }
+
+    fn get_tags(&self, node_id: &Uuid) -> Result<Vec<String>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT tag FROM node_tags WHERE node_id = ?1 ORDER BY tag")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let tags = query
+            .query_map([node_id.to_string()], |row| row.get(0))
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        tags.collect::<Result<Vec<_>, _>>()
+            .map_err(|_| InterfaceError::Other)
+    }
+
+    fn dump_tags(&self) -> Result<Vec<(Uuid, String)>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT node_id, tag FROM node_tags ORDER BY tag")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let tags = query
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        tags.map(|tag| {
+            let (node_id, tag) = tag.map_err(|_| InterfaceError::Other)?;
+            Ok((parse_uuid(&node_id, "node_id")?, tag))
+        })
+        .collect()
+    }
+
+    fn set_planning(
+        &self,
+        node_id: &Uuid,
+        kind: PlanningKind,
+        time: Option<Epoch>,
+    ) -> Result<(), InterfaceError> {
+        match time {
+            Some(time) => self.connection.execute(
+                "INSERT OR REPLACE INTO planning (node_id, kind, time) VALUES (?1, ?2, ?3)",
+                (node_id.to_string(), kind.to_string(), time.to_string()),
+            ),
+            None => self.connection.execute(
+                "DELETE FROM planning WHERE node_id = ?1 AND kind = ?2",
+                (node_id.to_string(), kind.to_string()),
+            ),
+        }
+        .map_err(|_| InterfaceError::PlanningWrite)?;
+
+        Ok(())
+    }
+
+    fn get_planning(&self, node_id: &Uuid) -> Result<Vec<Planning>, InterfaceError> {
+        let mut planning: Vec<Planning> = self
+            .query_planning(
+                "SELECT node_id, kind, time FROM planning WHERE node_id = ?1",
+                &[node_id.to_string()],
+            )?
+            .into_iter()
+            .map(|(_, planning)| planning)
+            .collect();
+
+        planning.sort_by_key(|planning| planning.kind);
+        Ok(planning)
+    }
+
+    fn dump_planning(&self) -> Result<Vec<(Uuid, Planning)>, InterfaceError> {
+        self.query_planning(
+            "SELECT node_id, kind, time FROM planning ORDER BY node_id",
+            &[],
+        )
+    }
+
+    fn collapsed_nodes(&self) -> Result<HashSet<Uuid>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT id FROM collapsed_nodes")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let ids = query
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        ids.map(|id| parse_uuid(&id.map_err(|_| InterfaceError::Other)?, "id"))
+            .collect()
+    }
+
+    fn set_collapsed(&self, node_ids: &[Uuid], collapsed: bool) -> Result<(), InterfaceError> {
+        let statement = if collapsed {
+            "INSERT OR IGNORE INTO collapsed_nodes (id) VALUES (?1)"
+        } else {
+            "DELETE FROM collapsed_nodes WHERE id = ?1"
+        };
+
+        let mut query = self
+            .connection
+            .prepare(statement)
+            .map_err(|_| InterfaceError::Other)?;
+
+        for node_id in node_ids {
+            query
+                .execute((node_id.to_string(),))
+                .map_err(|_| InterfaceError::CollapsedWrite)?;
+        }
+
+        Ok(())
+    }
+
+    fn get_setting(&self, key: &str) -> Result<Option<String>, InterfaceError> {
+        self.connection
+            .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|_| InterfaceError::InvalidQuery)
+    }
+
+    fn set_setting(&self, key: &str, value: Option<&str>) -> Result<(), InterfaceError> {
+        match value {
+            Some(value) => self.connection.execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2)
+                ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                (key, value),
+            ),
+            None => self
+                .connection
+                .execute("DELETE FROM settings WHERE key = ?1", (key,)),
+        }
+        .map_err(|_| InterfaceError::SettingWrite)?;
+
+        Ok(())
+    }
+
+    fn dump_settings(&self) -> Result<Vec<(String, String)>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT key, value FROM settings")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let settings = query
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        settings
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| InterfaceError::Other)
+    }
+
+    fn save_view(&self, view: &SavedView) -> Result<(), InterfaceError> {
+        self.connection
+            .execute(
+                "INSERT INTO views (name, query, sort, root) VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT(name) DO UPDATE SET
+                query = excluded.query, sort = excluded.sort, root = excluded.root",
+                (
+                    &view.name,
+                    &view.query,
+                    view.sort.to_string(),
+                    view.root.map(|id| id.to_string()),
+                ),
+            )
+            .map_err(|_| InterfaceError::ViewWrite)?;
+
+        Ok(())
+    }
+
+    fn get_view(&self, name: &str) -> Result<Option<SavedView>, InterfaceError> {
+        let view = self
+            .connection
+            .query_row(
+                "SELECT name, query, sort, root FROM views WHERE name = ?1",
+                [name],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        view.map(|(name, query, sort, root)| row_to_view(name, query, sort, root))
+            .transpose()
+    }
+
+    fn delete_view(&self, name: &str) -> Result<(), InterfaceError> {
+        self.connection
+            .execute("DELETE FROM views WHERE name = ?1", (name,))
+            .map_err(|_| InterfaceError::ViewWrite)?;
+
+        Ok(())
+    }
+
+    fn dump_views(&self) -> Result<Vec<SavedView>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT name, query, sort, root FROM views ORDER BY name")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let views = query
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        views
+            .map(|view| {
+                let (name, query, sort, root) = view.map_err(|_| InterfaceError::Other)?;
+                row_to_view(name, query, sort, root)
+            })
+            .collect()
+    }
+
+    fn save_template(&self, template: &Template) -> Result<(), InterfaceError> {
+        let roots =
+            serde_json::to_string(&template.roots).map_err(|_| InterfaceError::Serialization)?;
+
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO templates (id, name, roots) VALUES (?1, ?2, ?3)",
+                (template.id.to_string(), &template.name, roots),
+            )
+            .map_err(|_| InterfaceError::TemplateWrite)?;
+
+        Ok(())
+    }
+
+    fn get_template(&self, template_id: &Uuid) -> Result<Option<Template>, InterfaceError> {
+        let template = self
+            .connection
+            .query_row(
+                "SELECT id, name, roots FROM templates WHERE id = ?1",
+                [template_id.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        template
+            .map(|(id, name, roots)| row_to_template(id, name, roots))
+            .transpose()
+    }
+
+    fn delete_template(&self, template_id: &Uuid) -> Result<(), InterfaceError> {
+        self.connection
+            .execute(
+                "DELETE FROM templates WHERE id = ?1",
+                (template_id.to_string(),),
+            )
+            .map_err(|_| InterfaceError::TemplateWrite)?;
+
+        Ok(())
+    }
+
+    fn dump_templates(&self) -> Result<Vec<Template>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT id, name, roots FROM templates ORDER BY name")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let templates = query
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        templates
+            .map(|template| {
+                let (id, name, roots) = template.map_err(|_| InterfaceError::Other)?;
+                row_to_template(id, name, roots)
+            })
+            .collect()
+    }
+
+    fn add_revision(&self, revision: &Revision) -> Result<(), InterfaceError> {
+        self.connection
+            .execute(
+                "INSERT INTO revisions (node_id, replaced_time, text, note, author, source_type)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                (
+                    revision.node_id.to_string(),
+                    revision.replaced_time.to_string(),
+                    &revision.text,
+                    &revision.note,
+                    &revision.author,
+                    revision.source_type.as_ref().map(Source::to_string),
+                ),
+            )
+            .map_err(|_| InterfaceError::RevisionWrite)?;
+
+        Ok(())
+    }
+
+    fn get_revisions(&self, node_id: &Uuid) -> Result<Vec<Revision>, InterfaceError> {
+        self.query_revisions(
+            "SELECT node_id, replaced_time, text, note, author, source_type FROM revisions WHERE node_id = ?1 ORDER BY rowid",
+            &[node_id.to_string()],
+        )
+    }
+
+    fn dump_revisions(&self) -> Result<Vec<Revision>, InterfaceError> {
+        self.query_revisions(
+            "SELECT node_id, replaced_time, text, note, author, source_type FROM revisions ORDER BY node_id, rowid",
+            &[],
+        )
+    }
+
+    fn add_attachment(&self, attachment: &Attachment) -> Result<(), InterfaceError> {
+        let (blob_hash, path) = match &attachment.content {
+            AttachmentContent::Blob(hash) => (Some(hash.as_str()), None),
+            AttachmentContent::Path(path) => (None, Some(path.to_string_lossy())),
+        };
+
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO attachments (id, node_id, name, mime_type, size, blob_hash, path, added_time) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                (
+                    attachment.id.to_string(),
+                    attachment.node_id.to_string(),
+                    &attachment.name,
+                    &attachment.mime_type,
+                    attachment.size,
+                    blob_hash,
+                    path,
+                    attachment.added_time.to_string(),
+                ),
+            )
+            .map_err(|_| InterfaceError::AttachmentWrite)?;
+
+        // a replaced attachment may have been the last to use its blob
+        self.delete_unused_blobs()
+    }
+
+    fn get_attachment(&self, attachment_id: &Uuid) -> Result<Option<Attachment>, InterfaceError> {
+        Ok(self
+            .query_attachments(
+                "SELECT id, node_id, name, mime_type, size, blob_hash, path, added_time FROM attachments WHERE id = ?1",
+                &[attachment_id.to_string()],
+            )?
+            .pop())
+    }
+
+    fn get_attachments(&self, node_id: &Uuid) -> Result<Vec<Attachment>, InterfaceError> {
+        self.query_attachments(
+            "SELECT id, node_id, name, mime_type, size, blob_hash, path, added_time FROM attachments WHERE node_id = ?1 ORDER BY added_time, id",
+            &[node_id.to_string()],
+        )
+    }
+
+    fn delete_attachment(&self, attachment_id: &Uuid) -> Result<(), InterfaceError> {
+        self.connection
+            .execute(
+                "DELETE FROM attachments WHERE id = ?1",
+                (attachment_id.to_string(),),
+            )
+            .map_err(|_| InterfaceError::AttachmentWrite)?;
+
+        self.delete_unused_blobs()
+    }
+
+    fn dump_attachments(&self) -> Result<Vec<Attachment>, InterfaceError> {
+        self.query_attachments(
+            "SELECT id, node_id, name, mime_type, size, blob_hash, path, added_time FROM attachments ORDER BY node_id, added_time, id",
+            &[],
+        )
+    }
+
+    fn add_blob(&self, hash: &str, data: &[u8]) -> Result<(), InterfaceError> {
+        self.connection
+            .execute(
+                "INSERT OR IGNORE INTO blobs (hash, data) VALUES (?1, ?2)",
+                (hash, data),
+            )
+            .map_err(|_| InterfaceError::AttachmentWrite)?;
+
+        Ok(())
+    }
+
+    fn get_blob(&self, hash: &str) -> Result<Option<Vec<u8>>, InterfaceError> {
+        self.connection
+            .query_row("SELECT data FROM blobs WHERE hash = ?1", [hash], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|_| InterfaceError::InvalidQuery)
+    }
+
+    fn add_operations(&self, operations: &[Operation]) -> Result<(), InterfaceError> {
+        for operation in operations {
+            let kind = serde_json::to_string(&operation.kind)
+                .map_err(|_| InterfaceError::Serialization)?;
+
+            self.connection
+                .execute(
+                    "INSERT INTO operations (counter, replica, time, kind) VALUES (?1, ?2, ?3, ?4)",
+                    (
+                        operation.id.counter,
+                        operation.id.replica.to_string(),
+                        operation.time.to_string(),
+                        kind,
+                    ),
+                )
+                .map_err(|_| InterfaceError::OperationWrite)?;
+        }
+
+        Ok(())
+    }
+
+    fn get_operations(&self, skip: usize) -> Result<Vec<Operation>, InterfaceError> {
+        let mut query = self
+            .connection
+            .prepare("SELECT counter, replica, time, kind FROM operations ORDER BY rowid LIMIT -1 OFFSET ?1")
+            .map_err(|_| InterfaceError::Other)?;
+
+        let operations = query
+            .query_map([skip as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .map_err(|_| InterfaceError::InvalidQuery)?;
+
+        operations
+            .map(|operation| {
+                let (counter, replica, time, kind) =
+                    operation.map_err(|_| InterfaceError::Other)?;
+                row_to_operation(counter, replica, time, kind)
+            })
+            .collect()
+    }
+
+    fn data_version(&self) -> Result<u64, InterfaceError> {
+        self.connection
+            .query_row("PRAGMA data_version", [], |row| row.get(0))
+            .map_err(|_| InterfaceError::InvalidQuery)
+    }
+}
+
+fn row_to_view(
+    name: String,
+    query: String,
+    sort: String,
+    root: Option<String>,
+) -> Result<SavedView, InterfaceError> {
+    Ok(SavedView {
+        name,
+        query,
+        sort: sort.parse().map_err(InterfaceError::Domain)?,
+        root: root.map(|id| parse_uuid(&id, "root")).transpose()?,
+    })
+}
+
+fn row_to_template(id: String, name: String, roots: String) -> Result<Template, InterfaceError> {
+    let roots: Vec<Subtree> = serde_json::from_str(&roots)
+        .map_err(|_| InterfaceError::FieldParseError("roots".to_owned()))?;
+
+    Ok(Template {
+        id: parse_uuid(&id, "id")?,
+        name,
+        roots,
+    })
+}
+
+fn row_to_operation(
+    counter: u64,
+    replica: String,
+    time: String,
+    kind: String,
+) -> Result<Operation, InterfaceError> {
+    Ok(Operation {
+        id: Timestamp {
+            counter,
+            replica: parse_uuid(&replica, "replica")?,
+        },
+        time: Epoch::from_str(&time)
+            .map_err(|_| InterfaceError::FieldParseError("time".to_owned()))?,
+        kind: serde_json::from_str(&kind)
+            .map_err(|_| InterfaceError::FieldParseError("kind".to_owned()))?,
+    })
+}
+
+/// Compiles `query` into a condition on the `outline` table and its parameters.
+fn compile_query(query: &Query) -> (String, Vec<String>) {
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+
+    for term in &query.terms {
+        let mut param = |value: String| {
+            params.push(value);
+            format!("?{}", params.len())
+        };
+
+        let condition = match &term.filter {
+            Filter::Text(text) => {
+                let pattern = param(format!("%{}%", escape_like(text)));
+                format!("text LIKE {pattern} ESCAPE '\\' OR note LIKE {pattern} ESCAPE '\\'")
+            }
+            Filter::Type(node_type) => format!("node_type = {}", param(node_type.to_string())),
+            Filter::Author(author) => {
+                format!("author = {} COLLATE NOCASE", param(author.clone()))
+            }
+            Filter::Source(source) => format!("source_type = {}", param(source.to_string())),
+            Filter::Tag(tag) => format!(
+                "COALESCE(mirror_of, id) IN (SELECT node_id FROM node_tags WHERE tag = {})",
+                param(tag.clone())
+            ),
+            Filter::Planned(kind, range) => {
+                let kind = param(kind.to_string());
+                format!(
+                    "COALESCE(mirror_of, id) IN (SELECT node_id FROM planning WHERE kind = {kind} AND {})",
+                    time_condition("time", range, &mut param)
+                )
+            }
+            Filter::Created(range) => time_condition("created_time", range, &mut param),
+            Filter::Modified(range) => time_condition("modified_time", range, &mut param),
+            Filter::Under(ancestor_id) => format!(
+                "id IN (
+                WITH RECURSIVE descendants(id) AS (
+                    SELECT id FROM outline WHERE parent_id = {}
+                    UNION SELECT outline.id FROM outline
+                    JOIN descendants ON outline.parent_id = descendants.id
+                )
+                SELECT id FROM descendants
+                )",
+                param(ancestor_id.to_string())
+            ),
+        };
+
+        conditions.push(match term.negated {
+            true => format!("NOT ({condition})"),
+            false => format!("({condition})"),
+        });
+    }
+
+    if conditions.is_empty() {
+        return ("1".to_owned(), params);
+    }
+
+    (conditions.join(" AND "), params)
+}
+
+/// Times are stored as UTC timestamps whose fields run from the year down, so
+/// comparing them as text orders them chronologically. The fraction of a
+/// second is left out when it is zero, which still sorts first, as the `' '`
+/// before `UTC` sorts before the `'.'` of a fraction.
+fn time_condition(
+    column: &str,
+    range: &TimeRange,
+    param: &mut impl FnMut(String) -> String,
+) -> String {
+    let mut bounds = Vec::new();
+
+    if let Some(start) = range.start {
+        bounds.push(format!("{column} >= {}", param(start.to_string())));
+    }
+    if let Some(end) = range.end {
+        bounds.push(format!("{column} < {}", param(end.to_string())));
+    }
+
+    if bounds.is_empty() {
+        return "1".to_owned();
+    }
+
+    bounds.join(" AND ")
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn parse_uuid(id: &str, field: &str) -> Result<Uuid, InterfaceError> {
+    Uuid::parse_str(id).map_err(|_| InterfaceError::FieldParseError(field.to_owned()))
 }
 
 fn row_to_node(row: &Row<'_>) -> Result<Node, InterfaceError> {
@@ -153,6 +1341,14 @@ fn row_to_node(row: &Row<'_>) -> Result<Node, InterfaceError> {
         .get(8)
         .map_err(|_| InterfaceError::FieldParseError("source".to_owned()))?;
 
+    let mirror_of_str: Option<String> = row
+        .get(9)
+        .map_err(|_| InterfaceError::FieldParseError("mirror_of".to_owned()))?;
+
+    let note: String = row
+        .get(10)
+        .map_err(|_| InterfaceError::FieldParseError("note".to_owned()))?;
+
     Node::from_raw_strs(
         id_str,
         parent_id_str,
@@ -161,8 +1357,10 @@ fn row_to_node(row: &Row<'_>) -> Result<Node, InterfaceError> {
         modified_time_str,
         node_type_str,
         text,
+        note,
         author,
         source_str,
+        mirror_of_str,
     )
     .map_err(InterfaceError::Domain)
 }
@@ -0,0 +1,7 @@
+pub mod api;
+pub mod config;
+pub mod domain;
+pub mod interfaces;
+pub mod mcp;
+pub mod services;
+pub mod tui;
@@ -1,102 +1,603 @@
-use crate::{
-    domain::node::{CreateNodeRequest, NodeType, Source},
-    interfaces::SqliteRepository,
-    services::{graph_service::GraphService, logging::TerminalLogging, node_service::NodeService},
+use clap::Parser;
+use outliner::{
+    api,
+    config::Config,
+    domain::{
+        Clipboard, Query, SavedView, Selection, Template, attachment::AttachmentContent,
+        journal::parse_day, node::CreateNodeRequest, subtree::ClipboardMode,
+    },
+    interfaces::{
+        InterfaceError, JsonRepository, SqliteRepository, convert::copy_repository, export,
+        import::ImportFormat,
+    },
+    mcp::{self, Agent},
+    services::{
+        collab::ReplicaCache,
+        events::Notifier,
+        graph_service::GraphService,
+        import::MergeOptions,
+        logging::LoggingService,
+        node_service::NodeService,
+        sync::{Side, SyncChanges, SyncService},
+    },
+    tui,
 };
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+use uuid::Uuid;
 
-mod domain;
-mod interfaces;
-mod services;
+use crate::cli::{
+    AttachmentsCommand, BulkAction, Cli, Command, ConvertCommand, ImportArgs, LinksCommand,
+    TemplatesCommand, ViewsCommand,
+};
 
-fn main() {
-    let service = NodeService {
-        repository: SqliteRepository::new_memory().unwrap(),
-        logger: TerminalLogging::new(),
-    };
+mod cli;
 
-    let node1 = match service.create_node(CreateNodeRequest::new(
-        None,
-        100,
-        NodeType::Standard,
-        "New node 1",
-        "astra",
-        Source::User,
-    )) {
-        Ok(node) => node,
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
         Err(err) => {
             eprintln!("Error: {err}");
-            return;
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let mut config = Config::load(cli.config.as_deref())?;
+
+    // options given on the command line take precedence over the config file
+    if let Some(database) = cli.database {
+        config.database = Some(database);
+    }
+    if let Some(author) = cli.author {
+        config.author = author;
+    }
+    if let Some(source) = cli.source {
+        config.source = source;
+    }
+    if let Some(log) = cli.log {
+        config.log_target = log;
+    }
+
+    match cli.command.unwrap_or(Command::Tui { view: None }) {
+        Command::Convert(command) => {
+            let count = convert(command)?;
+            println!("Copied {count} nodes");
+        }
+        Command::Links(command) => links(&config, command)?,
+        Command::Views(command) => views(&config, command)?,
+        Command::Templates(command) => templates(&config, command)?,
+        Command::Attachments(command) => attachments(&config, command)?,
+        Command::Sync { local, remote } => sync(&config, local, remote)?,
+        Command::Tui { view } => {
+            let service = open_service(&config, true)?;
+            let mut graph_service = GraphService::new(service)?;
+
+            if let Some(name) = view {
+                graph_service.open_view(&name)?;
+            }
+
+            tui::run(graph_service, config.tui, config.author, config.source)?;
+        }
+        Command::Serve { port, token } => {
+            let service = open_service(&config, false)?;
+            let token = token.or(config.api_token).unwrap_or_else(|| {
+                let token = Uuid::new_v4().simple().to_string();
+                println!("Token: {token}");
+                token
+            });
+            let address = format!("127.0.0.1:{}", port.unwrap_or(config.api_port));
+
+            println!("Listening on http://{address}");
+            api::serve(GraphService::new(service)?, &address, &token)?;
+        }
+        Command::Mcp { agent, scope } => {
+            // standard output carries the protocol, so nothing else may be printed there
+            let service = open_service(&config, true)?;
+            let agent = Agent {
+                name: agent,
+                root: scope,
+            };
+
+            mcp::serve(
+                GraphService::new(service)?,
+                agent,
+                io::stdin().lock(),
+                io::stdout().lock(),
+            )?;
+        }
+        Command::Add {
+            text,
+            parent,
+            position,
+            node_type,
+            note,
+        } => {
+            let service = open_service(&config, true)?;
+            let mut request = CreateNodeRequest::new(
+                parent,
+                0,
+                node_type,
+                &text,
+                &config.author,
+                config.source.clone(),
+            );
+            request.note = note.unwrap_or_default();
+
+            let node = service.create_node_at(request, position.unwrap_or(usize::MAX))?;
+            println!("{}", node.id_str());
+        }
+        Command::Mirror {
+            node_id,
+            parent,
+            position,
+        } => {
+            let service = open_service(&config, true)?;
+            let node = service.create_mirror(
+                node_id,
+                parent,
+                position.unwrap_or(usize::MAX),
+                &config.author,
+                config.source.clone(),
+            )?;
+            println!("{}", node.id_str());
+        }
+        Command::Note { node_id, note } => {
+            let service = open_service(&config, true)?;
+            let mut node = service.get_node(&node_id)?;
+
+            match note.as_deref() {
+                None if node.note().is_empty() => {}
+                None => println!("{}", node.note()),
+                Some("-") => {
+                    let mut note = String::new();
+                    io::stdin().read_to_string(&mut note)?;
+                    service.update_note(&mut node, note.trim_end())?;
+                }
+                Some(note) => service.update_note(&mut node, note)?,
+            }
+        }
+        Command::Journal { date, rollover } => {
+            let service = open_service(&config, true)?;
+            let entry = service.journal_entry(
+                parse_day(&date)?,
+                &config.author,
+                rollover || config.journal_rollover,
+            )?;
+
+            if entry.rolled_over > 0 {
+                eprintln!("Rolled over {} tasks", entry.rolled_over);
+            }
+            println!("{}", entry.node.id_str());
+        }
+        Command::History { node_id } => {
+            let service = open_service(&config, true)?;
+
+            for revision in service.revisions(&node_id)? {
+                match &revision.author {
+                    Some(author) => {
+                        println!("{}  {}  ({author})", revision.replaced_time, revision.text)
+                    }
+                    None => println!("{}  {}", revision.replaced_time, revision.text),
+                }
+                for line in revision.note.lines() {
+                    println!("    {line}");
+                }
+            }
+        }
+        Command::Copy { node_id } => {
+            let service = open_service(&config, true)?;
+            println!("{}", service.copy_subtree(&node_id)?.to_text()?);
         }
+        Command::Cut { node_id } => {
+            let service = open_service(&config, true)?;
+            println!("{}", service.cut_subtree(&node_id)?.to_text()?);
+        }
+        Command::Paste { parent, position } => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+
+            let service = open_service(&config, true)?;
+            let clipboard = Clipboard::from_text(&text)?;
+
+            for node in service.paste(&clipboard, parent, position.unwrap_or(usize::MAX))? {
+                println!("{}", node.id_str());
+            }
+        }
+        Command::Duplicate {
+            node_id,
+            parent,
+            position,
+        } => {
+            let service = open_service(&config, true)?;
+            let parent = match parent {
+                Some(parent) => Some(parent),
+                None => service.get_node(&node_id)?.parent_id(),
+            };
+
+            let node =
+                service.duplicate_subtree(&node_id, parent, position.unwrap_or(usize::MAX))?;
+            println!("{}", node.id_str());
+        }
+        Command::Export { root, export } => {
+            let service = open_service(&config, true)?;
+            print!(
+                "{}",
+                service.export_outline(export.format, &export.options(root))?
+            );
+        }
+        Command::Import(args) => import(&config, args)?,
+        Command::Query { query, export } => {
+            let service = open_service(&config, true)?;
+            let graph = service.search(&Query::parse(&query)?)?;
+            print!(
+                "{}",
+                export::export(&graph, export.format, &export.options(None))
+            );
+        }
+        Command::Bulk { selection, action } => bulk(&config, selection.selection(), action)?,
+    }
+
+    Ok(())
+}
+
+/// Opens the configured outline. Front ends that own standard output, or print
+/// their results to it, get no terminal logging.
+fn open_service(
+    config: &Config,
+    owns_stdout: bool,
+) -> Result<NodeService<SqliteRepository, Box<dyn LoggingService>>, Box<dyn Error>> {
+    Ok(NodeService {
+        repository: SqliteRepository::open(config.database_path()?)?,
+        logger: config.logger(owns_stdout)?,
+        replica: ReplicaCache::default(),
+        notifier: Notifier::default(),
+        attachment_limit: config.attachment_limit,
+        rank_gap: config.rank_gap,
+    })
+}
+
+fn links(config: &Config, command: LinksCommand) -> Result<(), Box<dyn Error>> {
+    match command {
+        LinksCommand::Backlinks { node_id } => {
+            let service = open_service(config, true)?;
+
+            for node in service.backlinks(&node_id)? {
+                println!("{}\t{}", node.id_str(), node.text());
+            }
+        }
+        LinksCommand::Broken => {
+            let service = open_service(config, true)?;
+
+            for link in service.broken_links()? {
+                for target_id in link.target_ids {
+                    println!("{}\t{target_id}", link.source.id_str());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn sync(config: &Config, local: PathBuf, remote: PathBuf) -> Result<(), Box<dyn Error>> {
+    let service = SyncService {
+        local: SqliteRepository::open(local)?,
+        remote: SqliteRepository::open(remote)?,
+        logger: config.logger(false)?,
+        rank_gap: config.rank_gap,
     };
 
-    match service.create_node(CreateNodeRequest::new(
-        Some(node1.id()),
-        100,
-        NodeType::Standard,
-        "New node 2",
-        "astra",
-        Source::User,
-    )) {
-        Ok(node) => node,
-        Err(err) => {
-            eprintln!("Error: {err}");
-            return;
+    let report = service.sync()?;
+
+    let describe = |changes: &SyncChanges| {
+        format!(
+            "{} created, {} updated, {} moved, {} deleted",
+            changes.created, changes.updated, changes.moved, changes.deleted
+        )
+    };
+    println!("Local: {}", describe(&report.local));
+    println!("Remote: {}", describe(&report.remote));
+
+    for conflict in &report.conflicts {
+        let (kept, lost) = match conflict.kept {
+            Side::Local => (&conflict.local_text, &conflict.remote_text),
+            Side::Remote => (&conflict.remote_text, &conflict.local_text),
+        };
+        println!(
+            "Conflict\t{}\tkept: {kept}\tdiscarded: {lost}",
+            conflict.node_id
+        );
+    }
+
+    for node_id in &report.cycles_broken {
+        println!("Move undone to avoid a cycle\t{node_id}");
+    }
+
+    for node_id in &report.orphans {
+        println!("Moved to top level as its parent was deleted\t{node_id}");
+    }
+
+    Ok(())
+}
+
+fn import(config: &Config, args: ImportArgs) -> Result<(), Box<dyn Error>> {
+    let (text, detected) = match &args.file {
+        Some(path) => (fs::read_to_string(path)?, ImportFormat::detect(path)),
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            (text, ImportFormat::Text)
         }
     };
+    let format = args.format.unwrap_or(detected);
 
-    match service.create_node(CreateNodeRequest::new(
-        Some(node1.id()),
-        200,
-        NodeType::Standard,
-        "New node 3",
-        "astra",
-        Source::User,
-    )) {
-        Ok(node) => node,
-        Err(err) => {
-            eprintln!("Error: {err}");
-            return;
+    if !args.merge {
+        let service = open_service(config, true)?;
+        let created = service.import_outline(
+            &text,
+            format,
+            &config.author,
+            args.parent,
+            args.position.unwrap_or(usize::MAX),
+        )?;
+
+        for node in created {
+            println!("{}", node.id_str());
         }
+        return Ok(());
+    }
+
+    // the report goes to standard output, so it must not be mixed with log entries
+    let service = open_service(config, true)?;
+    let options = MergeOptions {
+        delete_missing: args.delete,
+        dry_run: args.dry_run,
     };
+    let report = service.merge_import(&text, format, &config.author, args.parent, options)?;
+
+    for (change, nodes) in [
+        ("Create", &report.created),
+        ("Update", &report.updated),
+        ("Move", &report.moved),
+        ("Delete", &report.deleted),
+    ] {
+        for node in nodes {
+            println!("{change}\t{}\t{}", node.node_id, node.text);
+        }
+    }
+
+    println!(
+        "{}{} created, {} updated, {} moved, {} deleted",
+        if args.dry_run { "Dry run: " } else { "" },
+        report.created.len(),
+        report.updated.len(),
+        report.moved.len(),
+        report.deleted.len()
+    );
+
+    Ok(())
+}
+
+fn views(config: &Config, command: ViewsCommand) -> Result<(), Box<dyn Error>> {
+    match command {
+        ViewsCommand::List => {
+            let service = open_service(config, true)?;
+
+            for view in service.list_views()? {
+                let root = view.root.map(|id| id.to_string()).unwrap_or_default();
+                println!("{}\t{}\t{}\t{root}", view.name, view.query, view.sort);
+            }
+        }
+        ViewsCommand::Save {
+            name,
+            query,
+            sort,
+            root,
+        } => {
+            let service = open_service(config, false)?;
+            service.save_view(&SavedView::new(&name, &query, sort, root)?)?;
+        }
+        ViewsCommand::Delete { name } => {
+            let service = open_service(config, false)?;
+            service.delete_view(&name)?;
+        }
+        ViewsCommand::Open { name, export } => {
+            let service = open_service(config, true)?;
+            let view = service.get_view(&name)?;
+            print!(
+                "{}",
+                service.export_view(&view, export.format, &export.options(None))?
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn templates(config: &Config, command: TemplatesCommand) -> Result<(), Box<dyn Error>> {
+    match command {
+        TemplatesCommand::List => {
+            let service = open_service(config, true)?;
+
+            for template in service.list_templates()? {
+                println!(
+                    "{}\t{}\t{}",
+                    template.id,
+                    template.name,
+                    template.placeholders().join(",")
+                );
+            }
+        }
+        TemplatesCommand::Create { name, from } => {
+            let service = open_service(config, true)?;
+
+            let template = match from {
+                Some(root_id) => service.create_template(&name, &root_id)?,
+                None => {
+                    let mut text = String::new();
+                    io::stdin().read_to_string(&mut text)?;
+
+                    let template = Template::new(&name, Clipboard::from_text(&text)?.roots)?;
+                    service.save_template(&template)?;
+                    template
+                }
+            };
+            println!("{}", template.id);
+        }
+        TemplatesCommand::Show { template } => {
+            let service = open_service(config, true)?;
+            let template = service.find_template(&template)?;
+
+            let clipboard = Clipboard {
+                mode: ClipboardMode::Copy,
+                roots: template.roots,
+            };
+            println!("{}", clipboard.to_text()?);
+        }
+        TemplatesCommand::Delete { template } => {
+            let service = open_service(config, false)?;
+            let template = service.find_template(&template)?;
+            service.delete_template(&template.id)?;
+        }
+        TemplatesCommand::Use {
+            template,
+            parent,
+            position,
+            vars,
+        } => {
+            let service = open_service(config, true)?;
+            let template = service.find_template(&template)?;
+
+            let mut values = HashMap::from([("author".to_owned(), config.author.clone())]);
+            values.extend(vars);
+
+            let roots = service.instantiate_template_at(
+                &template.id,
+                parent,
+                position.unwrap_or(usize::MAX),
+                &values,
+            )?;
+            for root in roots {
+                println!("{}", root.id_str());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn attachments(config: &Config, command: AttachmentsCommand) -> Result<(), Box<dyn Error>> {
+    match command {
+        AttachmentsCommand::Add {
+            node_id,
+            path,
+            reference,
+        } => {
+            let service = open_service(config, true)?;
+
+            let attachment = match reference {
+                true => service.attach_path(&node_id, &path)?,
+                false => {
+                    // oversized files are turned down before being read into memory
+                    service.check_attachment_size(fs::metadata(&path)?.len())?;
+
+                    let name = path
+                        .file_name()
+                        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                    service.attach(&node_id, &name, &fs::read(&path)?)?
+                }
+            };
+            println!("{}", attachment.id);
+        }
+        AttachmentsCommand::List { node_id } => {
+            let service = open_service(config, true)?;
+
+            for attachment in service.attachments(&node_id)? {
+                let location = match &attachment.content {
+                    AttachmentContent::Blob(_) => "stored".to_owned(),
+                    AttachmentContent::Path(path) => path.display().to_string(),
+                };
+                println!(
+                    "{}\t{}\t{}\t{}\t{location}",
+                    attachment.id, attachment.name, attachment.mime_type, attachment.size
+                );
+            }
+        }
+        AttachmentsCommand::Extract {
+            attachment_id,
+            output,
+        } => {
+            let service = open_service(config, true)?;
+            let data = service.extract(&attachment_id)?;
 
-    service
-        .create_node(CreateNodeRequest::new(
-            Some(node1.id()),
-            300,
-            NodeType::Standard,
-            "New node 4",
-            "astra",
-            Source::User,
-        ))
-        .unwrap();
-
-    service
-        .create_node(CreateNodeRequest::new(
-            Some(node1.id()),
-            400,
-            NodeType::Standard,
-            "New node 5",
-            "astra",
-            Source::User,
-        ))
-        .unwrap();
-
-    service
-        .create_node(CreateNodeRequest::new(
-            Some(node1.id()),
-            500,
-            NodeType::Standard,
-            "New node 6",
-            "astra",
-            Source::User,
-        ))
-        .unwrap();
-
-    let graph_service = GraphService::new(service).unwrap();
-
-    for element in graph_service.node_graph.graph {
-        for node in element.children {
-            println!("{node:?}");
+            match output {
+                Some(output) => fs::write(output, data)?,
+                None => io::stdout().write_all(&data)?,
+            }
+        }
+        AttachmentsCommand::Remove { attachment_id } => {
+            let service = open_service(config, false)?;
+            service.detach(&attachment_id)?;
         }
     }
+
+    Ok(())
+}
+
+fn bulk(config: &Config, selection: Selection, action: BulkAction) -> Result<(), Box<dyn Error>> {
+    // exports go to standard output, so they must not be mixed with log entries
+    if let BulkAction::Export(export) = action {
+        let service = open_service(config, true)?;
+        print!(
+            "{}",
+            service.bulk_export(&selection, export.format, &export.options(None))?
+        );
+        return Ok(());
+    }
+
+    let service = open_service(config, false)?;
+
+    let count = match action {
+        BulkAction::SetType { node_type } => service.bulk_set_type(&selection, node_type)?,
+        BulkAction::Delete { policy } => service.bulk_delete(&selection, policy)?,
+        BulkAction::Move { parent, position } => {
+            service.bulk_move(&selection, parent, position.unwrap_or(usize::MAX))?
+        }
+        BulkAction::Indent => service.bulk_indent(&selection)?,
+        BulkAction::Outdent => service.bulk_outdent(&selection)?,
+        BulkAction::Tag { tag } => service.bulk_tag(&selection, &tag)?,
+        BulkAction::Untag { tag } => service.bulk_untag(&selection, &tag)?,
+        BulkAction::Export(_) => unreachable!("exports are handled above"),
+    };
+
+    println!("Updated {count} nodes");
+
+    Ok(())
+}
+
+fn convert(command: ConvertCommand) -> Result<usize, InterfaceError> {
+    match command {
+        ConvertCommand::SqliteToJson {
+            database,
+            directory,
+        } => copy_repository(
+            &SqliteRepository::open(database)?,
+            &JsonRepository::open(directory)?,
+        ),
+        ConvertCommand::JsonToSqlite {
+            directory,
+            database,
+        } => copy_repository(
+            &JsonRepository::open(directory)?,
+            &SqliteRepository::open(database)?,
+        ),
+    }
 }
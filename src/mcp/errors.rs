@@ -0,0 +1,44 @@
+use std::io;
+
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::services::errors::ServiceError;
+
+#[derive(Error, Debug)]
+pub enum McpError {
+    #[error("IO error: {0}")]
+    Io(io::Error),
+    #[error("Parse error")]
+    Parse,
+    #[error("Invalid request")]
+    InvalidRequest,
+    #[error("Method not found: {0}")]
+    MethodNotFound(String),
+    #[error("Invalid params: {0}")]
+    InvalidParams(String),
+    #[error("Unknown tool: {0}")]
+    UnknownTool(String),
+    #[error("Serialization error")]
+    Serialization,
+    #[error("Node {0} is outside of the agent's scope")]
+    OutOfScope(Uuid),
+    #[error("{0}")]
+    Service(ServiceError),
+}
+
+impl McpError {
+    /// JSON-RPC error code to answer a request failing with this error, or `None`
+    /// when the failure belongs to a tool and is reported in the tool's result, so
+    /// the agent can see it and try again.
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            McpError::Parse => Some(-32700),
+            McpError::InvalidRequest => Some(-32600),
+            McpError::MethodNotFound(_) => Some(-32601),
+            McpError::InvalidParams(_) | McpError::UnknownTool(_) => Some(-32602),
+            McpError::Io(_) | McpError::Serialization => Some(-32603),
+            McpError::OutOfScope(_) | McpError::Service(_) => None,
+        }
+    }
+}
@@ -0,0 +1,179 @@
+pub mod errors;
+pub use errors::McpError;
+
+mod tools;
+
+use std::io::{BufRead, Write};
+
+use serde_json::{Map, Value, json};
+use uuid::Uuid;
+
+use crate::{
+    interfaces::NodeRepository,
+    services::{graph_service::GraphService, logging::LoggingService},
+};
+
+/// Protocol revision spoken by the server.
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Author recorded on an agent's writes when neither it nor its client has a name.
+const DEFAULT_AGENT: &str = "agent";
+
+/// The agent on the other end of the connection, and what it may touch.
+pub struct Agent {
+    /// Author recorded on the agent's writes, the name its client gives when `None`
+    pub name: Option<String>,
+    /// Node the agent is confined to, along with everything below it, or `None`
+    /// for the whole outline
+    pub root: Option<Uuid>,
+}
+
+impl Agent {
+    fn author(&self) -> &str {
+        self.name.as_deref().unwrap_or(DEFAULT_AGENT)
+    }
+}
+
+/// Serves the outline to `agent` over the Model Context Protocol, reading one
+/// JSON-RPC message per line from `input` and answering on `output`, until the
+/// input ends.
+///
+/// Every node the agent writes is stamped with [`Source::Agent`] and the agent's
+/// name as its author.
+///
+/// [`Source::Agent`]: crate::domain::node::Source::Agent
+pub fn serve<R, L>(
+    mut graph_service: GraphService<R, L>,
+    mut agent: Agent,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> Result<(), McpError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    if let Some(root) = agent.root {
+        graph_service
+            .node_service
+            .get_node(&root)
+            .map_err(McpError::Service)?;
+    }
+
+    for line in input.lines() {
+        let line = line.map_err(McpError::Io)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = respond(&mut graph_service, &mut agent, &line) {
+            writeln!(output, "{response}")
+                .and_then(|()| output.flush())
+                .map_err(McpError::Io)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Answers one message, or returns `None` for a notification, which has no answer.
+fn respond<R, L>(
+    graph_service: &mut GraphService<R, L>,
+    agent: &mut Agent,
+    line: &str,
+) -> Option<Value>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let message: Value = match serde_json::from_str(line) {
+        Ok(message) => message,
+        Err(_) => return Some(failure(Value::Null, &McpError::Parse)),
+    };
+
+    // notifications carry no ID, and none of them needs handling
+    let id = message.get("id").cloned()?;
+
+    let method = match (message.get("jsonrpc"), message.get("method")) {
+        (Some(version), Some(Value::String(method))) if version == "2.0" => method,
+        _ => return Some(failure(id, &McpError::InvalidRequest)),
+    };
+    let params = message
+        .get("params")
+        .cloned()
+        .unwrap_or(Value::Object(Map::new()));
+
+    let result = match method.as_str() {
+        "initialize" => Ok(initialize(agent, &params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools::definitions() })),
+        "tools/call" => call_tool(graph_service, agent, params),
+        _ => Err(McpError::MethodNotFound(method.clone())),
+    };
+
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => failure(id, &err),
+    })
+}
+
+fn initialize(agent: &mut Agent, params: &Value) -> Value {
+    if agent.name.is_none() {
+        agent.name = params
+            .pointer("/clientInfo/name")
+            .and_then(Value::as_str)
+            .map(str::to_owned);
+    }
+
+    let instructions = match agent.root {
+        Some(root) => format!(
+            "Tools for reading and editing an outline of nodes. Access is limited to \
+            node {root} and everything below it."
+        ),
+        None => "Tools for reading and editing an outline of nodes.".to_owned(),
+    };
+
+    json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "capabilities": { "tools": { "listChanged": false } },
+        "serverInfo": { "name": "outliner", "version": env!("CARGO_PKG_VERSION") },
+        "instructions": instructions
+    })
+}
+
+fn call_tool<R, L>(
+    graph_service: &mut GraphService<R, L>,
+    agent: &Agent,
+    params: Value,
+) -> Result<Value, McpError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let name = params
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or(McpError::InvalidParams("missing tool name".into()))?;
+    let arguments = params
+        .get("arguments")
+        .cloned()
+        .unwrap_or(Value::Object(Map::new()));
+
+    let (text, is_error) = match tools::call(graph_service, agent, name, arguments) {
+        Ok(output) => (output.to_string(), false),
+        Err(err) if err.code().is_none() => (err.to_string(), true),
+        Err(err) => return Err(err),
+    };
+
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error
+    }))
+}
+
+fn failure(id: Value, err: &McpError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": err.code().unwrap_or(-32603), "message": err.to_string() }
+    })
+}
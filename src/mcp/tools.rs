@@ -0,0 +1,402 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use crate::{
+    api::body::{NodeBody, TreeBody},
+    domain::{
+        DomainError, Node, NodeGraph, Query, Selection,
+        node::{CreateNodeRequest, NodeType, Source},
+    },
+    interfaces::NodeRepository,
+    mcp::{Agent, McpError},
+    services::{errors::ServiceError, graph_service::GraphService, logging::LoggingService},
+};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReadSubtreeArgs {
+    node_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SearchArgs {
+    query: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CreateNodeArgs {
+    parent_id: Option<Uuid>,
+    position: Option<usize>,
+    text: String,
+    node_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UpdateNodeArgs {
+    node_id: Uuid,
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SetTaskStateArgs {
+    node_id: Uuid,
+    state: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MoveNodeArgs {
+    node_id: Uuid,
+    parent_id: Option<Uuid>,
+    position: Option<usize>,
+}
+
+/// Describes every tool, in the form answered to `tools/list`.
+pub fn definitions() -> Value {
+    let node_id = json!({ "type": "string", "format": "uuid" });
+    let position = json!({
+        "type": "integer",
+        "minimum": 0,
+        "description": "Position among the new siblings, the end when omitted"
+    });
+    let parent_id = json!({
+        "type": "string",
+        "format": "uuid",
+        "description": "New parent, the top of the agent's scope when omitted"
+    });
+    let node_type = json!({
+        "type": "string",
+        "enum": ["standard", "todo", "inprogress", "done"]
+    });
+
+    json!([
+        {
+            "name": "read_subtree",
+            "description": "Read a node and everything below it. Without a node, reads \
+                the whole part of the outline the agent may access.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "node_id": node_id },
+                "additionalProperties": false
+            }
+        },
+        {
+            "name": "search",
+            "description": "Find nodes with a search such as \
+                `type:todo author:astra modified:>2026-10-01 \"some text\"`.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "query": { "type": "string" } },
+                "required": ["query"],
+                "additionalProperties": false
+            }
+        },
+        {
+            "name": "create_node",
+            "description": "Create a node.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "parent_id": parent_id,
+                    "position": position,
+                    "text": { "type": "string" },
+                    "node_type": node_type
+                },
+                "required": ["text"],
+                "additionalProperties": false
+            }
+        },
+        {
+            "name": "update_node",
+            "description": "Replace the text of a node, along with every mirror of it.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "node_id": node_id, "text": { "type": "string" } },
+                "required": ["node_id", "text"],
+                "additionalProperties": false
+            }
+        },
+        {
+            "name": "set_task_state",
+            "description": "Mark a node as a plain item, a todo, in progress or done.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "node_id": node_id, "state": node_type },
+                "required": ["node_id", "state"],
+                "additionalProperties": false
+            }
+        },
+        {
+            "name": "move_node",
+            "description": "Move a node, with everything below it, under a new parent.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "node_id": node_id,
+                    "parent_id": parent_id,
+                    "position": position
+                },
+                "required": ["node_id"],
+                "additionalProperties": false
+            }
+        }
+    ])
+}
+
+/// Runs tool `name`, returning its output.
+pub fn call<R, L>(
+    graph_service: &mut GraphService<R, L>,
+    agent: &Agent,
+    name: &str,
+    arguments: Value,
+) -> Result<Value, McpError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    // other writers may share the outline, so every call starts from its latest state
+    graph_service.refresh().map_err(McpError::Service)?;
+
+    match name {
+        "read_subtree" => read_subtree(graph_service, agent, parse_args(arguments)?),
+        "search" => search(graph_service, agent, parse_args(arguments)?),
+        "create_node" => create_node(graph_service, agent, parse_args(arguments)?),
+        "update_node" => update_node(graph_service, agent, parse_args(arguments)?),
+        "set_task_state" => set_task_state(graph_service, agent, parse_args(arguments)?),
+        "move_node" => move_node(graph_service, agent, parse_args(arguments)?),
+        _ => Err(McpError::UnknownTool(name.to_owned())),
+    }
+}
+
+fn read_subtree<R, L>(
+    graph_service: &GraphService<R, L>,
+    agent: &Agent,
+    args: ReadSubtreeArgs,
+) -> Result<Value, McpError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let graph = &graph_service.node_graph;
+    let elements = match args.node_id.or(agent.root) {
+        Some(node_id) => {
+            agent.check(graph, node_id)?;
+            Vec::from_iter(graph.find(&node_id))
+        }
+        None => graph.graph.iter().collect(),
+    };
+
+    to_value(
+        elements
+            .into_iter()
+            .filter_map(|element| TreeBody::new(graph, element))
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn search<R, L>(
+    graph_service: &GraphService<R, L>,
+    agent: &Agent,
+    args: SearchArgs,
+) -> Result<Value, McpError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let query =
+        Query::parse(&args.query).map_err(|err| McpError::Service(ServiceError::Domain(err)))?;
+    let results = graph_service
+        .node_service
+        .search(&query)
+        .map_err(McpError::Service)?;
+
+    to_value(
+        results
+            .nodes
+            .iter()
+            .filter(|node| results.is_match(&node.id()))
+            .filter(|node| agent.check(&graph_service.node_graph, node.id()).is_ok())
+            .map(NodeBody::from)
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn create_node<R, L>(
+    graph_service: &GraphService<R, L>,
+    agent: &Agent,
+    args: CreateNodeArgs,
+) -> Result<Value, McpError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let parent_id = args.parent_id.or(agent.root);
+    if let Some(parent_id) = parent_id {
+        agent.check(&graph_service.node_graph, parent_id)?;
+    }
+
+    let node_type = match args.node_type {
+        Some(node_type) => parse_node_type(&node_type)?,
+        None => NodeType::Standard,
+    };
+
+    let request = CreateNodeRequest::new(
+        parent_id,
+        0,
+        node_type,
+        &args.text,
+        agent.author(),
+        Source::Agent,
+    );
+    let node = graph_service
+        .node_service
+        .create_node_at(request, args.position.unwrap_or(usize::MAX))
+        .map_err(McpError::Service)?;
+
+    to_value(NodeBody::from(&node))
+}
+
+fn update_node<R, L>(
+    graph_service: &GraphService<R, L>,
+    agent: &Agent,
+    args: UpdateNodeArgs,
+) -> Result<Value, McpError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let mut node = editable_node(graph_service, agent, args.node_id)?;
+
+    graph_service
+        .node_service
+        .update_node_by(&mut node, &args.text, agent.author(), Source::Agent)
+        .map_err(McpError::Service)?;
+
+    to_value(NodeBody::from(&node))
+}
+
+fn set_task_state<R, L>(
+    graph_service: &GraphService<R, L>,
+    agent: &Agent,
+    args: SetTaskStateArgs,
+) -> Result<Value, McpError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let node_type = parse_node_type(&args.state)?;
+    let mut node = editable_node(graph_service, agent, args.node_id)?;
+
+    graph_service
+        .node_service
+        .set_node_type_by(&mut node, node_type, agent.author(), Source::Agent)
+        .map_err(McpError::Service)?;
+
+    to_value(NodeBody::from(&node))
+}
+
+fn move_node<R, L>(
+    graph_service: &GraphService<R, L>,
+    agent: &Agent,
+    args: MoveNodeArgs,
+) -> Result<Value, McpError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let graph = &graph_service.node_graph;
+    agent.check(graph, args.node_id)?;
+
+    // moving the top of the scope would take it, and the agent, elsewhere
+    if agent.root == Some(args.node_id) {
+        return Err(McpError::OutOfScope(args.node_id));
+    }
+
+    let parent_id = args.parent_id.or(agent.root);
+    if let Some(parent_id) = parent_id {
+        agent.check(graph, parent_id)?;
+    }
+
+    let node_service = &graph_service.node_service;
+    node_service
+        .bulk_move(
+            &Selection::single(args.node_id),
+            parent_id,
+            args.position.unwrap_or(usize::MAX),
+        )
+        .map_err(McpError::Service)?;
+
+    let node = node_service
+        .get_node(&args.node_id)
+        .map_err(McpError::Service)?;
+
+    to_value(NodeBody::from(&node))
+}
+
+/// Returns `node_id` when the agent may change its content. Changes reach every
+/// mirror of the node, so each of them must be within the agent's scope too.
+fn editable_node<R, L>(
+    graph_service: &GraphService<R, L>,
+    agent: &Agent,
+    node_id: Uuid,
+) -> Result<Node, McpError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let graph = &graph_service.node_graph;
+    agent.check(graph, node_id)?;
+
+    let node = graph_service
+        .node_service
+        .get_node(&node_id)
+        .map_err(McpError::Service)?;
+
+    for appearance in graph
+        .nodes
+        .iter()
+        .filter(|other| other.canonical_id() == node.canonical_id())
+    {
+        agent.check(graph, appearance.id())?;
+    }
+
+    Ok(node)
+}
+
+impl Agent {
+    /// Fails unless `node_id` is in `graph` and within the agent's scope.
+    fn check(&self, graph: &NodeGraph, node_id: Uuid) -> Result<(), McpError> {
+        if graph.node(&node_id).is_none() {
+            return Err(McpError::Service(ServiceError::Domain(
+                DomainError::MissingNode(node_id),
+            )));
+        }
+
+        match self.root {
+            Some(root) if root != node_id && !graph.ancestors(&node_id).contains(&root) => {
+                Err(McpError::OutOfScope(node_id))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn parse_node_type(node_type: &str) -> Result<NodeType, McpError> {
+    NodeType::from_str(node_type)
+        .map_err(|_| McpError::InvalidParams(format!("unknown node type `{node_type}`")))
+}
+
+fn parse_args<T: DeserializeOwned>(arguments: Value) -> Result<T, McpError> {
+    serde_json::from_value(arguments).map_err(|err| McpError::InvalidParams(err.to_string()))
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, McpError> {
+    serde_json::to_value(value).map_err(|_| McpError::Serialization)
+}
@@ -0,0 +1,435 @@
+use std::{collections::HashSet, str::FromStr};
+
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        Clipboard, DomainError, Node, NodeGraph, Selection, node::NodeType, subtree::ClipboardMode,
+        tag::normalize_tag,
+    },
+    interfaces::{
+        NodeRepository,
+        export::{self, ExportFormat, ExportOptions},
+    },
+    services::{
+        errors::ServiceError,
+        logging::LoggingService,
+        node_service::{NodeService, now},
+    },
+};
+
+/// What happens to the children of nodes removed by `bulk_delete`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeletePolicy {
+    /// Delete the whole subtree of every selected node
+    Cascade,
+    /// Delete only the selected nodes, moving their children up into their place
+    ReparentChildren,
+}
+
+impl FromStr for DeletePolicy {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<DeletePolicy, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "cascade" => Ok(DeletePolicy::Cascade),
+            "reparent" | "reparentchildren" => Ok(DeletePolicy::ReparentChildren),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Bulk operations over a `Selection`. Each one is applied in a single
+/// transaction and written to the log as a single entry.
+impl<R, L> NodeService<R, L>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    /// Sets the type of every selected node, returning the number of nodes changed.
+    pub fn bulk_set_type(
+        &self,
+        selection: &Selection,
+        node_type: NodeType,
+    ) -> Result<usize, ServiceError> {
+        let node_ids = self.resolve_selection(selection)?;
+
+        self.atomically(|| {
+            for node_id in &node_ids {
+                let mut node = self.get_node(node_id)?;
+                self.apply_node_type(&mut node, node_type.clone())?;
+            }
+
+            Ok(())
+        })?;
+
+        self.logger.write_log(format!(
+            "{} | Set node type: {} on {} nodes",
+            now()?,
+            node_type,
+            node_ids.len()
+        ))?;
+
+        Ok(node_ids.len())
+    }
+
+    /// Deletes the selected nodes, returning the number of nodes deleted.
+    pub fn bulk_delete(
+        &self,
+        selection: &Selection,
+        policy: DeletePolicy,
+    ) -> Result<usize, ServiceError> {
+        let graph = self.selection_graph()?;
+
+        let deleted = match policy {
+            DeletePolicy::Cascade => {
+                let roots = selection.roots(&graph).map_err(ServiceError::Domain)?;
+
+                self.atomically(|| {
+                    let mut subtree_nodes = Vec::new();
+                    for root in &roots {
+                        self.collect_post_order(self.get_node(root)?, &mut subtree_nodes)?;
+                    }
+
+                    let removed: HashSet<Uuid> =
+                        subtree_nodes.iter().map(|node| node.id()).collect();
+
+                    // children go first so that no node is ever left pointing at a deleted parent
+                    for node in &subtree_nodes {
+                        self.remove_node(node, &removed)?;
+                    }
+
+                    Ok(subtree_nodes.len())
+                })?
+            }
+            DeletePolicy::ReparentChildren => {
+                let node_ids = selection.resolve(&graph).map_err(ServiceError::Domain)?;
+
+                self.atomically(|| {
+                    for node_id in &node_ids {
+                        // re-read, as an earlier deletion may have moved this node
+                        let node = self.get_node(node_id)?;
+                        self.lift_children(&node)?;
+                        self.remove_node(&node, &HashSet::new())?;
+                    }
+
+                    Ok(node_ids.len())
+                })?
+            }
+        };
+
+        self.logger
+            .write_log(format!("{} | Deleted {} nodes", now()?, deleted))?;
+
+        Ok(deleted)
+    }
+
+    /// Moves the selected subtrees as one block under `new_parent`, starting at
+    /// sibling `position` and keeping their relative order.
+    pub fn bulk_move(
+        &self,
+        selection: &Selection,
+        new_parent: Option<Uuid>,
+        position: usize,
+    ) -> Result<usize, ServiceError> {
+        let graph = self.selection_graph()?;
+        let roots = selection.roots(&graph).map_err(ServiceError::Domain)?;
+
+        self.atomically(|| self.move_block(&roots, new_parent, position))?;
+
+        self.logger.write_log(format!(
+            "{} | Moved {} nodes under: {}",
+            now()?,
+            roots.len(),
+            new_parent.map_or("root".to_owned(), |id| id.to_string())
+        ))?;
+
+        Ok(roots.len())
+    }
+
+    /// Makes the selected siblings children of the sibling just above them.
+    /// Returns the number of nodes moved, which is zero when there is no such sibling.
+    pub fn bulk_indent(&self, selection: &Selection) -> Result<usize, ServiceError> {
+        let graph = self.selection_graph()?;
+        let roots = selection.roots(&graph).map_err(ServiceError::Domain)?;
+        let parent_id = shared_parent(&graph, &roots)?;
+
+        let siblings = self
+            .repository
+            .get_children(parent_id.as_ref())
+            .map_err(ServiceError::Interface)?;
+
+        let first = siblings
+            .iter()
+            .position(|sibling| sibling.id() == roots[0])
+            .unwrap_or(0);
+        let new_parent = siblings[..first]
+            .iter()
+            .rfind(|sibling| !roots.contains(&sibling.id()))
+            .map(|sibling| sibling.id());
+
+        let Some(new_parent) = new_parent else {
+            return Ok(0);
+        };
+
+        self.atomically(|| self.move_block(&roots, Some(new_parent), usize::MAX))?;
+
+        self.logger.write_log(format!(
+            "{} | Indented {} nodes under: {}",
+            now()?,
+            roots.len(),
+            new_parent
+        ))?;
+
+        Ok(roots.len())
+    }
+
+    /// Moves the selected siblings out of their parent, placing them right after it.
+    /// Returns the number of nodes moved, which is zero for top level nodes.
+    pub fn bulk_outdent(&self, selection: &Selection) -> Result<usize, ServiceError> {
+        let graph = self.selection_graph()?;
+        let roots = selection.roots(&graph).map_err(ServiceError::Domain)?;
+
+        let Some(parent_id) = shared_parent(&graph, &roots)? else {
+            return Ok(0);
+        };
+
+        let parent = self.get_node(&parent_id)?;
+        let position = self
+            .repository
+            .get_children(parent.parent_id().as_ref())
+            .map_err(ServiceError::Interface)?
+            .iter()
+            .position(|sibling| sibling.id() == parent_id)
+            .map_or(usize::MAX, |index| index + 1);
+
+        self.atomically(|| self.move_block(&roots, parent.parent_id(), position))?;
+
+        self.logger.write_log(format!(
+            "{} | Outdented {} nodes from: {}",
+            now()?,
+            roots.len(),
+            parent_id
+        ))?;
+
+        Ok(roots.len())
+    }
+
+    /// Adds `tag` to every selected node, returning the number of nodes tagged.
+    pub fn bulk_tag(&self, selection: &Selection, tag: &str) -> Result<usize, ServiceError> {
+        let tag = normalize_tag(tag).map_err(ServiceError::Domain)?;
+        let node_ids = self.canonical_selection(selection)?;
+
+        self.atomically(|| {
+            self.repository
+                .add_tag(&node_ids, &tag)
+                .map_err(ServiceError::Interface)
+        })?;
+
+        self.logger.write_log(format!(
+            "{} | Tagged {} nodes with: {}",
+            now()?,
+            node_ids.len(),
+            tag
+        ))?;
+
+        Ok(node_ids.len())
+    }
+
+    /// Removes `tag` from every selected node, returning the number of nodes affected.
+    pub fn bulk_untag(&self, selection: &Selection, tag: &str) -> Result<usize, ServiceError> {
+        let tag = normalize_tag(tag).map_err(ServiceError::Domain)?;
+        let node_ids = self.canonical_selection(selection)?;
+
+        self.atomically(|| {
+            self.repository
+                .remove_tag(&node_ids, &tag)
+                .map_err(ServiceError::Interface)
+        })?;
+
+        self.logger.write_log(format!(
+            "{} | Removed tag: {} from {} nodes",
+            now()?,
+            tag,
+            node_ids.len()
+        ))?;
+
+        Ok(node_ids.len())
+    }
+
+    /// Renders the selected subtrees in `format`. The root in `options` is
+    /// ignored, the selection taking its place.
+    pub fn bulk_export(
+        &self,
+        selection: &Selection,
+        format: ExportFormat,
+        options: &ExportOptions,
+    ) -> Result<String, ServiceError> {
+        let graph = self.selection_graph()?;
+        let roots = selection.roots(&graph).map_err(ServiceError::Domain)?;
+        let elements: Vec<_> = roots.iter().filter_map(|root| graph.find(root)).collect();
+        let output = export::export_elements(&graph, &elements, format, options);
+
+        self.logger.write_log(format!(
+            "{} | Exported {} nodes as {}",
+            now()?,
+            roots.len(),
+            format
+        ))?;
+
+        Ok(output)
+    }
+
+    /// Copies the selected subtrees to one clipboard, in outline order. In
+    /// `Cut` mode, pasting the clipboard moves the original nodes.
+    pub fn bulk_clip(
+        &self,
+        selection: &Selection,
+        mode: ClipboardMode,
+    ) -> Result<Clipboard, ServiceError> {
+        let graph = self.selection_graph()?;
+        let roots = selection
+            .roots(&graph)
+            .map_err(ServiceError::Domain)?
+            .iter()
+            .map(|root_id| self.subtree(&self.get_node(root_id)?))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Clipboard { mode, roots })
+    }
+
+    fn selection_graph(&self) -> Result<NodeGraph, ServiceError> {
+        let mut graph = NodeGraph::new(self.dump_nodes()?);
+        graph.set_tags(self.dump_tags()?);
+        graph.set_planning(self.dump_planning()?);
+
+        Ok(graph)
+    }
+
+    fn resolve_selection(&self, selection: &Selection) -> Result<Vec<Uuid>, ServiceError> {
+        let graph = self.selection_graph()?;
+
+        selection.resolve(&graph).map_err(ServiceError::Domain)
+    }
+
+    /// Selected node IDs with mirrors replaced by their canonical node.
+    fn canonical_selection(&self, selection: &Selection) -> Result<Vec<Uuid>, ServiceError> {
+        let graph = self.selection_graph()?;
+        let mut node_ids = Vec::new();
+
+        for node_id in selection.resolve(&graph).map_err(ServiceError::Domain)? {
+            let canonical_id = graph
+                .node(&node_id)
+                .map_or(node_id, |node| node.canonical_id());
+
+            if !node_ids.contains(&canonical_id) {
+                node_ids.push(canonical_id);
+            }
+        }
+
+        Ok(node_ids)
+    }
+
+    pub(crate) fn collect_post_order(
+        &self,
+        node: Node,
+        nodes: &mut Vec<Node>,
+    ) -> Result<(), ServiceError> {
+        let children = self
+            .repository
+            .get_children(Some(&node.id()))
+            .map_err(ServiceError::Interface)?;
+
+        for child in children {
+            self.collect_post_order(child, nodes)?;
+        }
+
+        nodes.push(node);
+
+        Ok(())
+    }
+
+    /// Moves the children of `node` into its place among its own siblings.
+    fn lift_children(&self, node: &Node) -> Result<(), ServiceError> {
+        let children = self
+            .repository
+            .get_children(Some(&node.id()))
+            .map_err(ServiceError::Interface)?;
+
+        if children.is_empty() {
+            return Ok(());
+        }
+
+        let position = self
+            .repository
+            .get_children(node.parent_id().as_ref())
+            .map_err(ServiceError::Interface)?
+            .iter()
+            .position(|sibling| sibling.id() == node.id())
+            .unwrap_or(usize::MAX);
+
+        let ranks =
+            self.insertion_ranks(node.parent_id(), position, children.len(), &[node.id()])?;
+
+        for (mut child, rank) in children.into_iter().zip(ranks) {
+            child
+                .move_to(node.parent_id(), rank)
+                .map_err(ServiceError::Domain)?;
+            self.store_node(&child)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn move_block(
+        &self,
+        node_ids: &[Uuid],
+        new_parent: Option<Uuid>,
+        position: usize,
+    ) -> Result<(), ServiceError> {
+        let new_parent = self.resolve_parent(new_parent)?;
+
+        // moving a node below itself would detach it from the outline
+        if let Some(parent_id) = new_parent {
+            let mut ancestry = vec![parent_id];
+            ancestry.extend(self.ancestor_ids(&self.get_node(&parent_id)?)?);
+
+            if let Some(node_id) = node_ids.iter().find(|id| ancestry.contains(id)) {
+                return Err(ServiceError::InvalidMove(*node_id));
+            }
+        }
+
+        let ranks = self.insertion_ranks(new_parent, position, node_ids.len(), node_ids)?;
+
+        for (node_id, rank) in node_ids.iter().zip(ranks) {
+            let mut node = self.get_node(node_id)?;
+            node.move_to(new_parent, rank)
+                .map_err(ServiceError::Domain)?;
+            self.store_node(&node)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parent shared by every node in `node_ids`, which must be siblings.
+fn shared_parent(graph: &NodeGraph, node_ids: &[Uuid]) -> Result<Option<Uuid>, ServiceError> {
+    let mut parents = node_ids.iter().map(|node_id| {
+        graph
+            .node(node_id)
+            .map(|node| node.parent_id())
+            .ok_or(ServiceError::Domain(DomainError::MissingNode(*node_id)))
+    });
+
+    let Some(parent_id) = parents.next().transpose()? else {
+        return Err(ServiceError::Domain(DomainError::InvalidSelection));
+    };
+
+    for other in parents {
+        if other? != parent_id {
+            return Err(ServiceError::Domain(DomainError::InvalidSelection));
+        }
+    }
+
+    Ok(parent_id)
+}
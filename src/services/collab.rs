@@ -0,0 +1,738 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        Node, Replica,
+        crdt::{Operation, OperationKind},
+        links::parse_links,
+        node::{CreateNodeRequest, NodeType},
+        rank::spread_ranks,
+    },
+    interfaces::{InterfaceError, NodeRepository},
+    services::{
+        errors::ServiceError,
+        events,
+        logging::LoggingService,
+        node_service::{NodeService, node_map, now},
+        sync::replica_id,
+    },
+};
+
+/// One end of an in-process connection between replicas, sending operations to
+/// every other peer and receiving theirs.
+pub struct Peer {
+    senders: Vec<Sender<Operation>>,
+    receiver: Receiver<Operation>,
+}
+
+impl Peer {
+    /// Connects `count` peers to each other.
+    pub fn mesh(count: usize) -> Vec<Peer> {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..count).map(|_| mpsc::channel()).unzip();
+
+        receivers
+            .into_iter()
+            .enumerate()
+            .map(|(index, receiver)| Peer {
+                senders: senders
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != index)
+                    .map(|(_, sender)| sender.clone())
+                    .collect(),
+                receiver,
+            })
+            .collect()
+    }
+
+    /// Sends operations to every other peer. Peers that have gone away are skipped.
+    pub fn send(&self, operations: &[Operation]) {
+        for sender in &self.senders {
+            for operation in operations {
+                let _ = sender.send(operation.clone());
+            }
+        }
+    }
+
+    /// Returns the operations received so far, without waiting for more.
+    pub fn receive(&self) -> Vec<Operation> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+/// Setting marking an outline whose edits are logged for other replicas.
+const SHARED_SETTING: &str = "shared";
+
+/// The replica of an outline, rebuilt from the operation log in its repository
+/// the first time it is needed and brought up to date with the log, which
+/// other processes may have added to, before every change.
+///
+/// Nothing is logged until the outline is first shared with another replica,
+/// when the log is seeded with its nodes, so outlines edited alone keep no log.
+#[derive(Default)]
+pub struct ReplicaCache {
+    loaded: RefCell<Option<LoadedReplica>>,
+}
+
+struct LoadedReplica {
+    replica: Replica,
+    /// Number of logged operations the replica has applied
+    logged: usize,
+}
+
+impl ReplicaCache {
+    /// Drops the replica, to be rebuilt from the log when next needed, as after
+    /// a rolled back transaction that took some logged operations with it.
+    pub(crate) fn reset(&self) {
+        self.loaded.take();
+    }
+
+    /// Marks the outline as shared and loads the replica, seeding the log with
+    /// the nodes written before it had one. Ranks read from the replica are
+    /// `rank_gap` apart.
+    pub(crate) fn share(
+        &self,
+        repository: &impl NodeRepository,
+        rank_gap: u64,
+    ) -> Result<(), ServiceError> {
+        if !self.is_shared(repository)? {
+            repository
+                .set_setting(SHARED_SETTING, Some("true"))
+                .map_err(ServiceError::Interface)?;
+        }
+
+        self.update(repository, rank_gap, |_| Ok(((), Vec::new())))
+    }
+
+    /// Whether the outline has been shared, and its edits are to be logged.
+    fn is_shared(&self, repository: &impl NodeRepository) -> Result<bool, ServiceError> {
+        if self.loaded.borrow().is_some() {
+            return Ok(true);
+        }
+
+        Ok(repository
+            .get_setting(SHARED_SETTING)
+            .map_err(ServiceError::Interface)?
+            .is_some())
+    }
+
+    /// Logs the operations bringing the replica in line with the repository's
+    /// version of `node_ids`, so that changes written to the repository reach
+    /// the other replicas. Parents should come before their children. Does
+    /// nothing for an outline that has not been shared.
+    pub(crate) fn record(
+        &self,
+        repository: &impl NodeRepository,
+        rank_gap: u64,
+        node_ids: &[Uuid],
+    ) -> Result<(), ServiceError> {
+        if !self.is_shared(repository)? {
+            return Ok(());
+        }
+
+        self.update(repository, rank_gap, |replica| {
+            let mut operations = Vec::new();
+            for node_id in node_ids {
+                record_node(repository, replica, *node_id, &mut operations)?;
+            }
+
+            Ok(((), operations))
+        })
+    }
+
+    /// Applies operations from other replicas and writes the nodes they change
+    /// to the repository, returning the operations not seen before. Sharing
+    /// the outline starts here if it has not already.
+    pub(crate) fn receive(
+        &self,
+        repository: &impl NodeRepository,
+        rank_gap: u64,
+        operations: Vec<Operation>,
+    ) -> Result<Vec<Operation>, ServiceError> {
+        self.share(repository, rank_gap)?;
+
+        self.update(repository, rank_gap, |replica| {
+            let seen = replica.history().len();
+            for operation in operations {
+                replica.apply(operation);
+            }
+
+            // held back operations may be applied along with the ones they waited for
+            let applied = replica.history()[seen..].to_vec();
+            write_received(repository, replica, &applied)?;
+
+            Ok((applied.clone(), applied))
+        })
+    }
+
+    /// Runs `change` on the up to date replica and logs the operations it returns.
+    fn update<R, T>(
+        &self,
+        repository: &R,
+        rank_gap: u64,
+        change: impl FnOnce(&mut Replica) -> Result<(T, Vec<Operation>), ServiceError>,
+    ) -> Result<T, ServiceError>
+    where
+        R: NodeRepository,
+    {
+        // on failure the replica is not put back, as it may be ahead of the log
+        let mut loaded = match self.loaded.take() {
+            Some(loaded) => loaded.caught_up(repository)?,
+            None => LoadedReplica::load(repository, rank_gap)?,
+        };
+
+        let (result, operations) = change(&mut loaded.replica)?;
+
+        if !operations.is_empty() {
+            repository
+                .add_operations(&operations)
+                .map_err(ServiceError::Interface)?;
+            loaded.logged += operations.len();
+        }
+
+        self.loaded.replace(Some(loaded));
+
+        Ok(result)
+    }
+}
+
+impl LoadedReplica {
+    fn load(repository: &impl NodeRepository, rank_gap: u64) -> Result<Self, ServiceError> {
+        let replica_id = Uuid::parse_str(&replica_id(repository)?).map_err(|_| {
+            ServiceError::Interface(InterfaceError::FieldParseError("replica_id".to_owned()))
+        })?;
+
+        let mut loaded = LoadedReplica {
+            replica: Replica::new(replica_id, rank_gap),
+            logged: 0,
+        }
+        .caught_up(repository)?;
+
+        if loaded.logged == 0 {
+            let nodes = repository.dump_nodes().map_err(ServiceError::Interface)?;
+            let operations = loaded.replica.seed(&nodes).map_err(ServiceError::Domain)?;
+
+            if !operations.is_empty() {
+                repository
+                    .add_operations(&operations)
+                    .map_err(ServiceError::Interface)?;
+                loaded.logged = operations.len();
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    fn caught_up(mut self, repository: &impl NodeRepository) -> Result<Self, ServiceError> {
+        let operations = repository
+            .get_operations(self.logged)
+            .map_err(ServiceError::Interface)?;
+
+        self.logged += operations.len();
+        for operation in operations {
+            self.replica.apply(operation);
+        }
+
+        Ok(self)
+    }
+}
+
+fn record_node(
+    repository: &impl NodeRepository,
+    replica: &mut Replica,
+    node_id: Uuid,
+    operations: &mut Vec<Operation>,
+) -> Result<(), ServiceError> {
+    let node = match repository.get_node(&node_id) {
+        Ok(node) => node,
+        Err(InterfaceError::MissingNode) => {
+            if replica.node(&node_id).is_some() {
+                operations.extend(replica.delete_node(node_id).map_err(ServiceError::Domain)?);
+            }
+            return Ok(());
+        }
+        Err(err) => return Err(ServiceError::Interface(err)),
+    };
+
+    // a parent the replica has not seen, such as one added by a sync, goes first
+    if let Some(parent_id) = node.parent_id()
+        && replica.node(&parent_id).is_none()
+    {
+        record_node(repository, replica, parent_id, operations)?;
+    }
+
+    let after = previous_sibling(repository, replica, &node)?;
+
+    if !replica.contains(&node_id) {
+        operations.extend(
+            replica
+                .add_node(&node, after)
+                .map_err(ServiceError::Domain)?,
+        );
+        return Ok(());
+    }
+
+    operations.extend(
+        replica
+            .place_node(node_id, node.parent_id(), after)
+            .map_err(ServiceError::Domain)?,
+    );
+    operations.extend(
+        replica
+            .set_text(node_id, node.text())
+            .map_err(ServiceError::Domain)?,
+    );
+
+    if replica
+        .node(&node_id)
+        .is_some_and(|shared| shared.node_type() != node.node_type())
+    {
+        operations.extend(
+            replica
+                .set_node_type(node_id, node.node_type().clone())
+                .map_err(ServiceError::Domain)?,
+        );
+    }
+
+    Ok(())
+}
+
+/// Closest sibling before `node` in the repository that the replica also
+/// holds under the same parent.
+fn previous_sibling(
+    repository: &impl NodeRepository,
+    replica: &Replica,
+    node: &Node,
+) -> Result<Option<Uuid>, ServiceError> {
+    let mut siblings = repository
+        .get_children(node.parent_id().as_ref())
+        .map_err(ServiceError::Interface)?;
+    siblings.sort_by_key(|sibling| (sibling.rank(), sibling.id()));
+
+    Ok(siblings
+        .iter()
+        .take_while(|sibling| sibling.id() != node.id())
+        .filter(|sibling| replica.is_child_of(&sibling.id(), node.parent_id()))
+        .last()
+        .map(|sibling| sibling.id()))
+}
+
+/// Writes the nodes changed by `operations`, just applied to `replica`, to the
+/// repository.
+fn write_received(
+    repository: &impl NodeRepository,
+    replica: &Replica,
+    operations: &[Operation],
+) -> Result<(), ServiceError> {
+    let is_move = |operation: &&Operation| matches!(operation.kind, OperationKind::Move { .. });
+    let mut node_ids: HashSet<Uuid> = operations.iter().map(Operation::node_id).collect();
+
+    // a move older than ones already applied undoes and redoes them, which
+    // can leave the nodes they moved somewhere else
+    if let Some(earliest) = operations
+        .iter()
+        .filter(is_move)
+        .map(|operation| operation.id)
+        .min()
+    {
+        node_ids.extend(
+            replica
+                .history()
+                .iter()
+                .filter(|operation| is_move(operation) && operation.id > earliest)
+                .map(Operation::node_id),
+        );
+    }
+
+    let mut parents = HashSet::new();
+    let mut removed = Vec::new();
+
+    for node_id in &node_ids {
+        let stored = match repository.get_node(node_id) {
+            Ok(stored) => Some(stored),
+            Err(InterfaceError::MissingNode) => None,
+            Err(err) => return Err(ServiceError::Interface(err)),
+        };
+
+        match (replica.node(node_id), stored) {
+            (Some(shared), Some(stored)) => {
+                if shared.text() != stored.text() || shared.node_type() != stored.node_type() {
+                    write_node(
+                        repository,
+                        &stored,
+                        &shared,
+                        stored.parent_id(),
+                        stored.rank(),
+                    )?;
+                }
+                parents.insert(shared.parent_id());
+            }
+            (Some(shared), None) => {
+                parents.insert(shared.parent_id());
+            }
+            (None, Some(_)) => removed.push(*node_id),
+            (None, None) => {}
+        }
+    }
+
+    // parents are placed before their children
+    let mut parents: Vec<_> = parents.into_iter().collect();
+    parents.sort_by_cached_key(|parent_id| depth(replica, *parent_id));
+
+    for parent_id in parents {
+        write_children(repository, replica, parent_id)?;
+    }
+
+    for node_id in removed {
+        remove_subtree(repository, &node_id)?;
+    }
+
+    Ok(())
+}
+
+/// Puts the children of `parent_id` in the repository in the replica's order,
+/// respacing their ranks when the order differs.
+fn write_children(
+    repository: &impl NodeRepository,
+    replica: &Replica,
+    parent_id: Option<Uuid>,
+) -> Result<(), ServiceError> {
+    let child_ids = replica.child_ids(parent_id);
+
+    let mut stored = repository
+        .get_children(parent_id.as_ref())
+        .map_err(ServiceError::Interface)?;
+    stored.sort_by_key(|child| (child.rank(), child.id()));
+
+    if stored.iter().map(Node::id).eq(child_ids.iter().copied()) {
+        return Ok(());
+    }
+
+    let stored: HashMap<Uuid, Node> = stored
+        .into_iter()
+        .map(|child| (child.id(), child))
+        .collect();
+
+    for (child_id, rank) in child_ids
+        .iter()
+        .zip(spread_ranks(child_ids.len(), replica.rank_gap()))
+    {
+        let Some(shared) = replica.node(child_id) else {
+            continue;
+        };
+
+        let stored = match stored.get(child_id) {
+            Some(stored) => Some(stored.clone()),
+            None => match repository.get_node(child_id) {
+                Ok(stored) => Some(stored),
+                Err(InterfaceError::MissingNode) => None,
+                Err(err) => return Err(ServiceError::Interface(err)),
+            },
+        };
+
+        match stored {
+            Some(stored) if stored.parent_id() == parent_id && stored.rank() == rank => {}
+            Some(stored) => write_node(repository, &stored, &shared, parent_id, rank)?,
+            None => {
+                let node = placed(&shared, parent_id, rank);
+                repository
+                    .add_node(&node)
+                    .map_err(ServiceError::Interface)?;
+
+                if node.mirror_of().is_none() {
+                    repository
+                        .set_links(&node.id(), &parse_links(node.text()))
+                        .map_err(ServiceError::Interface)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `stored` with the text and type it has in the replica, under
+/// `parent_id` at `rank`. Notes are not replicated and stay as they are.
+fn write_node(
+    repository: &impl NodeRepository,
+    stored: &Node,
+    shared: &Node,
+    parent_id: Option<Uuid>,
+    rank: u64,
+) -> Result<(), ServiceError> {
+    let mut request = CreateNodeRequest::new(
+        parent_id,
+        rank,
+        shared.node_type().clone(),
+        shared.text(),
+        stored.author(),
+        stored.source_type().clone(),
+    );
+    request.note = stored.note().to_owned();
+    request.mirror_of = stored.mirror_of();
+
+    let node = Node::from_parts(
+        stored.id(),
+        request,
+        stored.created_time(),
+        stored.modified_time().max(shared.modified_time()),
+    );
+
+    repository
+        .update_node(&node)
+        .map_err(ServiceError::Interface)?;
+
+    if node.text() != stored.text() && node.mirror_of().is_none() {
+        repository
+            .set_links(&node.id(), &parse_links(node.text()))
+            .map_err(ServiceError::Interface)?;
+    }
+
+    Ok(())
+}
+
+/// `node` under `parent_id` at `rank`.
+fn placed(node: &Node, parent_id: Option<Uuid>, rank: u64) -> Node {
+    let mut request = CreateNodeRequest::new(
+        parent_id,
+        rank,
+        node.node_type().clone(),
+        node.text(),
+        node.author(),
+        node.source_type().clone(),
+    );
+    request.mirror_of = node.mirror_of();
+
+    Node::from_parts(
+        node.id(),
+        request,
+        node.created_time(),
+        node.modified_time(),
+    )
+}
+
+/// Deletes a node from the repository along with whatever is still below it.
+fn remove_subtree(repository: &impl NodeRepository, node_id: &Uuid) -> Result<(), ServiceError> {
+    let children = repository
+        .get_children(Some(node_id))
+        .map_err(ServiceError::Interface)?;
+
+    for child in children {
+        remove_subtree(repository, &child.id())?;
+    }
+
+    match repository.delete_node(node_id) {
+        Ok(()) | Err(InterfaceError::MissingNode) => Ok(()),
+        Err(err) => Err(ServiceError::Interface(err)),
+    }
+}
+
+/// Number of ancestors the children of `parent_id` have in the replica.
+fn depth(replica: &Replica, parent_id: Option<Uuid>) -> usize {
+    let mut depth = 0;
+    let mut current = parent_id;
+
+    while let Some(node_id) = current {
+        depth += 1;
+        current = replica.node(&node_id).and_then(|node| node.parent_id());
+    }
+
+    depth
+}
+
+impl<R, L> NodeService<R, L>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    /// Logged operations of the outline's collaborative edits, in the order
+    /// they were logged, leaving out the first `skip`. The outline is shared
+    /// from then on, and its later edits are logged too.
+    pub fn operations(&self, skip: usize) -> Result<Vec<Operation>, ServiceError> {
+        self.replica.share(&self.repository, self.rank_gap)?;
+
+        self.repository
+            .get_operations(skip)
+            .map_err(ServiceError::Interface)
+    }
+
+    /// Applies operations made by other replicas and writes the nodes they
+    /// change, returning the number of operations not seen before.
+    pub fn apply_operations(&self, operations: Vec<Operation>) -> Result<usize, ServiceError> {
+        // received operations move siblings around, so their events come from
+        // comparing the whole outline
+        let previous = if self.notifier.is_watched() {
+            Some(node_map(self.dump_nodes()?))
+        } else {
+            None
+        };
+
+        let applied = self.atomically(|| {
+            self.replica
+                .receive(&self.repository, self.rank_gap, operations)
+        })?;
+
+        if let Some(previous) = previous
+            && !applied.is_empty()
+        {
+            let current = node_map(self.dump_nodes()?);
+            self.notifier.notify(events::diff(&previous, &current));
+        }
+
+        if !applied.is_empty() {
+            self.logger.write_log(format!(
+                "{} | Applied operations from other replicas: {}",
+                now()?,
+                applied.len()
+            ))?;
+        }
+
+        Ok(applied.len())
+    }
+
+    /// Logs the changes made to `node_ids` in the repository for the other replicas.
+    pub(crate) fn record(&self, node_ids: &[Uuid]) -> Result<(), ServiceError> {
+        self.replica
+            .record(&self.repository, self.rank_gap, node_ids)
+    }
+}
+
+/// Edits an outline together with other replicas connected through a [`Peer`].
+///
+/// Edits go through the node service, which logs them as operations, and the
+/// logged operations are then sent to the other peers. Operations received
+/// from them only rewrite the nodes they change.
+pub struct CollabService<R, L>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    pub node_service: NodeService<R, L>,
+    pub peer: Peer,
+    /// Number of logged operations sent to the other peers so far
+    sent: usize,
+}
+
+impl<R, L> CollabService<R, L>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    /// Connects to the other peers, sending them the whole log so they can
+    /// catch up with the outline held by `node_service`.
+    pub fn connect(node_service: NodeService<R, L>, peer: Peer) -> Result<Self, ServiceError> {
+        let mut service = Self {
+            node_service,
+            peer,
+            sent: 0,
+        };
+        service.share()?;
+
+        Ok(service)
+    }
+
+    pub fn create_node(
+        &mut self,
+        request: CreateNodeRequest,
+        position: usize,
+    ) -> Result<Uuid, ServiceError> {
+        let node = self.node_service.create_node_at(request, position)?;
+        self.share()?;
+
+        Ok(node.id())
+    }
+
+    pub fn update_text(&mut self, node_id: Uuid, text: &str) -> Result<(), ServiceError> {
+        let mut node = self.node_service.get_node(&node_id)?;
+        self.node_service.update_node(&mut node, text)?;
+
+        self.share()
+    }
+
+    pub fn set_node_type(
+        &mut self,
+        node_id: Uuid,
+        node_type: NodeType,
+    ) -> Result<(), ServiceError> {
+        let mut node = self.node_service.get_node(&node_id)?;
+        self.node_service.set_node_type(&mut node, node_type)?;
+
+        self.share()
+    }
+
+    /// Moves a node to `position` among the children of `parent_id`, counted
+    /// without the node itself.
+    pub fn move_node(
+        &mut self,
+        node_id: Uuid,
+        parent_id: Option<Uuid>,
+        position: usize,
+    ) -> Result<(), ServiceError> {
+        self.node_service.atomically(|| {
+            self.node_service
+                .move_block(&[node_id], parent_id, position)
+        })?;
+
+        self.node_service
+            .logger
+            .write_log(format!("{} | Moved node: {}", now()?, node_id))?;
+
+        self.share()
+    }
+
+    /// Deletes a node along with its subtree.
+    pub fn delete_node(&mut self, node_id: Uuid) -> Result<(), ServiceError> {
+        self.node_service.atomically(|| {
+            let mut subtree_nodes = Vec::new();
+            self.node_service
+                .collect_post_order(self.node_service.get_node(&node_id)?, &mut subtree_nodes)?;
+
+            let removed: HashSet<Uuid> = subtree_nodes.iter().map(|node| node.id()).collect();
+            for node in &subtree_nodes {
+                self.node_service.remove_node(node, &removed)?;
+            }
+
+            Ok(())
+        })?;
+
+        self.node_service
+            .logger
+            .write_log(format!("{} | Deleted node: {}", now()?, node_id))?;
+
+        self.share()
+    }
+
+    /// Applies the operations received from other peers, returning how many of
+    /// them had not been seen before.
+    pub fn receive(&mut self) -> Result<usize, ServiceError> {
+        // edits logged since the last send, such as by another process, go out first
+        self.share()?;
+
+        let operations = self.peer.receive();
+        if operations.is_empty() {
+            return Ok(0);
+        }
+
+        let applied = self.node_service.apply_operations(operations)?;
+
+        // the other peers already have what was just received
+        self.sent += applied;
+
+        Ok(applied)
+    }
+
+    /// Sends the operations logged since the last send to the other peers.
+    fn share(&mut self) -> Result<(), ServiceError> {
+        let operations = self.node_service.operations(self.sent)?;
+        self.sent += operations.len();
+        self.peer.send(&operations);
+
+        Ok(())
+    }
+}
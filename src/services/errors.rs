@@ -1,4 +1,5 @@
 use thiserror::Error;
+use uuid::Uuid;
 
 use crate::{domain::DomainError, interfaces::InterfaceError};
 
@@ -8,4 +9,22 @@ pub enum ServiceError {
     Domain(DomainError),
     #[error("Interface error encountered: {0}")]
     Interface(InterfaceError),
+    #[error("Cannot move node `{0}` into its own subtree")]
+    InvalidMove(Uuid),
+    #[error("No view named `{0}`")]
+    MissingView(String),
+    #[error("No template `{0}`")]
+    MissingTemplate(String),
+    #[error("A template named `{0}` already exists")]
+    DuplicateTemplate(String),
+    #[error("No attachment with ID `{0}`")]
+    MissingAttachment(Uuid),
+    #[error("File of {size} bytes is larger than the limit of {limit} bytes")]
+    AttachmentTooLarge { size: u64, limit: u64 },
+    #[error("Cannot read attached file `{0}`")]
+    AttachedFile(String),
+    #[error("Node `{0}` appears more than once in the imported outline")]
+    DuplicateImportedNode(Uuid),
+    #[error("Failed to write log entry")]
+    LogWrite,
 }
@@ -0,0 +1,273 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, PoisonError,
+        mpsc::{self, Receiver, Sender},
+    },
+    time::Duration,
+};
+
+use uuid::Uuid;
+
+use crate::{
+    domain::Node,
+    interfaces::NodeRepository,
+    services::{errors::ServiceError, node_service::node_map},
+};
+
+/// A change to the outline, announced to every subscriber of an [`EventBus`].
+#[derive(Debug, Clone)]
+pub enum NodeEvent {
+    NodeCreated(Node),
+    /// Text, note, type or mirror target changed
+    NodeUpdated(Node),
+    /// Parent or position among the siblings changed
+    NodeMoved {
+        node: Node,
+        previous_parent_id: Option<Uuid>,
+    },
+    NodeDeleted(Uuid),
+}
+
+impl NodeEvent {
+    pub fn node_id(&self) -> Uuid {
+        match self {
+            NodeEvent::NodeCreated(node)
+            | NodeEvent::NodeUpdated(node)
+            | NodeEvent::NodeMoved { node, .. } => node.id(),
+            NodeEvent::NodeDeleted(node_id) => *node_id,
+        }
+    }
+}
+
+/// Hands every published event to each subscriber, over a channel of its own.
+/// Clones share the same subscribers, so the bus can be passed between threads.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<NodeEvent>>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a channel receiving every event published from now on.
+    pub fn subscribe(&self) -> Receiver<NodeEvent> {
+        let (sender, receiver) = mpsc::channel();
+
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(sender);
+
+        receiver
+    }
+
+    /// Sends `event` to every subscriber, forgetting those that have gone away.
+    pub fn publish(&self, event: NodeEvent) {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|sender| sender.send(event.clone()).is_ok());
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+}
+
+/// How often front ends poll for changes made by other processes by default.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Publishes the changes to an outline on an [`EventBus`]. A service announces
+/// its own writes once they are committed, while writes from other processes
+/// are found through
+/// [`NodeService::poll_changes`](crate::services::node_service::NodeService::poll_changes).
+#[derive(Default)]
+pub struct Notifier {
+    bus: EventBus,
+    /// Events of the writes made in the open transactions
+    pending: RefCell<Vec<NodeEvent>>,
+    /// Number of pending events when each open transaction began
+    marks: RefCell<Vec<usize>>,
+    watcher: RefCell<Option<ChangeWatcher>>,
+}
+
+impl Notifier {
+    /// Returns a channel receiving every event published from now on.
+    pub fn subscribe(&self) -> Receiver<NodeEvent> {
+        self.bus.subscribe()
+    }
+
+    /// Whether anyone could be told about changes. Writes skip working out
+    /// their events when nobody is.
+    pub(crate) fn is_watched(&self) -> bool {
+        self.watcher.borrow().is_some() || self.bus.subscriber_count() > 0
+    }
+
+    /// Publishes `events`, or holds them back until the open transactions commit.
+    pub(crate) fn notify(&self, events: Vec<NodeEvent>) {
+        if self.marks.borrow().is_empty() {
+            self.publish(events);
+        } else {
+            self.pending.borrow_mut().extend(events);
+        }
+    }
+
+    pub(crate) fn begin(&self) {
+        let mark = self.pending.borrow().len();
+        self.marks.borrow_mut().push(mark);
+    }
+
+    /// Publishes the held back events once the outermost transaction commits.
+    pub(crate) fn commit(&self) {
+        let mut marks = self.marks.borrow_mut();
+        marks.pop();
+
+        if marks.is_empty() {
+            drop(marks);
+            let events = self.pending.take();
+            self.publish(events);
+        }
+    }
+
+    /// Drops the events of the writes the innermost transaction took back.
+    pub(crate) fn rollback(&self) {
+        if let Some(mark) = self.marks.borrow_mut().pop() {
+            self.pending.borrow_mut().truncate(mark);
+        }
+    }
+
+    /// Publishes the changes other processes committed to `repository` since
+    /// the last poll, returning how many events were sent. The first poll only
+    /// takes note of the outline as it is.
+    ///
+    /// Changes are noticed through [`NodeRepository::data_version`], so this
+    /// should be given the connection the service writes through. Its own
+    /// writes are then only announced once, when they are made.
+    pub(crate) fn poll(&self, repository: &impl NodeRepository) -> Result<usize, ServiceError> {
+        let mut loaded = self.watcher.borrow_mut();
+
+        let Some(watcher) = loaded.as_mut() else {
+            *loaded = Some(ChangeWatcher::new(repository)?);
+            return Ok(0);
+        };
+
+        let events = watcher.poll(repository)?;
+        let count = events.len();
+
+        for event in events {
+            self.bus.publish(event);
+        }
+
+        Ok(count)
+    }
+
+    fn publish(&self, events: Vec<NodeEvent>) {
+        for event in events {
+            if let Some(watcher) = self.watcher.borrow_mut().as_mut() {
+                watcher.observe(&event);
+            }
+
+            self.bus.publish(event);
+        }
+    }
+}
+
+/// The outline as last seen, to tell what other processes have changed.
+struct ChangeWatcher {
+    version: u64,
+    snapshot: HashMap<Uuid, Node>,
+}
+
+impl ChangeWatcher {
+    fn new(repository: &impl NodeRepository) -> Result<Self, ServiceError> {
+        let version = repository.data_version().map_err(ServiceError::Interface)?;
+        let snapshot = node_map(repository.dump_nodes().map_err(ServiceError::Interface)?);
+
+        Ok(Self { version, snapshot })
+    }
+
+    /// Events of the changes committed since the last poll.
+    fn poll(&mut self, repository: &impl NodeRepository) -> Result<Vec<NodeEvent>, ServiceError> {
+        let version = repository.data_version().map_err(ServiceError::Interface)?;
+
+        if version == self.version {
+            return Ok(Vec::new());
+        }
+
+        let current = node_map(repository.dump_nodes().map_err(ServiceError::Interface)?);
+        let events = diff(&self.snapshot, &current);
+
+        self.version = version;
+        self.snapshot = current;
+
+        Ok(events)
+    }
+
+    /// Takes an event announced by the service itself into the snapshot, so
+    /// that the next poll does not report its change again.
+    fn observe(&mut self, event: &NodeEvent) {
+        match event {
+            NodeEvent::NodeCreated(node)
+            | NodeEvent::NodeUpdated(node)
+            | NodeEvent::NodeMoved { node, .. } => {
+                self.snapshot.insert(node.id(), node.clone());
+            }
+            NodeEvent::NodeDeleted(node_id) => {
+                self.snapshot.remove(node_id);
+            }
+        }
+    }
+}
+
+/// Events turning the `previous` nodes into the `current` ones.
+pub(crate) fn diff(
+    previous: &HashMap<Uuid, Node>,
+    current: &HashMap<Uuid, Node>,
+) -> Vec<NodeEvent> {
+    let mut events: Vec<NodeEvent> = current
+        .values()
+        .flat_map(|node| changes(previous.get(&node.id()), node))
+        .collect();
+
+    events.extend(
+        previous
+            .keys()
+            .filter(|node_id| !current.contains_key(node_id))
+            .map(|node_id| NodeEvent::NodeDeleted(*node_id)),
+    );
+
+    events
+}
+
+/// Events turning `old` into `node`, which is new when there is no `old`.
+pub(crate) fn changes(old: Option<&Node>, node: &Node) -> Vec<NodeEvent> {
+    let Some(old) = old else {
+        return vec![NodeEvent::NodeCreated(node.clone())];
+    };
+
+    let mut events = Vec::new();
+
+    if old.parent_id() != node.parent_id() || old.rank() != node.rank() {
+        events.push(NodeEvent::NodeMoved {
+            node: node.clone(),
+            previous_parent_id: old.parent_id(),
+        });
+    }
+
+    if old.text() != node.text()
+        || old.note() != node.note()
+        || old.node_type() != node.node_type()
+        || old.mirror_of() != node.mirror_of()
+    {
+        events.push(NodeEvent::NodeUpdated(node.clone()));
+    }
+
+    events
+}
@@ -1,9 +1,15 @@
+use std::collections::HashSet;
+
+use uuid::Uuid;
+
 use crate::{
-    domain::NodeGraph,
+    domain::{DomainError, GraphView, NodeGraph, SavedView},
     interfaces::NodeRepository,
     services::{errors::ServiceError, logging::LoggingService, node_service::NodeService},
 };
 
+const ZOOM_SETTING: &str = "zoom_root";
+
 pub struct GraphService<R, L>
 where
     R: NodeRepository,
@@ -11,6 +17,11 @@ where
 {
     pub node_graph: NodeGraph,
     pub node_service: NodeService<R, L>,
+    zoom_root: Option<Uuid>,
+    /// Saved view the graph is currently filtered by
+    active_view: Option<SavedView>,
+    /// Nodes folded while the view is open, kept in memory only
+    view_collapsed: HashSet<Uuid>,
 }
 
 impl<R, L> GraphService<R, L>
@@ -20,11 +31,194 @@ where
 {
     pub fn new(node_service: NodeService<R, L>) -> Result<GraphService<R, L>, ServiceError> {
         let nodes = node_service.dump_nodes()?;
-        let graph = NodeGraph::new(nodes);
+        let mut graph = NodeGraph::new(nodes);
+        graph.set_collapsed_nodes(&node_service.collapsed_nodes()?);
+        graph.set_tags(node_service.dump_tags()?);
+        graph.set_planning(node_service.dump_planning()?);
+
+        // a persisted zoom pointing at a since deleted node falls back to the whole outline
+        let zoom_root = node_service
+            .get_setting(ZOOM_SETTING)?
+            .and_then(|value| Uuid::parse_str(&value).ok())
+            .filter(|id| graph.find(id).is_some());
 
         Ok(Self {
             node_graph: graph,
             node_service,
+            zoom_root,
+            active_view: None,
+            view_collapsed: HashSet::new(),
         })
     }
+
+    /// Rebuilds the graph from the repository, keeping the current zoom when possible.
+    /// An open saved view is evaluated again, so its results follow any changes.
+    pub fn refresh(&mut self) -> Result<(), ServiceError> {
+        self.node_graph = match &self.active_view {
+            Some(view) => self.node_service.evaluate_view(view)?,
+            None => NodeGraph::new(self.node_service.dump_nodes()?),
+        };
+
+        let collapsed = match self.active_view {
+            Some(_) => self.view_collapsed.clone(),
+            None => self.node_service.collapsed_nodes()?,
+        };
+        self.node_graph.set_collapsed_nodes(&collapsed);
+        self.node_graph.set_tags(self.node_service.dump_tags()?);
+        self.node_graph
+            .set_planning(self.node_service.dump_planning()?);
+
+        if let Some(root) = self.zoom_root
+            && self.node_graph.find(&root).is_none()
+        {
+            let fallback = self.active_view.as_ref().and_then(|view| view.root);
+            self.set_zoom(fallback)?;
+        }
+
+        Ok(())
+    }
+
+    /// Filters the graph by the saved view `name`, showing it from the view's root.
+    /// Zooming and folding inside a view are not persisted.
+    pub fn open_view(&mut self, name: &str) -> Result<GraphView, ServiceError> {
+        let view = self.node_service.get_view(name)?;
+
+        self.zoom_root = view.root;
+        self.active_view = Some(view);
+        self.view_collapsed.clear();
+        self.refresh()?;
+
+        self.view()
+    }
+
+    /// Returns to the whole outline and the zoom it was left at.
+    pub fn close_view(&mut self) -> Result<GraphView, ServiceError> {
+        self.active_view = None;
+        self.zoom_root = self
+            .node_service
+            .get_setting(ZOOM_SETTING)?
+            .and_then(|value| Uuid::parse_str(&value).ok());
+        self.refresh()?;
+
+        self.view()
+    }
+
+    pub fn active_view(&self) -> Option<&SavedView> {
+        self.active_view.as_ref()
+    }
+
+    pub fn zoom_root(&self) -> Option<Uuid> {
+        self.zoom_root
+    }
+
+    /// Returns the outline as seen from the current zoom root.
+    pub fn view(&self) -> Result<GraphView, ServiceError> {
+        self.node_graph
+            .view(self.zoom_root)
+            .map_err(ServiceError::Domain)
+    }
+
+    /// Hoists `node_id` as the temporary root of the outline.
+    pub fn zoom_in(&mut self, node_id: Uuid) -> Result<GraphView, ServiceError> {
+        self.zoom_to(Some(node_id))
+    }
+
+    /// Hoists `root`, or shows the whole outline when `None`.
+    pub fn zoom_to(&mut self, root: Option<Uuid>) -> Result<GraphView, ServiceError> {
+        let view = self.node_graph.view(root).map_err(ServiceError::Domain)?;
+
+        self.set_zoom(root)?;
+
+        Ok(view)
+    }
+
+    /// Moves the zoom root up to its parent, or to the whole outline from a top level node.
+    pub fn zoom_out(&mut self) -> Result<GraphView, ServiceError> {
+        let parent = self
+            .zoom_root
+            .and_then(|root| self.node_graph.node(&root))
+            .and_then(|node| node.parent_id());
+
+        self.set_zoom(parent)?;
+
+        self.view()
+    }
+
+    /// Collapses an expanded element or expands a collapsed one, given its key
+    /// so that appearances of a node inside mirrors fold on their own.
+    pub fn toggle_collapsed(&mut self, key: Uuid) -> Result<GraphView, ServiceError> {
+        let collapsed = !self
+            .node_graph
+            .find(&key)
+            .ok_or(ServiceError::Domain(DomainError::MissingNode(key)))?
+            .collapsed;
+
+        self.persist_collapsed(&[key], collapsed)?;
+        self.node_graph
+            .set_collapsed(&key, collapsed)
+            .map_err(ServiceError::Domain)?;
+
+        self.view()
+    }
+
+    /// Folds the current view so that only `level` levels stay visible.
+    pub fn fold_to_level(&mut self, level: usize) -> Result<GraphView, ServiceError> {
+        let changes = self
+            .node_graph
+            .fold_to_level(self.zoom_root, level)
+            .map_err(ServiceError::Domain)?;
+
+        self.persist_collapsed(&changes.collapsed, true)?;
+        self.persist_collapsed(&changes.expanded, false)?;
+
+        self.view()
+    }
+
+    /// Expands every collapsed node in the current view.
+    pub fn unfold_all(&mut self) -> Result<GraphView, ServiceError> {
+        let expanded = self
+            .node_graph
+            .unfold_all(self.zoom_root)
+            .map_err(ServiceError::Domain)?;
+
+        self.persist_collapsed(&expanded, false)?;
+
+        self.view()
+    }
+
+    fn set_zoom(&mut self, root: Option<Uuid>) -> Result<(), ServiceError> {
+        // views are always opened at their own root, so their zoom is not kept
+        if self.active_view.is_none() {
+            let value = root.map(|id| id.to_string());
+
+            self.node_service
+                .set_setting(ZOOM_SETTING, value.as_deref())?;
+        }
+        self.zoom_root = root;
+
+        Ok(())
+    }
+
+    /// Stores fold changes made to the whole outline. Views start fully
+    /// expanded every time they are opened, so folds made inside them are
+    /// only remembered until the view is closed.
+    fn persist_collapsed(
+        &mut self,
+        node_ids: &[Uuid],
+        collapsed: bool,
+    ) -> Result<(), ServiceError> {
+        if self.active_view.is_some() {
+            for node_id in node_ids {
+                if collapsed {
+                    self.view_collapsed.insert(*node_id);
+                } else {
+                    self.view_collapsed.remove(node_id);
+                }
+            }
+
+            return Ok(());
+        }
+
+        self.node_service.set_collapsed(node_ids, collapsed)
+    }
 }
@@ -0,0 +1,524 @@
+use std::collections::HashSet;
+
+use uuid::Uuid;
+
+use crate::{
+    domain::{Node, Planning, Selection, Subtree, planning::PlanningKind, tag::normalize_tag},
+    interfaces::{InterfaceError, NodeRepository, import::ImportFormat},
+    services::{
+        bulk::DeletePolicy,
+        errors::ServiceError,
+        logging::LoggingService,
+        node_service::{NodeService, now},
+    },
+};
+
+/// How a merging import treats the outline.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MergeOptions {
+    /// Whether to delete the nodes under the import's parent that the imported
+    /// outline leaves out
+    pub delete_missing: bool,
+    /// Whether to only report the changes, leaving the outline as it is
+    pub dry_run: bool,
+}
+
+/// Changes made by a merging import, or that it would make on a dry run.
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    pub created: Vec<MergedNode>,
+    pub updated: Vec<MergedNode>,
+    pub moved: Vec<MergedNode>,
+    pub deleted: Vec<MergedNode>,
+}
+
+impl MergeReport {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.updated.is_empty()
+            && self.moved.is_empty()
+            && self.deleted.is_empty()
+    }
+}
+
+/// A node changed by a merging import, with the text it ends up with.
+#[derive(Debug)]
+pub struct MergedNode {
+    pub node_id: Uuid,
+    pub text: String,
+}
+
+/// Outlines written elsewhere, read into this one.
+impl<R, L> NodeService<R, L>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    /// Parses `text` as `format` and adds the outline it holds under
+    /// `parent_id` at `position`, returning its top-level nodes. Nodes whose
+    /// ID the text names, as Org documents do, are updated where they are when
+    /// they exist already, and added with that ID otherwise. Nothing changes
+    /// when any line fails to parse.
+    pub fn import_outline(
+        &self,
+        text: &str,
+        format: ImportFormat,
+        author: &str,
+        parent_id: Option<Uuid>,
+        position: usize,
+    ) -> Result<Vec<Node>, ServiceError> {
+        let roots = format
+            .importer()
+            .import(text, author)
+            .map_err(ServiceError::Interface)?;
+
+        let parent_id = self.resolve_parent(parent_id)?;
+        let imported = self.atomically(|| {
+            let mut existing = Vec::with_capacity(roots.len());
+            for root in &roots {
+                existing.push(self.existing_node(root.id)?);
+            }
+
+            let added = existing.iter().filter(|node| node.is_none()).count();
+            let mut ranks = self
+                .insertion_ranks(parent_id, position, added, &[])?
+                .into_iter();
+
+            let mut imported = Vec::with_capacity(roots.len());
+            for (root, node) in roots.iter().zip(existing) {
+                let node = match node {
+                    Some(node) => self.update_imported(node, root)?,
+                    // one rank was found for each root that does not exist yet
+                    None => self.insert_subtree_root(
+                        root,
+                        parent_id,
+                        ranks.next().unwrap_or_default(),
+                        true,
+                    )?,
+                };
+
+                self.import_children(root, &node)?;
+                imported.push(node);
+            }
+
+            Ok(imported)
+        })?;
+
+        self.logger.write_log(format!(
+            "{} | Imported {} nodes from {} under: {}",
+            now()?,
+            roots.iter().map(Subtree::node_count).sum::<usize>(),
+            format,
+            parent_id.map_or("root".to_owned(), |id| id.to_string())
+        ))?;
+
+        Ok(imported)
+    }
+
+    /// Parses `text` as `format` and makes the nodes under `parent_id` match
+    /// the outline it holds, going by node ID. Nodes the text does not give an
+    /// ID are created. Existing nodes are updated when their content differs
+    /// and moved when their place among the imported nodes does, while nodes
+    /// the text leaves out are kept unless `options` asks to delete them.
+    pub fn merge_import(
+        &self,
+        text: &str,
+        format: ImportFormat,
+        author: &str,
+        parent_id: Option<Uuid>,
+        options: MergeOptions,
+    ) -> Result<MergeReport, ServiceError> {
+        let mut roots = format
+            .importer()
+            .import(text, author)
+            .map_err(ServiceError::Interface)?;
+
+        // new nodes are given their ID now, so that a dry run can name them
+        let mut imported_ids = HashSet::new();
+        for root in &mut roots {
+            assign_ids(root, &mut imported_ids)?;
+        }
+
+        let parent_id = match parent_id {
+            Some(parent_id) => {
+                let parent = self.get_node(&parent_id)?;
+
+                // imported nodes end up below the parent, so none of them may be above it
+                let mut ancestry = vec![parent.id(), parent.canonical_id()];
+                ancestry.extend(self.ancestor_ids(&parent)?);
+                if let Some(node_id) = ancestry.iter().find(|id| imported_ids.contains(id)) {
+                    return Err(ServiceError::InvalidMove(*node_id));
+                }
+
+                Some(parent.canonical_id())
+            }
+            None => None,
+        };
+
+        let mut report = MergeReport::default();
+        self.plan_merge(&roots, parent_id, &mut report)?;
+        if options.delete_missing {
+            self.plan_deletes(parent_id, &imported_ids, &mut report)?;
+        }
+
+        if options.dry_run || report.is_empty() {
+            return Ok(report);
+        }
+
+        let ids = |nodes: &[MergedNode]| -> HashSet<Uuid> {
+            nodes.iter().map(|node| node.node_id).collect()
+        };
+        let plan = MergePlan {
+            created: ids(&report.created),
+            updated: ids(&report.updated),
+            moved: ids(&report.moved),
+        };
+
+        self.atomically(|| {
+            self.apply_merge(&roots, parent_id, &plan)?;
+
+            // moves come first, taking the imported nodes out of the subtrees being deleted
+            if !report.deleted.is_empty() {
+                let deleted = report.deleted.iter().map(|node| node.node_id).collect();
+                self.bulk_delete(&Selection::Set(deleted), DeletePolicy::Cascade)?;
+            }
+
+            Ok(())
+        })?;
+
+        self.logger.write_log(format!(
+            "{} | Merged import from {}: {} created, {} updated, {} moved, {} deleted",
+            now()?,
+            format,
+            report.created.len(),
+            report.updated.len(),
+            report.moved.len(),
+            report.deleted.len()
+        ))?;
+
+        Ok(report)
+    }
+
+    /// Records the changes that would make the children of `parent_id` match
+    /// `subtrees`. The nodes already in the right order among them stay put,
+    /// and the others are moved.
+    fn plan_merge(
+        &self,
+        subtrees: &[Subtree],
+        parent_id: Option<Uuid>,
+        report: &mut MergeReport,
+    ) -> Result<(), ServiceError> {
+        let current: Vec<Uuid> = self
+            .repository
+            .get_children(parent_id.as_ref())
+            .map_err(ServiceError::Interface)?
+            .iter()
+            .map(|node| node.id())
+            .collect();
+
+        let positions: Vec<Option<usize>> = subtrees
+            .iter()
+            .map(|subtree| current.iter().position(|id| Some(*id) == subtree.id))
+            .collect();
+        let in_order = longest_increasing(&positions);
+
+        for (index, subtree) in subtrees.iter().enumerate() {
+            let Some(node_id) = subtree.id else {
+                continue;
+            };
+            let merged = || MergedNode {
+                node_id,
+                text: subtree.text.clone(),
+            };
+
+            let children_parent = match self.existing_node(Some(node_id))? {
+                Some(node) => {
+                    if self.content_differs(&node, subtree)? {
+                        report.updated.push(merged());
+                    }
+                    if !in_order.contains(&index) {
+                        report.moved.push(merged());
+                    }
+                    node.canonical_id()
+                }
+                None => {
+                    report.created.push(merged());
+                    node_id
+                }
+            };
+
+            self.plan_merge(&subtree.children, Some(children_parent), report)?;
+        }
+
+        Ok(())
+    }
+
+    /// Records the deletion of every node below `parent_id` missing from `imported_ids`.
+    fn plan_deletes(
+        &self,
+        parent_id: Option<Uuid>,
+        imported_ids: &HashSet<Uuid>,
+        report: &mut MergeReport,
+    ) -> Result<(), ServiceError> {
+        let children = self
+            .repository
+            .get_children(parent_id.as_ref())
+            .map_err(ServiceError::Interface)?;
+
+        for child in children {
+            if !imported_ids.contains(&child.id()) {
+                report.deleted.push(MergedNode {
+                    node_id: child.id(),
+                    text: child.text().to_owned(),
+                });
+            }
+
+            self.plan_deletes(Some(child.id()), imported_ids, report)?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates, updates and moves the nodes of `subtrees` as `plan` says,
+    /// working down from the top so that every parent is in place before its
+    /// children are placed under it.
+    fn apply_merge(
+        &self,
+        subtrees: &[Subtree],
+        parent_id: Option<Uuid>,
+        plan: &MergePlan,
+    ) -> Result<(), ServiceError> {
+        let siblings: HashSet<Uuid> = subtrees.iter().filter_map(|subtree| subtree.id).collect();
+        let mut previous = None;
+
+        for subtree in subtrees {
+            let Some(node_id) = subtree.id else {
+                continue;
+            };
+
+            let node = if plan.created.contains(&node_id) {
+                let position = self.merge_position(parent_id, previous, &siblings, node_id)?;
+                let ranks = self.insertion_ranks(parent_id, position, 1, &[])?;
+                let rank = ranks.first().copied().unwrap_or_default();
+                self.insert_subtree_root(subtree, parent_id, rank, true)?
+            } else {
+                let mut node = self.get_node(&node_id)?;
+                if plan.updated.contains(&node_id) {
+                    node = self.update_imported(node, subtree)?;
+                }
+                if plan.moved.contains(&node_id) {
+                    let position = self.merge_position(parent_id, previous, &siblings, node_id)?;
+                    self.move_block(&[node_id], parent_id, position)?;
+                }
+                node
+            };
+
+            self.apply_merge(&subtree.children, Some(node.canonical_id()), plan)?;
+            previous = Some(node_id);
+        }
+
+        Ok(())
+    }
+
+    /// Position under `parent_id` for `node_id`, counted without it: right
+    /// after the imported sibling before it, or before the first imported
+    /// sibling already there when it comes first.
+    fn merge_position(
+        &self,
+        parent_id: Option<Uuid>,
+        previous: Option<Uuid>,
+        siblings: &HashSet<Uuid>,
+        node_id: Uuid,
+    ) -> Result<usize, ServiceError> {
+        let children: Vec<Uuid> = self
+            .repository
+            .get_children(parent_id.as_ref())
+            .map_err(ServiceError::Interface)?
+            .iter()
+            .map(|node| node.id())
+            .filter(|id| *id != node_id)
+            .collect();
+
+        let position = match previous {
+            Some(previous) => children
+                .iter()
+                .position(|id| *id == previous)
+                .map(|index| index + 1),
+            None => children.iter().position(|id| siblings.contains(id)),
+        };
+
+        Ok(position.unwrap_or(children.len()))
+    }
+
+    fn content_differs(&self, node: &Node, subtree: &Subtree) -> Result<bool, ServiceError> {
+        if node.text() != subtree.text
+            || node.note() != subtree.note
+            || *node.node_type() != subtree.node_type
+        {
+            return Ok(true);
+        }
+
+        let mut tags = Vec::with_capacity(subtree.tags.len());
+        for tag in &subtree.tags {
+            tags.push(normalize_tag(tag).map_err(ServiceError::Domain)?);
+        }
+        tags.sort();
+        tags.dedup();
+
+        let mut current = self.get_tags(&node.canonical_id())?;
+        current.sort();
+
+        Ok(tags != current || planning_of(subtree) != self.get_planning(&node.canonical_id())?)
+    }
+
+    /// Imports the children of `subtree`, adding the new ones after the
+    /// existing children of `node`.
+    fn import_children(&self, subtree: &Subtree, node: &Node) -> Result<(), ServiceError> {
+        for child in &subtree.children {
+            let child_node = match self.existing_node(child.id)? {
+                Some(existing) => self.update_imported(existing, child)?,
+                None => {
+                    let parent_id = Some(node.canonical_id());
+                    let ranks = self.insertion_ranks(parent_id, usize::MAX, 1, &[])?;
+                    let rank = ranks.first().copied().unwrap_or_default();
+                    self.insert_subtree_root(child, parent_id, rank, true)?
+                }
+            };
+
+            self.import_children(child, &child_node)?;
+        }
+
+        Ok(())
+    }
+
+    fn existing_node(&self, node_id: Option<Uuid>) -> Result<Option<Node>, ServiceError> {
+        let Some(node_id) = node_id else {
+            return Ok(None);
+        };
+
+        match self.repository.get_node(&node_id) {
+            Ok(node) => Ok(Some(node)),
+            Err(InterfaceError::MissingNode) => Ok(None),
+            Err(err) => Err(ServiceError::Interface(err)),
+        }
+    }
+
+    /// Brings the content, tags and planned dates of `node` in line with
+    /// `subtree`, leaving it where it is.
+    fn update_imported(&self, mut node: Node, subtree: &Subtree) -> Result<Node, ServiceError> {
+        if node.text() != subtree.text {
+            self.update_node(&mut node, &subtree.text)?;
+        }
+
+        if node.note() != subtree.note {
+            self.update_note(&mut node, &subtree.note)?;
+        }
+
+        if *node.node_type() != subtree.node_type {
+            self.apply_node_type(&mut node, subtree.node_type.clone())?;
+        }
+
+        let mut tags = Vec::with_capacity(subtree.tags.len());
+        for tag in &subtree.tags {
+            tags.push(normalize_tag(tag).map_err(ServiceError::Domain)?);
+        }
+
+        let canonical_id = node.canonical_id();
+        for tag in self.get_tags(&canonical_id)? {
+            if !tags.contains(&tag) {
+                self.repository
+                    .remove_tag(&[canonical_id], &tag)
+                    .map_err(ServiceError::Interface)?;
+            }
+        }
+
+        for tag in &tags {
+            self.repository
+                .add_tag(&[canonical_id], tag)
+                .map_err(ServiceError::Interface)?;
+        }
+
+        let planning = planning_of(subtree);
+        for kind in [PlanningKind::Scheduled, PlanningKind::Deadline] {
+            let time = planning
+                .iter()
+                .find(|planning| planning.kind == kind)
+                .map(|planning| planning.time);
+
+            self.repository
+                .set_planning(&canonical_id, kind, time)
+                .map_err(ServiceError::Interface)?;
+        }
+
+        Ok(node)
+    }
+}
+
+/// Node IDs a merge creates, updates and moves.
+struct MergePlan {
+    created: HashSet<Uuid>,
+    updated: HashSet<Uuid>,
+    moved: HashSet<Uuid>,
+}
+
+/// Planned dates of `subtree` ordered by kind, keeping the last of each kind
+/// as storing them one after another would.
+fn planning_of(subtree: &Subtree) -> Vec<Planning> {
+    let mut planning: Vec<Planning> = Vec::new();
+
+    for entry in subtree.planning.iter().rev() {
+        if !planning.iter().any(|kept| kept.kind == entry.kind) {
+            planning.push(entry.clone());
+        }
+    }
+
+    planning.sort_by_key(|planning| planning.kind);
+    planning
+}
+
+/// Gives a new ID to every node of `subtree` without one, collecting the IDs
+/// into `ids` and failing on any seen twice.
+fn assign_ids(subtree: &mut Subtree, ids: &mut HashSet<Uuid>) -> Result<(), ServiceError> {
+    let node_id = *subtree.id.get_or_insert_with(Uuid::new_v4);
+    if !ids.insert(node_id) {
+        return Err(ServiceError::DuplicateImportedNode(node_id));
+    }
+
+    for child in &mut subtree.children {
+        assign_ids(child, ids)?;
+    }
+
+    Ok(())
+}
+
+/// Indexes into `positions` of the longest run of positions that only grow,
+/// skipping the missing ones. Siblings at these indexes are already in the
+/// order asked for.
+fn longest_increasing(positions: &[Option<usize>]) -> HashSet<usize> {
+    // tails[length - 1] is the index ending the best run of that length found so far
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; positions.len()];
+
+    for (index, position) in positions.iter().enumerate() {
+        let Some(position) = position else {
+            continue;
+        };
+
+        let length = tails.partition_point(|&tail| positions[tail] < Some(*position));
+        previous[index] = length.checked_sub(1).map(|before| tails[before]);
+
+        match tails.get_mut(length) {
+            Some(tail) => *tail = index,
+            None => tails.push(index),
+        }
+    }
+
+    let mut in_order = HashSet::new();
+    let mut next = tails.last().copied();
+    while let Some(index) = next {
+        in_order.insert(index);
+        next = previous[index];
+    }
+
+    in_order
+}
@@ -0,0 +1,197 @@
+use hifitime::Epoch;
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        Node,
+        journal::{JOURNAL_TITLE, entry_path},
+        node::{CreateNodeRequest, NodeType, Source},
+    },
+    interfaces::{InterfaceError, NodeRepository},
+    services::{
+        errors::ServiceError,
+        logging::LoggingService,
+        node_service::{NodeService, now},
+    },
+};
+
+/// Setting holding the ID of the node the journal lives under.
+const JOURNAL_SETTING: &str = "journal_root";
+
+/// The day node of a journal entry, and what it took to get there.
+#[derive(Debug)]
+pub struct JournalEntry {
+    pub node: Node,
+    /// Whether the entry did not exist before
+    pub created: bool,
+    /// Number of unfinished tasks taken over from the previous entry
+    pub rolled_over: usize,
+}
+
+/// The journal, a `Journal > year > month > day` hierarchy whose nodes are
+/// created the first time they are needed.
+impl<R, L> NodeService<R, L>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    /// Entry for the day of `date`, created along with any missing year and
+    /// month nodes on behalf of `author`. With `rollover`, a new entry takes
+    /// over the unfinished tasks of the latest entry before it, together with
+    /// their subtrees.
+    pub fn journal_entry(
+        &self,
+        date: Epoch,
+        author: &str,
+        rollover: bool,
+    ) -> Result<JournalEntry, ServiceError> {
+        let path = entry_path(date);
+
+        let entry = self.atomically(|| {
+            let root = self.journal_root(author)?;
+            let year = self.child_entry(&root, &path[0], author)?.0;
+            let month = self.child_entry(&year, &path[1], author)?.0;
+            let (node, created) = self.child_entry(&month, &path[2], author)?;
+
+            let previous = match created && rollover {
+                true => self.previous_entry(&root, &path[2])?,
+                false => None,
+            };
+
+            let rolled_over = match previous {
+                Some(previous) => {
+                    let mut tasks = Vec::new();
+                    self.unfinished_tasks(&previous, &mut tasks)?;
+                    self.move_block(&tasks, Some(node.id()), usize::MAX)?;
+                    tasks.len()
+                }
+                None => 0,
+            };
+
+            Ok(JournalEntry {
+                node,
+                created,
+                rolled_over,
+            })
+        })?;
+
+        if entry.rolled_over > 0 {
+            self.logger.write_log(format!(
+                "{} | Rolled over {} tasks into journal entry: {}",
+                now()?,
+                entry.rolled_over,
+                entry.node.id_str()
+            ))?;
+        }
+
+        Ok(entry)
+    }
+
+    /// The node the journal lives under: the one remembered in the settings,
+    /// else a top level node titled `Journal`, else a new one.
+    fn journal_root(&self, author: &str) -> Result<Node, ServiceError> {
+        let remembered = self
+            .get_setting(JOURNAL_SETTING)?
+            .and_then(|id| Uuid::parse_str(&id).ok());
+
+        if let Some(root_id) = remembered {
+            match self.repository.get_node(&root_id) {
+                Ok(root) => return Ok(root),
+                Err(InterfaceError::MissingNode) => {}
+                Err(err) => return Err(ServiceError::Interface(err)),
+            }
+        }
+
+        let existing = self
+            .repository
+            .get_children(None)
+            .map_err(ServiceError::Interface)?
+            .into_iter()
+            .find(|node| node.text() == JOURNAL_TITLE);
+
+        let root = match existing {
+            Some(root) => root,
+            None => {
+                self.create_node_at(journal_request(None, JOURNAL_TITLE, author), usize::MAX)?
+            }
+        };
+
+        self.set_setting(JOURNAL_SETTING, Some(&root.id_str()))?;
+
+        Ok(root)
+    }
+
+    /// The child of `parent` titled `text`, created in date order when there
+    /// is none. Also returns whether it was created.
+    fn child_entry(
+        &self,
+        parent: &Node,
+        text: &str,
+        author: &str,
+    ) -> Result<(Node, bool), ServiceError> {
+        let children = self
+            .repository
+            .get_children(Some(&parent.canonical_id()))
+            .map_err(ServiceError::Interface)?;
+
+        if let Some(child) = children.iter().find(|child| child.text() == text) {
+            return Ok((child.clone(), false));
+        }
+
+        let position = children.iter().filter(|child| child.text() < text).count();
+        let request = journal_request(Some(parent.canonical_id()), text, author);
+
+        Ok((self.create_node_at(request, position)?, true))
+    }
+
+    /// The latest day entry before the one titled `day`.
+    fn previous_entry(&self, root: &Node, day: &str) -> Result<Option<Node>, ServiceError> {
+        let mut previous: Option<Node> = None;
+
+        for year in self.children_of(root)? {
+            for month in self.children_of(&year)? {
+                for entry in self.children_of(&month)? {
+                    let later = previous
+                        .as_ref()
+                        .is_none_or(|previous| entry.text() > previous.text());
+
+                    if entry.text() < day && later {
+                        previous = Some(entry);
+                    }
+                }
+            }
+        }
+
+        Ok(previous)
+    }
+
+    /// Collects the highest unfinished tasks below `node`. Tasks nested in an
+    /// unfinished task go along with it.
+    fn unfinished_tasks(&self, node: &Node, tasks: &mut Vec<Uuid>) -> Result<(), ServiceError> {
+        for child in self.children_of(node)? {
+            match child.node_type() {
+                NodeType::Todo | NodeType::InProgress => tasks.push(child.id()),
+                NodeType::Standard | NodeType::Done => self.unfinished_tasks(&child, tasks)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn children_of(&self, node: &Node) -> Result<Vec<Node>, ServiceError> {
+        self.repository
+            .get_children(Some(&node.canonical_id()))
+            .map_err(ServiceError::Interface)
+    }
+}
+
+fn journal_request(parent_id: Option<Uuid>, text: &str, author: &str) -> CreateNodeRequest {
+    CreateNodeRequest::new(
+        parent_id,
+        0,
+        NodeType::Standard,
+        text,
+        author,
+        Source::Application,
+    )
+}
@@ -1,9 +1,19 @@
+use std::{fs::OpenOptions, io::Write, path::PathBuf};
+
 use crate::services::errors::ServiceError;
 
 pub trait LoggingService {
     fn write_log(&self, message: String) -> Result<(), ServiceError>;
 }
 
+/// Lets the logger be picked at runtime, such as from the configuration.
+impl<T: LoggingService + ?Sized> LoggingService for Box<T> {
+    fn write_log(&self, message: String) -> Result<(), ServiceError> {
+        (**self).write_log(message)
+    }
+}
+
+#[derive(Default)]
 pub struct TerminalLogging {}
 
 impl TerminalLogging {
@@ -19,3 +29,42 @@ impl LoggingService for TerminalLogging {
         Ok(())
     }
 }
+
+/// Discards all log messages, for front ends that own the terminal.
+#[derive(Default)]
+pub struct NullLogging {}
+
+impl NullLogging {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl LoggingService for NullLogging {
+    fn write_log(&self, _message: String) -> Result<(), ServiceError> {
+        Ok(())
+    }
+}
+
+/// Appends log messages to a file, one per line.
+pub struct FileLogging {
+    path: PathBuf,
+}
+
+impl FileLogging {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl LoggingService for FileLogging {
+    fn write_log(&self, message: String) -> Result<(), ServiceError> {
+        // opened for every message, so the file can be rotated or removed at any time
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{message}"))
+            .map_err(|_| ServiceError::LogWrite)
+    }
+}
@@ -1,4 +1,12 @@
+pub mod bulk;
+pub mod collab;
 pub mod errors;
+pub mod events;
 pub mod graph_service;
+pub mod import;
+pub mod journal;
 pub mod logging;
 pub mod node_service;
+pub mod sync;
+pub mod templates;
+pub mod views;
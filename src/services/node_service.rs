@@ -1,11 +1,41 @@
 use hifitime::Epoch;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
+use uuid::Uuid;
 
 use crate::{
-    domain::{Node, node::CreateNodeRequest},
-    interfaces::NodeRepository,
-    services::{errors::ServiceError, logging::LoggingService},
+    domain::{
+        Attachment, Clipboard, DomainError, Node, NodeGraph, Planning, Query, Revision, Subtree,
+        attachment::{AttachmentContent, SNIFF_LENGTH, content_hash, sniff_mime_type},
+        links::parse_links,
+        node::{CreateNodeRequest, NodeType, Source},
+        rank::{ranks_between, spread_ranks},
+        subtree::ClipboardMode,
+        tag::normalize_tag,
+    },
+    interfaces::{
+        InterfaceError, NodeRepository,
+        export::{self, ExportFormat, ExportOptions},
+    },
+    services::{
+        collab::ReplicaCache,
+        errors::ServiceError,
+        events::{self, NodeEvent, Notifier},
+        logging::LoggingService,
+    },
 };
 
+/// A node whose text links to nodes that no longer exist.
+#[derive(Debug)]
+pub struct BrokenLink {
+    pub source: Node,
+    pub target_ids: Vec<Uuid>,
+}
+
 pub struct NodeService<R, L>
 where
     R: NodeRepository,
@@ -13,6 +43,15 @@ where
 {
     pub repository: R,
     pub logger: L,
+    /// Replica of the outline, through which changes are logged for other replicas
+    pub replica: ReplicaCache,
+    /// Announces the changes made to the outline, here and by other processes
+    pub notifier: Notifier,
+    /// Largest file, in bytes, stored inside the outline. Files already
+    /// stored are kept whatever their size.
+    pub attachment_limit: u64,
+    /// Spacing left between the ranks of consecutive siblings
+    pub rank_gap: u64,
 }
 
 impl<R, L> NodeService<R, L>
@@ -20,12 +59,12 @@ where
     R: NodeRepository,
     L: LoggingService,
 {
-    pub fn create_node(&self, request: CreateNodeRequest) -> Result<Node, ServiceError> {
+    pub fn create_node(&self, mut request: CreateNodeRequest) -> Result<Node, ServiceError> {
+        request.parent_id = self.resolve_parent(request.parent_id)?;
+
         let node = Node::new(request).map_err(ServiceError::Domain)?;
 
-        self.repository
-            .add_node(&node)
-            .map_err(ServiceError::Interface)?;
+        self.insert_node(&node)?;
 
         self.logger.write_log(format!(
             "{} | Created node: {}",
@@ -36,15 +75,160 @@ where
         Ok(node)
     }
 
+    /// Creates a node at sibling `position` under the request's parent, in place
+    /// of the request's rank. Siblings are respaced when there is no room.
+    pub fn create_node_at(
+        &self,
+        mut request: CreateNodeRequest,
+        position: usize,
+    ) -> Result<Node, ServiceError> {
+        self.atomically(|| {
+            request.parent_id = self.resolve_parent(request.parent_id)?;
+            request.rank = self.insertion_ranks(request.parent_id, position, 1, &[])?[0];
+
+            self.create_node(request)
+        })
+    }
+
+    pub fn get_node(&self, node_id: &Uuid) -> Result<Node, ServiceError> {
+        self.repository
+            .get_node(node_id)
+            .map_err(ServiceError::Interface)
+    }
+
+    /// Creates a mirror of `canonical_id` at sibling `position` under
+    /// `parent_id`. Mirroring a mirror mirrors its canonical node instead, and
+    /// a mirror given as the parent stands for its canonical node.
+    pub fn create_mirror(
+        &self,
+        canonical_id: Uuid,
+        parent_id: Option<Uuid>,
+        position: usize,
+        author: &str,
+        source_type: Source,
+    ) -> Result<Node, ServiceError> {
+        let node = self.atomically(|| {
+            let canonical = self
+                .repository
+                .get_node(&canonical_id)
+                .map_err(ServiceError::Interface)?;
+
+            let parent_id = self.resolve_parent(parent_id)?;
+            if let Some(parent_id) = parent_id {
+                self.repository
+                    .get_node(&parent_id)
+                    .map_err(ServiceError::Interface)?;
+            }
+
+            let mut request = CreateNodeRequest::new(
+                parent_id,
+                self.insertion_ranks(parent_id, position, 1, &[])?[0],
+                canonical.node_type().clone(),
+                canonical.text(),
+                author,
+                source_type,
+            );
+            request.note = canonical.note().to_owned();
+            request.mirror_of = Some(canonical.canonical_id());
+
+            let node = Node::new(request).map_err(ServiceError::Domain)?;
+            self.insert_node(&node)?;
+
+            Ok(node)
+        })?;
+
+        self.logger.write_log(format!(
+            "{} | Created mirror node: {} of {}",
+            node.created_time_str(),
+            node.id_str(),
+            node.canonical_id()
+        ))?;
+
+        Ok(node)
+    }
+
+    /// Updates the text of `node` and of every other appearance of its content,
+    /// keeping the replaced content as a revision.
     pub fn update_node(&self, node: &mut Node, new_text: &str) -> Result<(), ServiceError> {
+        self.edit_text(node, new_text, None)
+    }
+
+    /// Like [`Self::update_node`], recording `author` and `source` as the editor
+    /// on the revision and in the log.
+    pub fn update_node_by(
+        &self,
+        node: &mut Node,
+        new_text: &str,
+        author: &str,
+        source: Source,
+    ) -> Result<(), ServiceError> {
+        self.edit_text(node, new_text, Some((author, source)))
+    }
+
+    fn edit_text(
+        &self,
+        node: &mut Node,
+        new_text: &str,
+        editor: Option<(&str, Source)>,
+    ) -> Result<(), ServiceError> {
+        let mut revision = self.revision_of(node)?;
+        if let Some((author, source)) = &editor {
+            revision.author = Some(author.to_string());
+            revision.source_type = Some(source.clone());
+        }
         node.update(new_text).map_err(ServiceError::Domain)?;
 
-        self.repository
-            .update_node(node)
-            .map_err(ServiceError::Interface)?;
+        self.atomically(|| {
+            self.add_revision(revision, node)?;
+
+            self.store_node(node)?;
+
+            for mut appearance in self.other_appearances(node)? {
+                appearance.update(new_text).map_err(ServiceError::Domain)?;
+
+                self.store_node(&appearance)?;
+            }
+
+            // links belong to the shared content, so they are recorded against the canonical node
+            self.repository
+                .set_links(&node.canonical_id(), &parse_links(new_text))
+                .map_err(ServiceError::Interface)
+        })?;
+
+        self.logger.write_log(format!(
+            "{} | Updated node: {}{}",
+            node.modified_time_str(),
+            node.id_str(),
+            edited_by(editor)
+        ))?;
+
+        Ok(())
+    }
+
+    /// Replaces the note of `node` and of every other appearance of its content,
+    /// keeping the replaced content as a revision.
+    pub fn update_note(&self, node: &mut Node, new_note: &str) -> Result<(), ServiceError> {
+        let revision = self.revision_of(node)?;
+        node.set_note(new_note).map_err(ServiceError::Domain)?;
+
+        self.atomically(|| {
+            self.add_revision(revision, node)?;
+
+            self.store_node(node)?;
+
+            for mut appearance in self.other_appearances(node)? {
+                appearance
+                    .set_note(new_note)
+                    .map_err(ServiceError::Domain)?;
+
+                self.store_node(&appearance)?;
+            }
+
+            Ok(())
+        })?;
 
         self.logger.write_log(format!(
-            "{} | Updated node: {}",
+            "{} | Updated note of node: {}",
             node.modified_time_str(),
             node.id_str()
         ))?;
@@ -52,13 +236,264 @@ where
         Ok(())
     }
 
-    pub fn delete_node(&self, node: Node) -> Result<(), ServiceError> {
-        // todo: figure out how to handle deleting a node when it contains children
-        // additionally, if deleted node has a sibling, update the next node's previous_id
+    /// Earlier content of `node_id`, oldest first. Mirrors share the history
+    /// of their canonical node.
+    pub fn revisions(&self, node_id: &Uuid) -> Result<Vec<Revision>, ServiceError> {
+        let node = self.get_node(node_id)?;
+
         self.repository
-            .delete_node(&node.id())
+            .get_revisions(&node.canonical_id())
+            .map_err(ServiceError::Interface)
+    }
+
+    /// The current content of `node`, to keep should an edit replace it.
+    fn revision_of(&self, node: &Node) -> Result<Revision, ServiceError> {
+        Ok(Revision {
+            node_id: node.canonical_id(),
+            replaced_time: now()?,
+            text: node.text().to_owned(),
+            note: node.note().to_owned(),
+            author: None,
+            source_type: None,
+        })
+    }
+
+    /// Stores `revision` unless the edit that produced `node` left its content as it was.
+    fn add_revision(&self, revision: Revision, node: &Node) -> Result<(), ServiceError> {
+        if revision.text == node.text() && revision.note == node.note() {
+            return Ok(());
+        }
+
+        self.repository
+            .add_revision(&revision)
+            .map_err(ServiceError::Interface)
+    }
+
+    /// Fails when a file of `size` bytes is too large to store inside the
+    /// outline, so callers can check before reading it.
+    pub fn check_attachment_size(&self, size: u64) -> Result<(), ServiceError> {
+        if size > self.attachment_limit {
+            return Err(ServiceError::AttachmentTooLarge {
+                size,
+                limit: self.attachment_limit,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Stores a copy of `data` inside the outline as an attachment of `node_id`
+    /// called `name`. Mirrors share the attachments of their canonical node.
+    pub fn attach(
+        &self,
+        node_id: &Uuid,
+        name: &str,
+        data: &[u8],
+    ) -> Result<Attachment, ServiceError> {
+        let size = data.len() as u64;
+        self.check_attachment_size(size)?;
+
+        let node = self.get_node(node_id)?;
+        let hash = content_hash(data);
+
+        let attachment = Attachment {
+            id: Uuid::new_v4(),
+            node_id: node.canonical_id(),
+            name: name.to_owned(),
+            mime_type: sniff_mime_type(data, name).to_owned(),
+            size,
+            content: AttachmentContent::Blob(hash.clone()),
+            added_time: now()?,
+        };
+
+        self.atomically(|| {
+            self.repository
+                .add_blob(&hash, data)
+                .map_err(ServiceError::Interface)?;
+
+            self.repository
+                .add_attachment(&attachment)
+                .map_err(ServiceError::Interface)
+        })?;
+
+        self.logger.write_log(format!(
+            "{} | Attached file: {} to node: {}",
+            attachment.added_time,
+            attachment.id,
+            node.id_str()
+        ))?;
+
+        Ok(attachment)
+    }
+
+    /// Attaches the file at `path` to `node_id` by reference, leaving it where it
+    /// is. The size limit does not apply, as nothing is copied into the outline.
+    pub fn attach_path(&self, node_id: &Uuid, path: &Path) -> Result<Attachment, ServiceError> {
+        let unreadable = || ServiceError::AttachedFile(path.display().to_string());
+
+        // the reference has to keep working from any directory
+        let path = path.canonicalize().map_err(|_| unreadable())?;
+        let file = File::open(&path).map_err(|_| unreadable())?;
+        let size = file.metadata().map_err(|_| unreadable())?.len();
+
+        let mut head = Vec::new();
+        file.take(SNIFF_LENGTH as u64)
+            .read_to_end(&mut head)
+            .map_err(|_| unreadable())?;
+
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+
+        let node = self.get_node(node_id)?;
+
+        let attachment = Attachment {
+            id: Uuid::new_v4(),
+            node_id: node.canonical_id(),
+            mime_type: sniff_mime_type(&head, &name).to_owned(),
+            name,
+            size,
+            content: AttachmentContent::Path(path),
+            added_time: now()?,
+        };
+
+        self.repository
+            .add_attachment(&attachment)
+            .map_err(ServiceError::Interface)?;
+
+        self.logger.write_log(format!(
+            "{} | Attached file reference: {} to node: {}",
+            attachment.added_time,
+            attachment.id,
+            node.id_str()
+        ))?;
+
+        Ok(attachment)
+    }
+
+    /// Attachments of `node_id`, in the order they were added.
+    pub fn attachments(&self, node_id: &Uuid) -> Result<Vec<Attachment>, ServiceError> {
+        let node = self.get_node(node_id)?;
+
+        self.repository
+            .get_attachments(&node.canonical_id())
+            .map_err(ServiceError::Interface)
+    }
+
+    pub fn get_attachment(&self, attachment_id: &Uuid) -> Result<Attachment, ServiceError> {
+        self.repository
+            .get_attachment(attachment_id)
+            .map_err(ServiceError::Interface)?
+            .ok_or(ServiceError::MissingAttachment(*attachment_id))
+    }
+
+    /// Contents of `attachment_id`, read from the outline, or from disk when the
+    /// file is only referred to.
+    pub fn extract(&self, attachment_id: &Uuid) -> Result<Vec<u8>, ServiceError> {
+        let attachment = self.get_attachment(attachment_id)?;
+
+        match &attachment.content {
+            AttachmentContent::Blob(hash) => self
+                .repository
+                .get_blob(hash)
+                .map_err(ServiceError::Interface)?
+                .ok_or(ServiceError::Interface(InterfaceError::MissingBlob)),
+            AttachmentContent::Path(path) => {
+                fs::read(path).map_err(|_| ServiceError::AttachedFile(path.display().to_string()))
+            }
+        }
+    }
+
+    /// Removes `attachment_id` from its node. A stored copy is deleted once no
+    /// other attachment uses it, while a referenced file is left on disk.
+    pub fn detach(&self, attachment_id: &Uuid) -> Result<Attachment, ServiceError> {
+        let attachment = self.get_attachment(attachment_id)?;
+
+        self.repository
+            .delete_attachment(attachment_id)
             .map_err(ServiceError::Interface)?;
 
+        self.logger.write_log(format!(
+            "{} | Detached file: {} from node: {}",
+            now()?,
+            attachment.id,
+            attachment.node_id
+        ))?;
+
+        Ok(attachment)
+    }
+
+    /// Changes the type of `node` and of every other appearance of its content.
+    pub fn set_node_type(&self, node: &mut Node, node_type: NodeType) -> Result<(), ServiceError> {
+        self.change_node_type(node, node_type, None)
+    }
+
+    /// Like [`Self::set_node_type`], recording `author` and `source` as the
+    /// editor in the log.
+    pub fn set_node_type_by(
+        &self,
+        node: &mut Node,
+        node_type: NodeType,
+        author: &str,
+        source: Source,
+    ) -> Result<(), ServiceError> {
+        self.change_node_type(node, node_type, Some((author, source)))
+    }
+
+    fn change_node_type(
+        &self,
+        node: &mut Node,
+        node_type: NodeType,
+        editor: Option<(&str, Source)>,
+    ) -> Result<(), ServiceError> {
+        self.atomically(|| self.apply_node_type(node, node_type))?;
+
+        self.logger.write_log(format!(
+            "{} | Set node type: {} to {}{}",
+            node.modified_time_str(),
+            node.id_str(),
+            node.node_type_str(),
+            edited_by(editor)
+        ))?;
+
+        Ok(())
+    }
+
+    pub(crate) fn apply_node_type(
+        &self,
+        node: &mut Node,
+        node_type: NodeType,
+    ) -> Result<(), ServiceError> {
+        node.set_node_type(node_type.clone())
+            .map_err(ServiceError::Domain)?;
+
+        self.store_node(node)?;
+
+        for mut appearance in self.other_appearances(node)? {
+            appearance
+                .set_node_type(node_type.clone())
+                .map_err(ServiceError::Domain)?;
+
+            self.store_node(&appearance)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes `node`. Deleting a mirror only removes that appearance, while deleting
+    /// a mirrored canonical node promotes its oldest mirror outside of its own subtree
+    /// to be the new canonical node and hands it the children and attachments. Mirrors
+    /// that cannot be promoted become standalone copies. Otherwise the attachments
+    /// of a deleted node are deleted with it, leaving referenced files on disk.
+    ///
+    /// Only `node` itself is deleted. The children of a node that is not
+    /// mirrored have to be deleted or moved first, as
+    /// [`bulk_delete`](Self::bulk_delete) does under its
+    /// [`DeletePolicy`](crate::services::bulk::DeletePolicy). Siblings keep
+    /// their ranks, which stay in order without the node.
+    pub fn delete_node(&self, node: Node) -> Result<(), ServiceError> {
+        self.atomically(|| self.remove_node(&node, &HashSet::new()))?;
+
         self.logger.write_log(format!(
             "{} | Deleted node: {}",
             Epoch::now().unwrap(),
@@ -68,9 +503,714 @@ where
         Ok(())
     }
 
+    /// Deletes a single node, promoting a mirror when it is a canonical node.
+    /// Mirrors in `removed` are about to be deleted as well and are never promoted.
+    pub(crate) fn remove_node(
+        &self,
+        node: &Node,
+        removed: &HashSet<Uuid>,
+    ) -> Result<(), ServiceError> {
+        if node.mirror_of().is_none() {
+            self.promote_mirror(node, removed)?;
+        }
+
+        self.repository
+            .delete_node(&node.id())
+            .map_err(ServiceError::Interface)?;
+
+        self.record(&[node.id()])?;
+        self.notifier
+            .notify(vec![NodeEvent::NodeDeleted(node.id())]);
+
+        Ok(())
+    }
+
+    fn promote_mirror(
+        &self,
+        canonical: &Node,
+        removed: &HashSet<Uuid>,
+    ) -> Result<(), ServiceError> {
+        let mut mirrors = self
+            .repository
+            .get_mirrors(&canonical.id())
+            .map_err(ServiceError::Interface)?;
+
+        mirrors.retain(|mirror| !removed.contains(&mirror.id()));
+        mirrors.sort_by_key(|mirror| mirror.created_time());
+
+        // a mirror nested below the canonical node would become its own ancestor
+        let mut promoted = None;
+        for (index, mirror) in mirrors.iter().enumerate() {
+            if !self.ancestor_ids(mirror)?.contains(&canonical.id()) {
+                promoted = Some(mirrors.remove(index));
+                break;
+            }
+        }
+
+        let promoted_id = match promoted {
+            Some(mut promoted) => {
+                promoted.set_mirror_of(None).map_err(ServiceError::Domain)?;
+                self.store_node(&promoted)?;
+
+                self.repository
+                    .set_links(&promoted.id(), &parse_links(promoted.text()))
+                    .map_err(ServiceError::Interface)?;
+
+                self.logger.write_log(format!(
+                    "{} | Promoted mirror node: {} to replace {}",
+                    promoted.modified_time_str(),
+                    promoted.id_str(),
+                    canonical.id_str()
+                ))?;
+
+                Some(promoted.id())
+            }
+            None => None,
+        };
+
+        for mut mirror in mirrors {
+            mirror
+                .set_mirror_of(promoted_id)
+                .map_err(ServiceError::Domain)?;
+            self.store_node(&mirror)?;
+        }
+
+        let Some(promoted_id) = promoted_id else {
+            return Ok(());
+        };
+
+        for tag in self
+            .repository
+            .get_tags(&canonical.id())
+            .map_err(ServiceError::Interface)?
+        {
+            self.repository
+                .add_tag(&[promoted_id], &tag)
+                .map_err(ServiceError::Interface)?;
+        }
+
+        for planning in self
+            .repository
+            .get_planning(&canonical.id())
+            .map_err(ServiceError::Interface)?
+        {
+            self.repository
+                .set_planning(&promoted_id, planning.kind, Some(planning.time))
+                .map_err(ServiceError::Interface)?;
+        }
+
+        let revisions = self
+            .repository
+            .get_revisions(&canonical.id())
+            .map_err(ServiceError::Interface)?;
+
+        for revision in revisions {
+            self.repository
+                .add_revision(&Revision {
+                    node_id: promoted_id,
+                    ..revision
+                })
+                .map_err(ServiceError::Interface)?;
+        }
+
+        let attachments = self
+            .repository
+            .get_attachments(&canonical.id())
+            .map_err(ServiceError::Interface)?;
+
+        for attachment in attachments {
+            self.repository
+                .add_attachment(&Attachment {
+                    node_id: promoted_id,
+                    ..attachment
+                })
+                .map_err(ServiceError::Interface)?;
+        }
+
+        let children = self
+            .repository
+            .get_children(Some(&canonical.id()))
+            .map_err(ServiceError::Interface)?;
+
+        for mut child in children {
+            let rank = child.rank();
+            child
+                .move_to(Some(promoted_id), rank)
+                .map_err(ServiceError::Domain)?;
+            self.store_node(&child)?;
+        }
+
+        Ok(())
+    }
+
+    /// Walks the parent chain of `node`, nearest ancestor first.
+    pub(crate) fn ancestor_ids(&self, node: &Node) -> Result<Vec<Uuid>, ServiceError> {
+        let mut ancestors = Vec::new();
+        let mut current = node.parent_id();
+
+        while let Some(parent_id) = current {
+            if ancestors.contains(&parent_id) {
+                break;
+            }
+
+            ancestors.push(parent_id);
+            current = match self.repository.get_node(&parent_id) {
+                Ok(parent) => parent.parent_id(),
+                Err(InterfaceError::MissingNode) => None,
+                Err(err) => return Err(ServiceError::Interface(err)),
+            };
+        }
+
+        Ok(ancestors)
+    }
+
+    /// Returns every node sharing the content of `node`, excluding `node` itself.
+    pub(crate) fn other_appearances(&self, node: &Node) -> Result<Vec<Node>, ServiceError> {
+        let canonical_id = node.canonical_id();
+
+        let mut appearances = self
+            .repository
+            .get_mirrors(&canonical_id)
+            .map_err(ServiceError::Interface)?;
+
+        if node.mirror_of().is_some() {
+            appearances.push(
+                self.repository
+                    .get_node(&canonical_id)
+                    .map_err(ServiceError::Interface)?,
+            );
+        }
+
+        appearances.retain(|appearance| appearance.id() != node.id());
+
+        Ok(appearances)
+    }
+
+    /// Returns every node whose text links to `node_id`.
+    pub fn backlinks(&self, node_id: &Uuid) -> Result<Vec<Node>, ServiceError> {
+        self.repository
+            .get_backlinks(node_id)
+            .map_err(ServiceError::Interface)?
+            .iter()
+            .map(|source_id| {
+                self.repository
+                    .get_node(source_id)
+                    .map_err(ServiceError::Interface)
+            })
+            .collect()
+    }
+
+    /// Runs `query`, returning the matching nodes along with their ancestors.
+    pub fn search(&self, query: &Query) -> Result<NodeGraph, ServiceError> {
+        self.filtered_graph(query, None)
+    }
+
+    /// Renders the outline, or the subtree of `options.root`, in `format`,
+    /// folded as it was last left.
+    pub fn export_outline(
+        &self,
+        format: ExportFormat,
+        options: &ExportOptions,
+    ) -> Result<String, ServiceError> {
+        if let Some(root) = options.root {
+            self.get_node(&root)?;
+        }
+
+        let mut graph = NodeGraph::new(self.dump_nodes()?);
+        graph.set_collapsed_nodes(&self.collapsed_nodes()?);
+        graph.set_tags(self.dump_tags()?);
+        graph.set_planning(self.dump_planning()?);
+
+        Ok(export::export(&graph, format, options))
+    }
+
+    /// Graph of the nodes matching `query` and their ancestors, also keeping
+    /// `root` so the graph can be viewed from it.
+    pub(crate) fn filtered_graph(
+        &self,
+        query: &Query,
+        root: Option<Uuid>,
+    ) -> Result<NodeGraph, ServiceError> {
+        let matches = self
+            .repository
+            .query_nodes(query)
+            .map_err(ServiceError::Interface)?
+            .iter()
+            .map(|node| node.id())
+            .collect();
+
+        let mut graph = NodeGraph::filtered(self.dump_nodes()?, matches, root);
+        graph.set_tags(self.dump_tags()?);
+        graph.set_planning(self.dump_planning()?);
+
+        Ok(graph)
+    }
+
+    /// Reports every link whose target node no longer exists.
+    pub fn broken_links(&self) -> Result<Vec<BrokenLink>, ServiceError> {
+        let nodes = self.dump_nodes()?;
+        let existing: HashSet<Uuid> = nodes.iter().map(|node| node.id()).collect();
+
+        let mut sources: HashMap<Uuid, Node> =
+            nodes.into_iter().map(|node| (node.id(), node)).collect();
+        let mut broken: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+
+        for (source_id, target_id) in self
+            .repository
+            .dump_links()
+            .map_err(ServiceError::Interface)?
+        {
+            if !existing.contains(&target_id) {
+                broken.entry(source_id).or_default().push(target_id);
+            }
+        }
+
+        Ok(broken
+            .into_iter()
+            .filter_map(|(source_id, target_ids)| {
+                sources
+                    .remove(&source_id)
+                    .map(|source| BrokenLink { source, target_ids })
+            })
+            .collect())
+    }
+
+    /// Copies the subtree rooted at `root_id` to a clipboard. Mirrors are copied
+    /// as plain nodes holding their text.
+    pub fn copy_subtree(&self, root_id: &Uuid) -> Result<Clipboard, ServiceError> {
+        self.clip_subtree(root_id, ClipboardMode::Copy)
+    }
+
+    /// Like `copy_subtree`, but pasting the clipboard moves the original nodes.
+    pub fn cut_subtree(&self, root_id: &Uuid) -> Result<Clipboard, ServiceError> {
+        self.clip_subtree(root_id, ClipboardMode::Cut)
+    }
+
+    /// Deep copies the subtree rooted at `root_id` under `new_parent` at sibling
+    /// `position`, giving every copied node a new ID.
+    pub fn duplicate_subtree(
+        &self,
+        root_id: &Uuid,
+        new_parent: Option<Uuid>,
+        position: usize,
+    ) -> Result<Node, ServiceError> {
+        let clipboard = self.copy_subtree(root_id)?;
+        let mut pasted = self.atomically(|| self.paste_roots(&clipboard, new_parent, position))?;
+        let root = pasted.remove(0);
+
+        self.logger.write_log(format!(
+            "{} | Duplicated subtree: {} as {} ({} nodes)",
+            root.created_time_str(),
+            root_id,
+            root.id_str(),
+            clipboard.node_count()
+        ))?;
+
+        Ok(root)
+    }
+
+    /// Pastes `clipboard` under `parent_id` starting at sibling `position`,
+    /// returning the pasted roots.
+    ///
+    /// Copied subtrees always get new IDs. Cut subtrees are moved when their root
+    /// exists in this document. A cut from another document is pasted as new
+    /// nodes with new IDs, so that the same node never lives in two documents
+    /// that may later be synced or merged, and the source document keeps its
+    /// nodes until they are deleted there.
+    pub fn paste(
+        &self,
+        clipboard: &Clipboard,
+        parent_id: Option<Uuid>,
+        position: usize,
+    ) -> Result<Vec<Node>, ServiceError> {
+        let pasted = self.atomically(|| self.paste_roots(clipboard, parent_id, position))?;
+
+        self.logger.write_log(format!(
+            "{} | Pasted {} nodes under: {}",
+            now()?,
+            clipboard.node_count(),
+            parent_id.map_or("root".to_owned(), |id| id.to_string())
+        ))?;
+
+        Ok(pasted)
+    }
+
+    fn clip_subtree(&self, root_id: &Uuid, mode: ClipboardMode) -> Result<Clipboard, ServiceError> {
+        let root = self
+            .repository
+            .get_node(root_id)
+            .map_err(ServiceError::Interface)?;
+
+        Ok(Clipboard {
+            mode,
+            roots: vec![self.subtree(&root)?],
+        })
+    }
+
+    pub(crate) fn subtree(&self, node: &Node) -> Result<Subtree, ServiceError> {
+        let children = self
+            .repository
+            .get_children(Some(&node.id()))
+            .map_err(ServiceError::Interface)?;
+
+        Ok(Subtree {
+            id: Some(node.id()),
+            node_type: node.node_type().clone(),
+            text: node.text().to_owned(),
+            note: node.note().to_owned(),
+            author: node.author().to_owned(),
+            source_type: node.source_type().clone(),
+            tags: self
+                .repository
+                .get_tags(&node.canonical_id())
+                .map_err(ServiceError::Interface)?,
+            planning: self
+                .repository
+                .get_planning(&node.canonical_id())
+                .map_err(ServiceError::Interface)?,
+            children: children
+                .iter()
+                .map(|child| self.subtree(child))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub(crate) fn paste_roots(
+        &self,
+        clipboard: &Clipboard,
+        parent_id: Option<Uuid>,
+        position: usize,
+    ) -> Result<Vec<Node>, ServiceError> {
+        let parent_id = self.resolve_parent(parent_id)?;
+        let parent_ancestry: Vec<Uuid> = match parent_id {
+            Some(parent_id) => {
+                let mut ancestry = vec![parent_id];
+                if let Ok(parent) = self.repository.get_node(&parent_id) {
+                    ancestry.extend(self.ancestor_ids(&parent)?);
+                }
+                ancestry
+            }
+            None => Vec::new(),
+        };
+
+        // cut nodes still present in this document are moved rather than recreated
+        let mut moved = Vec::new();
+        if clipboard.mode == ClipboardMode::Cut {
+            for root in &clipboard.roots {
+                let Some(root_id) = root.id else {
+                    continue;
+                };
+
+                match self.repository.get_node(&root_id) {
+                    Ok(node) if parent_ancestry.contains(&root_id) => {
+                        return Err(ServiceError::InvalidMove(node.id()));
+                    }
+                    Ok(node) => moved.push(node),
+                    Err(InterfaceError::MissingNode) => {}
+                    Err(err) => return Err(ServiceError::Interface(err)),
+                }
+            }
+        }
+
+        let moved_ids: Vec<Uuid> = moved.iter().map(|node| node.id()).collect();
+        let ranks = self.insertion_ranks(parent_id, position, clipboard.roots.len(), &moved_ids)?;
+        let mut pasted = Vec::with_capacity(clipboard.roots.len());
+
+        for (root, rank) in clipboard.roots.iter().zip(ranks) {
+            let existing = root
+                .id
+                .and_then(|id| moved.iter().position(|node| node.id() == id));
+
+            let node = match existing {
+                Some(index) => {
+                    let mut node = moved.swap_remove(index);
+                    node.move_to(parent_id, rank)
+                        .map_err(ServiceError::Domain)?;
+                    self.store_node(&node)?;
+                    node
+                }
+                None => self.insert_subtree(root, parent_id, rank)?,
+            };
+
+            pasted.push(node);
+        }
+
+        Ok(pasted)
+    }
+
+    fn insert_subtree(
+        &self,
+        subtree: &Subtree,
+        parent_id: Option<Uuid>,
+        rank: u64,
+    ) -> Result<Node, ServiceError> {
+        let node = self.insert_subtree_root(subtree, parent_id, rank, false)?;
+
+        let ranks = spread_ranks(subtree.children.len(), self.rank_gap);
+        for (child, rank) in subtree.children.iter().zip(ranks) {
+            self.insert_subtree(child, Some(node.id()), rank)?;
+        }
+
+        Ok(node)
+    }
+
+    /// Stores the root node of `subtree` along with its tags and planned dates,
+    /// leaving out its children.
+    pub(crate) fn insert_subtree_root(
+        &self,
+        subtree: &Subtree,
+        parent_id: Option<Uuid>,
+        rank: u64,
+        keep_id: bool,
+    ) -> Result<Node, ServiceError> {
+        let mut request = CreateNodeRequest::new(
+            parent_id,
+            rank,
+            subtree.node_type.clone(),
+            &subtree.text,
+            &subtree.author,
+            subtree.source_type.clone(),
+        );
+        request.note = subtree.note.clone();
+
+        let node = match subtree.id {
+            Some(id) if keep_id => Node::new_with_id(id, request),
+            _ => Node::new(request),
+        }
+        .map_err(ServiceError::Domain)?;
+
+        self.insert_node(&node)?;
+
+        for tag in &subtree.tags {
+            let tag = normalize_tag(tag).map_err(ServiceError::Domain)?;
+            self.repository
+                .add_tag(&[node.id()], &tag)
+                .map_err(ServiceError::Interface)?;
+        }
+
+        for planning in &subtree.planning {
+            self.repository
+                .set_planning(&node.id(), planning.kind, Some(planning.time))
+                .map_err(ServiceError::Interface)?;
+        }
+
+        Ok(node)
+    }
+
+    /// Finds ranks for `count` nodes inserted at sibling `position` under
+    /// `parent_id`, ignoring the siblings in `exclude`. When the neighbouring
+    /// ranks are too close together every sibling is respaced.
+    pub(crate) fn insertion_ranks(
+        &self,
+        parent_id: Option<Uuid>,
+        position: usize,
+        count: usize,
+        exclude: &[Uuid],
+    ) -> Result<Vec<u64>, ServiceError> {
+        let mut siblings = self
+            .repository
+            .get_children(parent_id.as_ref())
+            .map_err(ServiceError::Interface)?;
+        siblings.retain(|sibling| !exclude.contains(&sibling.id()));
+
+        let position = position.min(siblings.len());
+        let previous = position.checked_sub(1).map(|index| siblings[index].rank());
+        let next = siblings.get(position).map(|sibling| sibling.rank());
+
+        if let Some(ranks) = ranks_between(previous, next, count, self.rank_gap) {
+            return Ok(ranks);
+        }
+
+        let inserted_slots = position..position + count;
+        let mut siblings = siblings.into_iter();
+        let mut inserted = Vec::with_capacity(count);
+
+        for (slot, rank) in spread_ranks(siblings.len() + count, self.rank_gap)
+            .into_iter()
+            .enumerate()
+        {
+            if inserted_slots.contains(&slot) {
+                inserted.push(rank);
+                continue;
+            }
+
+            if let Some(mut sibling) = siblings.next()
+                && sibling.rank() != rank
+            {
+                sibling
+                    .move_to(sibling.parent_id(), rank)
+                    .map_err(ServiceError::Domain)?;
+                self.store_node(&sibling)?;
+            }
+        }
+
+        Ok(inserted)
+    }
+
+    /// Stores a new node along with the links found in its text.
+    pub(crate) fn insert_node(&self, node: &Node) -> Result<(), ServiceError> {
+        self.repository
+            .add_node(node)
+            .map_err(ServiceError::Interface)?;
+
+        self.repository
+            .set_links(&node.canonical_id(), &parse_links(node.text()))
+            .map_err(ServiceError::Interface)?;
+
+        self.record(&[node.id()])?;
+        self.notifier
+            .notify(vec![NodeEvent::NodeCreated(node.clone())]);
+
+        Ok(())
+    }
+
+    /// Stores changes to an existing node.
+    pub(crate) fn store_node(&self, node: &Node) -> Result<(), ServiceError> {
+        let old = if self.notifier.is_watched() {
+            Some(
+                self.repository
+                    .get_node(&node.id())
+                    .map_err(ServiceError::Interface)?,
+            )
+        } else {
+            None
+        };
+
+        self.repository
+            .update_node(node)
+            .map_err(ServiceError::Interface)?;
+
+        self.record(&[node.id()])?;
+
+        if let Some(old) = old {
+            self.notifier.notify(events::changes(Some(&old), node));
+        }
+
+        Ok(())
+    }
+
+    /// Publishes the changes other processes committed to the outline since
+    /// the last poll, returning how many events were sent. The first poll only
+    /// takes note of the outline as it is.
+    pub fn poll_changes(&self) -> Result<usize, ServiceError> {
+        self.notifier.poll(&self.repository)
+    }
+
+    /// Mirrors never own children, anything added under one belongs to its canonical node.
+    pub(crate) fn resolve_parent(
+        &self,
+        parent_id: Option<Uuid>,
+    ) -> Result<Option<Uuid>, ServiceError> {
+        let Some(parent_id) = parent_id else {
+            return Ok(None);
+        };
+
+        match self.repository.get_node(&parent_id) {
+            Ok(parent) => Ok(Some(parent.canonical_id())),
+            Err(InterfaceError::MissingNode) => Ok(Some(parent_id)),
+            Err(err) => Err(ServiceError::Interface(err)),
+        }
+    }
+
+    /// Runs `operation` in a single repository transaction, rolling back every
+    /// change it made when it fails.
+    pub(crate) fn atomically<T>(
+        &self,
+        operation: impl FnOnce() -> Result<T, ServiceError>,
+    ) -> Result<T, ServiceError> {
+        self.repository
+            .begin_transaction()
+            .map_err(ServiceError::Interface)?;
+        self.notifier.begin();
+
+        match operation() {
+            Ok(result) => {
+                if let Err(err) = self.repository.commit_transaction() {
+                    self.notifier.rollback();
+                    return Err(ServiceError::Interface(err));
+                }
+                self.notifier.commit();
+
+                Ok(result)
+            }
+            Err(err) => {
+                self.notifier.rollback();
+                self.repository
+                    .rollback_transaction()
+                    .map_err(ServiceError::Interface)?;
+                self.replica.reset();
+
+                Err(err)
+            }
+        }
+    }
+
     pub fn dump_nodes(&self) -> Result<Vec<Node>, ServiceError> {
         self.repository
             .dump_nodes()
             .map_err(ServiceError::Interface)
     }
+
+    pub fn collapsed_nodes(&self) -> Result<HashSet<Uuid>, ServiceError> {
+        self.repository
+            .collapsed_nodes()
+            .map_err(ServiceError::Interface)
+    }
+
+    pub fn set_collapsed(&self, node_ids: &[Uuid], collapsed: bool) -> Result<(), ServiceError> {
+        self.repository
+            .set_collapsed(node_ids, collapsed)
+            .map_err(ServiceError::Interface)
+    }
+
+    pub fn get_tags(&self, node_id: &Uuid) -> Result<Vec<String>, ServiceError> {
+        self.repository
+            .get_tags(node_id)
+            .map_err(ServiceError::Interface)
+    }
+
+    pub fn dump_tags(&self) -> Result<Vec<(Uuid, String)>, ServiceError> {
+        self.repository.dump_tags().map_err(ServiceError::Interface)
+    }
+
+    pub fn get_planning(&self, node_id: &Uuid) -> Result<Vec<Planning>, ServiceError> {
+        self.repository
+            .get_planning(node_id)
+            .map_err(ServiceError::Interface)
+    }
+
+    pub fn dump_planning(&self) -> Result<Vec<(Uuid, Planning)>, ServiceError> {
+        self.repository
+            .dump_planning()
+            .map_err(ServiceError::Interface)
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>, ServiceError> {
+        self.repository
+            .get_setting(key)
+            .map_err(ServiceError::Interface)
+    }
+
+    pub fn set_setting(&self, key: &str, value: Option<&str>) -> Result<(), ServiceError> {
+        self.repository
+            .set_setting(key, value)
+            .map_err(ServiceError::Interface)
+    }
+}
+
+/// Indexes nodes by their ID.
+pub(crate) fn node_map(nodes: Vec<Node>) -> HashMap<Uuid, Node> {
+    nodes.into_iter().map(|node| (node.id(), node)).collect()
+}
+
+pub(crate) fn now() -> Result<Epoch, ServiceError> {
+    Epoch::now().map_err(|_| ServiceError::Domain(DomainError::InvalidDateTime))
+}
+
+/// Log suffix naming who made an edit, empty when nobody was recorded.
+fn edited_by(editor: Option<(&str, Source)>) -> String {
+    match editor {
+        Some((author, source)) => format!(" by {author} ({source})"),
+        None => String::new(),
+    }
 }
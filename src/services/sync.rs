@@ -0,0 +1,478 @@
+use std::collections::{HashMap, HashSet};
+
+use hifitime::Epoch;
+use uuid::Uuid;
+
+use crate::{
+    domain::{DomainError, Node, links::parse_links},
+    interfaces::NodeRepository,
+    services::{
+        collab::ReplicaCache,
+        errors::ServiceError,
+        logging::LoggingService,
+        node_service::{node_map, now},
+    },
+};
+
+const REPLICA_SETTING: &str = "replica_id";
+const LAST_SYNC_SETTING: &str = "last_sync";
+
+/// Merges two copies of an outline, such as one on each of two laptops, so that
+/// both end up holding the same nodes.
+///
+/// Nodes are matched by ID and the most recently modified version of each wins.
+/// Deletions are carried over through tombstones, losing to any edit made after
+/// them. Only nodes, and the links derived from their text, are synced.
+pub struct SyncService<A, B, L>
+where
+    A: NodeRepository,
+    B: NodeRepository,
+    L: LoggingService,
+{
+    pub local: A,
+    pub remote: B,
+    pub logger: L,
+    /// Spacing of the ranks given to nodes moved to the top level
+    pub rank_gap: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+/// Changes written to one side of a sync.
+#[derive(Debug, Default)]
+pub struct SyncChanges {
+    pub created: usize,
+    pub updated: usize,
+    pub moved: usize,
+    pub deleted: usize,
+}
+
+/// A node changed on both sides since they were last synced, whose text now
+/// differs between them.
+#[derive(Debug)]
+pub struct Conflict {
+    pub node_id: Uuid,
+    pub local_text: String,
+    pub remote_text: String,
+    /// Side whose edit was kept, being the more recent one
+    pub kept: Side,
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub local: SyncChanges,
+    pub remote: SyncChanges,
+    pub conflicts: Vec<Conflict>,
+    /// Nodes whose move was undone because, combined with a move on the other
+    /// side, it would have made the node its own ancestor
+    pub cycles_broken: Vec<Uuid>,
+    /// Nodes moved to the top level because their parent was deleted on the other side
+    pub orphans: Vec<Uuid>,
+}
+
+impl<A, B, L> SyncService<A, B, L>
+where
+    A: NodeRepository,
+    B: NodeRepository,
+    L: LoggingService,
+{
+    pub fn sync(&self) -> Result<SyncReport, ServiceError> {
+        let local_replica = replica_id(&self.local)?;
+        let remote_replica = replica_id(&self.remote)?;
+        let last_sync = last_sync(&self.local, &remote_replica)?;
+
+        let local_nodes = node_map(self.local.dump_nodes().map_err(ServiceError::Interface)?);
+        let remote_nodes = node_map(self.remote.dump_nodes().map_err(ServiceError::Interface)?);
+
+        let mut tombstones: HashMap<Uuid, Epoch> = HashMap::new();
+        for (node_id, deleted_time) in self
+            .local
+            .dump_tombstones()
+            .map_err(ServiceError::Interface)?
+            .into_iter()
+            .chain(
+                self.remote
+                    .dump_tombstones()
+                    .map_err(ServiceError::Interface)?,
+            )
+        {
+            let latest = tombstones.entry(node_id).or_insert(deleted_time);
+            *latest = (*latest).max(deleted_time);
+        }
+
+        let mut report = SyncReport::default();
+        let mut merged: HashMap<Uuid, Node> = HashMap::new();
+        let node_ids: HashSet<Uuid> = local_nodes
+            .keys()
+            .chain(remote_nodes.keys())
+            .copied()
+            .collect();
+
+        for node_id in node_ids {
+            match (local_nodes.get(&node_id), remote_nodes.get(&node_id)) {
+                (Some(local), Some(remote)) => {
+                    let (winner, kept) = if remote.modified_time() > local.modified_time() {
+                        (remote, Side::Remote)
+                    } else {
+                        (local, Side::Local)
+                    };
+
+                    let edited_on_both = last_sync.is_none_or(|last_sync| {
+                        local.modified_time() > last_sync && remote.modified_time() > last_sync
+                    });
+
+                    if local.text() != remote.text() && edited_on_both {
+                        report.conflicts.push(Conflict {
+                            node_id,
+                            local_text: local.text().to_owned(),
+                            remote_text: remote.text().to_owned(),
+                            kept,
+                        });
+                    }
+
+                    merged.insert(node_id, winner.clone());
+                }
+                (Some(node), None) | (None, Some(node)) => {
+                    // a deletion only wins over edits made before it
+                    if tombstones
+                        .get(&node_id)
+                        .is_some_and(|deleted_time| *deleted_time >= node.modified_time())
+                    {
+                        continue;
+                    }
+
+                    merged.insert(node_id, node.clone());
+                }
+                (None, None) => {}
+            }
+        }
+
+        tombstones.retain(|node_id, _| !merged.contains_key(node_id));
+
+        // undoing a move can return a node to a parent deleted on the other side,
+        // so orphans are only looked for once every cycle is gone
+        break_cycles(
+            &mut merged,
+            &local_nodes,
+            &remote_nodes,
+            self.rank_gap,
+            &mut report,
+        )?;
+        adopt_orphans(&mut merged, self.rank_gap, &mut report)?;
+
+        // each side is updated in its own transaction, and as the result is the
+        // same whichever side fails, a failed sync can simply be run again
+        apply(
+            &self.local,
+            &local_nodes,
+            &merged,
+            &tombstones,
+            self.rank_gap,
+            &mut report.local,
+        )?;
+        apply(
+            &self.remote,
+            &remote_nodes,
+            &merged,
+            &tombstones,
+            self.rank_gap,
+            &mut report.remote,
+        )?;
+
+        let synced_at = now()?.to_string();
+        set_last_sync(&self.local, &remote_replica, &synced_at)?;
+        set_last_sync(&self.remote, &local_replica, &synced_at)?;
+
+        self.logger.write_log(format!(
+            "{} | Synced with replica: {}, {} conflicts",
+            synced_at,
+            remote_replica,
+            report.conflicts.len()
+        ))?;
+
+        Ok(report)
+    }
+}
+
+/// ID identifying a copy of the outline, created the first time it is synced.
+pub(crate) fn replica_id(repository: &impl NodeRepository) -> Result<String, ServiceError> {
+    if let Some(replica_id) = repository
+        .get_setting(REPLICA_SETTING)
+        .map_err(ServiceError::Interface)?
+    {
+        return Ok(replica_id);
+    }
+
+    let replica_id = Uuid::new_v4().to_string();
+    repository
+        .set_setting(REPLICA_SETTING, Some(&replica_id))
+        .map_err(ServiceError::Interface)?;
+
+    Ok(replica_id)
+}
+
+fn last_sync(repository: &impl NodeRepository, peer: &str) -> Result<Option<Epoch>, ServiceError> {
+    repository
+        .get_setting(&format!("{LAST_SYNC_SETTING}:{peer}"))
+        .map_err(ServiceError::Interface)?
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| ServiceError::Domain(DomainError::InvalidDateTime))
+        })
+        .transpose()
+}
+
+fn set_last_sync(
+    repository: &impl NodeRepository,
+    peer: &str,
+    synced_at: &str,
+) -> Result<(), ServiceError> {
+    repository
+        .set_setting(&format!("{LAST_SYNC_SETTING}:{peer}"), Some(synced_at))
+        .map_err(ServiceError::Interface)
+}
+
+/// Moves nodes whose parent no longer exists to the end of the top level.
+fn adopt_orphans(
+    merged: &mut HashMap<Uuid, Node>,
+    rank_gap: u64,
+    report: &mut SyncReport,
+) -> Result<(), ServiceError> {
+    let mut orphans: Vec<Uuid> = merged
+        .values()
+        .filter(|node| {
+            node.parent_id()
+                .is_some_and(|parent_id| !merged.contains_key(&parent_id))
+        })
+        .map(|node| node.id())
+        .collect();
+    orphans.sort();
+
+    for node_id in orphans {
+        let rank = next_root_rank(merged, rank_gap);
+
+        if let Some(node) = merged.get_mut(&node_id) {
+            node.move_to(None, rank).map_err(ServiceError::Domain)?;
+        }
+
+        report.orphans.push(node_id);
+    }
+
+    Ok(())
+}
+
+/// Undoes moves until no node is its own ancestor. Each side's tree is sound
+/// on its own, so a cycle always contains a node whose parent differs between
+/// the sides, and undoing the most recent such move is what happens to the
+/// later of two conflicting moves.
+fn break_cycles(
+    merged: &mut HashMap<Uuid, Node>,
+    local_nodes: &HashMap<Uuid, Node>,
+    remote_nodes: &HashMap<Uuid, Node>,
+    rank_gap: u64,
+    report: &mut SyncReport,
+) -> Result<(), ServiceError> {
+    while let Some(cycle) = find_cycle(merged) {
+        let moved = cycle
+            .iter()
+            .filter_map(|node_id| {
+                let (local, remote) = (local_nodes.get(node_id)?, remote_nodes.get(node_id)?);
+                (local.parent_id() != remote.parent_id()).then_some((node_id, local, remote))
+            })
+            .max_by_key(|(node_id, ..)| (merged[node_id].modified_time(), **node_id));
+
+        match moved {
+            Some((node_id, local, remote)) => {
+                let kept = &merged[node_id];
+                let other = if kept.parent_id() == local.parent_id() {
+                    remote
+                } else {
+                    local
+                };
+                let (parent_id, rank) = (other.parent_id(), other.rank());
+
+                if let Some(node) = merged.get_mut(node_id) {
+                    node.move_to(parent_id, rank)
+                        .map_err(ServiceError::Domain)?;
+                }
+                report.cycles_broken.push(*node_id);
+            }
+            None => {
+                // not reachable from two sound trees, but still never leave a cycle behind
+                let node_id = cycle[0];
+                let rank = next_root_rank(merged, rank_gap);
+
+                if let Some(node) = merged.get_mut(&node_id) {
+                    node.move_to(None, rank).map_err(ServiceError::Domain)?;
+                }
+                report.cycles_broken.push(node_id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the nodes of a parent cycle, if there is one.
+fn find_cycle(merged: &HashMap<Uuid, Node>) -> Option<Vec<Uuid>> {
+    let mut checked: HashSet<Uuid> = HashSet::new();
+    let mut node_ids: Vec<&Uuid> = merged.keys().collect();
+    node_ids.sort();
+
+    for start in node_ids {
+        let mut path: Vec<Uuid> = Vec::new();
+        let mut current = Some(*start);
+
+        while let Some(node_id) = current {
+            if checked.contains(&node_id) {
+                break;
+            }
+
+            if let Some(position) = path.iter().position(|id| *id == node_id) {
+                return Some(path.split_off(position));
+            }
+
+            path.push(node_id);
+            current = merged.get(&node_id).and_then(|node| node.parent_id());
+        }
+
+        checked.extend(path);
+    }
+
+    None
+}
+
+fn next_root_rank(merged: &HashMap<Uuid, Node>, rank_gap: u64) -> u64 {
+    merged
+        .values()
+        .filter(|node| node.parent_id().is_none())
+        .map(|node| node.rank())
+        .max()
+        .unwrap_or(0)
+        .saturating_add(rank_gap)
+}
+
+/// Rewrites `repository` from its `current` nodes to the `merged` ones, in a
+/// single transaction.
+pub(crate) fn apply(
+    repository: &impl NodeRepository,
+    current: &HashMap<Uuid, Node>,
+    merged: &HashMap<Uuid, Node>,
+    tombstones: &HashMap<Uuid, Epoch>,
+    rank_gap: u64,
+    changes: &mut SyncChanges,
+) -> Result<(), ServiceError> {
+    repository
+        .begin_transaction()
+        .map_err(ServiceError::Interface)?;
+
+    match write_changes(repository, current, merged, tombstones, rank_gap, changes) {
+        Ok(()) => repository
+            .commit_transaction()
+            .map_err(ServiceError::Interface),
+        Err(err) => {
+            repository
+                .rollback_transaction()
+                .map_err(ServiceError::Interface)?;
+            Err(err)
+        }
+    }
+}
+
+fn write_changes(
+    repository: &impl NodeRepository,
+    current: &HashMap<Uuid, Node>,
+    merged: &HashMap<Uuid, Node>,
+    tombstones: &HashMap<Uuid, Epoch>,
+    rank_gap: u64,
+    changes: &mut SyncChanges,
+) -> Result<(), ServiceError> {
+    let mut written = Vec::new();
+
+    for (node_id, node) in merged {
+        let text_changed = match current.get(node_id) {
+            None => {
+                repository.add_node(node).map_err(ServiceError::Interface)?;
+                changes.created += 1;
+                true
+            }
+            Some(existing) => {
+                let moved =
+                    existing.parent_id() != node.parent_id() || existing.rank() != node.rank();
+                let edited = existing.text() != node.text()
+                    || existing.note() != node.note()
+                    || existing.node_type() != node.node_type()
+                    || existing.mirror_of() != node.mirror_of();
+
+                if !moved && !edited && existing.modified_time() == node.modified_time() {
+                    continue;
+                }
+
+                repository
+                    .update_node(node)
+                    .map_err(ServiceError::Interface)?;
+
+                changes.moved += usize::from(moved);
+                changes.updated += usize::from(edited);
+                existing.text() != node.text()
+            }
+        };
+
+        written.push(*node_id);
+
+        // links live on canonical nodes, whose mirrors share their text
+        if text_changed && node.mirror_of().is_none() {
+            repository
+                .set_links(node_id, &parse_links(node.text()))
+                .map_err(ServiceError::Interface)?;
+        }
+    }
+
+    // parents are logged before their children, and deletions last
+    written.sort_by_cached_key(|node_id| depth(merged, node_id));
+
+    for node_id in current.keys().filter(|id| !merged.contains_key(id)) {
+        repository
+            .delete_node(node_id)
+            .map_err(ServiceError::Interface)?;
+        changes.deleted += 1;
+        written.push(*node_id);
+    }
+
+    ReplicaCache::default().record(repository, rank_gap, &written)?;
+
+    // deletions keep the time they were first made, so they compare fairly in later syncs
+    let existing: HashMap<Uuid, Epoch> = repository
+        .dump_tombstones()
+        .map_err(ServiceError::Interface)?
+        .into_iter()
+        .collect();
+
+    for (node_id, deleted_time) in tombstones {
+        if existing.get(node_id) != Some(deleted_time) {
+            repository
+                .add_tombstone(node_id, *deleted_time)
+                .map_err(ServiceError::Interface)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Number of ancestors `node_id` has among `nodes`.
+fn depth(nodes: &HashMap<Uuid, Node>, node_id: &Uuid) -> usize {
+    let mut depth = 0;
+    let mut current = nodes.get(node_id).and_then(|node| node.parent_id());
+
+    while let Some(parent_id) = current {
+        depth += 1;
+        current = nodes.get(&parent_id).and_then(|node| node.parent_id());
+    }
+
+    depth
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::{
+    domain::{Clipboard, Node, Subtree, Template, node::Source, subtree::ClipboardMode},
+    interfaces::NodeRepository,
+    services::{
+        errors::ServiceError,
+        logging::LoggingService,
+        node_service::{NodeService, now},
+    },
+};
+
+/// Templates, which are stored structures of nodes added to the outline as
+/// often as needed, with their placeholders filled in each time.
+impl<R, L> NodeService<R, L>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    /// Stores `template`, replacing any template with the same ID. No two
+    /// templates may share a name.
+    pub fn save_template(&self, template: &Template) -> Result<(), ServiceError> {
+        let clash = self
+            .list_templates()?
+            .into_iter()
+            .any(|other| other.name == template.name && other.id != template.id);
+
+        if clash {
+            return Err(ServiceError::DuplicateTemplate(template.name.clone()));
+        }
+
+        self.repository
+            .save_template(template)
+            .map_err(ServiceError::Interface)?;
+
+        self.logger.write_log(format!(
+            "{} | Saved template: {} ({})",
+            now()?,
+            template.id,
+            template.name
+        ))?;
+
+        Ok(())
+    }
+
+    /// Stores a copy of the subtree under `root_id` as a new template called `name`.
+    pub fn create_template(&self, name: &str, root_id: &Uuid) -> Result<Template, ServiceError> {
+        let root = self.get_node(root_id)?;
+        let mut subtree = self.subtree(&root)?;
+        forget_ids(&mut subtree);
+
+        let template = Template::new(name, vec![subtree]).map_err(ServiceError::Domain)?;
+        self.save_template(&template)?;
+
+        Ok(template)
+    }
+
+    pub fn get_template(&self, template_id: &Uuid) -> Result<Template, ServiceError> {
+        self.repository
+            .get_template(template_id)
+            .map_err(ServiceError::Interface)?
+            .ok_or_else(|| ServiceError::MissingTemplate(template_id.to_string()))
+    }
+
+    /// Looks a template up by its ID, or else by its name.
+    pub fn find_template(&self, template: &str) -> Result<Template, ServiceError> {
+        if let Ok(template_id) = Uuid::parse_str(template) {
+            return self.get_template(&template_id);
+        }
+
+        self.list_templates()?
+            .into_iter()
+            .find(|candidate| candidate.name == template)
+            .ok_or_else(|| ServiceError::MissingTemplate(template.to_owned()))
+    }
+
+    pub fn delete_template(&self, template_id: &Uuid) -> Result<(), ServiceError> {
+        let template = self.get_template(template_id)?;
+
+        self.repository
+            .delete_template(template_id)
+            .map_err(ServiceError::Interface)?;
+
+        self.logger.write_log(format!(
+            "{} | Deleted template: {} ({})",
+            now()?,
+            template.id,
+            template.name
+        ))?;
+
+        Ok(())
+    }
+
+    /// Every template, ordered by name.
+    pub fn list_templates(&self) -> Result<Vec<Template>, ServiceError> {
+        self.repository
+            .dump_templates()
+            .map_err(ServiceError::Interface)
+    }
+
+    /// Adds the nodes of `template_id` as the last children of `parent_id`,
+    /// returning the new roots. See [`NodeService::instantiate_template_at`].
+    pub fn instantiate_template(
+        &self,
+        template_id: &Uuid,
+        parent_id: Option<Uuid>,
+        vars: &HashMap<String, String>,
+    ) -> Result<Vec<Node>, ServiceError> {
+        self.instantiate_template_at(template_id, parent_id, usize::MAX, vars)
+    }
+
+    /// Adds the nodes of `template_id` under `parent_id` starting at sibling
+    /// `position`, returning the new roots. Placeholders take their values from
+    /// `vars`, with `date`, `time` and `title` falling back on the current UTC
+    /// date and time and the template's name. The nodes are recorded as made by
+    /// the application, on behalf of the `author` in `vars` when there is one.
+    pub fn instantiate_template_at(
+        &self,
+        template_id: &Uuid,
+        parent_id: Option<Uuid>,
+        position: usize,
+        vars: &HashMap<String, String>,
+    ) -> Result<Vec<Node>, ServiceError> {
+        let template = self.get_template(template_id)?;
+
+        let (year, month, day, hour, minute, ..) = now()?.to_gregorian_utc();
+        let mut values = HashMap::from([
+            ("date".to_owned(), format!("{year:04}-{month:02}-{day:02}")),
+            ("time".to_owned(), format!("{hour:02}:{minute:02}")),
+            ("title".to_owned(), template.name.clone()),
+        ]);
+        values.extend(vars.clone());
+
+        let mut roots = template.expand(&values).map_err(ServiceError::Domain)?;
+        for root in &mut roots {
+            mark_created(root, vars.get("author"));
+        }
+
+        let clipboard = Clipboard {
+            mode: ClipboardMode::Copy,
+            roots,
+        };
+        let created = self.atomically(|| self.paste_roots(&clipboard, parent_id, position))?;
+
+        self.logger.write_log(format!(
+            "{} | Instantiated template: {} ({} nodes) under: {}",
+            now()?,
+            template.id,
+            clipboard.node_count(),
+            parent_id.map_or("root".to_owned(), |id| id.to_string())
+        ))?;
+
+        Ok(created)
+    }
+}
+
+/// Clears the IDs of the nodes a subtree was taken from, which a template has
+/// no use for.
+fn forget_ids(subtree: &mut Subtree) {
+    subtree.id = None;
+    subtree.children.iter_mut().for_each(forget_ids);
+}
+
+fn mark_created(subtree: &mut Subtree, author: Option<&String>) {
+    subtree.source_type = Source::Application;
+    if let Some(author) = author {
+        subtree.author = author.clone();
+    }
+
+    for child in &mut subtree.children {
+        mark_created(child, author);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    domain::{NodeGraph, SavedView},
+    interfaces::{
+        NodeRepository,
+        export::{self, ExportFormat, ExportOptions},
+    },
+    services::{
+        errors::ServiceError,
+        logging::LoggingService,
+        node_service::{NodeService, now},
+    },
+};
+
+/// Saved views, which are named queries evaluated against the current outline
+/// whenever they are opened.
+impl<R, L> NodeService<R, L>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    /// Stores `view`, replacing any view with the same name.
+    pub fn save_view(&self, view: &SavedView) -> Result<(), ServiceError> {
+        if let Some(root) = view.root {
+            self.get_node(&root)?;
+        }
+
+        self.repository
+            .save_view(view)
+            .map_err(ServiceError::Interface)?;
+
+        self.logger
+            .write_log(format!("{} | Saved view: {}", now()?, view.name))?;
+
+        Ok(())
+    }
+
+    pub fn get_view(&self, name: &str) -> Result<SavedView, ServiceError> {
+        self.repository
+            .get_view(name)
+            .map_err(ServiceError::Interface)?
+            .ok_or_else(|| ServiceError::MissingView(name.to_owned()))
+    }
+
+    pub fn delete_view(&self, name: &str) -> Result<(), ServiceError> {
+        self.get_view(name)?;
+
+        self.repository
+            .delete_view(name)
+            .map_err(ServiceError::Interface)?;
+
+        self.logger
+            .write_log(format!("{} | Deleted view: {}", now()?, name))?;
+
+        Ok(())
+    }
+
+    pub fn list_views(&self) -> Result<Vec<SavedView>, ServiceError> {
+        self.repository
+            .dump_views()
+            .map_err(ServiceError::Interface)
+    }
+
+    /// Evaluates `view` against the outline as it is now, returning the matching
+    /// nodes with their ancestors, sorted as the view asks.
+    pub fn evaluate_view(&self, view: &SavedView) -> Result<NodeGraph, ServiceError> {
+        let query = view.query().map_err(ServiceError::Domain)?;
+
+        let mut graph = self.filtered_graph(&query, view.root)?;
+        graph.sort(view.sort);
+
+        Ok(graph)
+    }
+
+    /// Renders the current results of `view` in `format`. The root in
+    /// `options` is ignored, the view's own root taking its place.
+    pub fn export_view(
+        &self,
+        view: &SavedView,
+        format: ExportFormat,
+        options: &ExportOptions,
+    ) -> Result<String, ServiceError> {
+        let graph = self.evaluate_view(view)?;
+        let graph_view = graph.view(view.root).map_err(ServiceError::Domain)?;
+        let elements: Vec<_> = graph_view.elements.iter().collect();
+
+        Ok(export::export_elements(&graph, &elements, format, options))
+    }
+}
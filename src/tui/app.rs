@@ -0,0 +1,1260 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        Clipboard, GraphView, Node, SavedView, Selection,
+        journal::parse_day,
+        links::parse_links,
+        node::{CreateNodeRequest, NodeType, Source},
+        subtree::ClipboardMode,
+    },
+    interfaces::NodeRepository,
+    services::{
+        bulk::DeletePolicy, errors::ServiceError, graph_service::GraphService,
+        logging::LoggingService,
+    },
+    tui::keymap::{Action, Key, Keymap, Lookup, Mode},
+};
+
+/// A single line of the outline as displayed by the TUI.
+pub struct Row {
+    pub id: Uuid,
+    /// Key of the graph element shown, which tells apart appearances of a node
+    pub key: Uuid,
+    pub depth: usize,
+    pub has_children: bool,
+    pub collapsed: bool,
+    pub mirror: bool,
+    /// False for ancestors shown only as context for the results of a view
+    pub matched: bool,
+}
+
+/// Text of a node being edited in insert mode.
+pub struct Editor {
+    pub node_id: Uuid,
+    pub text: String,
+    /// Position of the cursor, in characters
+    pub cursor: usize,
+    /// Whether the node was added for this edit, so that discarding removes it
+    added: bool,
+}
+
+impl Editor {
+    fn byte_index(&self) -> usize {
+        self.text
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.text.len(), |(index, _)| index)
+    }
+
+    fn char_count(&self) -> usize {
+        self.text.chars().count()
+    }
+}
+
+/// List of saved views or templates to choose one from.
+pub struct Picker {
+    pub kind: PickerKind,
+    pub entries: Vec<PickerEntry>,
+    pub cursor: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PickerKind {
+    /// Choosing a view opens it
+    Views,
+    /// Choosing a template adds its nodes after the selected node
+    Templates,
+}
+
+pub struct PickerEntry {
+    pub name: String,
+    /// Shown dimmed after the name, such as the query of a view
+    pub detail: String,
+}
+
+/// A line of input asked for in the status bar.
+pub struct Prompt {
+    pub label: String,
+    pub input: String,
+    purpose: PromptPurpose,
+}
+
+enum PromptPurpose {
+    /// Goes to the journal entry for the date entered
+    JournalDate,
+    /// Saves the subtree under `root_id` as a template with the name entered
+    TemplateName { root_id: Uuid },
+    /// Fills in the placeholder `name`, then asks for the `remaining` ones
+    /// before adding the nodes of the template
+    TemplateValue {
+        template_id: Uuid,
+        name: String,
+        remaining: Vec<String>,
+        values: HashMap<String, String>,
+    },
+}
+
+/// Placeholders filled in without asking: `author` by the app, and `date` and
+/// `time` by the template service.
+const AUTOMATIC_PLACEHOLDERS: [&str; 3] = ["author", "date", "time"];
+
+pub struct App<R, L>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    pub graph_service: GraphService<R, L>,
+    keymap: Keymap,
+    mode: Mode,
+    /// Keys pressed so far of a binding that takes several
+    pending: Vec<Key>,
+    editor: Option<Editor>,
+    /// Author and source recorded on the nodes added here
+    author: String,
+    source: Source,
+    /// Whether new journal entries take over the unfinished tasks of the previous one
+    rollover: bool,
+    view: GraphView,
+    rows: Vec<Row>,
+    cursor: usize,
+    /// Zoom roots and selected row keys to return to after following links
+    history: Vec<(Option<Uuid>, Option<Uuid>)>,
+    /// Copied or cut subtrees in their text form
+    clipboard: Option<String>,
+    /// Start of a range selection that ends at the cursor
+    anchor: Option<Uuid>,
+    /// Nodes marked one by one
+    marked: Vec<Uuid>,
+    /// Nodes covered by the current selection, for highlighting
+    selected: HashSet<Uuid>,
+    picker: Option<Picker>,
+    /// Input being asked for, such as a date to go to its journal entry
+    prompt: Option<Prompt>,
+    /// Whether the note of the selected node is shown beside the outline
+    preview: bool,
+    status: Option<String>,
+    quit: bool,
+}
+
+impl<R, L> App<R, L>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    pub fn new(
+        graph_service: GraphService<R, L>,
+        keymap: Keymap,
+        author: String,
+        source: Source,
+        rollover: bool,
+    ) -> Result<Self, ServiceError> {
+        let view = graph_service.view()?;
+
+        let mut app = App {
+            graph_service,
+            keymap,
+            mode: Mode::Normal,
+            pending: Vec::new(),
+            editor: None,
+            author,
+            source,
+            rollover,
+            view,
+            rows: Vec::new(),
+            cursor: 0,
+            history: Vec::new(),
+            clipboard: None,
+            anchor: None,
+            marked: Vec::new(),
+            selected: HashSet::new(),
+            picker: None,
+            prompt: None,
+            preview: false,
+            status: None,
+            quit: false,
+        };
+        app.rebuild_rows();
+
+        Ok(app)
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn view(&self) -> &GraphView {
+        &self.view
+    }
+
+    pub fn node(&self, node_id: &Uuid) -> Option<&Node> {
+        self.graph_service.node_graph.node(node_id)
+    }
+
+    pub fn is_selected(&self, node_id: &Uuid) -> bool {
+        self.selected.contains(node_id)
+    }
+
+    pub fn picker(&self) -> Option<&Picker> {
+        self.picker.as_ref()
+    }
+
+    pub fn prompt(&self) -> Option<&Prompt> {
+        self.prompt.as_ref()
+    }
+
+    pub fn preview(&self) -> bool {
+        self.preview
+    }
+
+    pub fn active_view(&self) -> Option<&SavedView> {
+        self.graph_service.active_view()
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn editor(&self) -> Option<&Editor> {
+        self.editor.as_ref()
+    }
+
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        self.status = None;
+
+        if self.picker.is_some() {
+            if let Err(err) = self.handle_picker_key(key) {
+                self.status = Some(err.to_string());
+            }
+            return;
+        }
+
+        if self.prompt.is_some() {
+            if let Err(err) = self.handle_prompt_key(key) {
+                self.status = Some(err.to_string());
+            }
+            return;
+        }
+
+        self.pending.push(Key::from(key));
+
+        let result = match self.keymap.lookup(self.mode, &self.pending) {
+            Lookup::Action(action) => {
+                self.pending.clear();
+                self.perform(action)
+            }
+            Lookup::Pending => return,
+            Lookup::Unbound => {
+                // an unbound sequence is dropped, a single key may still mean something
+                let keys = mem::take(&mut self.pending);
+
+                match (self.mode, keys.as_slice()) {
+                    (Mode::Insert, [key]) => {
+                        if let Some(char) = key.char() {
+                            self.type_char(char);
+                        }
+                        Ok(())
+                    }
+                    (Mode::Normal, [key]) => match key.char() {
+                        Some(digit @ '1'..='9') => {
+                            self.fold_to_level(digit as usize - '0' as usize)
+                        }
+                        _ => Ok(()),
+                    },
+                    _ => Ok(()),
+                }
+            }
+        };
+
+        if let Err(err) = result {
+            self.status = Some(err.to_string());
+        }
+
+        self.resolve_selection();
+    }
+
+    fn perform(&mut self, action: Action) -> Result<(), ServiceError> {
+        let result = self.perform_action(action);
+
+        // operations on a visual selection end visual mode, as in vim
+        if self.mode == Mode::Visual
+            && !matches!(
+                action,
+                Action::Down
+                    | Action::Up
+                    | Action::Top
+                    | Action::Bottom
+                    | Action::Visual
+                    | Action::Preview
+            )
+        {
+            self.leave_visual();
+        }
+
+        result
+    }
+
+    fn perform_action(&mut self, action: Action) -> Result<(), ServiceError> {
+        match action {
+            Action::Cancel if self.anchor.is_some() || !self.marked.is_empty() => {
+                self.anchor = None;
+                self.marked.clear();
+                Ok(())
+            }
+            Action::Cancel if self.active_view().is_some() => self.close_view(),
+            Action::Cancel | Action::Quit => {
+                self.quit = true;
+                Ok(())
+            }
+            Action::Down => {
+                self.move_cursor(1);
+                Ok(())
+            }
+            Action::Up => {
+                self.move_cursor(-1);
+                Ok(())
+            }
+            Action::Top => {
+                self.cursor = 0;
+                Ok(())
+            }
+            Action::Bottom => {
+                self.cursor = self.rows.len().saturating_sub(1);
+                Ok(())
+            }
+            Action::ZoomIn => self.zoom_in(),
+            Action::ZoomOut => self.zoom_out(),
+            Action::FollowLink => self.follow_link(),
+            Action::JumpBack => self.jump_back(),
+            Action::Copy => self.copy(ClipboardMode::Copy),
+            Action::Cut => self.copy(ClipboardMode::Cut),
+            Action::Paste => self.paste(),
+            Action::Duplicate => self.duplicate(),
+            Action::Delete => self.delete(),
+            Action::SelectRange => {
+                self.toggle_range();
+                Ok(())
+            }
+            Action::Mark => {
+                self.toggle_mark();
+                Ok(())
+            }
+            Action::Visual if self.mode == Mode::Visual => {
+                self.leave_visual();
+                Ok(())
+            }
+            Action::Visual => {
+                self.mode = Mode::Visual;
+                self.anchor = self.selected();
+                Ok(())
+            }
+            Action::Indent => self.indent(),
+            Action::Outdent => self.outdent(),
+            Action::CycleType => self.cycle_type(),
+            Action::Views => self.open_view_picker(),
+            Action::Templates => self.open_template_picker(),
+            Action::SaveTemplate => {
+                if let Some(root_id) = self.selected() {
+                    self.ask("Save as template", PromptPurpose::TemplateName { root_id });
+                }
+                Ok(())
+            }
+            Action::JournalToday => self.go_to_journal("today"),
+            Action::JournalYesterday => self.go_to_journal("yesterday"),
+            Action::JournalDate => {
+                self.ask("Go to date", PromptPurpose::JournalDate);
+                Ok(())
+            }
+            Action::Preview => {
+                self.preview = !self.preview;
+                Ok(())
+            }
+            Action::ToggleFold => self.toggle_collapsed(),
+            Action::Expand => self.expand(),
+            Action::Collapse => self.collapse(),
+            Action::FoldAll => self.fold_to_level(1),
+            Action::UnfoldAll => self.unfold_all(),
+            Action::Insert => {
+                self.edit(false);
+                Ok(())
+            }
+            Action::Append => {
+                self.edit(true);
+                Ok(())
+            }
+            Action::AddBelow => self.add_node(1),
+            Action::AddAbove => self.add_node(0),
+            Action::Commit => self.commit(),
+            Action::CommitAndAdd => {
+                self.commit()?;
+                self.add_node(1)
+            }
+            Action::Discard => self.discard(),
+            Action::Left
+            | Action::Right
+            | Action::LineStart
+            | Action::LineEnd
+            | Action::DeleteBack
+            | Action::DeleteForward => {
+                self.edit_text(action);
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_picker_key(&mut self, key: KeyEvent) -> Result<(), ServiceError> {
+        let Some(picker) = &mut self.picker else {
+            return Ok(());
+        };
+
+        // enter, escape and delete work whatever the preset binds them to
+        let action = match key.code {
+            KeyCode::Enter => Some(Action::ZoomIn),
+            KeyCode::Esc => Some(Action::Cancel),
+            KeyCode::Delete => Some(Action::Delete),
+            _ => match self.keymap.lookup(Mode::Normal, &[Key::from(key)]) {
+                Lookup::Action(action) => Some(action),
+                Lookup::Pending | Lookup::Unbound => None,
+            },
+        };
+
+        match action {
+            Some(Action::Down) => {
+                picker.cursor = (picker.cursor + 1).min(picker.entries.len().saturating_sub(1));
+            }
+            Some(Action::Up) => {
+                picker.cursor = picker.cursor.saturating_sub(1);
+            }
+            Some(Action::ZoomIn | Action::Expand) => {
+                let name = picker.entries[picker.cursor].name.clone();
+                let kind = picker.kind;
+                self.picker = None;
+
+                match kind {
+                    PickerKind::Views => self.open_view(&name)?,
+                    PickerKind::Templates => self.use_template(&name)?,
+                }
+            }
+            Some(Action::Delete) if picker.kind == PickerKind::Templates => {
+                let name = picker.entries[picker.cursor].name.clone();
+                self.delete_template(&name)?;
+            }
+            Some(Action::Cancel | Action::Quit) => self.picker = None,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn ask(&mut self, label: impl Into<String>, purpose: PromptPurpose) {
+        self.prompt = Some(Prompt {
+            label: label.into(),
+            input: String::new(),
+            purpose,
+        });
+    }
+
+    /// Edits the input being asked for, acting on it on enter.
+    fn handle_prompt_key(&mut self, key: KeyEvent) -> Result<(), ServiceError> {
+        let Some(prompt) = &mut self.prompt else {
+            return Ok(());
+        };
+
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(prompt) = self.prompt.take() {
+                    self.answer(prompt.input, prompt.purpose)?;
+                }
+            }
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Char(char) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                prompt.input.push(char);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn answer(&mut self, input: String, purpose: PromptPurpose) -> Result<(), ServiceError> {
+        match purpose {
+            PromptPurpose::JournalDate => self.go_to_journal(&input),
+            PromptPurpose::TemplateName { root_id } => {
+                let template = self
+                    .graph_service
+                    .node_service
+                    .create_template(&input, &root_id)?;
+                self.status = Some(format!("Saved template {}", template.name));
+                Ok(())
+            }
+            PromptPurpose::TemplateValue {
+                template_id,
+                name,
+                remaining,
+                mut values,
+            } => {
+                // an empty title falls back on the template's name
+                if !(name == "title" && input.is_empty()) {
+                    values.insert(name, input);
+                }
+                self.ask_template_values(template_id, remaining, values)
+            }
+        }
+    }
+
+    /// Zooms into the journal entry for `date`, creating it if needed.
+    fn go_to_journal(&mut self, date: &str) -> Result<(), ServiceError> {
+        let date = parse_day(date).map_err(ServiceError::Domain)?;
+        let entry =
+            self.graph_service
+                .node_service
+                .journal_entry(date, &self.author, self.rollover)?;
+
+        // the entry may not be part of an open view's results
+        if self.active_view().is_some() {
+            self.close_view()?;
+        }
+
+        self.history.push((self.view.root, self.selected_key()));
+        self.graph_service.refresh()?;
+        self.view = self.graph_service.zoom_to(Some(entry.node.id()))?;
+        self.rebuild_rows();
+        self.cursor = 0;
+
+        if entry.rolled_over > 0 {
+            self.status = Some(format!("Rolled over {} tasks", entry.rolled_over));
+        }
+
+        Ok(())
+    }
+
+    fn open_view_picker(&mut self) -> Result<(), ServiceError> {
+        let views = self.graph_service.node_service.list_views()?;
+
+        if views.is_empty() {
+            self.status = Some("No saved views".to_owned());
+            return Ok(());
+        }
+
+        // start on the open view, if any
+        let cursor = self
+            .active_view()
+            .and_then(|active| views.iter().position(|view| view.name == active.name))
+            .unwrap_or(0);
+
+        let entries = views
+            .into_iter()
+            .map(|view| PickerEntry {
+                name: view.name,
+                detail: view.query,
+            })
+            .collect();
+
+        self.picker = Some(Picker {
+            kind: PickerKind::Views,
+            entries,
+            cursor,
+        });
+
+        Ok(())
+    }
+
+    fn open_template_picker(&mut self) -> Result<(), ServiceError> {
+        let templates = self.graph_service.node_service.list_templates()?;
+
+        if templates.is_empty() {
+            self.status = Some("No templates".to_owned());
+            return Ok(());
+        }
+
+        let entries = templates
+            .into_iter()
+            .map(|template| PickerEntry {
+                detail: template.placeholders().join(" "),
+                name: template.name,
+            })
+            .collect();
+
+        self.picker = Some(Picker {
+            kind: PickerKind::Templates,
+            entries,
+            cursor: 0,
+        });
+
+        Ok(())
+    }
+
+    /// Asks for the values of the placeholders of the template called `name`,
+    /// then adds its nodes after the selected node.
+    fn use_template(&mut self, name: &str) -> Result<(), ServiceError> {
+        let template = self.graph_service.node_service.find_template(name)?;
+
+        let remaining = template
+            .placeholders()
+            .into_iter()
+            .filter(|name| !AUTOMATIC_PLACEHOLDERS.contains(&name.as_str()))
+            .collect();
+        let values = HashMap::from([("author".to_owned(), self.author.clone())]);
+
+        self.ask_template_values(template.id, remaining, values)
+    }
+
+    /// Asks for the first of the `remaining` placeholders, or adds the nodes of
+    /// the template once there are none left, selecting the first of them.
+    fn ask_template_values(
+        &mut self,
+        template_id: Uuid,
+        mut remaining: Vec<String>,
+        values: HashMap<String, String>,
+    ) -> Result<(), ServiceError> {
+        if !remaining.is_empty() {
+            let name = remaining.remove(0);
+            self.ask(
+                name.clone(),
+                PromptPurpose::TemplateValue {
+                    template_id,
+                    name,
+                    remaining,
+                    values,
+                },
+            );
+            return Ok(());
+        }
+
+        let (parent_id, position) = self.insertion_point();
+        let roots = self.graph_service.node_service.instantiate_template_at(
+            &template_id,
+            parent_id,
+            position,
+            &values,
+        )?;
+
+        self.reload()?;
+        if let Some(root) = roots.first() {
+            self.select(root.id());
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the template called `name`, keeping the list open on the others.
+    fn delete_template(&mut self, name: &str) -> Result<(), ServiceError> {
+        let node_service = &self.graph_service.node_service;
+        let template = node_service.find_template(name)?;
+        node_service.delete_template(&template.id)?;
+
+        let cursor = self.picker.as_ref().map_or(0, |picker| picker.cursor);
+        self.open_template_picker()?;
+        if let Some(picker) = &mut self.picker {
+            picker.cursor = cursor.min(picker.entries.len().saturating_sub(1));
+        }
+
+        self.status = Some(format!("Deleted template {name}"));
+        Ok(())
+    }
+
+    fn open_view(&mut self, name: &str) -> Result<(), ServiceError> {
+        self.history.clear();
+        self.anchor = None;
+        self.marked.clear();
+
+        self.view = self.graph_service.open_view(name)?;
+        self.rebuild_rows();
+        self.cursor = 0;
+
+        let matches = self.graph_service.node_graph.match_count();
+        self.status = Some(format!("{matches} matching nodes"));
+
+        Ok(())
+    }
+
+    fn close_view(&mut self) -> Result<(), ServiceError> {
+        let selected = self.selected_key();
+
+        self.history.clear();
+        self.view = self.graph_service.close_view()?;
+        self.rebuild_rows();
+        self.select_nearest_visible(selected);
+
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<Uuid> {
+        self.rows.get(self.cursor).map(|row| row.id)
+    }
+
+    /// Key of the selected row, telling apart appearances of the same node.
+    fn selected_key(&self) -> Option<Uuid> {
+        self.rows.get(self.cursor).map(|row| row.key)
+    }
+
+    fn move_cursor(&mut self, offset: isize) {
+        let last = self.rows.len().saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(offset).min(last);
+    }
+
+    fn zoom_in(&mut self) -> Result<(), ServiceError> {
+        let Some(node_id) = self.selected() else {
+            return Ok(());
+        };
+
+        self.view = self.graph_service.zoom_in(node_id)?;
+        self.rebuild_rows();
+        self.cursor = 0;
+
+        Ok(())
+    }
+
+    fn zoom_out(&mut self) -> Result<(), ServiceError> {
+        let Some(previous_root) = self.view.root else {
+            return Ok(());
+        };
+
+        self.view = self.graph_service.zoom_out()?;
+        self.rebuild_rows();
+        self.select(previous_root);
+
+        Ok(())
+    }
+
+    /// Jumps to the first node linked from the selected node.
+    fn follow_link(&mut self) -> Result<(), ServiceError> {
+        let Some(target_id) = self
+            .selected()
+            .and_then(|id| self.node(&id))
+            .and_then(|node| parse_links(node.text()).first().copied())
+        else {
+            self.status = Some("No link on this node".to_owned());
+            return Ok(());
+        };
+
+        let Some(target) = self.node(&target_id) else {
+            self.status = Some("Link target no longer exists".to_owned());
+            return Ok(());
+        };
+
+        // zooming to the parent guarantees the target is visible
+        let parent_id = target.parent_id();
+
+        self.history.push((self.view.root, self.selected_key()));
+        self.view = self.graph_service.zoom_to(parent_id)?;
+        self.rebuild_rows();
+        self.select(target_id);
+
+        Ok(())
+    }
+
+    fn jump_back(&mut self) -> Result<(), ServiceError> {
+        let Some((root, selected)) = self.history.pop() else {
+            return Ok(());
+        };
+
+        self.view = self.graph_service.zoom_to(root)?;
+        self.rebuild_rows();
+        self.select_nearest_visible(selected);
+
+        Ok(())
+    }
+
+    fn copy(&mut self, mode: ClipboardMode) -> Result<(), ServiceError> {
+        let Some(selection) = self.selection() else {
+            return Ok(());
+        };
+
+        let clipboard = self
+            .graph_service
+            .node_service
+            .bulk_clip(&selection, mode)?;
+
+        self.clipboard = Some(clipboard.to_text().map_err(ServiceError::Domain)?);
+        self.status = Some(match mode {
+            ClipboardMode::Copy => format!("Copied {} nodes", clipboard.node_count()),
+            ClipboardMode::Cut => {
+                format!("Cut {} nodes, paste to move them", clipboard.node_count())
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Pastes the clipboard as the next sibling of the selected node.
+    fn paste(&mut self) -> Result<(), ServiceError> {
+        let Some(text) = &self.clipboard else {
+            self.status = Some("Clipboard is empty".to_owned());
+            return Ok(());
+        };
+
+        let mut clipboard = Clipboard::from_text(text).map_err(ServiceError::Domain)?;
+        let (parent_id, position) = self.insertion_point();
+
+        let pasted = self
+            .graph_service
+            .node_service
+            .paste(&clipboard, parent_id, position)?;
+
+        // once moved, further pastes of the same clipboard create copies
+        if clipboard.mode == ClipboardMode::Cut {
+            clipboard.mode = ClipboardMode::Copy;
+            self.clipboard = Some(clipboard.to_text().map_err(ServiceError::Domain)?);
+        }
+
+        self.reload()?;
+        if let Some(node) = pasted.first() {
+            self.select(node.id());
+        }
+
+        Ok(())
+    }
+
+    /// Deep copies the selected node's subtree as its next sibling.
+    fn duplicate(&mut self) -> Result<(), ServiceError> {
+        let Some(node_id) = self.selected() else {
+            return Ok(());
+        };
+
+        let (parent_id, position) = self.insertion_point();
+        let node = self
+            .graph_service
+            .node_service
+            .duplicate_subtree(&node_id, parent_id, position)?;
+
+        self.reload()?;
+        self.select(node.id());
+
+        Ok(())
+    }
+
+    /// Deletes the selected subtrees.
+    fn delete(&mut self) -> Result<(), ServiceError> {
+        let Some(selection) = self.selection() else {
+            return Ok(());
+        };
+
+        let cursor = self.cursor;
+        let count = self
+            .graph_service
+            .node_service
+            .bulk_delete(&selection, DeletePolicy::Cascade)?;
+
+        self.anchor = None;
+        self.marked.clear();
+        self.reload()?;
+        self.cursor = cursor;
+        self.move_cursor(0);
+        self.status = Some(format!("Deleted {count} nodes"));
+
+        Ok(())
+    }
+
+    fn leave_visual(&mut self) {
+        self.mode = Mode::Normal;
+        self.anchor = None;
+    }
+
+    fn toggle_range(&mut self) {
+        self.anchor = match self.anchor {
+            Some(_) => None,
+            None => self.selected(),
+        };
+    }
+
+    fn toggle_mark(&mut self) {
+        let Some(node_id) = self.selected() else {
+            return;
+        };
+
+        match self.marked.iter().position(|id| *id == node_id) {
+            Some(index) => {
+                self.marked.remove(index);
+            }
+            None => self.marked.push(node_id),
+        }
+    }
+
+    /// The range or marked nodes when there are any, otherwise the node under the cursor.
+    fn selection(&self) -> Option<Selection> {
+        match (self.anchor, self.selected()) {
+            (Some(anchor), Some(head)) => Some(Selection::Range { anchor, head }),
+            _ if !self.marked.is_empty() => Some(Selection::Set(self.marked.clone())),
+            (_, cursor) => cursor.map(Selection::single),
+        }
+    }
+
+    fn resolve_selection(&mut self) {
+        let node_graph = &self.graph_service.node_graph;
+
+        // marks on nodes that have since been deleted are dropped
+        self.marked.retain(|id| node_graph.node(id).is_some());
+        if self.anchor.is_some_and(|id| node_graph.node(&id).is_none()) {
+            self.anchor = None;
+        }
+
+        self.selected = match (self.anchor.is_some() || !self.marked.is_empty())
+            .then(|| self.selection())
+            .flatten()
+        {
+            Some(selection) => selection
+                .resolve(node_graph)
+                .map(|ids| ids.into_iter().collect())
+                .unwrap_or_default(),
+            None => HashSet::new(),
+        };
+    }
+
+    fn indent(&mut self) -> Result<(), ServiceError> {
+        let Some(selection) = self.selection() else {
+            return Ok(());
+        };
+
+        let cursor = self.selected_key();
+        self.graph_service.node_service.bulk_indent(&selection)?;
+        self.reload()?;
+        self.select_nearest_visible(cursor);
+
+        Ok(())
+    }
+
+    fn outdent(&mut self) -> Result<(), ServiceError> {
+        let Some(selection) = self.selection() else {
+            return Ok(());
+        };
+
+        let cursor = self.selected_key();
+        self.graph_service.node_service.bulk_outdent(&selection)?;
+        self.reload()?;
+        self.select_nearest_visible(cursor);
+
+        Ok(())
+    }
+
+    /// Gives every selected node the type that follows the cursor node's type.
+    fn cycle_type(&mut self) -> Result<(), ServiceError> {
+        let (Some(selection), Some(node)) = (
+            self.selection(),
+            self.selected().and_then(|id| self.node(&id)),
+        ) else {
+            return Ok(());
+        };
+
+        let node_type = match node.node_type() {
+            NodeType::Standard => NodeType::Todo,
+            NodeType::Todo => NodeType::InProgress,
+            NodeType::InProgress => NodeType::Done,
+            NodeType::Done => NodeType::Standard,
+        };
+
+        let count = self
+            .graph_service
+            .node_service
+            .bulk_set_type(&selection, node_type.clone())?;
+        self.reload()?;
+        self.status = Some(format!("Set {count} nodes to {node_type}"));
+
+        Ok(())
+    }
+
+    /// Starts editing the selected node's text, with the cursor at its end or start.
+    fn edit(&mut self, at_end: bool) {
+        let Some(node) = self.selected().and_then(|id| self.node(&id)) else {
+            return;
+        };
+
+        let mut editor = Editor {
+            node_id: node.id(),
+            text: node.text().to_owned(),
+            cursor: 0,
+            added: false,
+        };
+        if at_end {
+            editor.cursor = editor.char_count();
+        }
+
+        self.editor = Some(editor);
+        self.mode = Mode::Insert;
+    }
+
+    /// Adds an empty node after the selected one, or before it when `offset` is
+    /// zero, and starts editing it.
+    fn add_node(&mut self, offset: usize) -> Result<(), ServiceError> {
+        let (parent_id, position) = match self.insertion_point() {
+            (parent_id, position) if offset == 0 => (parent_id, position.saturating_sub(1)),
+            insertion_point => insertion_point,
+        };
+
+        let request = CreateNodeRequest::new(
+            parent_id,
+            0,
+            NodeType::Standard,
+            "",
+            &self.author,
+            self.source.clone(),
+        );
+        let node = self
+            .graph_service
+            .node_service
+            .create_node_at(request, position)?;
+
+        self.reload()?;
+        self.select_closest(node.id());
+        self.editor = Some(Editor {
+            node_id: node.id(),
+            text: String::new(),
+            cursor: 0,
+            added: true,
+        });
+        self.mode = Mode::Insert;
+
+        Ok(())
+    }
+
+    /// Saves the edited text, if it changed, and returns to normal mode.
+    fn commit(&mut self) -> Result<(), ServiceError> {
+        let Some(editor) = self.editor.take() else {
+            return Ok(());
+        };
+        self.mode = Mode::Normal;
+
+        let node_service = &self.graph_service.node_service;
+        let mut node = node_service.get_node(&editor.node_id)?;
+        if node.text() == editor.text {
+            return Ok(());
+        }
+
+        node_service.update_node(&mut node, &editor.text)?;
+        self.reload()?;
+        self.select_closest(editor.node_id);
+
+        Ok(())
+    }
+
+    /// Returns to normal mode without saving, removing a node added for the edit.
+    fn discard(&mut self) -> Result<(), ServiceError> {
+        let Some(editor) = self.editor.take() else {
+            return Ok(());
+        };
+        self.mode = Mode::Normal;
+
+        if editor.added {
+            let node_service = &self.graph_service.node_service;
+            node_service.delete_node(node_service.get_node(&editor.node_id)?)?;
+            self.reload()?;
+        }
+
+        Ok(())
+    }
+
+    fn type_char(&mut self, char: char) {
+        if let Some(editor) = &mut self.editor {
+            let index = editor.byte_index();
+            editor.text.insert(index, char);
+            editor.cursor += 1;
+        }
+    }
+
+    fn edit_text(&mut self, action: Action) {
+        let Some(editor) = &mut self.editor else {
+            return;
+        };
+
+        match action {
+            Action::Left => editor.cursor = editor.cursor.saturating_sub(1),
+            Action::Right => editor.cursor = (editor.cursor + 1).min(editor.char_count()),
+            Action::LineStart => editor.cursor = 0,
+            Action::LineEnd => editor.cursor = editor.char_count(),
+            Action::DeleteBack if editor.cursor > 0 => {
+                editor.cursor -= 1;
+                let index = editor.byte_index();
+                editor.text.remove(index);
+            }
+            Action::DeleteForward if editor.cursor < editor.char_count() => {
+                let index = editor.byte_index();
+                editor.text.remove(index);
+            }
+            _ => {}
+        }
+    }
+
+    /// Parent and sibling position directly after the selected node.
+    fn insertion_point(&self) -> (Option<Uuid>, usize) {
+        let node_graph = &self.graph_service.node_graph;
+
+        match self.selected().and_then(|id| node_graph.node(&id)) {
+            Some(node) => (
+                node.parent_id(),
+                node_graph
+                    .sibling_index(&node.id())
+                    .map_or(usize::MAX, |index| index + 1),
+            ),
+            None => (self.view.root, 0),
+        }
+    }
+
+    /// Reloads the outline after it was changed elsewhere, keeping the
+    /// selection where it can.
+    pub fn reload_changes(&mut self) {
+        let selected = self.selected_key();
+
+        match self.reload() {
+            Ok(()) => self.select_nearest_visible(selected),
+            Err(err) => self.status = Some(err.to_string()),
+        }
+    }
+
+    /// Rebuilds the outline after it was changed through the services.
+    fn reload(&mut self) -> Result<(), ServiceError> {
+        self.graph_service.refresh()?;
+        self.view = self.graph_service.view()?;
+        self.rebuild_rows();
+
+        Ok(())
+    }
+
+    fn toggle_collapsed(&mut self) -> Result<(), ServiceError> {
+        let Some(key) = self.selected_key() else {
+            return Ok(());
+        };
+
+        self.view = self.graph_service.toggle_collapsed(key)?;
+        self.rebuild_rows();
+
+        Ok(())
+    }
+
+    /// Unfolds the selected node, or moves to its first child when already unfolded.
+    fn expand(&mut self) -> Result<(), ServiceError> {
+        match self.rows.get(self.cursor) {
+            Some(row) if row.collapsed => self.toggle_collapsed(),
+            Some(row) if row.has_children => {
+                self.move_cursor(1);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Folds the selected node, or moves to its parent when it is folded or has no children.
+    fn collapse(&mut self) -> Result<(), ServiceError> {
+        let Some(row) = self.rows.get(self.cursor) else {
+            return Ok(());
+        };
+
+        if row.has_children && !row.collapsed {
+            return self.toggle_collapsed();
+        }
+
+        let depth = row.depth;
+        if let Some(parent) = self.rows[..self.cursor]
+            .iter()
+            .rposition(|row| row.depth < depth)
+        {
+            self.cursor = parent;
+        }
+
+        Ok(())
+    }
+
+    fn fold_to_level(&mut self, level: usize) -> Result<(), ServiceError> {
+        let selected = self.selected_key();
+
+        self.view = self.graph_service.fold_to_level(level)?;
+        self.rebuild_rows();
+        self.select_nearest_visible(selected);
+
+        Ok(())
+    }
+
+    fn unfold_all(&mut self) -> Result<(), ServiceError> {
+        let selected = self.selected_key();
+
+        self.view = self.graph_service.unfold_all()?;
+        self.rebuild_rows();
+        if let Some(key) = selected {
+            self.select(key);
+        }
+
+        Ok(())
+    }
+
+    /// Selects the row with `key`, or its closest visible ancestor when it was folded away.
+    fn select_nearest_visible(&mut self, key: Option<Uuid>) {
+        let Some(key) = key else {
+            return;
+        };
+
+        let node_graph = &self.graph_service.node_graph;
+        let candidates = node_graph.enclosing_keys(&key).into_iter().rev();
+
+        if let Some(visible) = std::iter::once(key)
+            .chain(candidates)
+            .find(|key| self.rows.iter().any(|row| row.key == *key))
+        {
+            self.select(visible);
+        }
+    }
+
+    /// Selects the row of `node_id` closest to the cursor, so that the cursor
+    /// stays inside the mirror it was in when the node is shown more than once.
+    fn select_closest(&mut self, node_id: Uuid) {
+        if let Some(index) = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.id == node_id)
+            .map(|(index, _)| index)
+            .min_by_key(|index| index.abs_diff(self.cursor))
+        {
+            self.cursor = index;
+        }
+    }
+
+    /// Selects the row with `key`. A node's ID selects it at its own place
+    /// rather than inside a mirror.
+    fn select(&mut self, key: Uuid) {
+        if let Some(index) = self.rows.iter().position(|row| row.key == key) {
+            self.cursor = index;
+        }
+    }
+
+    fn rebuild_rows(&mut self) {
+        self.rows = self
+            .view
+            .visible_rows()
+            .map(|element| Row {
+                id: element.id,
+                key: element.key,
+                depth: element.depth,
+                has_children: !element.children.is_empty(),
+                collapsed: element.collapsed,
+                mirror: element.mirror_of.is_some(),
+                matched: self.graph_service.node_graph.is_match(&element.id),
+            })
+            .collect();
+
+        self.move_cursor(0);
+    }
+}
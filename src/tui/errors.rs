@@ -0,0 +1,13 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::services::errors::ServiceError;
+
+#[derive(Error, Debug)]
+pub enum TuiError {
+    #[error("Terminal error: {0}")]
+    Terminal(io::Error),
+    #[error("Service error: {0}")]
+    Service(ServiceError),
+}
@@ -0,0 +1,574 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Something the user can ask the outline view to do.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Action {
+    /// Clears the selection, or else closes the open view, or else quits
+    Cancel,
+    Quit,
+    Down,
+    Up,
+    Top,
+    Bottom,
+    ZoomIn,
+    ZoomOut,
+    FollowLink,
+    JumpBack,
+    Copy,
+    Cut,
+    Paste,
+    Duplicate,
+    Delete,
+    SelectRange,
+    Mark,
+    /// Enters visual mode, or leaves it when already there
+    Visual,
+    Indent,
+    Outdent,
+    CycleType,
+    Views,
+    /// Adds the nodes of a template, chosen from a list, after the selected node.
+    /// Deleting in the list removes the template instead
+    Templates,
+    /// Asks for a name and saves the selected subtree as a template
+    SaveTemplate,
+    /// Zooms into the journal entry for today, creating it if needed
+    JournalToday,
+    JournalYesterday,
+    /// Asks for a date and zooms into its journal entry
+    JournalDate,
+    /// Shows or hides the note of the selected node beside the outline
+    Preview,
+    ToggleFold,
+    /// Unfolds the selected node, or else moves to its first child
+    Expand,
+    /// Folds the selected node, or else moves to its parent
+    Collapse,
+    FoldAll,
+    UnfoldAll,
+    /// Edits the selected node with the cursor at the start of its text
+    Insert,
+    /// Edits the selected node with the cursor at the end of its text
+    Append,
+    /// Adds an empty node after the selected one and edits it
+    AddBelow,
+    /// Adds an empty node before the selected one and edits it
+    AddAbove,
+    /// Saves the text being edited and returns to normal mode
+    Commit,
+    /// Saves the text being edited and adds a node after it
+    CommitAndAdd,
+    /// Returns to normal mode, discarding the changes to the text
+    Discard,
+    Left,
+    Right,
+    LineStart,
+    LineEnd,
+    DeleteBack,
+    DeleteForward,
+}
+
+const ACTIONS: [(Action, &str); 46] = [
+    (Action::Cancel, "cancel"),
+    (Action::Quit, "quit"),
+    (Action::Down, "down"),
+    (Action::Up, "up"),
+    (Action::Top, "top"),
+    (Action::Bottom, "bottom"),
+    (Action::ZoomIn, "zoom_in"),
+    (Action::ZoomOut, "zoom_out"),
+    (Action::FollowLink, "follow_link"),
+    (Action::JumpBack, "jump_back"),
+    (Action::Copy, "copy"),
+    (Action::Cut, "cut"),
+    (Action::Paste, "paste"),
+    (Action::Duplicate, "duplicate"),
+    (Action::Delete, "delete"),
+    (Action::SelectRange, "select_range"),
+    (Action::Mark, "mark"),
+    (Action::Visual, "visual"),
+    (Action::Indent, "indent"),
+    (Action::Outdent, "outdent"),
+    (Action::CycleType, "cycle_type"),
+    (Action::Views, "views"),
+    (Action::Templates, "templates"),
+    (Action::SaveTemplate, "save_template"),
+    (Action::JournalToday, "journal_today"),
+    (Action::JournalYesterday, "journal_yesterday"),
+    (Action::JournalDate, "journal_date"),
+    (Action::Preview, "preview"),
+    (Action::ToggleFold, "toggle_fold"),
+    (Action::Expand, "expand"),
+    (Action::Collapse, "collapse"),
+    (Action::FoldAll, "fold_all"),
+    (Action::UnfoldAll, "unfold_all"),
+    (Action::Insert, "insert"),
+    (Action::Append, "append"),
+    (Action::AddBelow, "add_below"),
+    (Action::AddAbove, "add_above"),
+    (Action::Commit, "commit"),
+    (Action::CommitAndAdd, "commit_and_add"),
+    (Action::Discard, "discard"),
+    (Action::Left, "left"),
+    (Action::Right, "right"),
+    (Action::LineStart, "line_start"),
+    (Action::LineEnd, "line_end"),
+    (Action::DeleteBack, "delete_back"),
+    (Action::DeleteForward, "delete_forward"),
+];
+
+impl Action {
+    /// Whether the action works on the text being edited, and so belongs in insert mode.
+    pub fn edits_text(&self) -> bool {
+        matches!(
+            self,
+            Action::Commit
+                | Action::CommitAndAdd
+                | Action::Discard
+                | Action::Left
+                | Action::Right
+                | Action::LineStart
+                | Action::LineEnd
+                | Action::DeleteBack
+                | Action::DeleteForward
+        )
+    }
+}
+
+impl FromStr for Action {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Action, Self::Err> {
+        ACTIONS
+            .iter()
+            .find(|(_, name)| *name == input.to_ascii_lowercase())
+            .map(|(action, _)| *action)
+            .ok_or(())
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = ACTIONS
+            .iter()
+            .find(|(action, _)| action == self)
+            .map(|(_, name)| *name)
+            .unwrap_or_default();
+
+        f.write_str(name)
+    }
+}
+
+/// A key along with the modifiers held with it, such as `ctrl-n`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const NAMED_KEYS: [(KeyCode, &str); 14] = [
+    (KeyCode::Esc, "esc"),
+    (KeyCode::Enter, "enter"),
+    (KeyCode::Tab, "tab"),
+    (KeyCode::Backspace, "backspace"),
+    (KeyCode::Delete, "delete"),
+    (KeyCode::Char(' '), "space"),
+    (KeyCode::Up, "up"),
+    (KeyCode::Down, "down"),
+    (KeyCode::Left, "left"),
+    (KeyCode::Right, "right"),
+    (KeyCode::Home, "home"),
+    (KeyCode::End, "end"),
+    (KeyCode::PageUp, "pageup"),
+    (KeyCode::PageDown, "pagedown"),
+];
+
+impl Key {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // shift is already part of the character typed, and terminals disagree on reporting it
+        let modifiers = match code {
+            KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+
+        Key { code, modifiers }
+    }
+
+    /// The character typed with this key, if it types one.
+    pub fn char(&self) -> Option<char> {
+        match self.code {
+            KeyCode::Char(char) if self.modifiers.is_empty() => Some(char),
+            _ => None,
+        }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Key::new(event.code, event.modifiers)
+    }
+}
+
+/// Parses keys written as in the configuration file, such as `j`, `J`, `enter`,
+/// `space`, `ctrl-n` or `alt-left`.
+impl FromStr for Key {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Key, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = input;
+
+        // a lone `-` is a key rather than a separator
+        while let Some((prefix, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match prefix.to_ascii_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(()),
+            };
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(char), None) => KeyCode::Char(char),
+            _ => NAMED_KEYS
+                .iter()
+                .find(|(_, name)| *name == rest.to_ascii_lowercase())
+                .map(|(code, _)| *code)
+                .ok_or(())?,
+        };
+
+        Ok(Key::new(code, modifiers))
+    }
+}
+
+/// Parses a sequence of keys pressed one after the other, written separated by
+/// spaces such as `ctrl-x ctrl-c`. A run of plain characters such as `zo` is
+/// read as one key per character.
+pub fn parse_sequence(input: &str) -> Option<Vec<Key>> {
+    let mut keys = Vec::new();
+
+    for word in input.split_whitespace() {
+        match Key::from_str(word) {
+            Ok(key) => keys.push(key),
+            Err(()) if word.contains('-') => return None,
+            Err(()) => {
+                for char in word.chars() {
+                    keys.push(Key::new(KeyCode::Char(char), KeyModifiers::NONE));
+                }
+            }
+        }
+    }
+
+    (!keys.is_empty()).then_some(keys)
+}
+
+/// Editing modes, each with key bindings of its own.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Mode {
+    /// Moving around and acting on nodes
+    Normal,
+    /// Selecting a run of sibling nodes
+    Visual,
+    /// Editing the text of a node, where keys without a binding are typed
+    Insert,
+}
+
+impl FromStr for Mode {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Mode, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "normal" => Ok(Mode::Normal),
+            "visual" => Ok(Mode::Visual),
+            "insert" => Ok(Mode::Insert),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Starting set of key bindings, which the configuration can then change.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Preset {
+    /// Single key commands, with `v` and `m` selecting ranges and single nodes
+    Default,
+    /// Modal editing in the style of vim
+    Vim,
+    /// Chorded commands in the style of emacs and org-mode
+    Emacs,
+}
+
+impl FromStr for Preset {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Preset, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "default" => Ok(Preset::Default),
+            "vim" => Ok(Preset::Vim),
+            "emacs" => Ok(Preset::Emacs),
+            _ => Err(()),
+        }
+    }
+}
+
+type Bindings = &'static [(Action, &'static [&'static str])];
+
+const DEFAULT_NORMAL: Bindings = &[
+    (Action::Cancel, &["esc"]),
+    (Action::Quit, &["q"]),
+    (Action::Down, &["j", "down"]),
+    (Action::Up, &["k", "up"]),
+    (Action::ZoomIn, &["l", "right", "enter"]),
+    (Action::ZoomOut, &["h", "left", "backspace"]),
+    (Action::FollowLink, &["f"]),
+    (Action::JumpBack, &["b"]),
+    (Action::Copy, &["y"]),
+    (Action::Cut, &["x"]),
+    (Action::Paste, &["p"]),
+    (Action::Duplicate, &["D"]),
+    (Action::Delete, &["delete"]),
+    (Action::SelectRange, &["v"]),
+    (Action::Mark, &["m"]),
+    (Action::Indent, &[">"]),
+    (Action::Outdent, &["<"]),
+    (Action::CycleType, &["t"]),
+    (Action::Views, &["V"]),
+    (Action::Templates, &["T"]),
+    (Action::SaveTemplate, &["S"]),
+    (Action::JournalToday, &["J"]),
+    (Action::JournalYesterday, &["Y"]),
+    (Action::JournalDate, &["G"]),
+    (Action::Preview, &["n"]),
+    (Action::ToggleFold, &["space", "tab"]),
+    (Action::UnfoldAll, &["0"]),
+    (Action::Insert, &["i"]),
+    (Action::Append, &["a"]),
+    (Action::AddBelow, &["o"]),
+    (Action::AddAbove, &["O"]),
+];
+
+/// Editing keys shared by every preset, which presets may add to.
+const COMMON_INSERT: Bindings = &[
+    (Action::Commit, &["esc"]),
+    (Action::CommitAndAdd, &["enter"]),
+    (Action::Discard, &["ctrl-c"]),
+    (Action::Left, &["left"]),
+    (Action::Right, &["right"]),
+    (Action::LineStart, &["home"]),
+    (Action::LineEnd, &["end"]),
+    (Action::DeleteBack, &["backspace"]),
+    (Action::DeleteForward, &["delete"]),
+];
+
+const VIM_NORMAL: Bindings = &[
+    (Action::Cancel, &["esc"]),
+    (Action::Quit, &["q", "Z Z"]),
+    (Action::Down, &["j", "down"]),
+    (Action::Up, &["k", "up"]),
+    (Action::Top, &["gg"]),
+    (Action::Bottom, &["G"]),
+    (Action::Collapse, &["h", "left", "zc"]),
+    (Action::Expand, &["l", "right", "zo"]),
+    (Action::ToggleFold, &["za", "tab"]),
+    (Action::FoldAll, &["zM"]),
+    (Action::UnfoldAll, &["zR"]),
+    (Action::ZoomIn, &["enter", "zi"]),
+    (Action::ZoomOut, &["backspace", "zu"]),
+    (Action::FollowLink, &["gf"]),
+    (Action::JumpBack, &["ctrl-o"]),
+    (Action::Copy, &["yy"]),
+    (Action::Cut, &["dd"]),
+    (Action::Paste, &["p"]),
+    (Action::Duplicate, &["yp"]),
+    (Action::Delete, &["x"]),
+    (Action::Visual, &["v", "V"]),
+    (Action::Mark, &["m"]),
+    (Action::Indent, &[">>"]),
+    (Action::Outdent, &["<<"]),
+    (Action::CycleType, &["t"]),
+    (Action::Views, &["gv"]),
+    (Action::Templates, &["gt"]),
+    (Action::SaveTemplate, &["gs"]),
+    (Action::JournalToday, &["gjj"]),
+    (Action::JournalYesterday, &["gjy"]),
+    (Action::JournalDate, &["gjd"]),
+    (Action::Preview, &["K"]),
+    (Action::Insert, &["i", "I"]),
+    (Action::Append, &["a", "A"]),
+    (Action::AddBelow, &["o"]),
+    (Action::AddAbove, &["O"]),
+];
+
+const VIM_VISUAL: Bindings = &[
+    (Action::Visual, &["esc", "v", "V"]),
+    (Action::Down, &["j", "down"]),
+    (Action::Up, &["k", "up"]),
+    (Action::Top, &["gg"]),
+    (Action::Bottom, &["G"]),
+    (Action::Copy, &["y"]),
+    (Action::Cut, &["d"]),
+    (Action::Delete, &["x"]),
+    (Action::Indent, &[">"]),
+    (Action::Outdent, &["<"]),
+    (Action::CycleType, &["t"]),
+];
+
+const EMACS_NORMAL: Bindings = &[
+    (Action::Cancel, &["ctrl-g", "esc"]),
+    (Action::Quit, &["ctrl-x ctrl-c"]),
+    (Action::Down, &["ctrl-n", "down"]),
+    (Action::Up, &["ctrl-p", "up"]),
+    (Action::Top, &["alt-<"]),
+    (Action::Bottom, &["alt->"]),
+    (Action::ToggleFold, &["tab"]),
+    (Action::UnfoldAll, &["ctrl-u tab"]),
+    (Action::ZoomIn, &["ctrl-x n s", "right"]),
+    (Action::ZoomOut, &["ctrl-x n w", "left"]),
+    (Action::FollowLink, &["ctrl-c ctrl-o"]),
+    (Action::JumpBack, &["ctrl-c &"]),
+    (Action::Copy, &["alt-w"]),
+    (Action::Cut, &["ctrl-w", "ctrl-k"]),
+    (Action::Paste, &["ctrl-y"]),
+    (Action::Duplicate, &["ctrl-c ctrl-x ctrl-d"]),
+    (Action::Delete, &["ctrl-d"]),
+    (Action::Visual, &["ctrl-space"]),
+    (Action::Indent, &["alt-right"]),
+    (Action::Outdent, &["alt-left"]),
+    (Action::CycleType, &["ctrl-c ctrl-t"]),
+    (Action::Views, &["ctrl-c a"]),
+    (Action::Templates, &["ctrl-c t"]),
+    (Action::SaveTemplate, &["ctrl-c s"]),
+    (Action::JournalToday, &["ctrl-c j t"]),
+    (Action::JournalYesterday, &["ctrl-c j y"]),
+    (Action::JournalDate, &["ctrl-c j d"]),
+    (Action::Preview, &["ctrl-c n"]),
+    (Action::Append, &["enter", "ctrl-e"]),
+    (Action::Insert, &["ctrl-a"]),
+    (Action::AddBelow, &["alt-enter"]),
+];
+
+const EMACS_VISUAL: Bindings = &[
+    (Action::Visual, &["ctrl-g", "esc", "ctrl-space"]),
+    (Action::Down, &["ctrl-n", "down"]),
+    (Action::Up, &["ctrl-p", "up"]),
+    (Action::Copy, &["alt-w"]),
+    (Action::Cut, &["ctrl-w"]),
+    (Action::Delete, &["ctrl-d"]),
+    (Action::Indent, &["alt-right"]),
+    (Action::Outdent, &["alt-left"]),
+    (Action::CycleType, &["ctrl-c ctrl-t"]),
+];
+
+const EMACS_INSERT: Bindings = &[
+    (Action::Commit, &["esc", "enter"]),
+    (Action::CommitAndAdd, &["alt-enter"]),
+    (Action::Discard, &["ctrl-g"]),
+    (Action::Left, &["ctrl-b"]),
+    (Action::Right, &["ctrl-f"]),
+    (Action::LineStart, &["ctrl-a"]),
+    (Action::LineEnd, &["ctrl-e"]),
+    (Action::DeleteForward, &["ctrl-d"]),
+];
+
+/// Result of looking up the keys pressed so far.
+#[derive(Debug, PartialEq)]
+pub enum Lookup {
+    Action(Action),
+    /// The keys begin a longer binding, so the next key is needed
+    Pending,
+    Unbound,
+}
+
+/// Which action each key sequence triggers, in each mode. In normal mode, keys
+/// `1` to `9` fold the outline to that level unless bound to an action.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    preset: Preset,
+    bindings: HashMap<Mode, HashMap<Vec<Key>, Action>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_preset(Preset::Default)
+    }
+}
+
+impl Keymap {
+    pub fn from_preset(preset: Preset) -> Self {
+        let tables: &[(Mode, Bindings)] = match preset {
+            Preset::Default => &[
+                (Mode::Normal, DEFAULT_NORMAL),
+                (Mode::Insert, COMMON_INSERT),
+            ],
+            Preset::Vim => &[
+                (Mode::Normal, VIM_NORMAL),
+                (Mode::Visual, VIM_VISUAL),
+                (Mode::Insert, COMMON_INSERT),
+            ],
+            Preset::Emacs => &[
+                (Mode::Normal, EMACS_NORMAL),
+                (Mode::Visual, EMACS_VISUAL),
+                (Mode::Insert, COMMON_INSERT),
+                (Mode::Insert, EMACS_INSERT),
+            ],
+        };
+
+        let mut keymap = Keymap {
+            preset,
+            bindings: HashMap::new(),
+        };
+
+        for (mode, table) in tables {
+            for (action, sequences) in *table {
+                keymap.bind(
+                    *mode,
+                    *action,
+                    sequences
+                        .iter()
+                        .filter_map(|sequence| parse_sequence(sequence)),
+                );
+            }
+        }
+
+        keymap
+    }
+
+    pub fn preset(&self) -> Preset {
+        self.preset
+    }
+
+    /// Looks up the keys pressed so far in `mode`.
+    pub fn lookup(&self, mode: Mode, keys: &[Key]) -> Lookup {
+        let Some(bindings) = self.bindings.get(&mode) else {
+            return Lookup::Unbound;
+        };
+
+        if let Some(action) = bindings.get(keys) {
+            return Lookup::Action(*action);
+        }
+
+        match bindings.keys().any(|sequence| sequence.starts_with(keys)) {
+            true => Lookup::Pending,
+            false => Lookup::Unbound,
+        }
+    }
+
+    /// Binds `sequences` to `action` in `mode`, in place of the keys it had,
+    /// taking them from any other action they were bound to.
+    pub fn bind(
+        &mut self,
+        mode: Mode,
+        action: Action,
+        sequences: impl IntoIterator<Item = Vec<Key>>,
+    ) {
+        let bindings = self.bindings.entry(mode).or_default();
+        bindings.retain(|_, bound| *bound != action);
+
+        for sequence in sequences {
+            bindings.insert(sequence, action);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+pub mod app;
+pub use app::App;
+
+pub mod errors;
+pub use errors::TuiError;
+
+pub mod keymap;
+pub use keymap::Keymap;
+
+pub mod theme;
+pub use theme::Theme;
+
+mod ui;
+
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyEventKind};
+
+use crate::{
+    domain::node::Source,
+    interfaces::NodeRepository,
+    services::{graph_service::GraphService, logging::LoggingService},
+};
+
+/// How long to wait for a key before checking for changes made elsewhere.
+const INPUT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Key bindings, colours and journal behaviour of the outline view.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub keymap: Keymap,
+    pub theme: Theme,
+    /// Whether new journal entries take over the unfinished tasks of the previous one
+    pub rollover: bool,
+}
+
+/// Runs the interactive outline view until the user quits. The outline is
+/// reloaded whenever it changes, here or in another process, and nodes added
+/// here are recorded with `author` and `source`.
+pub fn run<R, L>(
+    graph_service: GraphService<R, L>,
+    settings: Settings,
+    author: String,
+    source: Source,
+) -> Result<(), TuiError>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let Settings {
+        keymap,
+        theme,
+        rollover,
+    } = settings;
+    let changes = graph_service.node_service.notifier.subscribe();
+    let mut app =
+        App::new(graph_service, keymap, author, source, rollover).map_err(TuiError::Service)?;
+    let mut terminal = ratatui::init();
+
+    let result = loop {
+        if let Err(err) = terminal.draw(|frame| ui::draw(frame, &app, &theme)) {
+            break Err(TuiError::Terminal(err));
+        }
+
+        let input = match event::poll(INPUT_TIMEOUT) {
+            Ok(true) => event::read().map(Some),
+            Ok(false) => Ok(None),
+            Err(err) => Err(err),
+        };
+
+        match input {
+            Ok(Some(Event::Key(key))) if key.kind == KeyEventKind::Press => app.handle_key(key),
+            Ok(_) => {}
+            Err(err) => break Err(TuiError::Terminal(err)),
+        }
+
+        // a failed poll is simply tried again on the next one
+        let _ = app.graph_service.node_service.poll_changes();
+
+        // changes arrive in bursts, one reload covers them all
+        if changes.try_iter().count() > 0 {
+            app.reload_changes();
+        }
+
+        if app.should_quit() {
+            break Ok(());
+        }
+    };
+
+    ratatui::restore();
+
+    result
+}
@@ -0,0 +1,31 @@
+use ratatui::style::Color;
+
+/// Colours of the outline view. Colours are written as names such as `blue`
+/// or `lightgreen`, as `#rrggbb`, or as an index into the terminal's palette.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Background of the selected nodes
+    pub selection: Color,
+    /// Lines above and below the outline
+    pub border: Color,
+    pub breadcrumbs: Color,
+    /// Status and help line
+    pub status: Color,
+    /// Inline code in node text
+    pub code: Color,
+    /// Links to web pages and to other nodes
+    pub link: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            selection: Color::DarkGray,
+            border: Color::Reset,
+            breadcrumbs: Color::Reset,
+            status: Color::Reset,
+            code: Color::Yellow,
+            link: Color::Cyan,
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use std::mem;
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+};
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{
+    domain::{
+        Node,
+        markup::{Target, parse_inline, plain_text},
+        node::NodeType,
+    },
+    interfaces::NodeRepository,
+    services::logging::LoggingService,
+    tui::{
+        App, Theme,
+        app::{Editor, Picker, PickerKind},
+        keymap::{Mode, Preset},
+    },
+};
+
+const CRUMB_WIDTH: usize = 24;
+/// Narrowest room for a label that is still wrapped.
+const MIN_WRAP_WIDTH: usize = 8;
+const HELP: &str = "j/k move  l/h zoom  space fold  1-9/0 fold/unfold  f/b link/back  y/x/p copy/cut/paste  D duplicate  v/m range/mark  >/< indent  t type  i/a/o/O edit  n note  V/T/S views/templates/save template  J/Y/G journal  q quit";
+const VIM_HELP: &str = "j/k move  h/l fold  zo/zc/za/zM/zR folds  enter/backspace zoom  yy/dd/p copy/cut/paste  x delete  v visual  >>/<< indent  t type  i/a/o/O edit  K note  gv/gt/gs views/templates/save template  gjj/gjy/gjd journal  q quit";
+const EMACS_HELP: &str = "C-n/C-p move  tab fold  right/left zoom  M-w/C-w/C-y copy/cut/paste  C-space region  M-right/M-left indent  C-c C-t type  enter edit  C-c n note  C-c a/t/s views/templates/save template  C-c j t/y/d journal  C-x C-c quit";
+
+pub fn draw<R, L>(frame: &mut Frame, app: &App<R, L>, theme: &Theme)
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let [breadcrumb_area, outline_area, status_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let (outline_area, preview_area) = match app.preview() {
+        true => {
+            let [outline_area, preview_area] =
+                Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                    .areas(outline_area);
+            (outline_area, Some(preview_area))
+        }
+        false => (outline_area, None),
+    };
+
+    frame.render_widget(
+        Paragraph::new(breadcrumbs(app)).fg(theme.breadcrumbs),
+        breadcrumb_area,
+    );
+
+    let items: Vec<ListItem> = app
+        .rows()
+        .iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let indent = "  ".repeat(row.depth);
+            let bullet = match (row.has_children, row.collapsed) {
+                (false, _) => "• ",
+                (true, true) => "▸ ",
+                (true, false) => "▾ ",
+            };
+            let prefix = format!("{indent}{bullet}");
+            // a node shown more than once is only edited where the cursor is
+            let editing = app
+                .editor()
+                .filter(|editor| editor.node_id == row.id && index == app.cursor());
+            let mut item = match editing {
+                Some(editor) => ListItem::new(editor_line(prefix, editor)),
+                None => {
+                    let label = app
+                        .node(&row.id)
+                        .map(|node| node_label(app, node, theme))
+                        .unwrap_or_default();
+
+                    ListItem::new(wrap_row(prefix, label, outline_area.width as usize))
+                }
+            };
+            if app.is_selected(&row.id) {
+                item = item.bg(theme.selection);
+            }
+
+            // ancestors shown only for context are set apart from the results of a view
+            if !row.matched {
+                item = item.dim();
+            }
+
+            // mirrors are shown in italics to set them apart from their canonical node
+            if row.mirror { item.italic() } else { item }
+        })
+        .collect();
+
+    // the row being edited shows its own cursor instead
+    let highlight = match app.editor() {
+        Some(_) => Style::default(),
+        None => Style::default().add_modifier(Modifier::REVERSED),
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::TOP | Borders::BOTTOM)
+                .border_style(Style::default().fg(theme.border)),
+        )
+        .highlight_style(highlight);
+
+    let mut state = ListState::default().with_selected(Some(app.cursor()));
+    frame.render_stateful_widget(list, outline_area, &mut state);
+
+    let help = match app.keymap().preset() {
+        Preset::Default => HELP,
+        Preset::Vim => VIM_HELP,
+        Preset::Emacs => EMACS_HELP,
+    };
+    let mode = match app.mode() {
+        Mode::Normal => "",
+        Mode::Visual => "-- VISUAL -- ",
+        Mode::Insert => "-- INSERT -- ",
+    };
+    let status = match app.prompt() {
+        Some(prompt) => format!("{}: {}\u{2588}", prompt.label, prompt.input),
+        None => format!("{mode}{}", app.status().unwrap_or(help)),
+    };
+    frame.render_widget(Paragraph::new(status).fg(theme.status).dim(), status_area);
+
+    if let Some(area) = preview_area {
+        draw_preview(frame, app, area, theme);
+    }
+
+    if let Some(picker) = app.picker() {
+        draw_picker(frame, picker, outline_area, theme);
+    }
+}
+
+fn draw_picker(frame: &mut Frame, picker: &Picker, area: Rect, theme: &Theme) {
+    let height = (picker.entries.len() as u16 + 2).min(area.height);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::horizontal([Constraint::Percentage(60)])
+        .flex(Flex::Center)
+        .areas(area);
+
+    let items: Vec<ListItem> = picker
+        .entries
+        .iter()
+        .map(|entry| {
+            ListItem::new(Line::from(vec![
+                Span::raw(entry.name.clone()),
+                Span::raw(format!("  {}", entry.detail)).dim(),
+            ]))
+        })
+        .collect();
+
+    let title = match picker.kind {
+        PickerKind::Views => " Views ",
+        PickerKind::Templates => " Templates ",
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border))
+                .title(title),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut state = ListState::default().with_selected(Some(picker.cursor));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// The selected node's text and its note, wrapped to the pane.
+fn draw_preview<R, L>(frame: &mut Frame, app: &App<R, L>, area: Rect, theme: &Theme)
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let node = app
+        .rows()
+        .get(app.cursor())
+        .and_then(|row| app.node(&row.id));
+
+    let mut lines = Vec::new();
+    if let Some(node) = node {
+        lines.push(Line::from(plain_text(node.text())).bold());
+        lines.push(Line::default());
+
+        match node.note().is_empty() {
+            true => lines.push(Line::from("No note").dim()),
+            false => lines.extend(node.note().lines().map(|line| Line::from(line.to_owned()))),
+        }
+    }
+
+    let preview = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border))
+                .title(" Note "),
+        )
+        .wrap(Wrap { trim: false });
+
+    frame.render_widget(preview, area);
+}
+
+fn breadcrumbs<R, L>(app: &App<R, L>) -> Line<'static>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let view = app.view();
+    let mut spans = Vec::new();
+
+    if let Some(saved_view) = app.active_view() {
+        spans.push(Span::raw(format!("[{}] ", saved_view.name)).italic());
+    }
+    spans.push(Span::raw("Home"));
+
+    for crumb in view.breadcrumbs.iter().chain(view.root.iter()) {
+        let text = app
+            .node(crumb)
+            .map_or("?".to_owned(), |node| plain_text(node.text()));
+
+        spans.push(Span::raw(" › ").dim());
+        spans.push(Span::raw(truncate(&text, CRUMB_WIDTH)));
+    }
+
+    if let Some(current) = spans.last_mut() {
+        *current = current.clone().bold();
+    }
+
+    Line::from(spans)
+}
+
+/// The text being edited, underlined, with the character under the cursor reversed.
+fn editor_line(prefix: String, editor: &Editor) -> Line<'static> {
+    let mut chars = editor.text.chars();
+    let before: String = chars.by_ref().take(editor.cursor).collect();
+    let under = chars.next().unwrap_or(' ');
+    let after: String = chars.collect();
+
+    Line::from(vec![
+        Span::raw(prefix),
+        Span::raw(before).underlined(),
+        Span::raw(under.to_string()).reversed(),
+        Span::raw(after).underlined(),
+    ])
+}
+
+/// The node's task marker and text, styled by its inline Markdown.
+fn node_label<R, L>(app: &App<R, L>, node: &Node, theme: &Theme) -> Vec<Span<'static>>
+where
+    R: NodeRepository,
+    L: LoggingService,
+{
+    let marker = match node.node_type() {
+        NodeType::Standard => "",
+        NodeType::Todo => "[ ] ",
+        NodeType::InProgress => "[~] ",
+        NodeType::Done => "[x] ",
+    };
+
+    let mut spans = vec![Span::raw(marker)];
+
+    for inline in parse_inline(node.text()) {
+        let emphasis = inline.emphasis;
+        let mut style = Style::default();
+
+        if emphasis.bold {
+            style = style.bold();
+        }
+        if emphasis.italic {
+            style = style.italic();
+        }
+        if emphasis.code {
+            style = style.fg(theme.code);
+        }
+        // finished tasks are struck through as a whole
+        if emphasis.strikethrough || *node.node_type() == NodeType::Done {
+            style = style.crossed_out();
+        }
+
+        let text = match &inline.link {
+            Some(Target::Node(target_id)) => {
+                style = style.fg(theme.link);
+                match app.node(target_id) {
+                    Some(target) => format!("→{}", plain_text(target.text())),
+                    None => "→(missing)".to_owned(),
+                }
+            }
+            Some(Target::Url(_)) => {
+                style = style.fg(theme.link).underlined();
+                inline.text
+            }
+            None => inline.text,
+        };
+
+        spans.push(Span::styled(text, style));
+    }
+
+    spans
+}
+
+/// Lines of a row, with its label wrapped to `width` and the lines after the
+/// first indented to line up with the text.
+fn wrap_row(prefix: String, label: Vec<Span<'static>>, width: usize) -> Text<'static> {
+    let indent = prefix.width();
+    let available = width.saturating_sub(indent);
+
+    // too narrow to wrap usefully, so the label is cut off instead
+    let lines = match available < MIN_WRAP_WIDTH {
+        true => vec![label],
+        false => wrap(label, available),
+    };
+
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, spans)| {
+            let lead = match index {
+                0 => prefix.clone(),
+                _ => " ".repeat(indent),
+            };
+
+            Line::from(
+                std::iter::once(Span::raw(lead))
+                    .chain(spans)
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Splits styled text into lines no wider than `width`, breaking after spaces
+/// where it can and within words longer than a line.
+fn wrap(spans: Vec<Span<'static>>, width: usize) -> Vec<Vec<Span<'static>>> {
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    let mut used = 0;
+
+    for span in spans {
+        for word in span.content.split_inclusive(' ') {
+            if used > 0 && used + word.trim_end_matches(' ').width() > width {
+                lines.push(mem::take(&mut line));
+                used = 0;
+            }
+
+            let mut chunk = String::new();
+            for char in word.chars() {
+                let char_width = char.width().unwrap_or(0);
+
+                // trailing spaces may run past the edge, where they are not seen
+                if used + char_width > width && char != ' ' {
+                    if !chunk.is_empty() {
+                        line.push(Span::styled(mem::take(&mut chunk), span.style));
+                    }
+                    lines.push(mem::take(&mut line));
+                    used = 0;
+                }
+
+                chunk.push(char);
+                used += char_width;
+            }
+
+            if !chunk.is_empty() {
+                line.push(Span::styled(chunk, span.style));
+            }
+        }
+    }
+
+    lines.push(line);
+    lines
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_owned();
+    }
+
+    let mut truncated: String = text.chars().take(width - 1).collect();
+    truncated.push('…');
+    truncated
+}
@@ -0,0 +1,554 @@
+use outliner::{
+    domain::{
+        Node, Replica,
+        attachment::DEFAULT_SIZE_LIMIT,
+        crdt::Operation,
+        node::{CreateNodeRequest, NodeType, Source},
+        rank::DEFAULT_RANK_GAP,
+    },
+    interfaces::{NodeRepository, SqliteRepository},
+    services::{
+        collab::{CollabService, Peer, ReplicaCache},
+        events::Notifier,
+        logging::NullLogging,
+        node_service::NodeService,
+    },
+};
+use uuid::Uuid;
+
+/// Small deterministic generator, so that failures can be replayed.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % bound.max(1) as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for index in (1..items.len()).rev() {
+            items.swap(index, self.next(index + 1));
+        }
+    }
+}
+
+fn request(parent_id: Option<Uuid>, text: &str) -> CreateNodeRequest {
+    CreateNodeRequest::new(
+        parent_id,
+        0,
+        NodeType::Standard,
+        text,
+        "astra",
+        Source::User,
+    )
+}
+
+fn replicas(count: usize) -> Vec<Replica> {
+    (0..count)
+        .map(|_| Replica::new(Uuid::new_v4(), DEFAULT_RANK_GAP))
+        .collect()
+}
+
+/// Delivers `operations` to `replica`, in the given order.
+fn deliver(replica: &mut Replica, operations: &[Operation]) {
+    for operation in operations {
+        replica.apply(operation.clone());
+    }
+}
+
+/// Gives every replica the full history of every other, each in its own shuffled order.
+fn exchange_all(replicas: &mut [Replica], rng: &mut Lcg) {
+    let mut operations: Vec<Operation> = replicas
+        .iter()
+        .flat_map(|replica| replica.history().to_vec())
+        .collect();
+
+    for replica in replicas.iter_mut() {
+        rng.shuffle(&mut operations);
+        deliver(replica, &operations);
+    }
+}
+
+fn summary(nodes: Vec<Node>) -> Vec<(Uuid, Option<Uuid>, u64, String, NodeType)> {
+    let mut summary: Vec<_> = nodes
+        .into_iter()
+        .map(|node| {
+            (
+                node.id(),
+                node.parent_id(),
+                node.rank(),
+                node.text().to_owned(),
+                node.node_type().clone(),
+            )
+        })
+        .collect();
+    summary.sort_by_key(|(id, ..)| *id);
+    summary
+}
+
+fn assert_converged(replicas: &[Replica]) {
+    let expected = summary(replicas[0].nodes());
+
+    for replica in &replicas[1..] {
+        assert_eq!(replica.pending_count(), 0);
+        assert_eq!(summary(replica.nodes()), expected);
+    }
+}
+
+fn children(replica: &Replica, parent_id: Option<Uuid>) -> Vec<String> {
+    let mut children: Vec<Node> = replica
+        .nodes()
+        .into_iter()
+        .filter(|node| node.parent_id() == parent_id)
+        .collect();
+    children.sort_by_key(|node| node.rank());
+
+    children
+        .into_iter()
+        .map(|node| node.text().to_owned())
+        .collect()
+}
+
+#[test]
+fn concurrent_inserts_among_siblings_converge() {
+    let mut rng = Lcg(1);
+    let mut replicas = replicas(3);
+    let (parent_id, operations) = replicas[0].create_node(request(None, "parent"), 0).unwrap();
+    deliver(&mut replicas[1], &operations);
+    deliver(&mut replicas[2], &operations);
+
+    for (index, replica) in replicas.iter_mut().enumerate() {
+        replica
+            .create_node(request(Some(parent_id), &format!("first from {index}")), 0)
+            .unwrap();
+        replica
+            .create_node(request(Some(parent_id), &format!("last from {index}")), 1)
+            .unwrap();
+    }
+
+    exchange_all(&mut replicas, &mut rng);
+
+    assert_converged(&replicas);
+
+    // each replica's own inserts keep their relative order
+    let children = children(&replicas[0], Some(parent_id));
+    assert_eq!(children.len(), 6);
+    for index in 0..3 {
+        let first = children
+            .iter()
+            .position(|text| *text == format!("first from {index}"));
+        let last = children
+            .iter()
+            .position(|text| *text == format!("last from {index}"));
+        assert!(first < last);
+    }
+}
+
+#[test]
+fn ranks_follow_sibling_order() {
+    let mut replica = Replica::new(Uuid::new_v4(), DEFAULT_RANK_GAP);
+
+    let (b, _) = replica.create_node(request(None, "b"), 0).unwrap();
+    replica.create_node(request(None, "a"), 0).unwrap();
+    replica.create_node(request(None, "c"), 2).unwrap();
+    replica.move_node(b, None, 2).unwrap();
+
+    assert_eq!(children(&replica, None), ["a", "c", "b"]);
+
+    let mut ranks: Vec<u64> = replica.nodes().iter().map(|node| node.rank()).collect();
+    ranks.sort();
+    assert_eq!(
+        ranks,
+        [DEFAULT_RANK_GAP, 2 * DEFAULT_RANK_GAP, 3 * DEFAULT_RANK_GAP]
+    );
+}
+
+#[test]
+fn concurrent_text_edits_are_merged() {
+    let mut rng = Lcg(2);
+    let mut replicas = replicas(2);
+    let (node_id, operations) = replicas[0].create_node(request(None, "hello"), 0).unwrap();
+    deliver(&mut replicas[1], &operations);
+
+    replicas[0].set_text(node_id, "Hello").unwrap();
+    replicas[1].set_text(node_id, "hello world").unwrap();
+
+    exchange_all(&mut replicas, &mut rng);
+
+    assert_converged(&replicas);
+    assert_eq!(replicas[0].node(&node_id).unwrap().text(), "Hello world");
+}
+
+#[test]
+fn concurrent_inserts_at_the_same_place_in_text_are_kept() {
+    let mut rng = Lcg(3);
+    let mut replicas = replicas(3);
+    let (node_id, operations) = replicas[0].create_node(request(None, "ac"), 0).unwrap();
+    deliver(&mut replicas[1], &operations);
+    deliver(&mut replicas[2], &operations);
+
+    replicas[0].set_text(node_id, "abc").unwrap();
+    replicas[1].set_text(node_id, "aXc").unwrap();
+    replicas[2].set_text(node_id, "a").unwrap();
+
+    exchange_all(&mut replicas, &mut rng);
+
+    assert_converged(&replicas);
+
+    // the removal of "c" wins, and both inserts survive in some agreed order
+    let text = replicas[0].node(&node_id).unwrap().text().to_owned();
+    assert!(text == "abX" || text == "aXb", "unexpected text {text}");
+}
+
+#[test]
+fn concurrent_moves_never_form_a_cycle() {
+    let mut rng = Lcg(4);
+    let mut replicas = replicas(2);
+    let (a, mut operations) = replicas[0].create_node(request(None, "a"), 0).unwrap();
+    let (b, more) = replicas[0].create_node(request(None, "b"), 1).unwrap();
+    operations.extend(more);
+    deliver(&mut replicas[1], &operations);
+
+    replicas[0].move_node(a, Some(b), 0).unwrap();
+    replicas[1].move_node(b, Some(a), 0).unwrap();
+
+    exchange_all(&mut replicas, &mut rng);
+
+    assert_converged(&replicas);
+
+    // one of the moves is skipped, leaving the other in place
+    let nodes = summary(replicas[0].nodes());
+    let parents: Vec<Option<Uuid>> = nodes.iter().map(|(_, parent_id, ..)| *parent_id).collect();
+    assert_eq!(
+        parents
+            .iter()
+            .filter(|parent_id| parent_id.is_none())
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn moving_a_node_into_its_own_subtree_is_refused() {
+    let mut replica = Replica::new(Uuid::new_v4(), DEFAULT_RANK_GAP);
+    let (a, _) = replica.create_node(request(None, "a"), 0).unwrap();
+    let (b, _) = replica.create_node(request(Some(a), "b"), 0).unwrap();
+
+    assert!(replica.move_node(a, Some(b), 0).is_err());
+    assert!(replica.move_node(a, Some(a), 0).is_err());
+}
+
+#[test]
+fn concurrent_moves_of_one_node_settle_on_one_parent() {
+    let mut rng = Lcg(5);
+    let mut replicas = replicas(3);
+    let mut operations = Vec::new();
+    let mut parents = Vec::new();
+    for text in ["one", "two", "three"] {
+        let (node_id, created) = replicas[0].create_node(request(None, text), 0).unwrap();
+        parents.push(node_id);
+        operations.extend(created);
+    }
+    let (moved, created) = replicas[0].create_node(request(None, "moved"), 0).unwrap();
+    operations.extend(created);
+    deliver(&mut replicas[1], &operations);
+    deliver(&mut replicas[2], &operations);
+
+    for (replica, parent_id) in replicas.iter_mut().zip(&parents) {
+        replica.move_node(moved, Some(*parent_id), 0).unwrap();
+    }
+
+    exchange_all(&mut replicas, &mut rng);
+
+    assert_converged(&replicas);
+    let parent_id = replicas[0].node(&moved).unwrap().parent_id();
+    assert!(parents.iter().any(|id| Some(*id) == parent_id));
+    // the node appears once, under a single parent
+    assert_eq!(
+        replicas[0]
+            .nodes()
+            .iter()
+            .filter(|node| node.id() == moved)
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn deleting_a_node_removes_children_added_concurrently() {
+    let mut rng = Lcg(6);
+    let mut replicas = replicas(2);
+    let (parent_id, operations) = replicas[0].create_node(request(None, "parent"), 0).unwrap();
+    deliver(&mut replicas[1], &operations);
+
+    replicas[0].delete_node(parent_id).unwrap();
+    let (child_id, _) = replicas[1]
+        .create_node(request(Some(parent_id), "child"), 0)
+        .unwrap();
+    replicas[1].set_text(parent_id, "renamed").unwrap();
+
+    exchange_all(&mut replicas, &mut rng);
+
+    assert_converged(&replicas);
+    assert!(replicas[0].node(&parent_id).is_none());
+    assert!(replicas[0].node(&child_id).is_none());
+    assert!(replicas[0].nodes().is_empty());
+}
+
+#[test]
+fn operations_arriving_out_of_order_are_held_back() {
+    let mut source = Replica::new(Uuid::new_v4(), DEFAULT_RANK_GAP);
+    let (parent_id, _) = source.create_node(request(None, "parent"), 0).unwrap();
+    let (child_id, _) = source
+        .create_node(request(Some(parent_id), "child"), 0)
+        .unwrap();
+    source.set_text(child_id, "child edited").unwrap();
+
+    let mut replica = Replica::new(Uuid::new_v4(), DEFAULT_RANK_GAP);
+    let mut operations = source.history().to_vec();
+    operations.reverse();
+
+    deliver(&mut replica, &operations[..operations.len() - 1]);
+    assert!(replica.nodes().is_empty());
+    assert!(replica.pending_count() > 0);
+
+    // duplicates are ignored
+    deliver(&mut replica, &operations);
+    deliver(&mut replica, &operations);
+
+    assert_eq!(replica.pending_count(), 0);
+    assert_eq!(summary(replica.nodes()), summary(source.nodes()));
+}
+
+#[test]
+fn node_type_keeps_the_latest_change() {
+    let mut rng = Lcg(7);
+    let mut replicas = replicas(2);
+    let (node_id, operations) = replicas[0].create_node(request(None, "task"), 0).unwrap();
+    deliver(&mut replicas[1], &operations);
+
+    replicas[0].set_node_type(node_id, NodeType::Todo).unwrap();
+    let history = replicas[0].history().to_vec();
+    deliver(&mut replicas[1], &history);
+    replicas[1].set_node_type(node_id, NodeType::Done).unwrap();
+
+    exchange_all(&mut replicas, &mut rng);
+
+    assert_converged(&replicas);
+    assert_eq!(
+        replicas[0].node(&node_id).unwrap().node_type(),
+        &NodeType::Done
+    );
+}
+
+#[test]
+fn random_edits_on_partially_connected_replicas_converge() {
+    for seed in 0..20 {
+        let mut rng = Lcg(seed);
+        let mut replicas = replicas(3);
+
+        for _ in 0..150 {
+            let index = rng.next(replicas.len());
+            let replica = &mut replicas[index];
+            let node_ids: Vec<Uuid> = replica.nodes().iter().map(|node| node.id()).collect();
+            let pick = |rng: &mut Lcg| match node_ids.is_empty() {
+                true => None,
+                false => Some(node_ids[rng.next(node_ids.len())]),
+            };
+
+            match (rng.next(6), pick(&mut rng)) {
+                (0, _) | (_, None) => {
+                    let parent_id = pick(&mut rng);
+                    let position = rng.next(4);
+                    replica
+                        .create_node(request(parent_id, &format!("node {seed}")), position)
+                        .unwrap();
+                }
+                (1, Some(node_id)) => {
+                    let parent_id = pick(&mut rng);
+                    let position = rng.next(4);
+                    // refused moves into the node's own subtree are expected here
+                    let _ = replica.move_node(node_id, parent_id, position);
+                }
+                (2, Some(node_id)) => {
+                    if rng.next(3) == 0 {
+                        replica.delete_node(node_id).unwrap();
+                    }
+                }
+                (3, Some(node_id)) => {
+                    let node_type = [NodeType::Todo, NodeType::Done][rng.next(2)].clone();
+                    replica.set_node_type(node_id, node_type).unwrap();
+                }
+                (_, Some(node_id)) => {
+                    let mut text: Vec<char> =
+                        replica.node(&node_id).unwrap().text().chars().collect();
+                    let position = rng.next(text.len() + 1);
+                    match rng.next(2) {
+                        0 if !text.is_empty() => {
+                            text.remove(position.min(text.len() - 1));
+                        }
+                        _ => text.insert(position, char::from(b'a' + rng.next(26) as u8)),
+                    }
+                    replica
+                        .set_text(node_id, &text.into_iter().collect::<String>())
+                        .unwrap();
+                }
+            }
+
+            // now and then, one replica hears part of what another has done
+            if rng.next(4) == 0 {
+                let from = rng.next(replicas.len());
+                let to = rng.next(replicas.len());
+                let mut operations = replicas[from].history().to_vec();
+                rng.shuffle(&mut operations);
+                let count = rng.next(operations.len() + 1);
+                deliver(&mut replicas[to], &operations[..count]);
+            }
+        }
+
+        exchange_all(&mut replicas, &mut rng);
+
+        assert_converged(&replicas);
+    }
+}
+
+/// Nodes in outline order, without ranks, which each repository picks for itself.
+fn outline(nodes: Vec<Node>) -> Vec<(Uuid, Option<Uuid>, String, NodeType)> {
+    let mut nodes = nodes;
+    nodes.sort_by_key(|node| (node.parent_id(), node.rank(), node.id()));
+
+    nodes
+        .into_iter()
+        .map(|node| {
+            (
+                node.id(),
+                node.parent_id(),
+                node.text().to_owned(),
+                node.node_type().clone(),
+            )
+        })
+        .collect()
+}
+
+fn memory_service() -> NodeService<SqliteRepository, NullLogging> {
+    NodeService {
+        repository: SqliteRepository::new_memory().unwrap(),
+        logger: NullLogging::new(),
+        replica: ReplicaCache::default(),
+        notifier: Notifier::default(),
+        attachment_limit: DEFAULT_SIZE_LIMIT,
+        rank_gap: DEFAULT_RANK_GAP,
+    }
+}
+
+#[test]
+fn collaborating_repositories_end_up_the_same() {
+    let host_service = memory_service();
+    let existing = host_service
+        .create_node(CreateNodeRequest::new(
+            None,
+            500,
+            NodeType::Standard,
+            "existing",
+            "astra",
+            Source::User,
+        ))
+        .unwrap();
+
+    let mut peers = Peer::mesh(2);
+    let guest_peer = peers.pop().unwrap();
+    let host_peer = peers.pop().unwrap();
+
+    let mut host = CollabService::connect(host_service, host_peer).unwrap();
+    let mut guest = CollabService::connect(memory_service(), guest_peer).unwrap();
+    guest.receive().unwrap();
+
+    let added = guest
+        .create_node(request(Some(existing.id()), "from the guest"), 0)
+        .unwrap();
+    host.update_text(existing.id(), "existing, edited").unwrap();
+    guest.move_node(added, None, 0).unwrap();
+
+    host.receive().unwrap();
+    guest.receive().unwrap();
+
+    let dump = |collab: &CollabService<SqliteRepository, NullLogging>| {
+        outline(collab.node_service.dump_nodes().unwrap())
+    };
+
+    assert_eq!(dump(&host), dump(&guest));
+    assert_eq!(
+        guest.node_service.get_node(&existing.id()).unwrap().text(),
+        "existing, edited"
+    );
+
+    let roots: Vec<_> = dump(&host)
+        .into_iter()
+        .filter(|(_, parent_id, ..)| parent_id.is_none())
+        .map(|(id, ..)| id)
+        .collect();
+    assert_eq!(roots, [added, existing.id()]);
+
+    guest.delete_node(existing.id()).unwrap();
+    host.receive().unwrap();
+
+    assert_eq!(dump(&host), dump(&guest));
+    assert_eq!(host.node_service.dump_nodes().unwrap().len(), 1);
+}
+
+#[test]
+fn edits_are_only_logged_once_the_outline_is_shared() {
+    let service = memory_service();
+    let mut node = service.create_node(request(None, "alone")).unwrap();
+    service.update_node(&mut node, "still alone").unwrap();
+
+    assert!(service.repository.get_operations(0).unwrap().is_empty());
+
+    // sharing seeds the log with the outline as it stands
+    let seeded = service.operations(0).unwrap().len();
+    assert!(seeded > 0);
+
+    service.create_node(request(None, "shared")).unwrap();
+    assert!(!service.operations(seeded).unwrap().is_empty());
+}
+
+#[test]
+fn concurrent_edits_merge_after_a_restart() {
+    let path = std::env::temp_dir().join(format!("outliner-crdt-{}.db", Uuid::new_v4()));
+    let open = || NodeService {
+        repository: SqliteRepository::open(&path).unwrap(),
+        logger: NullLogging::new(),
+        replica: ReplicaCache::default(),
+        notifier: Notifier::default(),
+        attachment_limit: DEFAULT_SIZE_LIMIT,
+        rank_gap: DEFAULT_RANK_GAP,
+    };
+
+    let node = open().create_node(request(None, "shared text")).unwrap();
+
+    // the log, and with it the identity of every character, outlives the process
+    let mut peers = Peer::mesh(2);
+    let guest_peer = peers.pop().unwrap();
+    let mut host = CollabService::connect(open(), peers.pop().unwrap()).unwrap();
+    let mut guest = CollabService::connect(memory_service(), guest_peer).unwrap();
+    guest.receive().unwrap();
+
+    host.update_text(node.id(), "the shared text").unwrap();
+    guest.update_text(node.id(), "shared text, edited").unwrap();
+
+    host.receive().unwrap();
+    guest.receive().unwrap();
+
+    for collab in [&host, &guest] {
+        assert_eq!(
+            collab.node_service.get_node(&node.id()).unwrap().text(),
+            "the shared text, edited"
+        );
+    }
+
+    drop(host);
+    let _ = std::fs::remove_file(&path);
+}
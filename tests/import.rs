@@ -0,0 +1,56 @@
+use hifitime::Epoch;
+use outliner::{
+    domain::{Planning, node::NodeType, planning::PlanningKind},
+    interfaces::import::{Importer, org::Org, taskpaper::TaskPaper},
+};
+
+#[test]
+fn taskpaper_tags_inside_tag_values_are_not_tags() {
+    let nodes = TaskPaper.import("Errands: @due(fri @x(1)", "user").unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].node_type, NodeType::Standard);
+    assert_eq!(nodes[0].text, "Errands @due(fri @x(1)");
+
+    let nodes = TaskPaper.import("@a(x @b(y)", "user").unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].text, "@a(x @b(y)");
+}
+
+#[test]
+fn org_markup_becomes_markdown() {
+    let nodes = Org
+        .import(
+            "* *a /b/ c*, +gone+ and ~code~ with [[https://x.io][the *site*]] and/or",
+            "user",
+        )
+        .unwrap();
+    assert_eq!(
+        nodes[0].text,
+        "**a *b* c**, ~~gone~~ and `code` with [the **site**](https://x.io) and/or"
+    );
+}
+
+#[test]
+fn org_planning_becomes_planned_dates() {
+    let nodes = Org
+        .import(
+            "* TODO Ship\nDEADLINE: <2026-10-25 Sun> SCHEDULED: <2026-10-20 Tue 09:30 +1w> CLOSED: [2026-10-19 Mon]\nBody",
+            "user",
+        )
+        .unwrap();
+
+    assert_eq!(
+        nodes[0].planning,
+        [
+            Planning {
+                kind: PlanningKind::Deadline,
+                time: Epoch::from_gregorian_utc_at_midnight(2026, 10, 25),
+            },
+            Planning {
+                kind: PlanningKind::Scheduled,
+                time: Epoch::from_gregorian_utc_hms(2026, 10, 20, 9, 30, 0),
+            },
+        ]
+    );
+    assert_eq!(nodes[0].note, "CLOSED: [2026-10-19 Mon]\nBody");
+}
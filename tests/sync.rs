@@ -0,0 +1,351 @@
+use std::{collections::HashMap, thread, time::Duration};
+
+use outliner::{
+    domain::{
+        Node, Selection,
+        attachment::DEFAULT_SIZE_LIMIT,
+        node::{CreateNodeRequest, NodeType, Source},
+        rank::DEFAULT_RANK_GAP,
+    },
+    interfaces::{NodeRepository, SqliteRepository},
+    services::{
+        collab::ReplicaCache,
+        events::Notifier,
+        logging::NullLogging,
+        node_service::NodeService,
+        sync::{Side, SyncReport, SyncService},
+    },
+};
+use uuid::Uuid;
+
+type Service = NodeService<SqliteRepository, NullLogging>;
+
+fn service() -> Service {
+    NodeService {
+        repository: SqliteRepository::new_memory().unwrap(),
+        logger: NullLogging::new(),
+        replica: ReplicaCache::default(),
+        notifier: Notifier::default(),
+        attachment_limit: DEFAULT_SIZE_LIMIT,
+        rank_gap: DEFAULT_RANK_GAP,
+    }
+}
+
+fn sync(local: Service, remote: Service) -> (Service, Service, SyncReport) {
+    let sync_service = SyncService {
+        local: local.repository,
+        remote: remote.repository,
+        logger: NullLogging::new(),
+        rank_gap: DEFAULT_RANK_GAP,
+    };
+
+    let report = sync_service.sync().unwrap();
+    let SyncService { local, remote, .. } = sync_service;
+
+    (
+        NodeService {
+            repository: local,
+            logger: NullLogging::new(),
+            replica: ReplicaCache::default(),
+            notifier: Notifier::default(),
+            attachment_limit: DEFAULT_SIZE_LIMIT,
+            rank_gap: DEFAULT_RANK_GAP,
+        },
+        NodeService {
+            repository: remote,
+            logger: NullLogging::new(),
+            replica: ReplicaCache::default(),
+            notifier: Notifier::default(),
+            attachment_limit: DEFAULT_SIZE_LIMIT,
+            rank_gap: DEFAULT_RANK_GAP,
+        },
+        report,
+    )
+}
+
+fn create(service: &Service, parent_id: Option<Uuid>, text: &str) -> Uuid {
+    // modification times decide every merge, so keep them apart
+    thread::sleep(Duration::from_millis(2));
+
+    service
+        .create_node(CreateNodeRequest::new(
+            parent_id,
+            100,
+            NodeType::Standard,
+            text,
+            "astra",
+            Source::User,
+        ))
+        .unwrap()
+        .id()
+}
+
+fn move_node(service: &Service, node_id: Uuid, parent_id: Option<Uuid>) {
+    thread::sleep(Duration::from_millis(2));
+
+    service
+        .bulk_move(&Selection::single(node_id), parent_id, usize::MAX)
+        .unwrap();
+}
+
+fn edit(service: &Service, node_id: Uuid, text: &str) {
+    thread::sleep(Duration::from_millis(2));
+
+    let mut node = service.get_node(&node_id).unwrap();
+    service.update_node(&mut node, text).unwrap();
+}
+
+fn nodes(service: &Service) -> HashMap<Uuid, Node> {
+    service
+        .dump_nodes()
+        .unwrap()
+        .into_iter()
+        .map(|node| (node.id(), node))
+        .collect()
+}
+
+fn assert_converged(local: &Service, remote: &Service) {
+    let summary = |service: &Service| {
+        let mut summary: Vec<_> = nodes(service)
+            .into_values()
+            .map(|node| {
+                (
+                    node.id(),
+                    node.parent_id(),
+                    node.rank(),
+                    node.text().to_owned(),
+                    node.modified_time(),
+                )
+            })
+            .collect();
+        summary.sort_by_key(|(id, ..)| *id);
+        summary
+    };
+
+    assert_eq!(summary(local), summary(remote));
+}
+
+fn assert_acyclic(service: &Service) {
+    let nodes = nodes(service);
+
+    for node in nodes.values() {
+        let mut seen = vec![node.id()];
+        let mut current = node.parent_id();
+
+        while let Some(parent_id) = current {
+            assert!(
+                !seen.contains(&parent_id),
+                "node {} is its own ancestor",
+                node.id()
+            );
+            seen.push(parent_id);
+            current = nodes[&parent_id].parent_id();
+        }
+    }
+}
+
+fn parent(service: &Service, node_id: Uuid) -> Option<Uuid> {
+    service.get_node(&node_id).unwrap().parent_id()
+}
+
+#[test]
+fn changes_flow_in_both_directions() {
+    let local = service();
+    let remote = service();
+    let kept = create(&local, None, "kept");
+    let doomed = create(&local, None, "doomed");
+    let (local, remote, report) = sync(local, remote);
+
+    assert_eq!(report.remote.created, 2);
+    assert_converged(&local, &remote);
+
+    let added = create(&remote, Some(kept), "added remotely");
+    edit(&local, kept, "edited locally");
+    local.delete_node(local.get_node(&doomed).unwrap()).unwrap();
+
+    let (local, remote, report) = sync(local, remote);
+
+    assert_eq!(report.local.created, 1);
+    assert_eq!(report.remote.updated, 1);
+    assert_eq!(report.remote.deleted, 1);
+    assert!(report.conflicts.is_empty());
+    assert_converged(&local, &remote);
+    assert_eq!(remote.get_node(&kept).unwrap().text(), "edited locally");
+    assert_eq!(parent(&local, added), Some(kept));
+    assert!(remote.get_node(&doomed).is_err());
+
+    // a second sync finds nothing left to do
+    let (local, remote, report) = sync(local, remote);
+
+    assert_eq!(
+        report.local.created + report.local.updated + report.local.deleted,
+        0
+    );
+    assert_eq!(
+        report.remote.created + report.remote.updated + report.remote.deleted,
+        0
+    );
+    assert_converged(&local, &remote);
+}
+
+#[test]
+fn text_edited_on_both_sides_is_reported_as_a_conflict() {
+    let local = service();
+    let remote = service();
+    let node_id = create(&local, None, "original");
+    let (local, remote, _) = sync(local, remote);
+
+    edit(&local, node_id, "local edit");
+    edit(&remote, node_id, "remote edit");
+
+    let (local, remote, report) = sync(local, remote);
+
+    assert_eq!(report.conflicts.len(), 1);
+    assert_eq!(report.conflicts[0].node_id, node_id);
+    assert_eq!(report.conflicts[0].local_text, "local edit");
+    assert_eq!(report.conflicts[0].kept, Side::Remote);
+    assert_eq!(local.get_node(&node_id).unwrap().text(), "remote edit");
+    assert_converged(&local, &remote);
+}
+
+#[test]
+fn deletion_only_wins_over_earlier_edits() {
+    let local = service();
+    let remote = service();
+    let restored = create(&local, None, "restored");
+    let removed = create(&local, None, "removed");
+    let (local, remote, _) = sync(local, remote);
+
+    local
+        .delete_node(local.get_node(&restored).unwrap())
+        .unwrap();
+    edit(&remote, restored, "edited after the deletion");
+
+    edit(&remote, removed, "edited before the deletion");
+    thread::sleep(Duration::from_millis(2));
+    local
+        .delete_node(local.get_node(&removed).unwrap())
+        .unwrap();
+
+    let (local, remote, _) = sync(local, remote);
+
+    assert_eq!(
+        local.get_node(&restored).unwrap().text(),
+        "edited after the deletion"
+    );
+    assert!(local.get_node(&removed).is_err());
+    assert!(remote.get_node(&removed).is_err());
+    assert_converged(&local, &remote);
+}
+
+#[test]
+fn child_added_under_a_deleted_parent_moves_to_the_top_level() {
+    let local = service();
+    let remote = service();
+    let parent_id = create(&local, None, "parent");
+    let (local, remote, _) = sync(local, remote);
+
+    local
+        .delete_node(local.get_node(&parent_id).unwrap())
+        .unwrap();
+    let child_id = create(&remote, Some(parent_id), "child");
+
+    let (local, remote, report) = sync(local, remote);
+
+    assert_eq!(report.orphans, vec![child_id]);
+    assert_eq!(parent(&local, child_id), None);
+    assert!(remote.get_node(&parent_id).is_err());
+    assert_converged(&local, &remote);
+}
+
+#[test]
+fn concurrent_moves_into_each_other_do_not_form_a_cycle() {
+    let local = service();
+    let remote = service();
+    let a = create(&local, None, "a");
+    let b = create(&local, None, "b");
+    let (local, remote, _) = sync(local, remote);
+
+    move_node(&local, a, Some(b));
+    move_node(&remote, b, Some(a));
+
+    let (local, remote, report) = sync(local, remote);
+
+    // the later move is the one undone
+    assert_eq!(report.cycles_broken, vec![b]);
+    assert_eq!(parent(&local, a), Some(b));
+    assert_eq!(parent(&local, b), None);
+    assert_acyclic(&local);
+    assert_converged(&local, &remote);
+}
+
+#[test]
+fn concurrent_moves_forming_a_longer_cycle_are_broken() {
+    let local = service();
+    let remote = service();
+    let a = create(&local, None, "a");
+    let b = create(&local, None, "b");
+    let c = create(&local, None, "c");
+    let d = create(&local, Some(c), "d");
+    let (local, remote, _) = sync(local, remote);
+
+    // locally a ends up inside b, which ends up inside d
+    move_node(&local, a, Some(b));
+    move_node(&local, b, Some(d));
+    // remotely c, the parent of d, is moved inside a
+    move_node(&remote, c, Some(a));
+
+    let (local, remote, report) = sync(local, remote);
+
+    assert_eq!(report.cycles_broken, vec![c]);
+    assert_eq!(parent(&local, c), None);
+    assert_eq!(parent(&local, b), Some(d));
+    assert_acyclic(&local);
+    assert_acyclic(&remote);
+    assert_converged(&local, &remote);
+}
+
+#[test]
+fn several_cycles_from_one_sync_are_all_broken() {
+    let local = service();
+    let remote = service();
+    let ids: Vec<Uuid> = (0..4)
+        .map(|index| create(&local, None, &format!("node {index}")))
+        .collect();
+    let (local, remote, _) = sync(local, remote);
+
+    move_node(&local, ids[0], Some(ids[1]));
+    move_node(&local, ids[2], Some(ids[3]));
+    move_node(&remote, ids[1], Some(ids[0]));
+    move_node(&remote, ids[3], Some(ids[2]));
+
+    let (local, remote, report) = sync(local, remote);
+
+    assert_eq!(report.cycles_broken.len(), 2);
+    assert_acyclic(&local);
+    assert_converged(&local, &remote);
+
+    // the result is stable, and syncing again changes nothing
+    let (local, remote, report) = sync(local, remote);
+
+    assert!(report.cycles_broken.is_empty());
+    assert_eq!(report.local.moved + report.remote.moved, 0);
+    assert_converged(&local, &remote);
+}
+
+#[test]
+fn tombstones_are_shared_with_the_other_side() {
+    let local = service();
+    let remote = service();
+    let node_id = create(&local, None, "short lived");
+    let (local, remote, _) = sync(local, remote);
+
+    local
+        .delete_node(local.get_node(&node_id).unwrap())
+        .unwrap();
+    let (local, remote, _) = sync(local, remote);
+
+    let tombstones = |service: &Service| service.repository.dump_tombstones().unwrap();
+    assert_eq!(tombstones(&local), tombstones(&remote));
+    assert_eq!(tombstones(&remote)[0].0, node_id);
+}